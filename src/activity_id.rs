@@ -0,0 +1,120 @@
+//! Archive-wide activity identifiers.
+//!
+//! Strava IDs are stored as bare numbers, exactly as before imports existed, so
+//! existing archives and frontends keep working. Activities from any other
+//! source are namespaced by prefixing their source (`fit-42`), in JSON and in
+//! file names alike, so they can never collide with a Strava ID.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::{self, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Source {
+    Strava,
+    /// Imported from a FIT file
+    Fit,
+    /// Entered by hand
+    Manual,
+}
+
+impl Source {
+    pub const ALL: [Source; 3] = [Source::Strava, Source::Fit, Source::Manual];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Source::Strava => "strava",
+            Source::Fit => "fit",
+            Source::Manual => "manual",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ActivityId {
+    pub source: Source,
+    pub id: i64,
+}
+
+impl ActivityId {
+    pub fn strava(id: i64) -> Self {
+        ActivityId { source: Source::Strava, id }
+    }
+
+    /// The ID to use with the Strava API, if the activity came from Strava
+    pub fn strava_id(self) -> Result<i64, String> {
+        match self.source {
+            Source::Strava => Ok(self.id),
+            _ => Err(format!("activity {} was not imported from Strava", self)),
+        }
+    }
+}
+
+impl fmt::Display for ActivityId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.source {
+            Source::Strava => write!(f, "{}", self.id),
+            source => write!(f, "{}-{}", source.name(), self.id),
+        }
+    }
+}
+
+impl FromStr for ActivityId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid activity id '{}'", s);
+        match s.split_once('-') {
+            Some((prefix, id)) => {
+                let source = Source::ALL.into_iter().find(|src| src.name() == prefix).ok_or_else(invalid)?;
+                Ok(ActivityId { source, id: id.parse().map_err(|_| invalid())? })
+            }
+            None => s.parse().map(ActivityId::strava).map_err(|_| invalid()),
+        }
+    }
+}
+
+impl From<i64> for ActivityId {
+    fn from(id: i64) -> Self {
+        ActivityId::strava(id)
+    }
+}
+
+impl Serialize for ActivityId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.source {
+            Source::Strava => serializer.serialize_i64(self.id),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ActivityId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct IdVisitor;
+
+        impl Visitor<'_> for IdVisitor {
+            type Value = ActivityId;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a Strava activity ID or a source-prefixed ID such as \"fit-42\"")
+            }
+
+            fn visit_i64<E: de::Error>(self, id: i64) -> Result<ActivityId, E> {
+                Ok(ActivityId::strava(id))
+            }
+
+            fn visit_u64<E: de::Error>(self, id: u64) -> Result<ActivityId, E> {
+                i64::try_from(id).map(ActivityId::strava).map_err(E::custom)
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<ActivityId, E> {
+                s.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(IdVisitor)
+    }
+}
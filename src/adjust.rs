@@ -0,0 +1,87 @@
+use std::error::Error;
+use tracing::info;
+
+use crate::activity_id::ActivityId;
+use crate::clean;
+use crate::config::Config;
+use crate::model::{ActivityIndex, PowerAdjustment, PowerSummary};
+use crate::pipeline::{self, Analyzer, ANALYZERS};
+use crate::storage::{load_activity_file, stored_compression, write_activity_file};
+
+/// Apply `watts * scale + offset` to an activity's power stream.
+///
+/// The correction is always computed from the originally recorded stream (cleaned
+/// again if the file was cleaned), so adjusting twice replaces the first correction
+/// instead of compounding it, and `--scale 1 --offset 0` restores the recorded data.
+pub fn run(id: ActivityId, scale: f64, offset: f64) -> Result<(), Box<dyn Error>> {
+    let compression = stored_compression(id)?.ok_or_else(|| format!("activity {} is not in the archive", id))?;
+    let mut file = load_activity_file(id)?;
+    let config = Config::current()?;
+    let cleaned = file.cleaning.is_some();
+    let streams = file.streams.as_mut().ok_or("activity has no streams")?;
+    let original = streams
+        .original_watts
+        .take()
+        .or_else(|| streams.watts.clone())
+        .ok_or("activity has no watts stream")?;
+    let base = if cleaned {
+        clean::power(&original, &config.cleaning.clone().unwrap_or_default()).0
+    } else {
+        original.clone()
+    };
+
+    let before = file.activity.weighted_average_watts;
+    let recorded = match file.power_adjustment.take() {
+        Some(previous) => previous.recorded,
+        None => PowerSummary {
+            average_watts: file.activity.average_watts,
+            weighted_average_watts: file.activity.weighted_average_watts,
+            max_watts: file.activity.max_watts,
+            kilojoules: file.activity.kilojoules,
+        },
+    };
+    if scale == 1.0 && offset == 0.0 {
+        streams.watts = Some(base);
+        if cleaned {
+            streams.original_watts = Some(original);
+        }
+        file.activity.average_watts = recorded.average_watts;
+        file.activity.weighted_average_watts = recorded.weighted_average_watts;
+        file.activity.max_watts = recorded.max_watts;
+        file.activity.kilojoules = recorded.kilojoules;
+        info!("↩️  Restored recorded power for {}", file.activity.name);
+    } else {
+        streams.watts = Some(corrected(&base, scale, offset));
+        streams.original_watts = Some(original);
+        file.power_adjustment = Some(PowerAdjustment {
+            scale,
+            offset,
+            adjusted_at: chrono::Utc::now().to_rfc3339(),
+            recorded,
+        });
+        info!("🔧 Applied watts * {} {:+} W to {}", scale, offset, file.activity.name);
+    }
+
+    // Everything derived from watts is stale: rerun every analyzer, leaving out
+    // NP on a revert so Strava's recorded summary stands
+    let adjusted = file.power_adjustment.is_some();
+    let analyzers: Vec<&dyn Analyzer> = ANALYZERS.iter().copied().filter(|a| adjusted || a.name() != "np").collect();
+    let mut index = ActivityIndex::load()?;
+    pipeline::run(&analyzers, &mut file, &mut index, &config);
+
+    info!("   NP {:.0} W -> {:.0} W, avg {:.0} W",
+        before.unwrap_or(0.0),
+        file.activity.weighted_average_watts.unwrap_or(0.0),
+        file.activity.average_watts.unwrap_or(0.0)
+    );
+
+    write_activity_file(&file, compression)?;
+    index.save()?;
+    Ok(())
+}
+
+/// Calibration correction of a power stream
+pub fn corrected(watts: &[f64], scale: f64, offset: f64) -> Vec<f64> {
+    // Zeros are coasting, not a calibration artefact: leave them alone
+    watts.iter().map(|&w| if w > 0.0 { (w * scale + offset).max(0.0) } else { 0.0 }).collect()
+}
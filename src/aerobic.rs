@@ -0,0 +1,174 @@
+//! Aerobic fitness from power and heart rate together: efficiency factor,
+//! Pw:HR decoupling and a linear fit of heart rate against power, stored on
+//! each index entry.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::metrics;
+use crate::model::{ActivityIndex, ActivitySummary, ActivityWithStreams};
+use crate::pipeline::Analyzer;
+use crate::stats::GroupBy;
+use crate::storage::load_activity_file;
+
+/// Power is smoothed over this many samples before fitting heart rate to it
+const FIT_SMOOTHING: usize = 30;
+
+/// Heart rate lags tried against power, in samples
+const MAX_LAG: usize = 60;
+const LAG_STEP: usize = 5;
+
+/// Pedalling samples a fit needs, 10 minutes
+const FIT_MIN_SAMPLES: usize = 600;
+
+/// Standard deviation of power a fit needs, in watts: steady rides say
+/// nothing about how heart rate follows power
+const FIT_MIN_SPREAD: f64 = 15.0;
+
+/// Heart rate as a linear function of power, `heartrate = slope * watts + intercept`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HeartRateFit {
+    /// bpm per watt
+    pub slope: f64,
+    /// bpm at 0 W
+    pub intercept: f64,
+    /// Seconds heart rate trails power by, the lag that correlates best
+    pub lag: usize,
+    /// Coefficient of determination of the fit
+    pub r2: f64,
+}
+
+/// `metrics::linear_fit`, None unless x has a standard deviation of at least
+/// `min_spread` and y varies at all
+fn linear_fit(pairs: &[(f64, f64)], min_spread: f64) -> Option<(f64, f64, f64)> {
+    let n = pairs.len() as f64;
+    let mean = pairs.iter().map(|p| p.0).sum::<f64>() / n;
+    let spread = (pairs.iter().map(|p| (p.0 - mean).powi(2)).sum::<f64>() / n).sqrt();
+    if spread < min_spread || pairs.iter().all(|p| p.1 == pairs[0].1) {
+        return None;
+    }
+    metrics::linear_fit(pairs)
+}
+
+/// Fit heart rate to 30 s power, at the lag where they correlate best;
+/// coasting and heart rate dropouts are left out
+pub fn heart_rate_fit(watts: &[f64], heartrate: &[i32]) -> Option<HeartRateFit> {
+    let len = watts.len().min(heartrate.len());
+    if len < FIT_MIN_SAMPLES + MAX_LAG {
+        return None;
+    }
+    let mut smoothed = vec![0.0; len];
+    let mut window = 0.0;
+    for i in 0..len {
+        window += watts[i];
+        if i >= FIT_SMOOTHING {
+            window -= watts[i - FIT_SMOOTHING];
+        }
+        smoothed[i] = window / (i + 1).min(FIT_SMOOTHING) as f64;
+    }
+    let pairs = |lag: usize| -> Vec<(f64, f64)> {
+        (FIT_SMOOTHING..len - lag)
+            .filter(|&i| watts[i] > 0.0 && heartrate[i + lag] > 0)
+            .map(|i| (smoothed[i], heartrate[i + lag] as f64))
+            .collect()
+    };
+    let (lag, (slope, intercept, r2)) = (0..=MAX_LAG)
+        .step_by(LAG_STEP)
+        .filter_map(|lag| {
+            let pairs = pairs(lag);
+            (pairs.len() >= FIT_MIN_SAMPLES).then(|| linear_fit(&pairs, FIT_MIN_SPREAD)).flatten().map(|fit| (lag, fit))
+        })
+        .max_by(|a, b| a.1 .2.total_cmp(&b.1 .2))?;
+    Some(HeartRateFit {
+        slope: (slope * 10000.0).round() / 10000.0,
+        intercept: (intercept * 10.0).round() / 10.0,
+        lag,
+        r2: (r2 * 1000.0).round() / 1000.0,
+    })
+}
+
+/// Store efficiency factor, decoupling and the heart rate fit on an index entry from the ride's streams
+pub fn annotate(summary: &mut ActivitySummary, file: &ActivityWithStreams) {
+    let streams = file.streams.as_ref();
+    let watts = streams.and_then(|s| s.watts.as_deref());
+    let heartrate = streams.and_then(|s| s.heartrate.as_deref());
+    let (ef, decoupling, fit) = match (watts, heartrate) {
+        // Running power is not comparable with cycling power
+        _ if summary.is_run() => (None, None, None),
+        (Some(watts), Some(hr)) => {
+            (metrics::efficiency_factor(watts, hr), metrics::decoupling(watts, hr), heart_rate_fit(watts, hr))
+        }
+        _ => (None, None, None),
+    };
+    summary.efficiency_factor = ef.map(|ef| (ef * 1000.0).round() / 1000.0);
+    summary.decoupling = decoupling.map(|d| (d * 10.0).round() / 10.0);
+    summary.heart_rate_fit = fit;
+}
+
+/// Efficiency factor, decoupling and heart rate fit on the index entry
+pub struct Aerobic;
+
+impl Analyzer for Aerobic {
+    fn name(&self) -> &'static str {
+        "aerobic"
+    }
+
+    fn analyze(&self, file: &mut ActivityWithStreams, summary: &mut ActivitySummary, _config: &Config) {
+        annotate(summary, file);
+    }
+}
+
+/// One month of `aerobic`: EF, steady-ride decoupling and bpm per 100 W of each ride
+#[derive(Default)]
+struct MonthTrend {
+    ef: Vec<f64>,
+    decoupling: Vec<f64>,
+    slopes: Vec<f64>,
+}
+
+/// Recompute the metrics for every ride and print their monthly trend
+pub fn run(since: Option<NaiveDate>) -> Result<(), Box<dyn Error>> {
+    let mut index = ActivityIndex::load()?;
+    for summary in index.activities.iter_mut() {
+        match load_activity_file(summary.id) {
+            Ok(file) => annotate(summary, &file),
+            Err(e) => warn!("   ⚠️  Skipping {}: {}", summary.id, e),
+        }
+    }
+    index.save()?;
+
+    // Decoupling only means something on steady rides, so intervals and races are left out of its trend
+    let mut months: BTreeMap<NaiveDate, MonthTrend> = BTreeMap::new();
+    for summary in index.activities.iter().filter(|a| a.counted()) {
+        let day = summary.local_date();
+        if since.is_some_and(|s| day < s) {
+            continue;
+        }
+        let month = months.entry(GroupBy::Month.start(day, day)).or_default();
+        month.ef.extend(summary.efficiency_factor);
+        if matches!(summary.workout_type.as_deref(), None | Some("endurance") | Some("recovery")) {
+            month.decoupling.extend(summary.decoupling);
+        }
+        month.slopes.extend(summary.heart_rate_fit.map(|fit| fit.slope * 100.0));
+    }
+
+    info!("💓 Aerobic fitness by month");
+    println!("  {:<7} {:>5} {:>6}  {:>6} {:>11} {:>13}", "month", "rides", "EF", "steady", "decoupling", "bpm/100W");
+    for (month, MonthTrend { ef, decoupling, slopes }) in &months {
+        println!(
+            "  {:<7} {:>5} {:>6}  {:>6} {:>11} {:>13}",
+            month.format("%Y-%m"),
+            ef.len(),
+            metrics::mean(ef).map(|v| format!("{:.2}", v)).unwrap_or_else(|| "-".into()),
+            decoupling.len(),
+            metrics::mean(decoupling).map(|v| format!("{:.1}%", v)).unwrap_or_else(|| "-".into()),
+            metrics::mean(slopes).map(|v| format!("{:.1}", v)).unwrap_or_else(|| "-".into()),
+        );
+    }
+    Ok(())
+}
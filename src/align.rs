@@ -0,0 +1,82 @@
+//! Stream length alignment. Strava occasionally returns series with a few
+//! samples more or less than `time`, which every per-sample computation
+//! (zones, W'bal, intervals, charts) would otherwise have to guard against.
+//!
+//! On ingest each series is cut or padded to the time stream's length and the
+//! original length is kept in `misaligned`. Padding follows resampling: power,
+//! cadence and speed read zero, heart rate, altitude and position hold their
+//! last value.
+
+use std::collections::BTreeMap;
+
+use crate::model::ActivityStreams;
+
+/// Cut or pad every per-sample series to the length of `time`, or to the
+/// shortest series when there is no time stream to pad against. Returns the
+/// series that were changed with their original length, also added to
+/// `streams.misaligned`.
+pub fn align(streams: &mut ActivityStreams) -> BTreeMap<String, usize> {
+    let lengths = lengths(streams);
+    let target = match streams.time.as_ref() {
+        Some(time) => time.len(),
+        None => lengths.iter().map(|(_, len)| *len).min().unwrap_or(0),
+    };
+    let changed: BTreeMap<String, usize> =
+        lengths.into_iter().filter(|(_, len)| *len != target).map(|(name, len)| (name.to_string(), len)).collect();
+    if changed.is_empty() {
+        return changed;
+    }
+
+    fn zero<T: Default>(series: &mut Option<Vec<T>>, len: usize) {
+        if let Some(series) = series {
+            series.resize_with(len, T::default);
+        }
+    }
+    fn hold<T: Copy + Default>(series: &mut Option<Vec<T>>, len: usize) {
+        if let Some(series) = series {
+            let last = series.last().copied().unwrap_or_default();
+            series.resize(len, last);
+        }
+    }
+    zero(&mut streams.watts, target);
+    hold(&mut streams.heartrate, target);
+    zero(&mut streams.cadence, target);
+    zero(&mut streams.velocity_smooth, target);
+    hold(&mut streams.altitude, target);
+    hold(&mut streams.latlng, target);
+    zero(&mut streams.original_watts, target);
+    hold(&mut streams.original_heartrate, target);
+    hold(&mut streams.w_balance, target);
+    zero(&mut streams.estimated_watts, target);
+
+    for (name, len) in &changed {
+        streams.misaligned.entry(name.clone()).or_insert(*len);
+    }
+    changed
+}
+
+/// Length of every per-sample series present
+pub fn lengths(streams: &ActivityStreams) -> Vec<(&'static str, usize)> {
+    [
+        ("time", streams.time.as_ref().map(Vec::len)),
+        ("watts", streams.watts.as_ref().map(Vec::len)),
+        ("heartrate", streams.heartrate.as_ref().map(Vec::len)),
+        ("cadence", streams.cadence.as_ref().map(Vec::len)),
+        ("velocity_smooth", streams.velocity_smooth.as_ref().map(Vec::len)),
+        ("altitude", streams.altitude.as_ref().map(Vec::len)),
+        ("latlng", streams.latlng.as_ref().map(Vec::len)),
+        ("original_watts", streams.original_watts.as_ref().map(Vec::len)),
+        ("original_heartrate", streams.original_heartrate.as_ref().map(Vec::len)),
+        ("w_balance", streams.w_balance.as_ref().map(Vec::len)),
+        ("estimated_watts", streams.estimated_watts.as_ref().map(Vec::len)),
+    ]
+    .into_iter()
+    .filter_map(|(name, len)| len.map(|len| (name, len)))
+    .collect()
+}
+
+/// Whether every series has the same number of samples
+pub fn is_aligned(streams: &ActivityStreams) -> bool {
+    let lengths = lengths(streams);
+    lengths.iter().all(|(_, len)| *len == lengths[0].1)
+}
@@ -0,0 +1,89 @@
+//! Sanity checks on what a ride claims: no one holds 70 km/h for two hours
+//! or pushes 2500 W, and time never runs backwards. A ride failing one is
+//! labelled suspect on its index entry, with the reasons, and left out of
+//! best efforts, sprints and power curves unless `"include_suspect"` is set
+//! in watts.json. It still counts in volume and training load.
+
+use tracing::warn;
+
+use crate::config::Config;
+use crate::model::{ActivitySummary, ActivityWithStreams};
+use crate::pipeline::Analyzer;
+
+/// Highest believable speed held over `SPEED_WINDOW_SECONDS`, km/h
+const MAX_SUSTAINED_SPEED_KMH: f64 = 70.0;
+const SPEED_WINDOW_SECONDS: usize = 2 * 3600;
+
+/// Highest believable power sample, W
+const MAX_WATTS: f64 = 2500.0;
+
+/// Suspect label on the index entry
+pub struct Anomalies;
+
+impl Analyzer for Anomalies {
+    fn name(&self) -> &'static str {
+        "anomalies"
+    }
+
+    fn analyze(&self, file: &mut ActivityWithStreams, summary: &mut ActivitySummary, _config: &Config) {
+        summary.anomalies = detect(file);
+        if !summary.anomalies.is_empty() {
+            warn!("   ⚠️  {} looks suspect: {}", summary.id, summary.anomalies.join(", "));
+        }
+    }
+}
+
+/// What is impossible about a ride, empty when nothing is
+pub fn detect(file: &ActivityWithStreams) -> Vec<String> {
+    let activity = &file.activity;
+    let streams = file.streams.as_ref();
+    let mut found = Vec::new();
+
+    if activity.moving_time < 0 || activity.elapsed_time < 0 {
+        found.push(format!("negative time ({} s moving, {} s elapsed)", activity.moving_time, activity.elapsed_time));
+    }
+    if let Some(i) = streams.and_then(|s| s.time.as_deref()).and_then(|t| (1..t.len()).find(|&i| t[i] < t[i - 1])) {
+        found.push(format!("time stream runs backwards at sample {}", i));
+    }
+    if activity.distance < 0.0 {
+        found.push(format!("negative distance ({:.0} m)", activity.distance));
+    }
+
+    // Held over the whole window: the speed stream when there is one, else the ride's average
+    let sustained = match streams.and_then(|s| s.velocity_smooth.as_deref()) {
+        Some(speed) => window_average(speed, streams.and_then(|s| s.time.as_deref())),
+        None => (activity.moving_time as usize >= SPEED_WINDOW_SECONDS).then_some(activity.average_speed),
+    };
+    if let Some(speed) = sustained.map(|s| s * 3.6).filter(|&kmh| kmh > MAX_SUSTAINED_SPEED_KMH) {
+        found.push(format!("{:.0} km/h held for {} h", speed, SPEED_WINDOW_SECONDS / 3600));
+    }
+
+    let peak = streams
+        .and_then(|s| s.watts.as_deref())
+        .and_then(|w| w.iter().copied().reduce(f64::max))
+        .or(activity.max_watts);
+    if let Some(watts) = peak.filter(|&w| w > MAX_WATTS) {
+        found.push(format!("{:.0} W peak", watts));
+    }
+    found
+}
+
+/// Best average speed over `SPEED_WINDOW_SECONDS` of the stream, None on a shorter ride
+fn window_average(speed: &[f64], time: Option<&[i32]>) -> Option<f64> {
+    let time = time.filter(|t| t.len() == speed.len());
+    let at = |i: usize| time.map_or(i as i64, |t| t[i] as i64);
+    let mut best: Option<f64> = None;
+    let (mut start, mut distance) = (0, 0.0);
+    for i in 1..speed.len() {
+        distance += speed[i] * (at(i) - at(i - 1)).max(0) as f64;
+        while at(i) - at(start + 1) >= SPEED_WINDOW_SECONDS as i64 {
+            distance -= speed[start + 1] * (at(start + 1) - at(start)).max(0) as f64;
+            start += 1;
+        }
+        let span = at(i) - at(start);
+        if span >= SPEED_WINDOW_SECONDS as i64 {
+            best = Some(best.map_or(distance / span as f64, |b: f64| b.max(distance / span as f64)));
+        }
+    }
+    best
+}
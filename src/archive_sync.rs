@@ -0,0 +1,148 @@
+use std::error::Error;
+use std::process::Command;
+use tracing::{debug, info};
+
+use crate::format;
+use crate::model::ActivityIndex;
+
+/// Patterns for `format::MACHINE_LOCAL_FILES`, which neither side should receive
+fn excludes() -> impl Iterator<Item = &'static str> {
+    format::MACHINE_LOCAL_FILES.into_iter().chain(["*.tmp"])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Push,
+    Pull,
+}
+
+impl Direction {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "push" => Ok(Direction::Push),
+            "pull" => Ok(Direction::Pull),
+            other => Err(format!("unknown direction '{}' (expected push or pull)", other)),
+        }
+    }
+}
+
+/// Transfer changed archive files between data/ and a remote copy.
+///
+/// `s3://bucket/prefix` remotes go through `aws s3 sync`, anything else
+/// (`host:path`, `user@nas:/volume/watts`, a mounted directory) through rsync.
+/// Both only copy files that differ, and neither deletes anything on the
+/// receiving side, so a laptop holding a subset never prunes the NAS copy.
+/// rsync keeps whichever copy of a file is newer, but `aws s3 sync` uploads
+/// any local file that differs, so pushing to S3 is refused while the remote
+/// index was synced after ours: pull first.
+pub fn run(direction: Direction, remote: &str, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let local = format!("{}/", format::data_dir().display());
+    let remote = if remote.ends_with('/') { remote.to_string() } else { format!("{}/", remote) };
+    let (source, dest) = match direction {
+        Direction::Push => (local, remote.clone()),
+        Direction::Pull => (remote.clone(), local),
+    };
+
+    let mut command = if remote.starts_with("s3://") {
+        let mut command = Command::new("aws");
+        command.args(["s3", "sync", &source, &dest]);
+        for pattern in excludes() {
+            command.args(["--exclude", pattern]);
+        }
+        if dry_run {
+            command.arg("--dryrun");
+        }
+        command
+    } else {
+        let mut command = Command::new("rsync");
+        // --update keeps whichever side has the newer copy of a file
+        command.args(["-az", "--update", "--itemize-changes"]);
+        for pattern in excludes() {
+            command.arg(format!("--exclude={}", pattern));
+        }
+        if dry_run {
+            command.arg("--dry-run");
+        }
+        command.args([&source, &dest]);
+        command
+    };
+
+    // A pull may bring in an older index.json than ours; keep our entries
+    let local_index = ActivityIndex::load()?;
+
+    // Likewise a push must not drop entries only the remote knows about
+    if direction == Direction::Push {
+        if let Some(remote_index) = fetch_remote_index(&remote)? {
+            if remote.starts_with("s3://") && synced_after(&remote_index.last_updated, &local_index.last_updated) {
+                return Err(format!(
+                    "{} was synced at {}, after this archive ({}); pull first so the push doesn't overwrite newer files",
+                    remote,
+                    remote_index.last_updated,
+                    if local_index.last_updated.is_empty() { "never" } else { &local_index.last_updated }
+                )
+                .into());
+            }
+            let mut index = local_index.clone();
+            let merged = index.merge(&remote_index);
+            if merged > 0 && !dry_run {
+                index.save()?;
+                info!("   📋 Merged {} entries from the remote index before pushing", merged);
+            }
+        }
+    }
+
+    info!("🔁 Syncing archive {} -> {}{}", source, dest, if dry_run { " (dry run)" } else { "" });
+    debug!("   Running {:?}", command);
+    let status = command
+        .status()
+        .map_err(|e| format!("could not run {:?}: {}", command.get_program(), e))?;
+    if !status.success() {
+        return Err(format!("archive sync failed ({})", status).into());
+    }
+
+    if direction == Direction::Pull && !dry_run {
+        let mut index = ActivityIndex::load()?;
+        let merged = index.merge(&local_index);
+        if merged > 0 {
+            index.save()?;
+            info!("   📋 Kept {} local index entries missing from the remote index", merged);
+        }
+    }
+
+    info!("✅ Archive sync complete");
+    Ok(())
+}
+
+/// Whether the `remote` last_updated stamp is later than the `local` one; a
+/// missing or unreadable stamp counts as never synced
+fn synced_after(remote: &str, local: &str) -> bool {
+    let parse = |stamp: &str| chrono::DateTime::parse_from_rfc3339(stamp).ok();
+    parse(remote) > parse(local)
+}
+
+/// Copy the remote index.json aside, returning None when the remote has none yet
+fn fetch_remote_index(remote: &str) -> Result<Option<ActivityIndex>, Box<dyn Error>> {
+    let tmp = format::data_dir().join("index.remote.json.tmp");
+    let source = format!("{}index.json", remote);
+    let mut command = if remote.starts_with("s3://") {
+        let mut command = Command::new("aws");
+        command.args(["s3", "cp", "--quiet", &source]).arg(&tmp);
+        command
+    } else {
+        let mut command = Command::new("rsync");
+        command.args(["-q", &source]).arg(&tmp);
+        command
+    };
+    let status = command
+        .status()
+        .map_err(|e| format!("could not run {:?}: {}", command.get_program(), e))?;
+    if !status.success() {
+        debug!("   No remote index fetched ({})", status);
+        return Ok(None);
+    }
+
+    let json = std::fs::read_to_string(&tmp)?;
+    std::fs::remove_file(&tmp)?;
+    let index = serde_json::from_str(&json).map_err(|e| format!("remote index.json is corrupt: {}", e))?;
+    Ok(Some(index))
+}
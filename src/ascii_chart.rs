@@ -0,0 +1,100 @@
+//! Charts drawn with Unicode blocks, for terminals on headless machines:
+//! sparklines, filled charts of a stream over time and horizontal bar charts.
+//! `show --chart` and `report` without `--html` use them.
+
+use crate::locale::Locale;
+
+/// Width when the terminal doesn't say (`COLUMNS`)
+const DEFAULT_WIDTH: usize = 60;
+
+/// Room left for the axis labels and indentation
+const AXIS_WIDTH: usize = 12;
+
+/// Eighths of a cell, from the bottom up and from the left
+const VERTICAL: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const HORIZONTAL: [char; 8] = ['▏', '▎', '▍', '▌', '▋', '▊', '▉', '█'];
+
+/// Columns available for a chart in this terminal, between 20 and 120
+pub fn width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse::<usize>().ok())
+        .map_or(DEFAULT_WIDTH, |c| c.saturating_sub(AXIS_WIDTH).clamp(20, 120))
+}
+
+/// `values` squeezed into at most `width` bucket averages
+fn buckets(values: &[f64], width: usize) -> Vec<f64> {
+    if values.is_empty() || width == 0 {
+        return Vec::new();
+    }
+    values.chunks(values.len().div_ceil(width)).map(|chunk| chunk.iter().sum::<f64>() / chunk.len() as f64).collect()
+}
+
+/// One line of `width` bucket averages, drawn from zero to the highest
+pub fn sparkline(values: &[f64], width: usize) -> String {
+    let buckets = buckets(values, width);
+    let max = buckets.iter().copied().fold(0.0, f64::max);
+    buckets
+        .iter()
+        .map(|&v| {
+            let level = if max > 0.0 { (v.max(0.0) / max * (VERTICAL.len() - 1) as f64).round() as usize } else { 0 };
+            VERTICAL[level.min(VERTICAL.len() - 1)]
+        })
+        .collect()
+}
+
+/// `values` over time as `height` rows of filled columns, top row first, from
+/// the lowest to the highest bucket average (both labelled on the axis)
+pub fn area_chart(values: &[f64], width: usize, height: usize) -> Vec<String> {
+    let buckets = buckets(values, width);
+    if buckets.is_empty() || height == 0 {
+        return Vec::new();
+    }
+    let max = buckets.iter().copied().fold(f64::MIN, f64::max);
+    let min = buckets.iter().copied().fold(f64::MAX, f64::min);
+    // Eighths of a row each column fills
+    let fills: Vec<usize> = buckets
+        .iter()
+        .map(|&v| if max > min { ((v - min) / (max - min) * (height * 8) as f64).round() as usize } else { height * 8 })
+        .collect();
+    (0..height)
+        .map(|row| {
+            let floor = (height - 1 - row) * 8;
+            let cells: String = fills
+                .iter()
+                .map(|&fill| match fill.saturating_sub(floor).min(8) {
+                    0 => ' ',
+                    eighths => VERTICAL[eighths - 1],
+                })
+                .collect();
+            let axis = match row {
+                0 => format!("{:>6.0} ┤", max),
+                r if r == height - 1 => format!("{:>6.0} ┤", min),
+                _ => format!("{:>6} │", ""),
+            };
+            format!("{}{}", axis, cells.trim_end())
+        })
+        .collect()
+}
+
+/// One labelled bar per value, the largest `width` long, followed by the value
+/// with `decimals` digits
+pub fn bar_chart(bars: &[(String, f64)], width: usize, decimals: usize, unit: &str) -> Vec<String> {
+    localized_bar_chart(bars, width, decimals, unit, Locale::default())
+}
+
+/// `bar_chart` with the values formatted for `locale`
+pub fn localized_bar_chart(bars: &[(String, f64)], width: usize, decimals: usize, unit: &str, locale: Locale) -> Vec<String> {
+    let max = bars.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+    let label_width = bars.iter().map(|(label, _)| label.chars().count()).max().unwrap_or(0);
+    bars.iter()
+        .map(|(label, value)| {
+            let eighths = if max > 0.0 { (value.max(0.0) / max * (width * 8) as f64).round() as usize } else { 0 };
+            let mut bar = "█".repeat(eighths / 8);
+            if eighths % 8 > 0 {
+                bar.push(HORIZONTAL[eighths % 8 - 1]);
+            }
+            format!("{:<label_width$} {} {} {}", label, bar, locale.number(*value, decimals), unit)
+        })
+        .collect()
+}
@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use crate::config::{Config, Zones};
+
+/// One of several athletes whose data this installation manages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AthleteProfile {
+    /// Strava athlete ID
+    pub id: i64,
+    pub name: String,
+    /// Defaults to data/<id>; set to "data" for the athlete the static site shows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_dir: Option<PathBuf>,
+    /// Environment variable holding the refresh token, STRAVA_REFRESH_TOKEN_<id> by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token_env: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ftp: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resting_hr: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_hr: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zones: Option<Zones>,
+}
+
+impl AthleteProfile {
+    pub fn data_dir(&self) -> PathBuf {
+        self.data_dir.clone().unwrap_or_else(|| PathBuf::from(format!("data/{}", self.id)))
+    }
+
+    pub fn refresh_token_env(&self) -> String {
+        self.refresh_token_env.clone().unwrap_or_else(|| format!("STRAVA_REFRESH_TOKEN_{}", self.id))
+    }
+}
+
+/// The athlete commands currently operate on; None means the single-athlete layout
+static ACTIVE: RwLock<Option<AthleteProfile>> = RwLock::new(None);
+
+pub fn select(profile: Option<AthleteProfile>) {
+    *ACTIVE.write().unwrap_or_else(|e| e.into_inner()) = profile;
+}
+
+pub fn active() -> Option<AthleteProfile> {
+    ACTIVE.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Name of the active athlete's caches outside its data directory
+pub fn cache_key() -> String {
+    active().map_or_else(|| "default".to_string(), |p| p.id.to_string())
+}
+
+/// Find a configured athlete by Strava ID or name
+pub fn find<'a>(config: &'a Config, key: &str) -> Result<&'a AthleteProfile, String> {
+    config
+        .athletes
+        .iter()
+        .find(|a| a.id.to_string() == key || a.name.eq_ignore_ascii_case(key))
+        .ok_or_else(|| format!("no athlete '{}' in watts.json", key))
+}
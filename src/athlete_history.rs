@@ -0,0 +1,114 @@
+use std::error::Error;
+use std::fs;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use crate::format;
+use crate::storage::write_atomic;
+use crate::strava::{access_token, fetch_athlete, fetch_athlete_stats};
+
+const HISTORY_FILE: &str = "athlete_history.json";
+
+/// Profile and lifetime totals as Strava reported them at a point in time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AthleteSnapshot {
+    pub fetched_at: String,
+    pub athlete_id: i64,
+    /// Kilograms, as entered on Strava
+    pub weight: Option<f64>,
+    /// FTP from the Strava profile (Strava subscribers only)
+    pub ftp: Option<f64>,
+    pub stats: AthleteStats,
+}
+
+/// `/athletes/{id}/stats`, rides only
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AthleteStats {
+    #[serde(default)]
+    pub biggest_ride_distance: Option<f64>,
+    #[serde(default)]
+    pub biggest_climb_elevation_gain: Option<f64>,
+    pub recent_ride_totals: RideTotals,
+    pub ytd_ride_totals: RideTotals,
+    pub all_ride_totals: RideTotals,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RideTotals {
+    pub count: i64,
+    pub distance: f64,
+    pub moving_time: i64,
+    pub elapsed_time: i64,
+    pub elevation_gain: f64,
+}
+
+pub fn load() -> Result<Vec<AthleteSnapshot>, Box<dyn Error>> {
+    match fs::read_to_string(format::data_dir().join(HISTORY_FILE)) {
+        Ok(json) => Ok(serde_json::from_str(&json)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Weight from the snapshot closest in time to `date` (RFC 3339 or YYYY-MM-DD)
+pub fn weight_at(history: &[AthleteSnapshot], target: NaiveDate) -> Option<f64> {
+    let day = |s: &str| s.get(..10).and_then(|d| d.parse::<NaiveDate>().ok());
+    history
+        .iter()
+        .filter_map(|s| Some((s.weight?, day(&s.fetched_at)?)))
+        .min_by_key(|(_, fetched)| (*fetched - target).num_days().abs())
+        .map(|(weight, _)| weight)
+}
+
+/// Fetch the profile and totals, appending them to data/athlete_history.json
+/// unless nothing changed since the last snapshot
+pub async fn snapshot(access_token: &str) -> Result<(), Box<dyn Error>> {
+    let athlete = fetch_athlete(access_token).await?;
+    let stats = fetch_athlete_stats(access_token, athlete.id).await?;
+    let snapshot = AthleteSnapshot {
+        fetched_at: chrono::Utc::now().to_rfc3339(),
+        athlete_id: athlete.id,
+        weight: athlete.weight.filter(|w| *w > 0.0),
+        ftp: athlete.ftp.filter(|f| *f > 0.0),
+        stats,
+    };
+
+    let mut history = load()?;
+    let unchanged = history.last().is_some_and(|last| {
+        last.weight == snapshot.weight && last.ftp == snapshot.ftp && last.stats == snapshot.stats
+    });
+    if unchanged {
+        debug!("   Athlete profile and totals unchanged");
+        return Ok(());
+    }
+    if let Some(weight) = snapshot.weight.filter(|w| history.last().and_then(|l| l.weight) != Some(*w)) {
+        info!("⚖️  Weight on Strava: {:.1} kg", weight);
+    }
+    history.push(snapshot);
+    write_atomic(&format::data_dir().join(HISTORY_FILE), serde_json::to_string_pretty(&history)?.as_bytes())
+}
+
+/// Print the weight and lifetime totals history, taking a new snapshot with `fetch`
+pub async fn run(fetch: bool) -> Result<(), Box<dyn Error>> {
+    if fetch {
+        snapshot(&access_token().await?).await?;
+    }
+    let history = load()?;
+    info!("🧍 {} athlete snapshots", history.len());
+    println!("  {:<10} {:>7} {:>5} {:>6} {:>9} {:>8}", "date", "weight", "FTP", "rides", "km", "hours");
+    for s in &history {
+        let totals = &s.stats.all_ride_totals;
+        println!(
+            "  {:<10} {:>7} {:>5} {:>6} {:>9.0} {:>8.0}",
+            &s.fetched_at[..s.fetched_at.len().min(10)],
+            s.weight.map(|w| format!("{:.1}", w)).unwrap_or_else(|| "-".into()),
+            s.ftp.map(|f| format!("{:.0}", f)).unwrap_or_else(|| "-".into()),
+            totals.count,
+            totals.distance / 1000.0,
+            totals.moving_time as f64 / 3600.0,
+        );
+    }
+    Ok(())
+}
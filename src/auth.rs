@@ -0,0 +1,234 @@
+//! Where the Strava refresh token comes from, and where a rotated one goes.
+//!
+//! `WATTS_TOKEN_STORE` picks the store:
+//! - `env` (default): `STRAVA_REFRESH_TOKEN`, or the athlete's own variable
+//! - `file` or `file:PATH`: a 0600 file, `<data dir>/strava_token` by default
+//! - `keyring`: the OS keychain, via `secret-tool` on Linux and Keychain
+//!   Services on macOS
+//! - `command:CMD`: stdout of a shell command, e.g. `pass show strava`
+//!
+//! Strava may rotate the refresh token on every refresh; writable stores keep
+//! the newest one so long-running servers don't end up with a revoked token.
+
+use std::error::Error;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use tracing::{debug, warn};
+
+use crate::athlete;
+use crate::format;
+use crate::storage::write_private;
+
+const KEYRING_SERVICE: &str = "watts-happening";
+
+pub trait TokenStore {
+    /// Human-readable description for logs and errors
+    fn describe(&self) -> String;
+
+    fn load(&self) -> Result<String, Box<dyn Error>>;
+
+    /// Persist a new refresh token; read-only stores leave it to the user
+    fn save(&self, refresh_token: &str) -> Result<(), Box<dyn Error>>;
+}
+
+/// The store configured by WATTS_TOKEN_STORE, for the active athlete
+pub fn token_store() -> Result<Box<dyn TokenStore>, String> {
+    let setting = std::env::var("WATTS_TOKEN_STORE").unwrap_or_else(|_| "env".to_string());
+    let store: Box<dyn TokenStore> = match setting.split_once(':') {
+        None if setting == "env" => Box::new(EnvStore {
+            var: athlete::active().map(|p| p.refresh_token_env()).unwrap_or_else(|| "STRAVA_REFRESH_TOKEN".into()),
+        }),
+        None if setting == "file" => Box::new(FileStore { path: format::data_dir().join(format::TOKEN_FILE) }),
+        Some(("file", path)) => Box::new(FileStore { path: PathBuf::from(path) }),
+        None if setting == "keyring" => Box::new(KeyringStore { account: account() }),
+        Some(("command", command)) => Box::new(CommandStore { command: command.to_string() }),
+        _ => return Err(format!("invalid WATTS_TOKEN_STORE '{}', expected env, file[:PATH], keyring or command:CMD", setting)),
+    };
+    Ok(store)
+}
+
+/// Key separating athletes in shared stores
+fn account() -> String {
+    athlete::active().map(|p| p.id.to_string()).unwrap_or_else(|| "default".to_string())
+}
+
+struct EnvStore {
+    var: String,
+}
+
+impl TokenStore for EnvStore {
+    fn describe(&self) -> String {
+        format!("environment variable {}", self.var)
+    }
+
+    fn load(&self) -> Result<String, Box<dyn Error>> {
+        Ok(std::env::var(&self.var).map_err(|_| format!("{} is not set", self.var))?)
+    }
+
+    fn save(&self, _refresh_token: &str) -> Result<(), Box<dyn Error>> {
+        warn!("   ⚠️  Strava issued a new refresh token; update {} (or use a writable WATTS_TOKEN_STORE)", self.var);
+        Ok(())
+    }
+}
+
+struct FileStore {
+    path: PathBuf,
+}
+
+impl TokenStore for FileStore {
+    fn describe(&self) -> String {
+        format!("file {}", self.path.display())
+    }
+
+    fn load(&self) -> Result<String, Box<dyn Error>> {
+        let token = std::fs::read_to_string(&self.path).map_err(|e| format!("could not read {}: {}", self.path.display(), e))?;
+        Ok(token.trim().to_string())
+    }
+
+    fn save(&self, refresh_token: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        write_private(&self.path, format!("{}\n", refresh_token).as_bytes())
+    }
+}
+
+struct KeyringStore {
+    account: String,
+}
+
+impl TokenStore for KeyringStore {
+    fn describe(&self) -> String {
+        format!("keyring entry {}/{}", KEYRING_SERVICE, self.account)
+    }
+
+    fn load(&self) -> Result<String, Box<dyn Error>> {
+        self.read()
+    }
+
+    fn save(&self, refresh_token: &str) -> Result<(), Box<dyn Error>> {
+        self.write(refresh_token).map_err(|e| format!("could not write {}: {}", self.describe(), e).into())
+    }
+}
+
+/// The login keychain through Keychain Services, as the generic password `security` would find
+#[cfg(target_os = "macos")]
+impl KeyringStore {
+    fn read(&self) -> Result<String, Box<dyn Error>> {
+        debug!("Reading refresh token from {}", self.describe());
+        let token = keyring::Entry::new(KEYRING_SERVICE, &self.account)?
+            .get_password()
+            .map_err(|e| format!("no refresh token in {}: {}", self.describe(), e))?;
+        Ok(token.trim().to_string())
+    }
+
+    fn write(&self, refresh_token: &str) -> Result<(), Box<dyn Error>> {
+        Ok(keyring::Entry::new(KEYRING_SERVICE, &self.account)?.set_password(refresh_token)?)
+    }
+}
+
+/// The Secret Service through `secret-tool`
+#[cfg(not(target_os = "macos"))]
+impl KeyringStore {
+    fn read(&self) -> Result<String, Box<dyn Error>> {
+        let mut command = Command::new("secret-tool");
+        command.args(["lookup", "service", KEYRING_SERVICE, "account", &self.account]);
+        output(&mut command, &self.describe())
+    }
+
+    fn write(&self, refresh_token: &str) -> Result<(), Box<dyn Error>> {
+        // secret-tool reads the secret from stdin, keeping it out of the process list
+        let mut child = Command::new("secret-tool")
+            .args(["store", "--label", "Strava refresh token", "service", KEYRING_SERVICE, "account", &self.account])
+            .stdin(Stdio::piped())
+            .spawn()?;
+        child.stdin.take().ok_or("secret-tool has no stdin")?.write_all(refresh_token.as_bytes())?;
+        if !child.wait()?.success() {
+            return Err("secret-tool failed".into());
+        }
+        Ok(())
+    }
+}
+
+struct CommandStore {
+    command: String,
+}
+
+impl TokenStore for CommandStore {
+    fn describe(&self) -> String {
+        format!("command `{}`", self.command)
+    }
+
+    fn load(&self) -> Result<String, Box<dyn Error>> {
+        let mut command = Command::new("sh");
+        command.args(["-c", &self.command]).env("WATTS_ATHLETE", account());
+        output(&mut command, &self.describe())
+    }
+
+    fn save(&self, _refresh_token: &str) -> Result<(), Box<dyn Error>> {
+        warn!("   ⚠️  Strava issued a new refresh token; update the source of {}", self.describe());
+        Ok(())
+    }
+}
+
+/// Trimmed stdout of a command that must succeed and print something
+fn output(command: &mut Command, what: &str) -> Result<String, Box<dyn Error>> {
+    debug!("Reading refresh token from {}", what);
+    let output = command.stderr(Stdio::inherit()).output().map_err(|e| format!("could not read {}: {}", what, e))?;
+    let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || token.is_empty() {
+        return Err(format!("no refresh token in {}", what).into());
+    }
+    Ok(token)
+}
+
+/// `auth set-token`: store a refresh token obtained from Strava's OAuth flow.
+/// The token is read from stdin, never argv, so it stays out of `ps` and shell history.
+pub fn set_token() -> Result<(), Box<dyn Error>> {
+    let refresh_token = if std::io::stdin().is_terminal() { prompt_hidden("Strava refresh token: ")? } else { read_line()? };
+    let refresh_token = refresh_token.trim();
+    if refresh_token.is_empty() {
+        return Err("no refresh token given on stdin".into());
+    }
+    let store = token_store()?;
+    store.save(refresh_token)?;
+    tracing::info!("🔑 Saved refresh token to {}", store.describe());
+    Ok(())
+}
+
+fn read_line() -> Result<String, Box<dyn Error>> {
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    Ok(line)
+}
+
+/// Read a line from the terminal without echoing it
+fn prompt_hidden(prompt: &str) -> Result<String, Box<dyn Error>> {
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+
+    eprint!("{}", prompt);
+    std::io::stderr().flush()?;
+    crossterm::terminal::enable_raw_mode()?;
+    let mut token = String::new();
+    let read = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Enter => break Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break Err("cancelled".into()),
+                KeyCode::Char(c) => token.push(c),
+                KeyCode::Backspace => {
+                    token.pop();
+                }
+                _ => {}
+            },
+            Ok(Event::Paste(text)) => token.push_str(&text),
+            Ok(_) => {}
+            Err(e) => break Err(Box::<dyn Error>::from(e)),
+        }
+    };
+    crossterm::terminal::disable_raw_mode()?;
+    eprintln!();
+    read.map(|()| token)
+}
@@ -0,0 +1,234 @@
+//! `backup` and `restore`: the whole archive as a single tarball, to move it
+//! between machines or keep a copy somewhere safe.
+//!
+//! The tarball holds the data directory as laid out on disk (the index and
+//! activity files read through the configured store), plus `backup.json`
+//! listing every file with its size and SHA-256. `restore` checks all of them
+//! before writing anything, so a truncated or corrupted backup never replaces
+//! a good archive. Refresh tokens are left out: they belong to the machine.
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::compression::Compression;
+use crate::format;
+use crate::model::ActivityIndex;
+use crate::storage::{sha256, write_atomic};
+use crate::store;
+
+const MANIFEST_FILE: &str = "backup.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    created_at: String,
+    format_version: String,
+    files: Vec<BackupFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BackupFile {
+    /// Relative to the data directory, '/'-separated
+    path: String,
+    size: u64,
+    sha256: String,
+}
+
+/// Default name of a backup taken today
+pub fn default_path() -> PathBuf {
+    PathBuf::from(format!("watts-backup-{}.tar.zst", Utc::now().format("%Y-%m-%d")))
+}
+
+/// Write the archive to `to`, compressed according to its extension (.tar.zst, .tar.gz or .tar)
+pub fn backup(to: &Path) -> Result<(), Box<dyn Error>> {
+    let store = store::current()?;
+    let data_dir = format::data_dir();
+    info!("📦 Backing up {} to {}", data_dir.display(), to.display());
+
+    let mut tar = tar::Builder::new(Vec::new());
+    let mut files = Vec::new();
+    let mut add = |path: String, contents: Vec<u8>| -> Result<(), Box<dyn Error>> {
+        append(&mut tar, &path, &contents)?;
+        files.push(BackupFile { path, size: contents.len() as u64, sha256: sha256(&contents) });
+        Ok(())
+    };
+
+    // The index and activities come from the store, which may not be this disk
+    if let Some(index) = store.get(format::INDEX_FILE)? {
+        add(format::INDEX_FILE.to_string(), index)?;
+    }
+    let activities = format!("{}/", format::ACTIVITIES_DIR);
+    for name in store.list(format::ACTIVITIES_DIR)? {
+        let key = format!("{}{}", activities, name);
+        if let Some(contents) = store.get(&key)? {
+            add(key, contents)?;
+        }
+    }
+    for path in local_files(&data_dir)? {
+        if path != format::INDEX_FILE && !path.starts_with(&activities) {
+            add(path.clone(), fs::read(data_dir.join(&path))?)?;
+        }
+    }
+
+    let manifest = Manifest {
+        created_at: Utc::now().to_rfc3339(),
+        format_version: format::FORMAT_VERSION.to_string(),
+        files,
+    };
+    append(&mut tar, MANIFEST_FILE, serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+    let tarball = Compression::from_path(to).compress(&tar.into_inner()?)?;
+    write_atomic(to, &tarball)?;
+
+    let bytes: u64 = manifest.files.iter().map(|f| f.size).sum();
+    info!("💾 {} files ({:.1} MB) written to {} ({:.1} MB)",
+        manifest.files.len(),
+        bytes as f64 / 1e6,
+        to.display(),
+        tarball.len() as f64 / 1e6
+    );
+    Ok(())
+}
+
+/// Check a backup and, unless `verify_only`, write its files into the archive.
+/// An archive that already holds activities is only overwritten with `force`.
+pub fn restore(from: &Path, verify_only: bool, force: bool) -> Result<(), Box<dyn Error>> {
+    let tarball = fs::read(from).map_err(|e| format!("could not read {}: {}", from.display(), e))?;
+    let tar = Compression::from_path(from).decompress(&tarball)?;
+    let staging = staging_dir("restore")?;
+    let result = extract(&tar, &staging).and_then(|()| verify(&staging)).and_then(|manifest| {
+        info!("🔐 {}: {} files from {}, checksums match", from.display(), manifest.files.len(), manifest.created_at);
+        if manifest.format_version.split('.').next() != format::FORMAT_VERSION.split('.').next() {
+            warn!("   ⚠️  Backup format v{} differs from this build's v{}, run `migrate` after restoring",
+                manifest.format_version, format::FORMAT_VERSION);
+        }
+        if verify_only {
+            return Ok(());
+        }
+        let existing = ActivityIndex::load()?.activities.len();
+        if existing > 0 && !force {
+            return Err(format!("the archive already holds {} activities; pass --force to overwrite it", existing).into());
+        }
+        write_files(&staging, &manifest)
+    });
+    fs::remove_dir_all(&staging)?;
+    result
+}
+
+/// Add a regular file to the tarball being built
+fn append(tar: &mut tar::Builder<Vec<u8>>, path: &str, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp().max(0) as u64);
+    tar.append_data(&mut header, path, contents)?;
+    Ok(())
+}
+
+fn extract(tar: &[u8], staging: &Path) -> Result<(), Box<dyn Error>> {
+    tar::Archive::new(tar).unpack(staging).map_err(|e| format!("not a readable backup: {}", e).into())
+}
+
+/// The manifest of an extracted backup, once every file it lists is present and intact
+fn verify(staging: &Path) -> Result<Manifest, Box<dyn Error>> {
+    let json = fs::read(staging.join(MANIFEST_FILE)).map_err(|_| format!("no {} in the backup", MANIFEST_FILE))?;
+    let manifest: Manifest = serde_json::from_slice(&json).map_err(|e| format!("{} is corrupt: {}", MANIFEST_FILE, e))?;
+    let mut problems = 0;
+    for file in &manifest.files {
+        if !safe_path(&file.path) {
+            warn!("   ❌ {}: outside the data directory", file.path);
+            problems += 1;
+            continue;
+        }
+        match fs::read(staging.join(&file.path)) {
+            Ok(contents) if contents.len() as u64 == file.size && sha256(&contents) == file.sha256 => {}
+            Ok(_) => {
+                warn!("   ❌ {}: checksum mismatch", file.path);
+                problems += 1;
+            }
+            Err(_) => {
+                warn!("   ❌ {}: missing", file.path);
+                problems += 1;
+            }
+        }
+    }
+    let listed: BTreeSet<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+    let extra = local_files(staging)?.into_iter().filter(|p| p != MANIFEST_FILE && !listed.contains(p.as_str())).count();
+    if extra > 0 {
+        warn!("   ⚠️  {} files in the backup are not in its manifest and will be ignored", extra);
+    }
+    if problems > 0 {
+        return Err(format!("backup is damaged ({} problems), nothing was restored", problems).into());
+    }
+    Ok(manifest)
+}
+
+/// Index and activities go to the store, everything else to the data directory
+fn write_files(staging: &Path, manifest: &Manifest) -> Result<(), Box<dyn Error>> {
+    let store = store::current()?;
+    let data_dir = format::data_dir();
+    let activities = format!("{}/", format::ACTIVITIES_DIR);
+    // Activities before the index, so the index never lists a file not yet restored
+    let (archive, other): (Vec<&BackupFile>, Vec<&BackupFile>) =
+        manifest.files.iter().partition(|f| f.path.starts_with(&activities));
+    for file in archive.iter().chain(other.iter().filter(|f| f.path != format::INDEX_FILE)) {
+        let contents = fs::read(staging.join(&file.path))?;
+        if file.path.starts_with(&activities) {
+            store.put(&file.path, &contents)?;
+        } else {
+            let target = data_dir.join(&file.path);
+            if let Some(dir) = target.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            write_atomic(&target, &contents)?;
+        }
+    }
+    if let Some(index) = other.iter().find(|f| f.path == format::INDEX_FILE) {
+        store.put(format::INDEX_FILE, &fs::read(staging.join(&index.path))?)?;
+    }
+    info!("♻️  Restored {} files into {}", manifest.files.len(), store.describe(""));
+    Ok(())
+}
+
+/// Every file under `dir` as a '/'-separated relative path, leaving out machine-local state
+fn local_files(dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let entries = match fs::read_dir(&current) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("could not read {}: {}", current.display(), e).into()),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            if path.is_dir() {
+                pending.push(path);
+            } else if !format::machine_local(&name) {
+                let relative = path.strip_prefix(dir)?.components().map(|c| c.as_os_str().to_string_lossy()).collect::<Vec<_>>();
+                files.push(relative.join("/"));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// A manifest path that stays inside the directory it is restored into
+fn safe_path(path: &str) -> bool {
+    Path::new(path).components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+fn staging_dir(purpose: &str) -> Result<PathBuf, Box<dyn Error>> {
+    let dir = std::env::temp_dir().join(format!("watts-{}-{}", purpose, std::process::id()));
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
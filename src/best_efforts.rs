@@ -0,0 +1,251 @@
+//! `data/best_efforts.json`: the all-time and season-best mean-maximal power
+//! for standard durations, with the ride and the moment into it each was set.
+//! Sync updates it from the rides it adds and reports the bests they beat;
+//! `best-efforts --rebuild` scans the whole archive again (after a refetch,
+//! an exclusion or a power adjustment).
+//!
+//! The stored seasons are calendar years, by the ride's UTC start date;
+//! `--season` with a season from watts.json scans the rides within it.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::cli::{self, OutputFormat};
+use crate::config::Config;
+use crate::format;
+use crate::metrics;
+use crate::model::{ActivityIndex, ActivitySummary, ActivityWithStreams};
+use crate::season::Season;
+use crate::storage::{load_activity_file, write_atomic};
+
+/// Durations in seconds that bests are kept for
+pub const DURATIONS: [usize; 7] = [5, 30, 60, 300, 600, 1200, 3600];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Effort {
+    pub watts: f64,
+    pub id: ActivityId,
+    pub start_date: String,
+    /// Seconds into the ride the effort started
+    pub offset: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DurationBests {
+    /// Seconds
+    pub duration: usize,
+    pub all_time: Option<Effort>,
+    /// Best of each calendar year
+    pub seasons: BTreeMap<i32, Effort>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BestEfforts {
+    pub schema_version: u32,
+    pub updated_at: String,
+    pub durations: Vec<DurationBests>,
+}
+
+/// A best beaten by a newly added ride
+#[derive(Debug, Clone, Serialize)]
+pub struct NewBest {
+    pub id: ActivityId,
+    pub duration: usize,
+    pub watts: f64,
+    /// None for an all-time best
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub season: Option<i32>,
+    /// The best it beat, None if nothing was long enough before
+    pub previous: Option<f64>,
+}
+
+impl Default for BestEfforts {
+    fn default() -> Self {
+        BestEfforts {
+            schema_version: format::SCHEMA_VERSION,
+            updated_at: String::new(),
+            durations: DURATIONS.iter().map(|&duration| DurationBests { duration, all_time: None, seasons: BTreeMap::new() }).collect(),
+        }
+    }
+}
+
+/// "5s", "5m", "1h"; `90` as 1m30 and `5400` as 1h30
+pub fn label(seconds: usize) -> String {
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 && s % 60 == 0 => format!("{}m", s / 60),
+        s if s < 3600 => format!("{}m{:02}", s / 60, s % 60),
+        s if s % 3600 == 0 => format!("{}h", s / 3600),
+        s => cli::hours_minutes(s as i32),
+    }
+}
+
+/// The best effort of one ride at each of `DURATIONS`
+fn efforts(file: &ActivityWithStreams) -> Vec<Option<Effort>> {
+    let streams = file.streams.as_ref();
+    let watts = streams.and_then(|s| s.watts.as_deref()).unwrap_or_default();
+    let time = streams.and_then(|s| s.time.as_deref()).filter(|t| t.len() == watts.len());
+    DURATIONS
+        .iter()
+        .map(|&duration| {
+            let (power, start) = metrics::mean_maximal_power_at(watts, duration)?;
+            Some(Effort {
+                watts: power.round(),
+                id: file.activity.id,
+                start_date: file.activity.start_date.clone(),
+                offset: time.map_or(start as i32, |t| t[start]),
+            })
+        })
+        .collect()
+}
+
+impl BestEfforts {
+    /// The stored table, or one built from the archive when there is none yet
+    pub fn load(index: &ActivityIndex, config: &Config) -> Result<Self, Box<dyn Error>> {
+        match fs::read_to_string(format::best_efforts_path()) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::rebuild(index, config, &[])),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Scan every ride that counts for records but those in `skip`
+    pub fn rebuild(index: &ActivityIndex, config: &Config, skip: &[ActivityId]) -> Self {
+        Self::scan(index, config, |a| !skip.contains(&a.id))
+    }
+
+    /// Scan the rides counting for records that `keep` accepts
+    fn scan(index: &ActivityIndex, config: &Config, keep: impl Fn(&ActivitySummary) -> bool) -> Self {
+        let mut table = BestEfforts::default();
+        for summary in index.activities.iter().filter(|a| config.counts_for_records(a) && !a.is_run() && keep(a)) {
+            match load_activity_file(summary.id) {
+                Ok(file) => {
+                    table.add(&file);
+                }
+                Err(e) => warn!("   ⚠️  Skipping {}: {}", summary.id, e),
+            }
+        }
+        table
+    }
+
+    /// Count a ride, returning the bests it beat
+    pub fn add(&mut self, file: &ActivityWithStreams) -> Vec<NewBest> {
+        let mut beaten = Vec::new();
+        // Seasons are local calendar years, like the rest of the date grouping
+        let season = file.activity.local_start().map(|start| start.year());
+        for (bests, effort) in self.durations.iter_mut().zip(efforts(file)) {
+            let Some(effort) = effort else { continue };
+            let previous = bests.all_time.as_ref().map(|b| b.watts);
+            if previous.is_none_or(|p| effort.watts > p) {
+                beaten.push(NewBest { id: effort.id, duration: bests.duration, watts: effort.watts, season: None, previous });
+                bests.all_time = Some(effort.clone());
+            }
+            if let Some(year) = season {
+                let previous = bests.seasons.get(&year).map(|b| b.watts);
+                if previous.is_none_or(|p| effort.watts > p) {
+                    beaten.push(NewBest { id: effort.id, duration: bests.duration, watts: effort.watts, season: Some(year), previous });
+                    bests.seasons.insert(year, effort);
+                }
+            }
+        }
+        beaten
+    }
+
+    pub fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        self.schema_version = format::SCHEMA_VERSION;
+        self.updated_at = chrono::Utc::now().to_rfc3339();
+        write_atomic(&format::best_efforts_path(), serde_json::to_string_pretty(self)?.as_bytes())
+    }
+}
+
+/// Add the newly synced rides to the table and log the bests they set;
+/// suspect rides don't compete unless `include_suspect` is set
+pub fn update(index: &ActivityIndex, added: &[ActivityWithStreams], config: &Config) -> Result<Vec<NewBest>, Box<dyn Error>> {
+    let added: Vec<&ActivityWithStreams> = added
+        .iter()
+        .filter(|f| index.activities.iter().any(|a| a.id == f.activity.id && config.counts_for_records(a) && !a.is_run()))
+        .collect();
+    let path = format::best_efforts_path();
+    let mut table = if path.exists() {
+        BestEfforts::load(index, config)?
+    } else {
+        // First run: the rest of the archive is what the new rides compete with
+        BestEfforts::rebuild(index, config, &added.iter().map(|f| f.activity.id).collect::<Vec<_>>())
+    };
+    let mut beaten: Vec<NewBest> = Vec::new();
+    for file in added {
+        for best in table.add(file) {
+            // A later ride of the same sync beating an earlier one replaces it
+            beaten.retain(|b| !(b.duration == best.duration && b.season == best.season));
+            beaten.push(best);
+        }
+    }
+    for best in &beaten {
+        let previous = best.previous.map(|p| format!(", was {:.0} W", p)).unwrap_or_default();
+        match best.season {
+            None => info!("🏆 New all-time {} best: {:.0} W ({}{})", label(best.duration), best.watts, best.id, previous),
+            Some(year) => info!("🥇 New {} season {} best: {:.0} W ({}{})", year, label(best.duration), best.watts, best.id, previous),
+        }
+    }
+    table.save()?;
+    Ok(beaten)
+}
+
+#[derive(Debug, Serialize)]
+struct Row {
+    duration: usize,
+    all_time: Option<Effort>,
+    season: Option<Effort>,
+}
+
+/// Print the all-time and `season` bests (this year by default), rebuilding
+/// the table first if asked
+pub fn run(rebuild: bool, season: Option<&str>, output: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let index = ActivityIndex::load()?;
+    let config = Config::current()?;
+    let season = match season {
+        Some(name) => Season::resolve(&config, name)?,
+        None => Season::calendar_year(chrono::Datelike::year(&chrono::Utc::now())).ok_or("invalid current year")?,
+    };
+    let mut table = if rebuild { BestEfforts::rebuild(&index, &config, &[]) } else { BestEfforts::load(&index, &config)? };
+    if rebuild || !format::best_efforts_path().exists() {
+        table.save()?;
+    }
+    let season_bests: Vec<Option<Effort>> = match season.year() {
+        Some(year) => table.durations.iter().map(|d| d.seasons.get(&year).cloned()).collect(),
+        None => BestEfforts::scan(&index, &config, |a| season.includes(a)).durations.into_iter().map(|d| d.all_time).collect(),
+    };
+    let rows: Vec<Row> = table
+        .durations
+        .iter()
+        .zip(season_bests)
+        .map(|(d, season)| Row { duration: d.duration, all_time: d.all_time.clone(), season })
+        .collect();
+    if output == OutputFormat::Json {
+        return cli::print_json(&rows);
+    }
+
+    info!("🏆 Best efforts, all time and {} season ({} to {})", season.name, season.start, season.end);
+    let header = format!("  {:>4}  {:>6} {:<10} {:<16} {:>7}   {:>6} {:<10} {:<16}", "", "W", "date", "id", "at", "W", "date", "id");
+    println!("{}", header.trim_end());
+    let cells = |effort: Option<&Effort>, with_offset: bool| match effort {
+        Some(e) => {
+            let mut cell = format!("{:>6.0} {:<10} {:<16}", e.watts, e.start_date.get(..10).unwrap_or(&e.start_date), e.id.to_string());
+            if with_offset {
+                cell.push_str(&format!(" {:>7}", format!("{}:{:02}:{:02}", e.offset / 3600, (e.offset % 3600) / 60, e.offset % 60)));
+            }
+            cell
+        }
+        None => format!("{:>6} {:<10} {:<16}{}", "-", "", "", if with_offset { format!(" {:>7}", "") } else { String::new() }),
+    };
+    for row in &rows {
+        println!("  {:>4}  {}   {}", label(row.duration), cells(row.all_time.as_ref(), true), cells(row.season.as_ref(), false).trim_end());
+    }
+    Ok(())
+}
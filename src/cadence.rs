@@ -0,0 +1,197 @@
+//! Cadence profile of a ride: time in each 10 rpm bin, time coasting at 0 rpm
+//! and the self-selected cadence in each 50 W power band. It is stored on the
+//! index entry at sync, like EF and the route, so `cadence` and `report` can
+//! sum it up over any period without reading the streams again.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::ascii_chart;
+use crate::cli::{self, OutputFormat};
+use crate::config::Config;
+use crate::metrics;
+use crate::model::{ActivityIndex, ActivitySummary, ActivityWithStreams};
+use crate::pipeline::Analyzer;
+use crate::storage::load_activity_file;
+
+/// Width of a histogram bin
+pub const BIN_RPM: i32 = 10;
+
+/// Width of a power band
+pub const BAND_WATTS: f64 = 50.0;
+
+/// Cadences above this are sensor glitches
+const MAX_RPM: i32 = 200;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CadenceProfile {
+    /// Average while pedalling, rpm
+    pub average: f64,
+    /// Seconds pedalling
+    pub pedalling: i32,
+    /// Seconds at 0 rpm
+    pub coasting: i32,
+    /// Seconds pedalling in each bin, by its lower bound in rpm
+    pub histogram: BTreeMap<i32, i32>,
+    /// Cadence while pedalling in each power band, by its lower bound in watts
+    pub by_power: BTreeMap<u32, BandCadence>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct BandCadence {
+    pub seconds: i32,
+    /// Average, rpm
+    pub cadence: f64,
+}
+
+impl CadenceProfile {
+    /// Share of the ride spent coasting, in percent
+    pub fn coasting_percent(&self) -> Option<f64> {
+        let total = self.pedalling + self.coasting;
+        (total > 0).then(|| self.coasting as f64 / total as f64 * 100.0)
+    }
+
+    /// Sum profiles, weighting averages by the time behind them
+    pub fn combine<'a>(profiles: impl IntoIterator<Item = &'a CadenceProfile>) -> Option<CadenceProfile> {
+        let mut total = CadenceProfile::default();
+        let mut revolutions = 0.0;
+        let mut band_revolutions: BTreeMap<u32, f64> = BTreeMap::new();
+        let mut any = false;
+        for profile in profiles {
+            any = true;
+            total.pedalling += profile.pedalling;
+            total.coasting += profile.coasting;
+            revolutions += profile.average * profile.pedalling as f64;
+            for (&bin, &seconds) in &profile.histogram {
+                *total.histogram.entry(bin).or_default() += seconds;
+            }
+            for (&band, cadence) in &profile.by_power {
+                total.by_power.entry(band).or_default().seconds += cadence.seconds;
+                *band_revolutions.entry(band).or_default() += cadence.cadence * cadence.seconds as f64;
+            }
+        }
+        if !any {
+            return None;
+        }
+        total.average = metrics::round1(revolutions / total.pedalling.max(1) as f64);
+        for (band, cadence) in total.by_power.iter_mut() {
+            cadence.cadence = metrics::round1(band_revolutions[band] / cadence.seconds.max(1) as f64);
+        }
+        Some(total)
+    }
+}
+
+/// The cadence profile of a ride, None without a cadence stream
+pub fn profile(file: &ActivityWithStreams) -> Option<CadenceProfile> {
+    let streams = file.streams.as_ref()?;
+    let cadence = streams.cadence.as_deref().filter(|c| !c.is_empty())?;
+    let watts = streams.watts.as_deref().filter(|w| w.len() == cadence.len());
+
+    let mut profile = CadenceProfile::default();
+    let mut revolutions = 0.0;
+    let mut band_revolutions: BTreeMap<u32, f64> = BTreeMap::new();
+    for (i, &rpm) in cadence.iter().enumerate() {
+        if rpm <= 0 {
+            profile.coasting += 1;
+            continue;
+        }
+        if rpm > MAX_RPM {
+            continue;
+        }
+        profile.pedalling += 1;
+        revolutions += rpm as f64;
+        *profile.histogram.entry(rpm / BIN_RPM * BIN_RPM).or_default() += 1;
+        if let Some(power) = watts.map(|w| w[i]).filter(|&p| p > 0.0) {
+            let band = ((power / BAND_WATTS).floor() * BAND_WATTS) as u32;
+            profile.by_power.entry(band).or_default().seconds += 1;
+            *band_revolutions.entry(band).or_default() += rpm as f64;
+        }
+    }
+    if profile.pedalling == 0 {
+        return None;
+    }
+    profile.average = metrics::round1(revolutions / profile.pedalling as f64);
+    for (band, cadence) in profile.by_power.iter_mut() {
+        cadence.cadence = metrics::round1(band_revolutions[band] / cadence.seconds as f64);
+    }
+    Some(profile)
+}
+
+/// Store the cadence profile on an index entry; runs have steps, not pedal strokes
+pub fn annotate(summary: &mut ActivitySummary, file: &ActivityWithStreams) {
+    summary.cadence = if summary.is_run() { None } else { profile(file) };
+}
+
+/// Cadence profile on the index entry
+pub struct Cadence;
+
+impl Analyzer for Cadence {
+    fn name(&self) -> &'static str {
+        "cadence"
+    }
+
+    fn analyze(&self, file: &mut ActivityWithStreams, summary: &mut ActivitySummary, _config: &Config) {
+        annotate(summary, file);
+    }
+}
+
+/// The profile of every counted ride since `since`, and how many rides had one
+pub fn aggregate(index: &ActivityIndex, since: Option<NaiveDate>) -> (Option<CadenceProfile>, usize) {
+    let profiles: Vec<&CadenceProfile> = index
+        .activities
+        .iter()
+        .filter(|a| a.counted() && since.is_none_or(|s| a.local_date() >= s))
+        .filter_map(|a| a.cadence.as_ref())
+        .collect();
+    (CadenceProfile::combine(profiles.iter().copied()), profiles.len())
+}
+
+/// Histogram bars in minutes, labelled by bin
+pub fn histogram_bars(profile: &CadenceProfile) -> Vec<(String, f64)> {
+    profile
+        .histogram
+        .iter()
+        .map(|(&bin, &seconds)| (format!("{}-{}", bin, bin + BIN_RPM - 1), seconds as f64 / 60.0))
+        .collect()
+}
+
+/// Recompute the profiles of the archive and print their sum since `since`
+pub fn run(since: Option<NaiveDate>, output: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let mut index = ActivityIndex::load()?;
+    for summary in index.activities.iter_mut() {
+        match load_activity_file(summary.id) {
+            Ok(file) => annotate(summary, &file),
+            Err(e) => warn!("   ⚠️  Skipping {}: {}", summary.id, e),
+        }
+    }
+    index.save()?;
+
+    let (total, rides) = aggregate(&index, since);
+    if output == OutputFormat::Json {
+        return cli::print_json(&total);
+    }
+    let Some(total) = total else {
+        info!("🦵 No rides with cadence");
+        return Ok(());
+    };
+    info!("🦵 Cadence over {} rides: {:.0} rpm average pedalling, {:.1}% coasting ({})",
+        rides,
+        total.average,
+        total.coasting_percent().unwrap_or(0.0),
+        cli::hours_minutes(total.coasting)
+    );
+    println!("  rpm (minutes)");
+    for line in ascii_chart::bar_chart(&histogram_bars(&total), ascii_chart::width(), 0, "min") {
+        println!("  {}", line);
+    }
+    println!();
+    println!("  {:>9} {:>8} {:>6}", "power", "minutes", "rpm");
+    for (band, cadence) in &total.by_power {
+        println!("  {:>9} {:>8} {:>6.0}", format!("{}-{}W", band, band + BAND_WATTS as u32 - 1), cadence.seconds / 60, cadence.cadence);
+    }
+    Ok(())
+}
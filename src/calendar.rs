@@ -0,0 +1,120 @@
+//! iCalendar feed of the archive: one event per counted activity, lasting its
+//! moving time, with distance, power, heart rate and TSS in the description.
+//! `calendar` writes it to data/calendar.ics, `publish` puts it next to the
+//! JSON API and `serve-metrics` serves it on /calendar.ics, so a calendar app
+//! can subscribe to the URL and pick up new rides by itself.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Utc};
+use tracing::info;
+
+use crate::cli;
+use crate::config::Config;
+use crate::escape;
+use crate::format;
+use crate::model::{ActivityIndex, ActivitySummary};
+use crate::storage::write_atomic;
+use crate::training_load;
+
+pub const CALENDAR_FILE: &str = "calendar.ics";
+
+/// Lines longer than this many bytes are folded (RFC 5545)
+const MAX_LINE: usize = 75;
+
+pub fn default_path() -> PathBuf {
+    format::data_dir().join(CALENDAR_FILE)
+}
+
+/// Append `line` folded into chunks of at most `MAX_LINE` bytes, CRLF-terminated
+fn push_line(out: &mut String, line: &str) {
+    let mut start = 0;
+    let mut limit = MAX_LINE;
+    while line.len() - start > limit {
+        let mut end = start + limit;
+        while !line.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.push_str(&line[start..end]);
+        out.push_str("\r\n ");
+        start = end;
+        // Continuation lines start with the space
+        limit = MAX_LINE - 1;
+    }
+    out.push_str(&line[start..]);
+    out.push_str("\r\n");
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn description(summary: &ActivitySummary, config: &Config) -> String {
+    let mut lines = vec![format!(
+        "{:.1} km in {}",
+        summary.distance / 1000.0,
+        cli::hours_minutes(summary.moving_time)
+    )];
+    if let Some(watts) = summary.average_watts {
+        lines.push(format!("Average power: {:.0} W", watts));
+    }
+    if let Some(hr) = summary.average_heartrate {
+        lines.push(format!("Average heart rate: {:.0} bpm", hr));
+    }
+    if let Some(load) = training_load::ride_load(summary, config) {
+        lines.push(format!("Training load: {:.0}", load));
+    }
+    if let Some(kind) = &summary.workout_type {
+        lines.push(format!("Type: {}", kind));
+    }
+    if let Some(route) = &summary.route {
+        lines.push(format!("Route: {}", route));
+    }
+    if let Some(notes) = &summary.notes {
+        lines.push(notes.clone());
+    }
+    if let Ok(id) = summary.id.strava_id() {
+        lines.push(format!("https://www.strava.com/activities/{}", id));
+    }
+    lines.join("\n")
+}
+
+/// The feed as an .ics document
+pub fn render(index: &ActivityIndex, config: &Config) -> String {
+    let mut ics = String::new();
+    for line in ["BEGIN:VCALENDAR", "VERSION:2.0", "PRODID:-//Watts Happening//Activities//EN", "CALSCALE:GREGORIAN", "METHOD:PUBLISH", "X-WR-CALNAME:Watts Happening"] {
+        push_line(&mut ics, line);
+    }
+    let stamp = timestamp(Utc::now());
+    for summary in index.activities.iter().filter(|a| a.counted()) {
+        let start = summary.start_date;
+        push_line(&mut ics, "BEGIN:VEVENT");
+        push_line(&mut ics, &format!("UID:{}@watts-happening", summary.id));
+        push_line(&mut ics, &format!("DTSTAMP:{}", stamp));
+        push_line(&mut ics, &format!("DTSTART:{}", timestamp(start)));
+        push_line(&mut ics, &format!("DTEND:{}", timestamp(start + Duration::seconds(summary.moving_time.max(60) as i64))));
+        push_line(&mut ics, &format!("SUMMARY:{}", escape::ics_text(&format!("🚴 {} ({:.1} km)", summary.name, summary.distance / 1000.0))));
+        push_line(&mut ics, &format!("DESCRIPTION:{}", escape::ics_text(&description(summary, config))));
+        if let Ok(id) = summary.id.strava_id() {
+            push_line(&mut ics, &format!("URL:https://www.strava.com/activities/{}", id));
+        }
+        push_line(&mut ics, "END:VEVENT");
+    }
+    push_line(&mut ics, "END:VCALENDAR");
+    ics
+}
+
+/// Write the feed to `output` (data/calendar.ics by default)
+pub fn run(output: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let index = ActivityIndex::load()?;
+    let config = Config::current()?;
+    let path = output.map(Path::to_path_buf).unwrap_or_else(default_path);
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    write_atomic(&path, render(&index, &config).as_bytes())?;
+    info!("📆 Calendar of {} activities written to {}", index.activities.iter().filter(|a| a.counted()).count(), path.display());
+    Ok(())
+}
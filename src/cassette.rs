@@ -0,0 +1,86 @@
+//! Record and replay of raw Strava API responses (`WATTS_CASSETTE`).
+//!
+//! `record:DIR` saves every successful response body under DIR as it arrives;
+//! `replay:DIR` answers every request from DIR without touching the network.
+//! Responses are laid out like the `mock-strava` fixtures, so a recording can be
+//! served by either: `<path>.json`, or `<path>/page-<n>.json` for paged
+//! endpoints. Tokens and the athlete in recorded OAuth responses are dropped.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tracing::debug;
+
+use crate::storage::write_atomic;
+
+#[derive(Debug, Clone)]
+pub enum Mode {
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+/// The cassette mode from `WATTS_CASSETTE`, None when unset
+pub fn mode() -> Result<Option<Mode>, String> {
+    let Ok(value) = std::env::var("WATTS_CASSETTE") else { return Ok(None) };
+    match value.split_once(':') {
+        Some(("record", dir)) if !dir.is_empty() => Ok(Some(Mode::Record(PathBuf::from(dir)))),
+        Some(("replay", dir)) if !dir.is_empty() => Ok(Some(Mode::Replay(PathBuf::from(dir)))),
+        _ => Err(format!("invalid WATTS_CASSETTE '{}', expected record:DIR or replay:DIR", value)),
+    }
+}
+
+/// Where the response to `path` (an URL path such as /api/v3/athlete/activities) lives
+pub fn file_for(dir: &Path, path: &str, query: &[(&str, &str)]) -> Option<PathBuf> {
+    let path = path.trim_matches('/');
+    if path.is_empty() || path.split('/').any(|part| part == "..") {
+        return None;
+    }
+    Some(match query.iter().find(|(key, _)| *key == "page") {
+        Some((_, page)) => dir.join(path).join(format!("page-{}.json", page)),
+        None => dir.join(format!("{}.json", path)),
+    })
+}
+
+/// The path part of a request URL
+fn url_path(url: &str) -> Result<String, Box<dyn Error>> {
+    Ok(reqwest::Url::parse(url)?.path().to_string())
+}
+
+/// A recorded response; past the last recorded page a paged endpoint answers `[]`
+pub fn replay(dir: &Path, url: &str, query: &[(&str, &str)]) -> Result<String, Box<dyn Error>> {
+    let path = url_path(url)?;
+    let file = file_for(dir, &path, query).ok_or_else(|| format!("no cassette entry for {}", path))?;
+    debug!("   📼 Replaying {}", file.display());
+    match fs::read_to_string(&file) {
+        Ok(body) => Ok(body),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound && query.iter().any(|(key, _)| *key == "page") => Ok("[]".to_string()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(format!("no recorded response for {} in {}", path, dir.display()).into()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Save a response body as received
+pub fn record(dir: &Path, url: &str, query: &[(&str, &str)], body: &str) -> Result<(), Box<dyn Error>> {
+    let path = url_path(url)?;
+    let Some(file) = file_for(dir, &path, query) else { return Ok(()) };
+    let body = if path.ends_with("/oauth/token") { redact_tokens(body)? } else { body.to_string() };
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    debug!("   📼 Recording {}", file.display());
+    write_atomic(&file, body.as_bytes())
+}
+
+fn redact_tokens(body: &str) -> Result<String, Box<dyn Error>> {
+    let mut json: serde_json::Value = serde_json::from_str(body)?;
+    if let Some(object) = json.as_object_mut() {
+        if object.contains_key("access_token") {
+            object.insert("access_token".to_string(), "recorded".into());
+        }
+        // Without a refresh token, replay never looks like a rotation
+        object.remove("refresh_token");
+        object.remove("athlete");
+    }
+    Ok(serde_json::to_string_pretty(&json)?)
+}
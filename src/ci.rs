@@ -0,0 +1,117 @@
+//! `--ci`: for the scheduled GitHub Action that commits data/ back to the repo.
+//!
+//! Every file a run writes or removes under the data directory is tracked, and
+//! at the end two files are left in `.watts-ci/` for the commit step:
+//!
+//! ```text
+//! changed_files.txt   "A|M|D path" per changed file, sorted
+//! commit_message.txt  a subject such as "sync: 3 new activities", then the rides
+//! ```
+//!
+//! With `GITHUB_OUTPUT` set, `changed` (file count) and `commit_subject` are
+//! also exported as step outputs.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use tracing::info;
+
+use crate::format;
+
+const CI_DIR: &str = ".watts-ci";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Touched paths, and whether each existed before the run touched it
+static TOUCHED: Mutex<BTreeMap<PathBuf, bool>> = Mutex::new(BTreeMap::new());
+
+/// Subject and body lines of the suggested commit message
+static MESSAGE: Mutex<Option<(String, Vec<String>)>> = Mutex::new(None);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Files the workflow commits: the active archive, and state shared by all athletes
+fn tracked(path: &Path) -> bool {
+    path.starts_with(format::data_dir()) || path.starts_with(format::DATA_DIR)
+}
+
+/// Note that `path` is about to be replaced by `contents`; rewrites with the same bytes don't count
+pub fn record_write(path: &Path, contents: &[u8]) {
+    if !enabled() || !tracked(path) {
+        return;
+    }
+    let previous = fs::read(path).ok();
+    if previous.as_deref() == Some(contents) {
+        return;
+    }
+    TOUCHED.lock().unwrap_or_else(|e| e.into_inner()).entry(path.to_path_buf()).or_insert(previous.is_some());
+}
+
+/// Note that `path` is about to be removed
+pub fn record_removal(path: &Path) {
+    if !enabled() || !tracked(path) {
+        return;
+    }
+    TOUCHED.lock().unwrap_or_else(|e| e.into_inner()).entry(path.to_path_buf()).or_insert(path.exists());
+}
+
+/// Suggest a commit message; without one, it names the command and the file count
+pub fn describe(subject: String, body: Vec<String>) {
+    *MESSAGE.lock().unwrap_or_else(|e| e.into_inner()) = Some((subject, body));
+}
+
+/// Write the changed-files manifest and commit message for `command`
+pub fn finish(command: &str) -> Result<(), Box<dyn Error>> {
+    let changes: Vec<(char, PathBuf)> = TOUCHED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter_map(|(path, existed)| match (*existed, path.exists()) {
+            (false, true) => Some(('A', path.clone())),
+            (true, true) => Some(('M', path.clone())),
+            (true, false) => Some(('D', path.clone())),
+            // Written and removed again, like the sync journal
+            (false, false) => None,
+        })
+        .collect();
+    let (subject, body) = MESSAGE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| match changes.len() {
+            0 => (format!("{}: no changes", command), Vec::new()),
+            n => (format!("{}: update {} files", command, n), Vec::new()),
+        });
+
+    let dir = Path::new(CI_DIR);
+    fs::create_dir_all(dir)?;
+    let manifest: String = changes.iter().map(|(kind, path)| format!("{} {}\n", kind, path.display())).collect();
+    fs::write(dir.join("changed_files.txt"), manifest)?;
+    let mut message = format!("{}\n", subject);
+    if !body.is_empty() {
+        message.push('\n');
+        for line in &body {
+            message.push_str(&format!("{}\n", line));
+        }
+    }
+    fs::write(dir.join("commit_message.txt"), message)?;
+
+    if let Ok(output) = std::env::var("GITHUB_OUTPUT") {
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(output)?;
+        writeln!(file, "changed={}", changes.len())?;
+        writeln!(file, "commit_subject={}", subject)?;
+    }
+    info!("🤖 {} changed files, suggested commit: {}", changes.len(), subject);
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use chrono::NaiveDate;
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::config::Config;
+use crate::intervals;
+use crate::metrics;
+use crate::model::{ActivityIndex, ActivitySummary, ActivityWithStreams};
+use crate::pipeline::Analyzer;
+use crate::storage::load_activity_file;
+
+pub const WORKOUT_TYPES: [&str; 5] = ["recovery", "endurance", "tempo", "vo2", "race"];
+
+/// Words Zwift and organisers put in event names
+const RACE_KEYWORDS: [&str; 5] = ["race", "zrl", "crit", "tt ", "chase"];
+
+/// Label a ride from its intensity, power zone distribution and interval structure.
+/// Returns None when there is no power data or no FTP to compare against.
+pub fn classify(file: &ActivityWithStreams, config: &Config) -> Option<&'static str> {
+    let ftp = config.ftp()?;
+    let floors = config.power_zone_floors()?;
+    let watts = file.streams.as_ref()?.watts.as_deref()?;
+    let np = metrics::normalized_power(watts)?;
+    let intensity = np / ftp;
+
+    let name = file.activity.name.to_lowercase();
+    if RACE_KEYWORDS.iter().any(|k| name.contains(k)) {
+        return Some("race");
+    }
+
+    // Zones are Coggan-style: Z3 (index 2) is tempo, Z5 (index 4) and up is above threshold
+    let in_zones = metrics::time_in_zones(watts, &floors);
+    let share_from = |zone: usize| in_zones.iter().skip(zone).sum::<i32>() as f64 / watts.len() as f64;
+    let above_tempo = share_from(2);
+    let above_threshold = share_from(4);
+
+    // Repeated short efforts over threshold are the signature of VO2 work
+    let (_, efforts) = intervals::intervals(file);
+    let hard_efforts = efforts
+        .iter()
+        .filter(|i| i.duration <= 8 * 60 && i.average_watts.is_some_and(|w| w >= 1.06 * ftp))
+        .count();
+
+    let label = if intensity < 0.65 && above_tempo < 0.05 {
+        "recovery"
+    } else if hard_efforts >= 3 && above_threshold >= 0.08 {
+        "vo2"
+    } else if intensity >= 0.76 || above_tempo >= 0.25 {
+        "tempo"
+    } else {
+        "endurance"
+    };
+    Some(label)
+}
+
+/// Ride type label on the index entry
+pub struct Classify;
+
+impl Analyzer for Classify {
+    fn name(&self) -> &'static str {
+        "classify"
+    }
+
+    fn inputs(&self, config: &Config) -> Value {
+        json!([config.ftp(), config.power_zone_floors()])
+    }
+
+    fn analyze(&self, file: &mut ActivityWithStreams, summary: &mut ActivitySummary, config: &Config) {
+        summary.workout_type = classify(file, config).map(String::from);
+    }
+}
+
+/// Re-label every stored activity and print how many of each type fall in the range
+pub fn run(since: Option<NaiveDate>, until: Option<NaiveDate>) -> Result<(), Box<dyn Error>> {
+    let config = Config::current()?;
+    let ftp = config.ftp().ok_or("set \"ftp\" in watts.json (or fetch power zones) to classify rides")?;
+    let mut index = ActivityIndex::load()?;
+
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    let ids: Vec<ActivityId> = index.activities.iter().map(|a| a.id).collect();
+    for id in ids {
+        let file = match load_activity_file(id) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("   ⚠️  Skipping {}: {}", id, e);
+                continue;
+            }
+        };
+        let label = classify(&file, &config);
+        if let Some(summary) = index.get_mut(id) {
+            summary.workout_type = label.map(String::from);
+            let day = summary.local_date();
+            if since.is_none_or(|s| day >= s) && until.is_none_or(|u| day <= u) {
+                *counts.entry(label.unwrap_or("unclassified")).or_default() += 1;
+            }
+        }
+    }
+    index.save()?;
+
+    info!("🏷️  Classified rides with FTP {:.0} W", ftp);
+    for workout_type in WORKOUT_TYPES.iter().chain(["unclassified"].iter()) {
+        if let Some(count) = counts.get(workout_type) {
+            println!("  {:<12} {:>4}", workout_type, count);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,248 @@
+use std::error::Error;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::adjust;
+use crate::config::Config;
+use crate::metrics;
+use crate::model::{ActivityIndex, ActivityWithStreams};
+use crate::pipeline;
+use crate::storage::{load_activity_file, stored_compression, write_activity_file};
+
+/// Thresholds of the cleaning pass, under `"cleaning"` in watts.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleaningSettings {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Power above this is a spike, whatever its length
+    #[serde(default = "default_max_watts")]
+    pub max_watts: f64,
+    /// Zero-power runs up to this many samples between pedalling samples are dropouts
+    #[serde(default = "default_max_gap")]
+    pub max_gap: usize,
+    /// Power on both sides of a zero run must reach this for it to count as a dropout
+    /// rather than coasting
+    #[serde(default = "default_dropout_floor")]
+    pub dropout_floor: f64,
+    /// Heart rate outside this range is a strap artefact
+    #[serde(default = "default_min_heartrate")]
+    pub min_heartrate: i32,
+    #[serde(default = "default_max_heartrate")]
+    pub max_heartrate: i32,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_max_watts() -> f64 {
+    2000.0
+}
+
+fn default_max_gap() -> usize {
+    5
+}
+
+fn default_dropout_floor() -> f64 {
+    100.0
+}
+
+fn default_min_heartrate() -> i32 {
+    30
+}
+
+fn default_max_heartrate() -> i32 {
+    230
+}
+
+impl Default for CleaningSettings {
+    fn default() -> Self {
+        CleaningSettings {
+            enabled: default_enabled(),
+            max_watts: default_max_watts(),
+            max_gap: default_max_gap(),
+            dropout_floor: default_dropout_floor(),
+            min_heartrate: default_min_heartrate(),
+            max_heartrate: default_max_heartrate(),
+        }
+    }
+}
+
+/// What the cleaning pass repaired, stored in the activity file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CleaningReport {
+    pub cleaned_at: String,
+    pub power_spikes: usize,
+    pub power_dropouts: usize,
+    pub heartrate_gaps: usize,
+}
+
+/// Watts with spikes and short dropouts linearly interpolated, plus how many
+/// samples of each were repaired
+pub fn power(watts: &[f64], settings: &CleaningSettings) -> (Vec<f64>, usize, usize) {
+    let mut cleaned = watts.to_vec();
+    let (mut spikes, mut dropouts) = (0, 0);
+    let mut i = 0;
+    let spike = |w: f64| w > settings.max_watts;
+    while i < watts.len() {
+        if !spike(watts[i]) && watts[i] > 0.0 {
+            i += 1;
+            continue;
+        }
+        let end = (i..watts.len()).find(|&j| !spike(watts[j]) && watts[j] > 0.0).unwrap_or(watts.len());
+        let before = i.checked_sub(1).map(|j| cleaned[j]);
+        let after = watts.get(end).copied();
+        let run_spikes = watts[i..end].iter().filter(|&&w| spike(w)).count();
+        let flanked = before.is_some_and(|w| w >= settings.dropout_floor) && after.is_some_and(|w| w >= settings.dropout_floor);
+        if run_spikes > 0 || (flanked && end - i <= settings.max_gap) {
+            interpolate(&mut cleaned[i..end], before, after);
+            spikes += run_spikes;
+            dropouts += end - i - run_spikes;
+        }
+        i = end;
+    }
+    (cleaned, spikes, dropouts)
+}
+
+/// Heart rate with short out-of-range runs interpolated; longer ones are left
+/// untouched since the strap was really off
+pub fn heartrate(heartrate: &[i32], settings: &CleaningSettings) -> (Vec<i32>, usize) {
+    let valid = |hr: i32| (settings.min_heartrate..=settings.max_heartrate).contains(&hr);
+    let mut cleaned: Vec<f64> = heartrate.iter().map(|&hr| hr as f64).collect();
+    let mut gaps = 0;
+    let mut i = 0;
+    while i < heartrate.len() {
+        if valid(heartrate[i]) {
+            i += 1;
+            continue;
+        }
+        let end = (i..heartrate.len()).find(|&j| valid(heartrate[j])).unwrap_or(heartrate.len());
+        let before = i.checked_sub(1).map(|j| cleaned[j]);
+        let after = heartrate.get(end).map(|&hr| hr as f64);
+        if before.is_some() && after.is_some() && end - i <= settings.max_gap {
+            interpolate(&mut cleaned[i..end], before, after);
+            gaps += end - i;
+        }
+        i = end;
+    }
+    (cleaned.iter().map(|hr| hr.round() as i32).collect(), gaps)
+}
+
+/// Fill a run between two known samples; at either end of the stream, repeat the known side
+fn interpolate(run: &mut [f64], before: Option<f64>, after: Option<f64>) {
+    let (start, end) = match (before, after) {
+        (Some(b), Some(a)) => (b, a),
+        (Some(b), None) => (b, b),
+        (None, Some(a)) => (a, a),
+        (None, None) => (0.0, 0.0),
+    };
+    let steps = run.len() as f64 + 1.0;
+    for (k, sample) in run.iter_mut().enumerate() {
+        *sample = start + (end - start) * (k as f64 + 1.0) / steps;
+    }
+}
+
+/// Clean an activity's power and heart rate streams from the recorded data,
+/// keeping the raw streams alongside. Any power adjustment is re-applied on top.
+/// Returns whether anything was repaired.
+pub fn apply(file: &mut ActivityWithStreams, settings: &CleaningSettings) -> bool {
+    let Some(streams) = file.streams.as_mut() else { return false };
+    let mut report = CleaningReport {
+        cleaned_at: chrono::Utc::now().to_rfc3339(),
+        power_spikes: 0,
+        power_dropouts: 0,
+        heartrate_gaps: 0,
+    };
+
+    let mut power_changed = false;
+    if let Some(recorded) = streams.original_watts.take().or_else(|| streams.watts.clone()) {
+        let (cleaned, spikes, dropouts) = power(&recorded, settings);
+        report.power_spikes = spikes;
+        report.power_dropouts = dropouts;
+        let base = if spikes + dropouts > 0 { cleaned } else { recorded.clone() };
+        let watts = match &file.power_adjustment {
+            Some(adjustment) => adjust::corrected(&base, adjustment.scale, adjustment.offset),
+            None => base,
+        };
+        power_changed = streams.watts.as_ref() != Some(&watts);
+        if spikes + dropouts > 0 || file.power_adjustment.is_some() {
+            streams.original_watts = Some(recorded);
+        }
+        streams.watts = Some(watts);
+    }
+
+    if let Some(recorded) = streams.original_heartrate.take().or_else(|| streams.heartrate.clone()) {
+        let (cleaned, gaps) = heartrate(&recorded, settings);
+        report.heartrate_gaps = gaps;
+        if gaps > 0 {
+            streams.heartrate = Some(cleaned);
+            streams.original_heartrate = Some(recorded);
+        } else {
+            streams.heartrate = Some(recorded);
+        }
+    }
+
+    if power_changed {
+        metrics::recompute_power_summary(&mut file.activity, streams);
+    }
+    let repaired = report.power_spikes + report.power_dropouts + report.heartrate_gaps > 0;
+    file.cleaning = repaired.then_some(report);
+    repaired
+}
+
+/// Re-run the cleaning pass over the archive, e.g. after changing thresholds
+pub fn run(since: Option<NaiveDate>, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let config = Config::current()?;
+    let settings = config.cleaning.clone().unwrap_or_default();
+    let mut index = ActivityIndex::load()?;
+    let ids: Vec<_> = index
+        .activities
+        .iter()
+        .filter(|a| since.is_none_or(|s| a.local_date() >= s))
+        .map(|a| a.id)
+        .collect();
+
+    let mut cleaned = 0;
+    for id in ids {
+        let compression = match stored_compression(id) {
+            Ok(Some(compression)) => compression,
+            Ok(None) => {
+                warn!("   ⚠️  Skipping {}: not in the archive", id);
+                continue;
+            }
+            Err(e) => {
+                warn!("   ⚠️  Skipping {}: {}", id, e);
+                continue;
+            }
+        };
+        let mut file = match load_activity_file(id) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("   ⚠️  Skipping {}: {}", id, e);
+                continue;
+            }
+        };
+        let was_cleaned = file.cleaning.is_some();
+        if !apply(&mut file, &settings) && !was_cleaned {
+            continue;
+        }
+        if let Some(report) = &file.cleaning {
+            cleaned += 1;
+            info!("   🧹 {}: {} power spikes, {} dropouts, {} HR gaps",
+                file.activity.name, report.power_spikes, report.power_dropouts, report.heartrate_gaps);
+        }
+        if dry_run {
+            continue;
+        }
+        // Every metric derived from the streams has to see the repaired data
+        pipeline::run(pipeline::ANALYZERS, &mut file, &mut index, &config);
+        write_activity_file(&file, compression)?;
+    }
+    if !dry_run {
+        index.save()?;
+    }
+    info!("🧹 {} activities with repaired streams{}", cleaned, if dry_run { " (dry run)" } else { "" });
+    Ok(())
+}
@@ -0,0 +1,138 @@
+use std::error::Error;
+use std::io::Write as _;
+
+use serde::Serialize;
+use tracing::Level;
+
+use crate::logging::{self, LogFormat};
+
+/// Global command line options, plus whatever arguments remain for the command
+#[derive(Debug)]
+pub struct Cli {
+    pub log_level: Level,
+    pub log_format: LogFormat,
+    /// Athlete ID or name from watts.json, for multi-athlete setups
+    pub athlete: Option<String>,
+    /// Strava API server, for `mock-strava` and recorded fixtures
+    pub base_url: Option<String>,
+    /// Running in a scheduled job that commits data/ (see `ci`)
+    pub ci: bool,
+    pub args: Vec<String>,
+}
+
+impl Cli {
+    pub fn parse() -> Result<Self, String> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    pub fn parse_from(argv: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        // Environment provides the defaults, flags override them
+        let mut log_level = match std::env::var("WATTS_LOG") {
+            Ok(level) => logging::parse_level(&level)?,
+            Err(_) => Level::INFO,
+        };
+        let mut log_format = match std::env::var("WATTS_LOG_FORMAT") {
+            Ok(format) => LogFormat::parse(&format)?,
+            Err(_) => LogFormat::Pretty,
+        };
+        let mut athlete = None;
+        let mut base_url = None;
+        let mut ci = false;
+        let mut args = Vec::new();
+
+        let mut argv = argv.into_iter();
+        while let Some(arg) = argv.next() {
+            match arg.as_str() {
+                "-q" | "--quiet" => log_level = Level::WARN,
+                "-v" | "--verbose" => {
+                    log_level = if log_level >= Level::DEBUG { Level::TRACE } else { Level::DEBUG }
+                }
+                "--log-format" => {
+                    let value = argv.next().ok_or("--log-format requires a value")?;
+                    log_format = LogFormat::parse(&value)?;
+                }
+                "--athlete" => {
+                    athlete = Some(argv.next().ok_or("--athlete requires a value")?);
+                }
+                "--base-url" => {
+                    base_url = Some(argv.next().ok_or("--base-url requires a value")?);
+                }
+                "--ci" => ci = true,
+                _ => args.push(arg),
+            }
+        }
+
+        Ok(Cli { log_level, log_format, athlete, base_url, ci, args })
+    }
+}
+
+/// Remove `name <value>` from a command's arguments, returning the value
+pub fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    let Some(pos) = args.iter().position(|a| a == name) else { return Ok(None) };
+    if pos + 1 >= args.len() {
+        return Err(format!("{} requires a value", name));
+    }
+    let value = args.remove(pos + 1);
+    args.remove(pos);
+    Ok(Some(value))
+}
+
+/// Remove a boolean switch from a command's arguments, returning whether it was present
+pub fn take_switch(args: &mut Vec<String>, name: &str) -> bool {
+    let before = args.len();
+    args.retain(|a| a != name);
+    args.len() != before
+}
+
+/// Parse an option value, naming the option in the error
+pub fn parse_option<T: std::str::FromStr>(args: &mut Vec<String>, name: &str) -> Result<Option<T>, String> {
+    match take_option(args, name)? {
+        Some(value) => value.parse().map(Some).map_err(|_| format!("invalid value '{}' for {}", value, name)),
+        None => Ok(None),
+    }
+}
+
+/// How a command prints its result: tables for people, or one JSON object on
+/// stdout for scripts (logs stay on stderr either way)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            other => Err(format!("unknown output '{}', expected text or json", other)),
+        }
+    }
+
+    /// Remove `--output text|json` from a command's arguments, text if absent
+    pub fn take(args: &mut Vec<String>) -> Result<Self, String> {
+        take_option(args, "--output")?.map(|o| Self::parse(&o)).transpose().map(Option::unwrap_or_default)
+    }
+}
+
+/// Print a command result as pretty JSON on stdout
+pub fn print_json(value: &impl Serialize) -> Result<(), Box<dyn Error>> {
+    // An error rather than a panic when piped into `head`
+    writeln!(std::io::stdout().lock(), "{}", serde_json::to_string_pretty(value)?)?;
+    Ok(())
+}
+
+/// A duration as "m:ss", or "h:mm:ss" from an hour, as Strava shows times
+pub fn clock(seconds: i32) -> String {
+    if seconds >= 3600 {
+        format!("{}:{:02}:{:02}", seconds / 3600, seconds % 3600 / 60, seconds % 60)
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
+/// A duration as "1h05", for moving times and totals
+pub fn hours_minutes(seconds: i32) -> String {
+    format!("{}h{:02}", seconds / 3600, seconds % 3600 / 60)
+}
@@ -0,0 +1,238 @@
+//! Club leaderboard without a server: each rider exports an anonymous file of
+//! weekly totals (alias, rides, time, distance, TSS; no activity names, IDs,
+//! dates or routes) into a folder the club shares however it likes (a git
+//! repo, Dropbox, a USB stick), and `leaderboard` ranks everyone in it.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::cli::{self, OutputFormat};
+use crate::config::Config;
+use crate::format;
+use crate::model::ActivityIndex;
+use crate::storage::write_atomic;
+use crate::weekly;
+
+/// Folder of club files when watts.json doesn't set one
+const DEFAULT_DIR: &str = "club";
+
+/// Weeks an exported file covers
+const EXPORT_WEEKS: usize = 52;
+
+/// Weeks ranked by default, this one included
+pub const DEFAULT_WEEKS: usize = 4;
+
+/// Who you are to the club, under `"club"` in watts.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClubSettings {
+    /// Alias written into your file and shown in the ranking
+    pub name: String,
+    /// Shared folder of club files; `club/` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+}
+
+/// One rider's exported file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClubFile {
+    pub schema_version: u32,
+    pub name: String,
+    pub generated_at: String,
+    /// Oldest first
+    pub weeks: Vec<ClubWeek>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClubWeek {
+    /// Monday
+    pub week: NaiveDate,
+    pub rides: usize,
+    /// Seconds
+    pub moving_time: i32,
+    /// Metres
+    pub distance: f64,
+    /// None when the rider has no FTP set
+    pub tss: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RankBy {
+    Distance,
+    Time,
+    Tss,
+    Rides,
+}
+
+impl RankBy {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "distance" => Ok(RankBy::Distance),
+            "time" => Ok(RankBy::Time),
+            "tss" => Ok(RankBy::Tss),
+            "rides" => Ok(RankBy::Rides),
+            other => Err(format!("unknown ranking '{}', expected distance, time, tss or rides", other)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Standing {
+    pub rank: usize,
+    pub name: String,
+    /// Your own row, computed from the archive rather than read from a file
+    pub you: bool,
+    pub rides: usize,
+    pub moving_time: i32,
+    pub distance: f64,
+    pub tss: Option<f64>,
+    /// When the rider last exported
+    pub generated_at: String,
+}
+
+fn club_dir(config: &Config, dir: Option<&Path>) -> PathBuf {
+    dir.map(Path::to_path_buf)
+        .or_else(|| config.club.as_ref().and_then(|c| c.dir.clone()))
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_DIR))
+}
+
+/// Your alias: `--name`, else `club.name` in watts.json
+fn alias(config: &Config, name: Option<String>) -> Option<String> {
+    name.or_else(|| config.club.as_ref().map(|c| c.name.clone())).filter(|n| !n.trim().is_empty())
+}
+
+/// The last `EXPORT_WEEKS` weeks of the archive under `name`
+fn summarize(index: &ActivityIndex, config: &Config, name: &str) -> ClubFile {
+    let weeks = weekly::summarize(index, config);
+    ClubFile {
+        schema_version: format::SCHEMA_VERSION,
+        name: name.to_string(),
+        generated_at: Utc::now().to_rfc3339(),
+        weeks: weeks[weeks.len().saturating_sub(EXPORT_WEEKS)..]
+            .iter()
+            .filter_map(|w| {
+                Some(ClubWeek {
+                    week: w.week.parse().ok()?,
+                    rides: w.totals.rides,
+                    moving_time: w.totals.moving_time,
+                    distance: w.totals.distance,
+                    tss: w.totals.tss,
+                })
+            })
+            .collect(),
+    }
+}
+
+/// Write your weekly totals to `output`, or `<name>.json` in the club folder
+pub fn export(name: Option<String>, output: Option<&Path>, dir: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let config = Config::current()?;
+    let name = alias(&config, name).ok_or("no alias to export under: pass --name or set \"club\": { \"name\": ... } in watts.json")?;
+    let file = summarize(&ActivityIndex::load()?, &config, &name);
+    let path = match output {
+        Some(path) => path.to_path_buf(),
+        None => {
+            let dir = club_dir(&config, dir);
+            fs::create_dir_all(&dir)?;
+            dir.join(format!("{}.json", file_name(&name)))
+        }
+    };
+    write_atomic(&path, serde_json::to_string_pretty(&file)?.as_bytes())?;
+    info!("🤝 Exported {} weeks as \"{}\" to {}", file.weeks.len(), name, path.display());
+    Ok(())
+}
+
+/// An alias made safe as a file name
+fn file_name(name: &str) -> String {
+    name.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c.to_ascii_lowercase() } else { '_' }).collect()
+}
+
+/// Every readable club file in `dir`, skipping (with a warning) the rest
+fn load_all(dir: &Path) -> Result<Vec<ClubFile>, Box<dyn Error>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("{}: {}", dir.display(), e).into()),
+    };
+    let mut files = Vec::new();
+    for path in entries.filter_map(|e| e.ok().map(|e| e.path())).filter(|p| p.extension().is_some_and(|e| e == "json")) {
+        match fs::read_to_string(&path).map_err(|e| e.to_string()).and_then(|json| serde_json::from_str::<ClubFile>(&json).map_err(|e| e.to_string())) {
+            Ok(file) if file.schema_version != format::SCHEMA_VERSION => {
+                warn!("   ⚠️  Skipping {}: schema version {} (expected {})", path.display(), file.schema_version, format::SCHEMA_VERSION)
+            }
+            Ok(file) => files.push(file),
+            Err(e) => warn!("   ⚠️  Skipping {}: {}", path.display(), e),
+        }
+    }
+    Ok(files)
+}
+
+/// Rank the club folder's riders over the last `weeks` weeks, you included
+/// from the archive when you have an alias
+pub fn leaderboard(weeks: usize, by: RankBy, dir: Option<&Path>, output: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let config = Config::current()?;
+    let dir = club_dir(&config, dir);
+    let mut files: Vec<(ClubFile, bool)> = load_all(&dir)?.into_iter().map(|f| (f, false)).collect();
+    if let Some(name) = alias(&config, None) {
+        // Your exported file may be stale; the archive isn't
+        files.retain(|(f, _)| f.name != name);
+        files.push((summarize(&ActivityIndex::load()?, &config, &name), true));
+    }
+
+    let today = Utc::now().date_naive();
+    let since: NaiveDate = today - Duration::days(today.weekday().num_days_from_monday() as i64 + 7 * (weeks.max(1) as i64 - 1));
+    let mut standings: Vec<Standing> = files
+        .into_iter()
+        .map(|(file, you)| {
+            let recent: Vec<&ClubWeek> = file.weeks.iter().filter(|w| w.week >= since).collect();
+            let tss = recent.iter().filter_map(|w| w.tss).reduce(|a, b| a + b);
+            Standing {
+                rank: 0,
+                name: file.name,
+                you,
+                rides: recent.iter().map(|w| w.rides).sum(),
+                moving_time: recent.iter().map(|w| w.moving_time).sum(),
+                distance: recent.iter().map(|w| w.distance).sum::<f64>().round(),
+                tss: tss.map(f64::round),
+                generated_at: file.generated_at,
+            }
+        })
+        .collect();
+    let key = |s: &Standing| match by {
+        RankBy::Distance => s.distance,
+        RankBy::Time => s.moving_time as f64,
+        RankBy::Tss => s.tss.unwrap_or(0.0),
+        RankBy::Rides => s.rides as f64,
+    };
+    standings.sort_by(|a, b| key(b).total_cmp(&key(a)).then_with(|| a.name.cmp(&b.name)));
+    for (i, standing) in standings.iter_mut().enumerate() {
+        standing.rank = i + 1;
+    }
+
+    if output == OutputFormat::Json {
+        return cli::print_json(&standings);
+    }
+    if standings.is_empty() {
+        info!("🏁 No club files in {}; riders add theirs with `leaderboard export`", dir.display());
+        return Ok(());
+    }
+    info!("🏁 Club leaderboard since {} ({} weeks, {} riders from {})", since, weeks.max(1), standings.len(), dir.display());
+    println!("  {:>3}  {:<20} {:>5} {:>8} {:>6} {:>6}  exported", "#", "name", "rides", "km", "hours", "TSS");
+    for s in &standings {
+        let name = if s.you { format!("{} (you)", s.name) } else { s.name.clone() };
+        println!(
+            "  {:>3}  {:<20} {:>5} {:>8.1} {:>6.1} {:>6}  {}",
+            s.rank,
+            name.chars().take(20).collect::<String>(),
+            s.rides,
+            s.distance / 1000.0,
+            s.moving_time as f64 / 3600.0,
+            s.tss.map_or_else(|| "-".to_string(), |t| format!("{:.0}", t)),
+            if s.you { "now" } else { s.generated_at.get(..10).unwrap_or(&s.generated_at) }
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,224 @@
+//! A Strava club's recent activities, kept in `data/club/{club_id}.json` apart
+//! from your own archive, for the team's weekly volume.
+//!
+//! `/clubs/{id}/activities` gives neither an activity ID nor a date, so each
+//! entry is stamped with when it was first fetched and told apart from the
+//! others by athlete, name, distance and times. Weeks are only as exact as
+//! the fetches are frequent: sync fetches the feed on every run once
+//! `"strava_club"` is set in watts.json.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs;
+
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::cli::{self, OutputFormat};
+use crate::config::Config;
+use crate::format;
+use crate::storage::write_atomic;
+use crate::strava::{self, access_token};
+
+/// Activities per feed page, Strava's maximum
+const PER_PAGE: u32 = 200;
+
+/// Pages read per fetch; the feed only goes back a few hundred activities anyway
+const MAX_PAGES: u32 = 5;
+
+/// Weeks printed by default, this one included
+pub const DEFAULT_WEEKS: usize = 8;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClubArchive {
+    pub schema_version: u32,
+    pub club_id: u64,
+    pub updated_at: String,
+    /// Newest first
+    pub activities: Vec<ClubActivity>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClubActivity {
+    /// When a fetch first saw it, standing in for its date
+    pub first_seen: DateTime<Utc>,
+    /// First name and last initial, as Strava shows them to club members
+    pub athlete: String,
+    pub name: String,
+    pub sport_type: String,
+    /// Metres
+    pub distance: f64,
+    /// Seconds
+    pub moving_time: i32,
+    pub elapsed_time: i32,
+    /// Metres
+    pub total_elevation_gain: f64,
+}
+
+impl ClubActivity {
+    /// What tells two feed entries apart in the absence of an ID
+    fn key(&self) -> (String, String, u64, i32, i32) {
+        (self.athlete.clone(), self.name.clone(), self.distance.to_bits(), self.moving_time, self.elapsed_time)
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ClubWeek {
+    /// Monday
+    pub week: NaiveDate,
+    pub activities: usize,
+    /// Distinct athletes
+    pub athletes: usize,
+    pub distance: f64,
+    pub moving_time: i64,
+    pub elevation_gain: f64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ClubMember {
+    pub athlete: String,
+    pub activities: usize,
+    pub distance: f64,
+    pub moving_time: i64,
+    pub elevation_gain: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct ClubStats {
+    club_id: u64,
+    weeks: Vec<ClubWeek>,
+    /// Totals over the same weeks, most moving time first
+    members: Vec<ClubMember>,
+}
+
+fn load(club_id: u64) -> Result<ClubArchive, Box<dyn Error>> {
+    match fs::read_to_string(format::club_path(club_id)) {
+        Ok(json) => Ok(serde_json::from_str(&json)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            Ok(ClubArchive { schema_version: format::SCHEMA_VERSION, club_id, updated_at: String::new(), activities: Vec::new() })
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Fetch the club's feed and add the activities not seen before to
+/// data/club/{club_id}.json, returning how many were new
+pub async fn fetch(access_token: &str, club_id: u64) -> Result<usize, Box<dyn Error>> {
+    let mut archive = load(club_id)?;
+    let known: BTreeSet<_> = archive.activities.iter().map(ClubActivity::key).collect();
+    let now = Utc::now();
+    let mut new = Vec::new();
+    for page in 1..=MAX_PAGES {
+        let feed = strava::fetch_club_activities(access_token, club_id, page, PER_PAGE).await?;
+        let full = feed.len() == PER_PAGE as usize;
+        let mut reached_known = false;
+        for entry in feed {
+            let activity = ClubActivity {
+                first_seen: now,
+                athlete: format!("{} {}", entry.athlete.firstname, entry.athlete.lastname).trim().to_string(),
+                name: entry.name,
+                sport_type: entry.sport_type,
+                distance: entry.distance,
+                moving_time: entry.moving_time,
+                elapsed_time: entry.elapsed_time,
+                total_elevation_gain: entry.total_elevation_gain,
+            };
+            if known.contains(&activity.key()) {
+                reached_known = true;
+            } else {
+                new.push(activity);
+            }
+        }
+        // The feed is newest first: past the last fetch, the rest is known too
+        if reached_known || !full {
+            break;
+        }
+    }
+
+    let added = new.len();
+    new.append(&mut archive.activities);
+    archive.activities = new;
+    archive.schema_version = format::SCHEMA_VERSION;
+    archive.updated_at = now.to_rfc3339();
+    fs::create_dir_all(format::data_dir().join(format::CLUB_DIR))?;
+    write_atomic(&format::club_path(club_id), serde_json::to_string_pretty(&archive)?.as_bytes())?;
+    info!("👥 {} new activities from club {} ({} stored)", added, club_id, archive.activities.len());
+    Ok(added)
+}
+
+/// Weekly totals of the activities `sync` would keep (VirtualRide unless
+/// watts.json lists sports) since `since`, with each member's share
+fn stats(archive: &ClubArchive, config: &Config, since: NaiveDate) -> (Vec<ClubWeek>, Vec<ClubMember>) {
+    let monday = |d: NaiveDate| d - Duration::days(d.weekday().num_days_from_monday() as i64);
+    let mut weeks: BTreeMap<NaiveDate, (ClubWeek, BTreeSet<&str>)> = BTreeMap::new();
+    let mut members: BTreeMap<&str, ClubMember> = BTreeMap::new();
+    for activity in archive.activities.iter().filter(|a| config.syncs(&a.sport_type) && a.first_seen.date_naive() >= since) {
+        let week = monday(activity.first_seen.date_naive());
+        let (totals, athletes) = weeks.entry(week).or_insert_with(|| (ClubWeek { week, ..Default::default() }, BTreeSet::new()));
+        totals.activities += 1;
+        totals.distance += activity.distance;
+        totals.moving_time += activity.moving_time as i64;
+        totals.elevation_gain += activity.total_elevation_gain;
+        athletes.insert(&activity.athlete);
+
+        let member = members.entry(&activity.athlete).or_insert_with(|| ClubMember { athlete: activity.athlete.clone(), ..Default::default() });
+        member.activities += 1;
+        member.distance += activity.distance;
+        member.moving_time += activity.moving_time as i64;
+        member.elevation_gain += activity.total_elevation_gain;
+    }
+    let weeks = weeks.into_values().map(|(week, athletes)| ClubWeek { athletes: athletes.len(), ..week }).collect();
+    let mut members: Vec<ClubMember> = members.into_values().collect();
+    members.sort_by(|a, b| b.moving_time.cmp(&a.moving_time).then_with(|| a.athlete.cmp(&b.athlete)));
+    (weeks, members)
+}
+
+/// Print the club's weekly volume over the last `weeks` weeks, fetching the
+/// feed first with `fetch_first`; the club is `club_id` or `strava_club` in watts.json
+pub async fn run(club_id: Option<u64>, fetch_first: bool, weeks: usize, output: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let config = Config::current()?;
+    let club_id = club_id.or(config.strava_club).ok_or("no club: pass --club ID or set \"strava_club\" in watts.json")?;
+    if fetch_first {
+        fetch(&access_token().await?, club_id).await?;
+    }
+    let archive = load(club_id)?;
+    let today = Utc::now().date_naive();
+    let since = today - Duration::days(today.weekday().num_days_from_monday() as i64 + 7 * (weeks.max(1) as i64 - 1));
+    let (weeks, members) = stats(&archive, &config, since);
+    if output == OutputFormat::Json {
+        return cli::print_json(&ClubStats { club_id, weeks, members });
+    }
+    if archive.activities.is_empty() {
+        info!("👥 Nothing stored for club {} yet, run with --fetch", club_id);
+        return Ok(());
+    }
+    info!("👥 Club {} since {} ({} activities stored, last fetched {})", club_id, since, archive.activities.len(), archive.updated_at.get(..10).unwrap_or("never"));
+    println!("  {:<10} {:>10} {:>8} {:>8} {:>7} {:>7}", "week", "activities", "athletes", "km", "hours", "m up");
+    for week in &weeks {
+        println!(
+            "  {:<10} {:>10} {:>8} {:>8.1} {:>7.1} {:>7.0}",
+            week.week.to_string(),
+            week.activities,
+            week.athletes,
+            week.distance / 1000.0,
+            week.moving_time as f64 / 3600.0,
+            week.elevation_gain
+        );
+    }
+    if !members.is_empty() {
+        println!();
+        println!("  {:<20} {:>10} {:>8} {:>7} {:>7}", "athlete", "activities", "km", "hours", "m up");
+        for member in &members {
+            println!(
+                "  {:<20} {:>10} {:>8.1} {:>7.1} {:>7.0}",
+                member.athlete.chars().take(20).collect::<String>(),
+                member.activities,
+                member.distance / 1000.0,
+                member.moving_time as f64 / 3600.0,
+                member.elevation_gain
+            );
+        }
+    }
+    Ok(())
+}
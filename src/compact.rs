@@ -0,0 +1,25 @@
+use std::error::Error;
+use tracing::{info, warn};
+
+use crate::compression::Compression;
+use crate::storage::{list_activity_ids, load_activity_file, write_activity_file};
+
+/// Re-encode every stored activity file with the given compression
+pub fn run(target: Compression) -> Result<(), Box<dyn Error>> {
+    let ids = list_activity_ids()?;
+    info!("🗜️  Converting {} activity files to {}", ids.len(), target.name());
+
+    let mut converted = 0;
+    for id in ids {
+        match load_activity_file(id).and_then(|activity| write_activity_file(&activity, target)) {
+            Ok(()) => converted += 1,
+            Err(e) => warn!("   ⚠️  Could not convert {}: {}", id, e),
+        }
+    }
+
+    info!("💾 Converted {} files", converted);
+    if target != Compression::from_env()? {
+        info!("   Set WATTS_COMPRESSION={} so new activities use the same encoding", target.name());
+    }
+    Ok(())
+}
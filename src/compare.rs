@@ -0,0 +1,203 @@
+use std::error::Error;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use tracing::info;
+
+use crate::activity_id::ActivityId;
+use crate::cli::clock;
+use crate::metrics;
+use crate::model::ActivityWithStreams;
+use crate::resample;
+use crate::storage::{load_activity_file, write_atomic};
+
+/// Rows of the side-by-side segment table
+const SEGMENTS: usize = 10;
+
+/// Sampling step along the course when aligning by distance, in metres
+const DISTANCE_STEP: f64 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Axis {
+    Time,
+    Distance,
+}
+
+impl Axis {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "time" => Ok(Axis::Time),
+            "distance" => Ok(Axis::Distance),
+            _ => Err(format!("unknown alignment '{}' (expected time or distance)", s)),
+        }
+    }
+}
+
+/// One second of an activity, with the distance covered so far
+#[derive(Debug, Clone, Copy)]
+struct Point {
+    time: f64,
+    distance: f64,
+    watts: Option<f64>,
+    heartrate: Option<f64>,
+    speed: Option<f64>,
+}
+
+/// Axis value and the point of each activity found there
+type Pair<'a> = (f64, &'a Point, &'a Point);
+
+/// The activity resampled to 1 s, distance integrated from speed
+fn points(file: &ActivityWithStreams) -> Result<Vec<Point>, String> {
+    let streams = resample::resample(file.streams.as_ref().ok_or("activity has no streams")?);
+    let time = streams.time.as_deref().ok_or("activity has no time stream")?;
+    let value = |stream: Option<&Vec<f64>>, i: usize| stream.and_then(|s| s.get(i)).copied();
+    let mut distance = 0.0;
+    let mut points = Vec::with_capacity(time.len());
+    for (i, &t) in time.iter().enumerate() {
+        let speed = value(streams.velocity_smooth.as_ref(), i);
+        if i > 0 {
+            distance += speed.unwrap_or(0.0);
+        }
+        points.push(Point {
+            time: (t - time[0]) as f64,
+            distance,
+            watts: value(streams.watts.as_ref(), i),
+            heartrate: streams.heartrate.as_ref().and_then(|h| h.get(i)).map(|&hr| hr as f64),
+            speed,
+        });
+    }
+    Ok(points)
+}
+
+/// Points at the same elapsed time or the same distance
+fn align<'a>(a: &'a [Point], b: &'a [Point], axis: Axis) -> Vec<Pair<'a>> {
+    match axis {
+        Axis::Time => a.iter().zip(b).map(|(pa, pb)| (pa.time, pa, pb)).collect(),
+        Axis::Distance => {
+            let (mut ia, mut ib) = (0, 0);
+            let mut pairs = Vec::new();
+            let mut d = 0.0;
+            loop {
+                while ia < a.len() && a[ia].distance < d {
+                    ia += 1;
+                }
+                while ib < b.len() && b[ib].distance < d {
+                    ib += 1;
+                }
+                if ia == a.len() || ib == b.len() {
+                    break;
+                }
+                pairs.push((d, &a[ia], &b[ib]));
+                d += DISTANCE_STEP;
+            }
+            pairs
+        }
+    }
+}
+
+/// Print two activities side by side, segment by segment, and optionally write
+/// the aligned delta series as CSV for charting
+pub fn run(first: ActivityId, second: ActivityId, axis: Axis, export: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let (file_a, file_b) = (load_activity_file(first)?, load_activity_file(second)?);
+    let (a, b) = (points(&file_a)?, points(&file_b)?);
+    let pairs = align(&a, &b, axis);
+    if pairs.is_empty() {
+        return Err("the activities have no overlapping samples to compare".into());
+    }
+
+    info!("⚖️  {} vs {}", file_a.activity.name, file_b.activity.name);
+    println!("  {:<14} {:>12} {:>12}", "", short_date(&file_a), short_date(&file_b));
+    let row = |label: &str, f: &dyn Fn(&ActivityWithStreams, &[Point]) -> String| {
+        println!("  {:<14} {:>12} {:>12}", label, f(&file_a, &a), f(&file_b, &b));
+    };
+    row("moving time", &|file, _| clock(file.activity.moving_time));
+    row("distance", &|file, _| format!("{:.2} km", file.activity.distance / 1000.0));
+    row("avg power", &|_, points| watts(average(points.iter().map(|p| p.watts))));
+    row("NP", &|_, points| {
+        let watts: Vec<f64> = points.iter().filter_map(|p| p.watts).collect();
+        self::watts(metrics::normalized_power(&watts))
+    });
+    row("avg HR", &|_, points| bpm(average(points.iter().map(|p| p.heartrate))));
+    row("avg speed", &|file, _| format!("{:.1} km/h", file.activity.average_speed * 3.6));
+
+    println!();
+    println!("  {:<15} {:>6} {:>6} {:>6}  {:>4} {:>4}  {:>5} {:>5}  {:>6}",
+        match axis { Axis::Time => "time", Axis::Distance => "distance" },
+        "W A", "W B", "ΔW", "HR A", "HR B", "kph A", "kph B", "B lead");
+    let size = pairs.len().div_ceil(SEGMENTS);
+    for segment in pairs.chunks(size) {
+        let (start, end) = (segment[0].0, segment[segment.len() - 1].0);
+        let avg = |f: &dyn Fn(&Pair) -> Option<f64>| average(segment.iter().map(f));
+        let (wa, wb) = (avg(&|p| p.1.watts), avg(&|p| p.2.watts));
+        let last = segment[segment.len() - 1];
+        println!("  {:<15} {:>6} {:>6} {:>6}  {:>4} {:>4}  {:>5} {:>5}  {:>6}",
+            match axis {
+                Axis::Time => format!("{}-{}", clock(start as i32), clock(end as i32)),
+                Axis::Distance => format!("{:.1}-{:.1} km", start / 1000.0, end / 1000.0),
+            },
+            watts(wa),
+            watts(wb),
+            wa.zip(wb).map(|(wa, wb)| format!("{:+.0}", wb - wa)).unwrap_or_else(|| "-".into()),
+            bpm(avg(&|p| p.1.heartrate)),
+            bpm(avg(&|p| p.2.heartrate)),
+            kph(avg(&|p| p.1.speed)),
+            kph(avg(&|p| p.2.speed)),
+            gap(axis, last.1, last.2),
+        );
+    }
+
+    if let Some(path) = export {
+        let mut csv = String::from("axis,watts_a,watts_b,delta_watts,heartrate_a,heartrate_b,speed_a,speed_b,b_ahead\n");
+        for (x, pa, pb) in &pairs {
+            let cell = |v: Option<f64>| v.map(|v| format!("{:.1}", v)).unwrap_or_default();
+            let _ = writeln!(csv, "{},{},{},{},{},{},{},{},{}",
+                x,
+                cell(pa.watts),
+                cell(pb.watts),
+                cell(pa.watts.zip(pb.watts).map(|(wa, wb)| wb - wa)),
+                cell(pa.heartrate),
+                cell(pb.heartrate),
+                cell(pa.speed),
+                cell(pb.speed),
+                cell(Some(lead(axis, pa, pb))),
+            );
+        }
+        write_atomic(path, csv.as_bytes())?;
+        info!("📈 Wrote {} aligned samples to {}", pairs.len(), path.display());
+    }
+    Ok(())
+}
+
+/// How far ahead B is: metres when aligned by time, seconds when aligned by distance
+fn lead(axis: Axis, a: &Point, b: &Point) -> f64 {
+    match axis {
+        Axis::Time => b.distance - a.distance,
+        Axis::Distance => a.time - b.time,
+    }
+}
+
+fn gap(axis: Axis, a: &Point, b: &Point) -> String {
+    let unit = if axis == Axis::Time { "m" } else { "s" };
+    format!("{:+.0}{}", lead(axis, a, b), unit)
+}
+
+fn average(values: impl Iterator<Item = Option<f64>>) -> Option<f64> {
+    let values: Vec<f64> = values.flatten().collect();
+    metrics::mean(&values)
+}
+
+fn short_date(file: &ActivityWithStreams) -> &str {
+    &file.activity.start_date_local[..file.activity.start_date_local.len().min(10)]
+}
+
+fn watts(value: Option<f64>) -> String {
+    value.map(|w| format!("{:.0}", w)).unwrap_or_else(|| "-".into())
+}
+
+fn bpm(value: Option<f64>) -> String {
+    value.map(|hr| format!("{:.0}", hr)).unwrap_or_else(|| "-".into())
+}
+
+fn kph(value: Option<f64>) -> String {
+    value.map(|v| format!("{:.1}", v * 3.6)).unwrap_or_else(|| "-".into())
+}
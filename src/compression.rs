@@ -0,0 +1,95 @@
+use std::error::Error;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+
+/// zstd level for new files; decompression speed doesn't depend on it
+const ZSTD_LEVEL: i32 = 19;
+
+/// On-disk encoding of per-activity files, compressed in-process with
+/// flate2 and zstd.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub const ALL: [Compression; 3] = [Compression::None, Compression::Gzip, Compression::Zstd];
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "none" | "" => Ok(Compression::None),
+            "gzip" | "gz" => Ok(Compression::Gzip),
+            "zstd" | "zst" => Ok(Compression::Zstd),
+            other => Err(format!("unknown compression '{}' (expected none, gzip or zstd)", other)),
+        }
+    }
+
+    /// Configured through WATTS_COMPRESSION, plain JSON by default so the
+    /// static site can keep fetching files directly
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var("WATTS_COMPRESSION") {
+            Ok(value) => Compression::parse(&value),
+            Err(_) => Ok(Compression::None),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Compression::None => "none",
+            Compression::Gzip => "gzip",
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    /// File extension appended after `.json`
+    pub fn extension(self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    pub fn compress(self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
+                encoder.write_all(data)?;
+                Ok(encoder.finish()?)
+            }
+            Compression::Zstd => Ok(zstd::encode_all(data, ZSTD_LEVEL)?),
+        }
+    }
+
+    pub fn decompress(self, data: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        match self {
+            Compression::None => Ok(data.to_vec()),
+            Compression::Gzip => {
+                let mut out = Vec::new();
+                GzDecoder::new(data).read_to_end(&mut out).map_err(|e| format!("gzip: {}", e))?;
+                Ok(out)
+            }
+            Compression::Zstd => Ok(zstd::decode_all(data).map_err(|e| format!("zstd: {}", e))?),
+        }
+    }
+}
+
+/// Read a file, transparently decompressing it based on its extension
+pub fn read_file(path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+    let data = std::fs::read(path)?;
+    Compression::from_path(path).decompress(&data)
+}
@@ -0,0 +1,219 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::athlete::{self, AthleteProfile};
+use crate::clean::CleaningSettings;
+use crate::club::ClubSettings;
+use crate::expression::{self, Expr};
+use crate::gear::GearConfig;
+use crate::locale::Locale;
+use crate::mail::EmailConfig;
+use crate::matches::MatchSettings;
+use crate::model::ActivitySummary;
+use crate::notify::Webhook;
+use crate::power_estimate::PowerModel;
+use crate::season::Season;
+use crate::segments::SegmentSettings;
+use crate::storage::write_atomic;
+use crate::store::StoreConfig;
+use crate::streaks::StreakSettings;
+
+/// Settings that don't fit in an environment variable live in watts.json
+pub const CONFIG_PATH: &str = "watts.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Config {
+    /// Functional threshold power in watts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ftp: Option<f64>,
+    /// Critical power (W) and W' (J) for W'bal; the latest `cp` fit is used when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cp: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub w_prime: Option<f64>,
+    /// Resting and maximum heart rate in bpm, for TRIMP on rides without power
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resting_hr: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_hr: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zones: Option<Zones>,
+    /// Bikes, trainers and components tracked in data/gear.json, with service intervals
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gear: Vec<GearConfig>,
+    /// Spike and dropout thresholds for stream cleaning
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cleaning: Option<CleaningSettings>,
+    /// Extra athletes synced with `--athlete` or `sync --all`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub athletes: Vec<AthleteProfile>,
+    /// Webhooks told about newly synced activities
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notifications: Vec<Webhook>,
+    /// SMTP server and recipients for the post-sync summary email
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<EmailConfig>,
+    /// Named training periods for `--season`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub seasons: Vec<Season>,
+    /// Strava sport types kept by `sync`; only VirtualRide when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sports: Vec<String>,
+    /// Threshold running pace in seconds per km, for rTSS on runs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold_pace: Option<f64>,
+    /// Estimated TSS per hour of activities without streams, by sport type,
+    /// over the defaults in `streamless`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub load_per_hour: BTreeMap<String, f64>,
+    /// Rides left out of CTL/ATL/TSB, e.g. `["commute"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude_from_load: Vec<LoadExclusion>,
+    /// Number and date formatting of `report` (en, fr)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<Locale>,
+    /// Where the index and activity files live; data/ on disk when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store: Option<StoreConfig>,
+    /// Per-ride metrics computed from expressions, e.g. `"kj_per_hour": "kilojoules / (moving_time / 3600)"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub custom_metrics: BTreeMap<String, String>,
+    /// Power and duration of an effort counted as a match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matches: Option<MatchSettings>,
+    /// Rider and bike mass, CdA and Crr for estimating power on rides without a power meter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power_model: Option<PowerModel>,
+    /// Count rides flagged suspect by `anomalies` in best efforts, sprints and power curves
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub include_suspect: bool,
+    /// Least daily and weekly TSS for a streak to go on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub streaks: Option<StreakSettings>,
+    /// Alias and shared folder for `leaderboard`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub club: Option<ClubSettings>,
+    /// Strava club whose feed sync keeps in data/club/, for `club-activities`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strava_club: Option<u64>,
+    /// Refresh of starred segments' PR and KOM times by sync
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub segments: Option<SegmentSettings>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoadExclusion {
+    Commute,
+    Trainer,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Zones {
+    #[serde(default)]
+    pub heart_rate: Vec<ZoneRange>,
+    #[serde(default)]
+    pub power: Vec<ZoneRange>,
+}
+
+/// Zone bounds as Strava reports them; `max` is -1 for the open-ended top zone
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ZoneRange {
+    pub min: i32,
+    pub max: i32,
+}
+
+impl Config {
+    /// Load watts.json, using defaults when it does not exist
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        match fs::read_to_string(CONFIG_PATH) {
+            Ok(json) => {
+                let config: Config = serde_json::from_str(&json).map_err(|e| format!("{} is invalid: {}", CONFIG_PATH, e))?;
+                config.custom_metrics().map_err(|e| format!("{} is invalid: {}", CONFIG_PATH, e))?;
+                Ok(config)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(format!("could not read {}: {}", CONFIG_PATH, e).into()),
+        }
+    }
+
+    /// Settings for the active athlete: their own FTP, heart rates and zones override the shared ones
+    pub fn current() -> Result<Self, Box<dyn Error>> {
+        let mut config = Config::load()?;
+        if let Some(profile) = athlete::active() {
+            config.ftp = profile.ftp.or(config.ftp);
+            config.resting_hr = profile.resting_hr.or(config.resting_hr);
+            config.max_hr = profile.max_hr.or(config.max_hr);
+            config.zones = profile.zones.or(config.zones);
+        }
+        Ok(config)
+    }
+
+    /// FTP from watts.json, or derived from Strava's power zones (Z5 starts at 106% FTP)
+    pub fn ftp(&self) -> Option<f64> {
+        self.ftp.or_else(|| {
+            let power = &self.zones.as_ref()?.power;
+            (power.len() >= 5).then(|| (power[4].min as f64 / 1.06).round())
+        })
+    }
+
+    /// Whether a ride is left out of the training load by `exclude_from_load`
+    pub fn excluded_from_load(&self, summary: &ActivitySummary) -> bool {
+        self.exclude_from_load.iter().any(|exclusion| match exclusion {
+            LoadExclusion::Commute => summary.commute,
+            LoadExclusion::Trainer => summary.indoor(),
+        })
+    }
+
+    /// Whether a ride's efforts may set records: suspect ones only with `include_suspect`
+    pub fn counts_for_records(&self, summary: &ActivitySummary) -> bool {
+        summary.counted() && (self.include_suspect || !summary.suspect())
+    }
+
+    /// Whether `sync` keeps activities of this Strava sport type
+    pub fn syncs(&self, sport_type: &str) -> bool {
+        if self.sports.is_empty() {
+            sport_type == "VirtualRide"
+        } else {
+            self.sports.iter().any(|s| s.eq_ignore_ascii_case(sport_type))
+        }
+    }
+
+    /// Lower bound of each power zone in watts: the configured power zones,
+    /// or Coggan's 7 zones from FTP. None when neither is set.
+    pub fn power_zone_floors(&self) -> Option<Vec<f64>> {
+        if let Some(zones) = self.zones.as_ref().filter(|z| !z.power.is_empty()) {
+            return Some(zones.power.iter().map(|z| z.min as f64).collect());
+        }
+        let ftp = self.ftp()?;
+        Some([0.0, 0.56, 0.76, 0.91, 1.06, 1.21, 1.51].iter().map(|pct| pct * ftp).collect())
+    }
+
+    /// The parsed `custom_metrics`, by name
+    pub fn custom_metrics(&self) -> Result<Vec<(&str, Expr)>, String> {
+        self.custom_metrics
+            .iter()
+            .map(|(name, source)| {
+                let valid_name = name.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+                    && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                if !valid_name || expression::VARIABLES.contains(&name.as_str()) {
+                    return Err(format!("custom metric name '{}' must be a new identifier such as kj_per_hour", name));
+                }
+                let expr = Expr::parse(source).map_err(|e| format!("custom metric '{}': {}", name, e))?;
+                Ok((name.as_str(), expr))
+            })
+            .collect()
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        write_atomic(Path::new(CONFIG_PATH), serde_json::to_string_pretty(self)?.as_bytes())
+    }
+}
+
+/// Boolean settings read from the environment (or .env): "1"/"true" enable them
+pub fn flag(name: &str) -> bool {
+    matches!(std::env::var(name).as_deref(), Ok("1") | Ok("true"))
+}
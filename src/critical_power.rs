@@ -0,0 +1,140 @@
+use std::error::Error;
+use std::fs;
+
+use chrono::{Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::format;
+use crate::metrics;
+use crate::model::ActivityIndex;
+use crate::storage::{load_activity_file, write_atomic};
+
+const HISTORY_FILE: &str = "cp_history.json";
+
+pub const DEFAULT_WINDOW_DAYS: i64 = 90;
+
+/// Efforts the 2-parameter model is fitted on; it is only valid between ~2 and ~20 minutes
+const DURATIONS: [usize; 6] = [180, 300, 420, 600, 900, 1200];
+
+/// A CP/W' fit over the window ending at `until`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CpEstimate {
+    pub computed_at: String,
+    pub until: String,
+    pub window_days: i64,
+    /// Critical power in watts
+    pub cp: f64,
+    /// Work capacity above CP in joules
+    pub w_prime: f64,
+    /// Coefficient of determination of the work-time regression
+    pub r_squared: f64,
+    /// Standard error of the CP estimate in watts
+    pub cp_error: f64,
+}
+
+/// Most recent fit from data/cp_history.json
+pub fn latest() -> Option<CpEstimate> {
+    let json = fs::read_to_string(format::data_dir().join(HISTORY_FILE)).ok()?;
+    let history: Vec<CpEstimate> = serde_json::from_str(&json).ok()?;
+    history.into_iter().max_by(|a, b| a.until.cmp(&b.until))
+}
+
+/// Fit CP and W' to the best efforts of the window, print them and record them in the history
+pub fn run(window_days: i64, until: Option<NaiveDate>) -> Result<(), Box<dyn Error>> {
+    let until = until.unwrap_or_else(|| Utc::now().date_naive());
+    let from = until - Duration::days(window_days);
+    let index = ActivityIndex::load()?;
+
+    let mut best = [0.0f64; DURATIONS.len()];
+    for summary in index.activities.iter().filter(|a| a.counted() && !a.is_run()) {
+        let day = summary.local_date();
+        if day <= from || day > until {
+            continue;
+        }
+        let file = match load_activity_file(summary.id) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("   ⚠️  Skipping {}: {}", summary.id, e);
+                continue;
+            }
+        };
+        let Some(watts) = file.streams.as_ref().and_then(|s| s.watts.as_deref()) else { continue };
+        for (best, &duration) in best.iter_mut().zip(DURATIONS.iter()) {
+            if let Some(power) = metrics::mean_maximal_power(watts, duration) {
+                *best = best.max(power);
+            }
+        }
+    }
+
+    let points: Vec<(f64, f64)> = DURATIONS
+        .iter()
+        .zip(best)
+        .filter(|(_, power)| *power > 0.0)
+        .map(|(&duration, power)| (duration as f64, power))
+        .collect();
+    let estimate = fit(&points)
+        .map(|(cp, w_prime, r_squared, cp_error)| CpEstimate {
+            computed_at: Utc::now().to_rfc3339(),
+            until: until.to_string(),
+            window_days,
+            cp,
+            w_prime,
+            r_squared,
+            cp_error,
+        })
+        .ok_or_else(|| format!("not enough efforts between {} and {} to fit CP (need 3 durations)", from, until))?;
+
+    info!("📈 Critical power from {} to {}", from, until);
+    println!("  {:>8} {:>7} {:>7}", "duration", "best W", "model W");
+    for (duration, power) in &points {
+        let model = estimate.cp + estimate.w_prime / duration;
+        println!("  {:>6.0} s {:>7.0} {:>7.0}", duration, power, model);
+    }
+    println!();
+    println!("  CP:  {:.0} W (± {:.1})", estimate.cp, estimate.cp_error);
+    println!("  W':  {:.1} kJ", estimate.w_prime / 1000.0);
+    println!("  R²:  {:.4}", estimate.r_squared);
+
+    record(estimate)
+}
+
+/// Linear regression of work on time (`metrics::linear_fit`): work = CP·t + W'.
+/// Returns (CP, W', R², standard error of CP), None with fewer than 3 points.
+fn fit(points: &[(f64, f64)]) -> Option<(f64, f64, f64, f64)> {
+    if points.len() < 3 {
+        return None;
+    }
+    let work: Vec<(f64, f64)> = points.iter().map(|&(t, p)| (t, p * t)).collect();
+    let (cp, w_prime, r_squared) = metrics::linear_fit(&work)?;
+
+    let n = work.len() as f64;
+    let mean_t = work.iter().map(|(t, _)| t).sum::<f64>() / n;
+    let sxx: f64 = work.iter().map(|(t, _)| (t - mean_t).powi(2)).sum();
+    let ss_res: f64 = work.iter().map(|(t, w)| (w - (cp * t + w_prime)).powi(2)).sum();
+    let cp_error = (ss_res / (n - 2.0) / sxx).sqrt();
+    Some((cp, w_prime, r_squared, cp_error))
+}
+
+/// Append to data/cp_history.json, replacing an earlier fit of the same window
+fn record(estimate: CpEstimate) -> Result<(), Box<dyn Error>> {
+    let path = format::data_dir().join(HISTORY_FILE);
+    let mut history: Vec<CpEstimate> = match fs::read_to_string(&path) {
+        Ok(json) => serde_json::from_str(&json)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    history.retain(|e| !(e.until == estimate.until && e.window_days == estimate.window_days));
+    history.push(estimate);
+    history.sort_by(|a, b| a.until.cmp(&b.until));
+    write_atomic(&path, serde_json::to_string_pretty(&history)?.as_bytes())?;
+
+    if history.len() > 1 {
+        println!();
+        println!("  History ({}):", path.display());
+        for e in &history {
+            println!("    {}  {:>2}d  CP {:>4.0} W  W' {:>5.1} kJ  R² {:.3}", e.until, e.window_days, e.cp, e.w_prime / 1000.0, e.r_squared);
+        }
+    }
+    Ok(())
+}
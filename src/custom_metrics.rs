@@ -0,0 +1,69 @@
+//! Metrics defined in watts.json as expressions over a ride's numbers (see
+//! `expression`), computed with the rest of the pipeline and stored on the
+//! index entry, from where `list`, `export-influx` and `report` show them.
+
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::model::{ActivitySummary, ActivityWithStreams};
+use crate::pipeline::Analyzer;
+use crate::training_load;
+
+pub struct CustomMetrics;
+
+impl Analyzer for CustomMetrics {
+    fn name(&self) -> &'static str {
+        "custom"
+    }
+
+    fn inputs(&self, config: &Config) -> Value {
+        json!([config.custom_metrics, config.ftp()])
+    }
+
+    fn analyze(&self, file: &mut ActivityWithStreams, summary: &mut ActivitySummary, config: &Config) {
+        // Config::load already rejected invalid expressions
+        let Ok(metrics) = config.custom_metrics() else { return };
+        let variables = variables(file, summary, config);
+        summary.custom_metrics = metrics
+            .into_iter()
+            .filter_map(|(name, expr)| expr.eval(&variables).map(|value| (name.to_string(), round3(value))))
+            .collect();
+    }
+}
+
+/// The values of `expression::VARIABLES` this ride has
+fn variables<'a>(file: &ActivityWithStreams, summary: &ActivitySummary, config: &Config) -> BTreeMap<&'a str, f64> {
+    let activity = &file.activity;
+    let ftp = config.ftp();
+    let tss = file.streams.as_ref().zip(ftp).and_then(|(streams, ftp)| training_load::streams_tss(streams, activity.moving_time, ftp));
+    [
+        ("distance", Some(activity.distance)),
+        ("moving_time", Some(activity.moving_time as f64)),
+        ("elapsed_time", Some(activity.elapsed_time as f64)),
+        ("total_elevation_gain", Some(activity.total_elevation_gain)),
+        ("average_speed", Some(activity.average_speed)),
+        ("max_speed", Some(activity.max_speed)),
+        ("average_watts", activity.average_watts),
+        ("np", activity.weighted_average_watts),
+        ("max_watts", activity.max_watts),
+        ("kilojoules", activity.kilojoules),
+        ("average_heartrate", activity.average_heartrate),
+        ("max_heartrate", activity.max_heartrate),
+        ("average_cadence", activity.average_cadence),
+        ("calories", activity.calories),
+        ("suffer_score", activity.suffer_score),
+        ("ftp", ftp),
+        ("tss", tss),
+        ("efficiency_factor", summary.efficiency_factor),
+        ("decoupling", summary.decoupling),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| value.map(|v| (name, v)))
+    .collect()
+}
+
+fn round3(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
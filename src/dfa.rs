@@ -0,0 +1,188 @@
+//! Experimental: DFA alpha 1 from beat-to-beat intervals, and the aerobic
+//! threshold (HRVT1) where it crosses 0.75.
+//!
+//! Only activities carrying an `rr_intervals` stream can be analysed; Strava
+//! does not provide one, so this applies to rides imported from FIT files
+//! recorded with a chest strap.
+
+use std::error::Error;
+
+use tracing::{info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::metrics;
+use crate::model::{ActivityIndex, ActivityWithStreams};
+use crate::storage::load_activity_file;
+
+/// Analysis window and step, in seconds (Rogers et al. use 2-minute windows)
+const WINDOW_SECONDS: f64 = 120.0;
+const STEP_SECONDS: f64 = 30.0;
+
+/// Short-term scaling range of alpha 1, in beats
+const BOX_SIZES: std::ops::RangeInclusive<usize> = 4..=16;
+
+/// alpha 1 at the first ventilatory threshold
+const AEROBIC_THRESHOLD_ALPHA1: f64 = 0.75;
+
+/// Fewer windows than this give a meaningless regression
+const MIN_WINDOWS: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct Window {
+    /// Start of the window in seconds since the first beat
+    pub start: f64,
+    pub heartrate: f64,
+    pub watts: Option<f64>,
+    pub alpha1: f64,
+}
+
+/// Heart rate and power at which alpha 1 crosses 0.75
+#[derive(Debug, Clone)]
+pub struct Threshold {
+    pub heartrate: f64,
+    pub watts: Option<f64>,
+}
+
+/// alpha 1 over sliding windows of the ride, None without RR data
+pub fn windows(file: &ActivityWithStreams) -> Option<Vec<Window>> {
+    let streams = file.streams.as_ref()?;
+    let rr = clean(streams.rr_intervals.as_deref()?);
+    let watts = streams.watts.as_deref();
+
+    let mut beat_times = Vec::with_capacity(rr.len());
+    let mut t = 0.0;
+    for &interval in &rr {
+        t += interval / 1000.0;
+        beat_times.push(t);
+    }
+
+    let mut windows = Vec::new();
+    let mut start = 0.0;
+    while start + WINDOW_SECONDS <= t {
+        let first = beat_times.partition_point(|&b| b < start);
+        let last = beat_times.partition_point(|&b| b < start + WINDOW_SECONDS);
+        let beats = &rr[first..last];
+        if let (Some(alpha1), Some(mean_rr)) = (alpha1(beats), metrics::mean(beats)) {
+            // Stream samples are ~1 Hz, so seconds index the watts stream directly
+            let power = watts
+                .and_then(|w| w.get(start as usize..((start + WINDOW_SECONDS) as usize).min(w.len())))
+                .and_then(metrics::mean);
+            windows.push(Window { start, heartrate: 60_000.0 / mean_rr, watts: power, alpha1 });
+        }
+        start += STEP_SECONDS;
+    }
+    Some(windows)
+}
+
+/// Drop artefacts: implausible intervals and jumps of more than 20% from the previous beat
+fn clean(rr: &[f64]) -> Vec<f64> {
+    let mut out: Vec<f64> = Vec::with_capacity(rr.len());
+    for &interval in rr.iter().filter(|&&r| (300.0..=2000.0).contains(&r)) {
+        if out.last().is_none_or(|&previous| (interval - previous).abs() <= previous * 0.2) {
+            out.push(interval);
+        }
+    }
+    out
+}
+
+/// Detrended fluctuation analysis slope over the short-term box sizes
+pub fn alpha1(rr: &[f64]) -> Option<f64> {
+    if rr.len() < BOX_SIZES.end() * 4 {
+        return None;
+    }
+    let mean = metrics::mean(rr)?;
+    let mut integrated = Vec::with_capacity(rr.len());
+    let mut sum = 0.0;
+    for &r in rr {
+        sum += r - mean;
+        integrated.push(sum);
+    }
+
+    let mut points = Vec::new();
+    for n in BOX_SIZES {
+        let boxes = integrated.len() / n;
+        let mut squares = 0.0;
+        for chunk in integrated.chunks_exact(n) {
+            let line: Vec<(f64, f64)> = chunk.iter().enumerate().map(|(i, &y)| (i as f64, y)).collect();
+            let (slope, intercept, _) = metrics::linear_fit(&line)?;
+            squares += chunk
+                .iter()
+                .enumerate()
+                .map(|(i, &y)| (y - (slope * i as f64 + intercept)).powi(2))
+                .sum::<f64>();
+        }
+        let fluctuation = (squares / (boxes * n) as f64).sqrt();
+        if fluctuation > 0.0 {
+            points.push(((n as f64).ln(), fluctuation.ln()));
+        }
+    }
+    metrics::linear_fit(&points).map(|(slope, _, _)| slope)
+}
+
+/// HRVT1 from a regression of alpha 1 on heart rate (and on power when present)
+pub fn threshold(windows: &[Window]) -> Option<Threshold> {
+    if windows.len() < MIN_WINDOWS {
+        return None;
+    }
+    let crossing = |points: Vec<(f64, f64)>| {
+        let (slope, intercept, _) = metrics::linear_fit(&points)?;
+        // alpha 1 falls as intensity rises; anything else is noise
+        (slope < 0.0).then(|| (AEROBIC_THRESHOLD_ALPHA1 - intercept) / slope)
+    };
+    Some(Threshold {
+        heartrate: crossing(windows.iter().map(|w| (w.heartrate, w.alpha1)).collect())?,
+        watts: crossing(windows.iter().filter_map(|w| Some((w.watts?, w.alpha1))).collect()),
+    })
+}
+
+/// Print alpha 1 through one ride, or HRVT1 for every ride with RR data
+pub fn run(id: Option<ActivityId>) -> Result<(), Box<dyn Error>> {
+    if let Some(id) = id {
+        let file = load_activity_file(id)?;
+        let windows = windows(&file).ok_or("activity has no rr_intervals stream (import it from a FIT file)")?;
+        info!("💗 DFA alpha 1 for {} (experimental)", file.activity.name);
+        println!("  {:>6} {:>5} {:>6} {:>7}", "time", "HR", "power", "alpha1");
+        for w in &windows {
+            println!(
+                "  {:>3}:{:02} {:>5.0} {:>6} {:>7.2}",
+                w.start as i64 / 60,
+                w.start as i64 % 60,
+                w.heartrate,
+                w.watts.map(|p| format!("{:.0}", p)).unwrap_or_else(|| "-".into()),
+                w.alpha1
+            );
+        }
+        match threshold(&windows) {
+            Some(t) => println!("\n  HRVT1: {:.0} bpm{}", t.heartrate,
+                t.watts.map(|p| format!(", {:.0} W", p)).unwrap_or_default()),
+            None => println!("\n  HRVT1: not enough spread in intensity to estimate"),
+        }
+        return Ok(());
+    }
+
+    let index = ActivityIndex::load()?;
+    let mut analysed = 0;
+    println!("  {:<10} {:>6} {:>6}  name", "date", "HRVT1", "power");
+    for summary in index.activities.iter().rev().filter(|a| a.counted()) {
+        let file = match load_activity_file(summary.id) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("   ⚠️  Skipping {}: {}", summary.id, e);
+                continue;
+            }
+        };
+        let Some(windows) = windows(&file) else { continue };
+        analysed += 1;
+        if let Some(t) = threshold(&windows) {
+            println!(
+                "  {:<10} {:>6.0} {:>6}  {}",
+                summary.local_date(),
+                t.heartrate,
+                t.watts.map(|p| format!("{:.0}", p)).unwrap_or_else(|| "-".into()),
+                summary.name
+            );
+        }
+    }
+    info!("💗 {} rides with RR data (experimental DFA alpha 1)", analysed);
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use crate::activity_id::ActivityId;
+use crate::compression;
+
+/// Compare two archive snapshots and print added/missing/modified activities
+pub fn run(dir_a: &str, dir_b: &str) -> Result<(), Box<dyn Error>> {
+    let a = list_activity_files(Path::new(dir_a))?;
+    let b = list_activity_files(Path::new(dir_b))?;
+
+    let missing: Vec<ActivityId> = a.keys().filter(|id| !b.contains_key(id)).copied().collect();
+    let added: Vec<ActivityId> = b.keys().filter(|id| !a.contains_key(id)).copied().collect();
+    let mut modified: Vec<(ActivityId, Vec<String>)> = Vec::new();
+    for (id, path_a) in &a {
+        if let Some(path_b) = b.get(id) {
+            let changed = changed_fields(&read_json(path_a)?, &read_json(path_b)?);
+            if !changed.is_empty() {
+                modified.push((*id, changed));
+            }
+        }
+    }
+
+    println!("Comparing {} -> {}", dir_a, dir_b);
+    println!("  {} activities in {}, {} in {}", a.len(), dir_a, b.len(), dir_b);
+    println!();
+    println!("Added in {} ({}):", dir_b, added.len());
+    for id in &added {
+        println!("  + {}", id);
+    }
+    println!("Missing from {} ({}):", dir_b, missing.len());
+    for id in &missing {
+        println!("  - {}", id);
+    }
+    println!("Modified ({}):", modified.len());
+    for (id, fields) in &modified {
+        println!("  ~ {} ({})", id, fields.join(", "));
+    }
+
+    let index_a = read_json(&Path::new(dir_a).join("index.json")).ok();
+    let index_b = read_json(&Path::new(dir_b).join("index.json")).ok();
+    let ids_a = index_ids(index_a.as_ref());
+    let ids_b = index_ids(index_b.as_ref());
+    if ids_a != ids_b {
+        println!();
+        println!("Index entries differ: {} in {}, {} in {}", ids_a.len(), dir_a, ids_b.len(), dir_b);
+    }
+
+    Ok(())
+}
+
+fn list_activity_files(dir: &Path) -> Result<BTreeMap<ActivityId, PathBuf>, Box<dyn Error>> {
+    let activities_dir = dir.join("activities");
+    let entries = fs::read_dir(&activities_dir)
+        .map_err(|e| format!("could not read {}: {}", activities_dir.display(), e))?;
+    let mut files = BTreeMap::new();
+    for entry in entries {
+        let path = entry?.path();
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let stem = name.trim_end_matches(".gz").trim_end_matches(".zst");
+        if let Some(id) = stem.strip_suffix(".json").and_then(|s| s.parse().ok()) {
+            files.insert(id, path);
+        }
+    }
+    Ok(files)
+}
+
+fn read_json(path: &Path) -> Result<Value, Box<dyn Error>> {
+    let json = compression::read_file(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+    Ok(serde_json::from_slice(&json).map_err(|e| format!("could not parse {}: {}", path.display(), e))?)
+}
+
+/// Top-level keys whose values differ (formatting differences are ignored)
+fn changed_fields(a: &Value, b: &Value) -> Vec<String> {
+    match (a.as_object(), b.as_object()) {
+        (Some(a), Some(b)) => {
+            let mut keys: Vec<&String> = a.keys().chain(b.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            keys.into_iter()
+                .filter(|k| a.get(*k) != b.get(*k))
+                .cloned()
+                .collect()
+        }
+        _ if a != b => vec!["<root>".to_string()],
+        _ => Vec::new(),
+    }
+}
+
+fn index_ids(index: Option<&Value>) -> Vec<ActivityId> {
+    let mut ids: Vec<ActivityId> = index
+        .and_then(|i| i.get("activities"))
+        .and_then(|a| a.as_array())
+        .map(|a| a.iter().filter_map(|s| s.get("id").and_then(|id| serde_json::from_value(id.clone()).ok())).collect())
+        .unwrap_or_default();
+    ids.sort();
+    ids
+}
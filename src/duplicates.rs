@@ -0,0 +1,142 @@
+//! The same ride recorded by two sources (a FIT import and its Strava upload).
+//!
+//! Entries from different sources whose start and duration overlap are paired;
+//! the richer recording is kept and the other is marked `duplicate_of` in the
+//! index, which leaves it out of every report and the training load like an
+//! excluded ride. Nothing is deleted, and detection is re-run from scratch so a
+//! decision follows the files as they change.
+
+use std::error::Error;
+
+use chrono::{DateTime, Duration, Utc};
+use tracing::{info, warn};
+
+use crate::activity_id::{ActivityId, Source};
+use crate::model::{ActivityIndex, ActivitySummary, ActivityWithStreams};
+use crate::storage::load_activity_file;
+
+/// Starts further apart than this are never the same ride
+const MAX_START_OFFSET_MINUTES: i64 = 15;
+
+/// Shared time as a fraction of the shorter recording
+const MIN_OVERLAP: f64 = 0.8;
+
+/// A kept/dropped pair with the reason for keeping
+#[derive(Debug)]
+pub struct Decision {
+    pub kept: ActivityId,
+    pub dropped: ActivityId,
+    pub overlap: f64,
+    pub reason: String,
+}
+
+fn span(summary: &ActivitySummary) -> (DateTime<Utc>, DateTime<Utc>) {
+    let start = summary.start_date;
+    (start, start + Duration::seconds(summary.moving_time.max(1) as i64))
+}
+
+/// Fraction of the shorter ride covered by the other, if they start close enough
+fn overlap(a: &ActivitySummary, b: &ActivitySummary) -> Option<f64> {
+    let ((start_a, end_a), (start_b, end_b)) = (span(a), span(b));
+    if (start_a - start_b).num_minutes().abs() > MAX_START_OFFSET_MINUTES {
+        return None;
+    }
+    let shared = (end_a.min(end_b) - start_a.max(start_b)).num_seconds() as f64;
+    let shorter = a.moving_time.min(b.moving_time).max(1) as f64;
+    Some(shared / shorter).filter(|o| *o >= MIN_OVERLAP)
+}
+
+/// What a recording holds: streams present, samples, detail fields
+fn richness(file: &ActivityWithStreams) -> (usize, usize, usize) {
+    let streams = file.streams.as_ref();
+    let present = streams.map_or(0, |s| {
+        [s.watts.is_some(), s.heartrate.is_some(), s.cadence.is_some(), s.velocity_smooth.is_some(),
+            s.altitude.is_some(), s.latlng.is_some(), s.rr_intervals.is_some()]
+            .into_iter()
+            .filter(|p| *p)
+            .count()
+    });
+    let samples = streams.and_then(|s| s.time.as_ref()).map_or(0, |t| t.len());
+    let activity = &file.activity;
+    let detail = [activity.description.is_some(), activity.gear_id.is_some(), file.laps.is_some(), file.social.is_some()]
+        .into_iter()
+        .filter(|p| *p)
+        .count();
+    (present, samples, detail)
+}
+
+/// Pick the richer of two recordings; Strava wins ties, it carries kudos and gear
+fn choose(a: &ActivityWithStreams, b: &ActivityWithStreams) -> (ActivityId, ActivityId, String) {
+    let (ra, rb) = (richness(a), richness(b));
+    let (a_id, b_id) = (a.activity.id, b.activity.id);
+    let (kept, dropped, (kept_r, dropped_r)) = match ra.cmp(&rb) {
+        std::cmp::Ordering::Greater => (a_id, b_id, (ra, rb)),
+        std::cmp::Ordering::Less => (b_id, a_id, (rb, ra)),
+        std::cmp::Ordering::Equal if b_id.source == Source::Strava => (b_id, a_id, (rb, ra)),
+        std::cmp::Ordering::Equal => (a_id, b_id, (ra, rb)),
+    };
+    let reason = if kept_r.0 != dropped_r.0 {
+        format!("{} streams vs {}", kept_r.0, dropped_r.0)
+    } else if kept_r.1 != dropped_r.1 {
+        format!("{} samples vs {}", kept_r.1, dropped_r.1)
+    } else if kept_r.2 != dropped_r.2 {
+        "more detail (description, gear, laps, social)".to_string()
+    } else {
+        format!("same content, {} preferred", kept.source.name())
+    };
+    (kept, dropped, reason)
+}
+
+/// Re-detect duplicates across sources and mark the dropped entries in the index
+pub fn detect(index: &mut ActivityIndex) -> Vec<Decision> {
+    let mut pairs = Vec::new();
+    for (i, a) in index.activities.iter().enumerate() {
+        // Newest first: only neighbours within the start window can overlap
+        for b in index.activities[i + 1..].iter() {
+            if b.id.source == a.id.source {
+                continue;
+            }
+            if let Some(overlap) = overlap(a, b) {
+                pairs.push((a.id, b.id, overlap));
+            } else if (span(a).0 - span(b).0).num_minutes() > MAX_START_OFFSET_MINUTES {
+                break;
+            }
+        }
+    }
+
+    let mut decisions = Vec::new();
+    for (a, b, overlap) in pairs {
+        let (file_a, file_b) = match (load_activity_file(a), load_activity_file(b)) {
+            (Ok(file_a), Ok(file_b)) => (file_a, file_b),
+            (Err(e), _) | (_, Err(e)) => {
+                warn!("   ⚠️  Skipping possible duplicate {} / {}: {}", a, b, e);
+                continue;
+            }
+        };
+        let (kept, dropped, reason) = choose(&file_a, &file_b);
+        // A ride already dropped against a third copy stays dropped
+        if decisions.iter().any(|d: &Decision| d.dropped == kept || d.dropped == dropped) {
+            continue;
+        }
+        decisions.push(Decision { kept, dropped, overlap, reason });
+    }
+
+    for summary in index.activities.iter_mut() {
+        summary.duplicate_of = decisions.iter().find(|d| d.dropped == summary.id).map(|d| d.kept);
+    }
+    decisions
+}
+
+/// Detect duplicates across the archive and print the merge decisions
+pub fn run(dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let mut index = ActivityIndex::load()?;
+    let decisions = detect(&mut index);
+    info!("👯 {} duplicate rides across sources{}", decisions.len(), if dry_run { " (dry run)" } else { "" });
+    for d in &decisions {
+        println!("  keep {:<16} drop {:<16} {:>4.0}% overlap, {}", d.kept.to_string(), d.dropped.to_string(), d.overlap * 100.0, d.reason);
+    }
+    if !dry_run {
+        index.save()?;
+    }
+    Ok(())
+}
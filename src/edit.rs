@@ -0,0 +1,240 @@
+use std::error::Error;
+
+use chrono::SecondsFormat;
+use tracing::info;
+
+use crate::activity_id::ActivityId;
+use crate::cli;
+use crate::model::{ActivityIndex, ActivitySummary};
+
+/// Index fields `--filter` can match on
+const FIELDS: [&str; 14] = ["id", "name", "start_date", "distance", "moving_time", "average_watts",
+    "average_heartrate", "workout_type", "tag", "notes", "excluded", "duplicate_of", "trainer", "commute"];
+
+/// One `--filter` condition: `field~=text` (case-insensitive contains),
+/// `field=value`, or a numeric/date comparison with `<`, `<=`, `>`, `>=`
+#[derive(Debug, Clone)]
+pub struct Filter {
+    field: String,
+    op: &'static str,
+    value: String,
+}
+
+impl Filter {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        // Two-character operators first so `>=` isn't read as `>`
+        for op in ["~=", "<=", ">=", "=", "<", ">"] {
+            if let Some((field, value)) = s.split_once(op) {
+                if !FIELDS.contains(&field) {
+                    return Err(format!("unknown field '{}', expected one of {}", field, FIELDS.join(", ")));
+                }
+                return Ok(Filter { field: field.to_string(), op, value: value.to_string() });
+            }
+        }
+        Err(format!("invalid filter '{}', expected field~=text, field=value or field<value", s))
+    }
+
+    pub fn matches(&self, summary: &ActivitySummary) -> bool {
+        if self.field == "tag" {
+            return match self.op {
+                "~=" => summary.tags.iter().any(|t| t.to_lowercase().contains(&self.value.to_lowercase())),
+                "=" => summary.tags.contains(&self.value),
+                _ => false,
+            };
+        }
+        let Some(actual) = field_value(summary, &self.field) else { return false };
+        match self.op {
+            "~=" => actual.to_lowercase().contains(&self.value.to_lowercase()),
+            "=" => actual == self.value,
+            op => {
+                // Numbers compare numerically, anything else (dates) as text
+                let ordering = match (actual.parse::<f64>(), self.value.parse::<f64>()) {
+                    (Ok(a), Ok(b)) => a.partial_cmp(&b),
+                    _ => Some(actual.as_str().cmp(self.value.as_str())),
+                };
+                matches!(
+                    (op, ordering),
+                    ("<", Some(std::cmp::Ordering::Less))
+                        | ("<=", Some(std::cmp::Ordering::Less | std::cmp::Ordering::Equal))
+                        | (">", Some(std::cmp::Ordering::Greater))
+                        | (">=", Some(std::cmp::Ordering::Greater | std::cmp::Ordering::Equal))
+                )
+            }
+        }
+    }
+}
+
+/// One `--set field=value` or `--unset field[=value]` change
+#[derive(Debug, Clone)]
+pub enum Change {
+    AddTag(String),
+    RemoveTag(String),
+    Notes(Option<String>),
+    Excluded(bool),
+}
+
+impl Change {
+    pub fn parse_set(s: &str) -> Result<Self, String> {
+        match s.split_once('=') {
+            Some(("tag", tag)) if !tag.is_empty() => Ok(Change::AddTag(tag.to_string())),
+            Some(("notes", notes)) => Ok(Change::Notes(Some(notes.to_string()))),
+            Some(("excluded", value)) => value
+                .parse()
+                .map(Change::Excluded)
+                .map_err(|_| format!("invalid value '{}' for excluded, expected true or false", value)),
+            _ => Err(format!("invalid --set '{}', expected tag=NAME, notes=TEXT or excluded=true|false", s)),
+        }
+    }
+
+    pub fn parse_unset(s: &str) -> Result<Self, String> {
+        match s.split_once('=') {
+            Some(("tag", tag)) => Ok(Change::RemoveTag(tag.to_string())),
+            None if s == "notes" => Ok(Change::Notes(None)),
+            None if s == "excluded" => Ok(Change::Excluded(false)),
+            _ => Err(format!("invalid --unset '{}', expected tag=NAME, notes or excluded", s)),
+        }
+    }
+
+    /// Apply to a summary, returning whether anything changed
+    fn apply(&self, summary: &mut ActivitySummary) -> bool {
+        match self {
+            Change::AddTag(tag) if !summary.tags.contains(tag) => {
+                summary.tags.push(tag.clone());
+                true
+            }
+            Change::RemoveTag(tag) if summary.tags.contains(tag) => {
+                summary.tags.retain(|t| t != tag);
+                true
+            }
+            Change::Notes(notes) if summary.notes != *notes => {
+                summary.notes = notes.clone();
+                true
+            }
+            Change::Excluded(excluded) if summary.excluded != *excluded => {
+                summary.excluded = *excluded;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// `--tag` and `--without-tag` selection for reports and exports: rides must
+/// carry every `--tag` and none of the `--without-tag`s. `--trainer`/`--no-trainer`
+/// and `--commute`/`--no-commute` select on Strava's flags the same way.
+#[derive(Debug, Clone, Default)]
+pub struct TagFilter {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    trainer: Option<bool>,
+    commute: Option<bool>,
+}
+
+/// `--NAME` as Some(true), `--no-NAME` as Some(false)
+fn take_flag(args: &mut Vec<String>, name: &str) -> Result<Option<bool>, String> {
+    let yes = cli::take_switch(args, &format!("--{}", name));
+    let no = cli::take_switch(args, &format!("--no-{}", name));
+    match (yes, no) {
+        (true, true) => Err(format!("--{} and --no-{} can't be combined", name, name)),
+        (true, false) => Ok(Some(true)),
+        (false, true) => Ok(Some(false)),
+        (false, false) => Ok(None),
+    }
+}
+
+impl TagFilter {
+    /// Remove the (repeatable) tag options from a command's arguments
+    pub fn take(args: &mut Vec<String>) -> Result<Self, String> {
+        let mut filter = TagFilter::default();
+        while let Some(tag) = cli::take_option(args, "--tag")? {
+            filter.include.push(tag);
+        }
+        while let Some(tag) = cli::take_option(args, "--without-tag")? {
+            filter.exclude.push(tag);
+        }
+        filter.trainer = take_flag(args, "trainer")?;
+        filter.commute = take_flag(args, "commute")?;
+        Ok(filter)
+    }
+
+    pub fn matches(&self, summary: &ActivitySummary) -> bool {
+        self.include.iter().all(|t| summary.tags.contains(t))
+            && !self.exclude.iter().any(|t| summary.tags.contains(t))
+            && self.trainer.is_none_or(|trainer| summary.indoor() == trainer)
+            && self.commute.is_none_or(|commute| summary.commute == commute)
+    }
+
+    /// Drop the index entries that don't match
+    pub fn apply(&self, index: &mut ActivityIndex) {
+        index.activities.retain(|a| self.matches(a));
+    }
+}
+
+/// Add tags to one activity (or remove them), printing its tags afterwards
+pub fn tag(id: ActivityId, tags: &[String], remove: bool) -> Result<(), Box<dyn Error>> {
+    let mut index = ActivityIndex::load()?;
+    let summary = index.get_mut(id).ok_or_else(|| format!("activity {} is not in the index", id))?;
+    let changed = tags.iter().fold(false, |changed, tag| {
+        let change = if remove { Change::RemoveTag(tag.clone()) } else { Change::AddTag(tag.clone()) };
+        change.apply(summary) | changed
+    });
+    println!("  {} {}: {}", summary.id, summary.name,
+        if summary.tags.is_empty() { "no tags".to_string() } else { summary.tags.join(", ") });
+    if changed {
+        index.save()?;
+        info!("🏷️  Updated tags of {}", id);
+    }
+    Ok(())
+}
+
+/// Apply changes to every index entry matching all filters, printing each one changed
+pub fn run(filters: &[Filter], changes: &[Change], dry_run: bool) -> Result<(), Box<dyn Error>> {
+    if changes.is_empty() {
+        return Err("nothing to change, pass --set or --unset".into());
+    }
+    let mut index = ActivityIndex::load()?;
+    let mut matched = 0;
+    let mut changed = 0;
+    for summary in index.activities.iter_mut().filter(|a| filters.iter().all(|f| f.matches(a))) {
+        matched += 1;
+        let mut preview = summary.clone();
+        let modified = changes.iter().fold(false, |modified, change| change.apply(&mut preview) | modified);
+        if modified {
+            changed += 1;
+            println!("  ~ {} {} {}", summary.id, summary.local_date(), summary.name);
+            if !dry_run {
+                *summary = preview;
+            }
+        }
+    }
+
+    if dry_run {
+        info!("✏️  Would change {} of {} matching activities (dry run)", changed, matched);
+    } else {
+        if changed > 0 {
+            index.save()?;
+        }
+        info!("✏️  Changed {} of {} matching activities", changed, matched);
+    }
+    Ok(())
+}
+
+/// A summary field as text, None when unset
+fn field_value(summary: &ActivitySummary, field: &str) -> Option<String> {
+    match field {
+        "id" => Some(summary.id.to_string()),
+        "name" => Some(summary.name.clone()),
+        "start_date" => Some(summary.start_date.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        "distance" => Some(summary.distance.to_string()),
+        "moving_time" => Some(summary.moving_time.to_string()),
+        "average_watts" => summary.average_watts.map(|w| w.to_string()),
+        "average_heartrate" => summary.average_heartrate.map(|h| h.to_string()),
+        "workout_type" => summary.workout_type.clone(),
+        "notes" => summary.notes.clone(),
+        "excluded" => Some(summary.excluded.to_string()),
+        "duplicate_of" => summary.duplicate_of.map(|id| id.to_string()),
+        "trainer" => Some(summary.indoor().to_string()),
+        "commute" => Some(summary.commute.to_string()),
+        _ => None,
+    }
+}
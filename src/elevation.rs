@@ -0,0 +1,160 @@
+//! Elevation gain recomputed from the altitude stream, to check Strava's
+//! `total_elevation_gain` against. Zwift's altitude sometimes disagrees
+//! badly with the gain it reports (teleports between routes, the lead-in
+//! counted twice), and GPS altitude jitters: the stream is smoothed and
+//! climbing only counts once it clears a hysteresis band.
+
+use std::error::Error;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::cli::{self, OutputFormat};
+use crate::config::Config;
+use crate::model::{ActivityIndex, ActivitySummary, ActivityWithStreams};
+use crate::pipeline::Analyzer;
+use crate::storage::load_activity_file;
+
+/// Samples in the moving average applied before counting
+const SMOOTHING_SAMPLES: usize = 5;
+
+/// Metres the smoothed altitude must move past the last turning point to
+/// count as climbing or descending; wider bands lose Zwift's short rollers
+const HYSTERESIS_METRES: f64 = 0.5;
+
+/// Differences under this many metres are never flagged, whatever the percentage
+const MIN_DIFFERENCE_METRES: f64 = 30.0;
+
+/// Flagged above this difference, in percent of the recomputed gain
+pub const DEFAULT_THRESHOLD_PERCENT: f64 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ElevationCheck {
+    /// Metres climbed according to the altitude stream
+    pub gain: f64,
+    /// Strava's total_elevation_gain
+    pub strava_gain: f64,
+}
+
+impl ElevationCheck {
+    /// Strava's figure over the recomputed one, in percent; None on a flat ride
+    pub fn difference_percent(&self) -> Option<f64> {
+        (self.gain > 0.0).then(|| (self.strava_gain - self.gain) / self.gain * 100.0)
+    }
+
+    /// Whether the two disagree by more than `threshold_percent`, and by a meaningful number of metres
+    pub fn flagged(&self, threshold_percent: f64) -> bool {
+        (self.strava_gain - self.gain).abs() >= MIN_DIFFERENCE_METRES
+            && self.difference_percent().is_none_or(|p| p.abs() > threshold_percent)
+    }
+}
+
+/// Recomputed gain next to Strava's on the index entry
+pub struct Elevation;
+
+impl Analyzer for Elevation {
+    fn name(&self) -> &'static str {
+        "elevation"
+    }
+
+    fn analyze(&self, file: &mut ActivityWithStreams, summary: &mut ActivitySummary, _config: &Config) {
+        summary.elevation = check(file);
+    }
+}
+
+/// Recomputed and reported gain of a ride, None without an altitude stream
+pub fn check(file: &ActivityWithStreams) -> Option<ElevationCheck> {
+    let altitude = file.streams.as_ref()?.altitude.as_deref().filter(|a| !a.is_empty())?;
+    Some(ElevationCheck { gain: gain(altitude).round(), strava_gain: file.activity.total_elevation_gain.round() })
+}
+
+/// Metres climbed over a smoothed `altitude`, counting rises past the hysteresis band
+pub fn gain(altitude: &[f64]) -> f64 {
+    let smoothed: Vec<f64> = (0..altitude.len())
+        .map(|i| {
+            let window = &altitude[i.saturating_sub(SMOOTHING_SAMPLES / 2)..(i + SMOOTHING_SAMPLES / 2 + 1).min(altitude.len())];
+            window.iter().sum::<f64>() / window.len() as f64
+        })
+        .collect();
+    let Some((&first, rest)) = smoothed.split_first() else { return 0.0 };
+    // Last turning point; rises count from there once they clear the band
+    let mut reference = first;
+    let mut total = 0.0;
+    for &a in rest {
+        if a > reference + HYSTERESIS_METRES {
+            total += a - reference;
+            reference = a;
+        } else if a < reference - HYSTERESIS_METRES {
+            reference = a;
+        }
+    }
+    total
+}
+
+#[derive(Debug, Serialize)]
+struct Entry {
+    id: ActivityId,
+    date: NaiveDate,
+    name: String,
+    gain: f64,
+    strava_gain: f64,
+    difference_percent: Option<f64>,
+}
+
+/// Recompute the gain of every ride and print those since `since` where
+/// Strava disagrees by more than `threshold_percent`, worst first
+pub fn run(since: Option<NaiveDate>, threshold_percent: Option<f64>, output: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let threshold = threshold_percent.unwrap_or(DEFAULT_THRESHOLD_PERCENT);
+    let mut index = ActivityIndex::load()?;
+    for summary in index.activities.iter_mut() {
+        match load_activity_file(summary.id) {
+            Ok(file) => summary.elevation = check(&file),
+            Err(e) => warn!("   ⚠️  Skipping {}: {}", summary.id, e),
+        }
+    }
+    index.save()?;
+
+    let checked: Vec<&ActivitySummary> =
+        index.activities.iter().filter(|a| a.counted() && since.is_none_or(|s| a.local_date() >= s) && a.elevation.is_some()).collect();
+    let mut flagged: Vec<Entry> = checked
+        .iter()
+        .filter_map(|a| {
+            let check = a.elevation.filter(|c| c.flagged(threshold))?;
+            Some(Entry {
+                id: a.id,
+                date: a.local_date(),
+                name: a.name.clone(),
+                gain: check.gain,
+                strava_gain: check.strava_gain,
+                difference_percent: check.difference_percent().map(|p| p.round()),
+            })
+        })
+        .collect();
+    flagged.sort_by(|a, b| (b.strava_gain - b.gain).abs().total_cmp(&(a.strava_gain - a.gain).abs()));
+
+    if output == OutputFormat::Json {
+        return cli::print_json(&flagged);
+    }
+    let (gain, strava_gain) = checked.iter().filter_map(|a| a.elevation).fold((0.0, 0.0), |(g, s), c| (g + c.gain, s + c.strava_gain));
+    info!("⛰️  {} rides with altitude: {:.0} m climbed from the streams, {:.0} m according to Strava", checked.len(), gain, strava_gain);
+    if flagged.is_empty() {
+        info!("✅ No ride differs by more than {:.0}%", threshold);
+        return Ok(());
+    }
+    info!("⚠️  {} rides differ by more than {:.0}%", flagged.len(), threshold);
+    println!("  {:<10} {:<16} {:>7} {:>7} {:>6}  name", "date", "id", "stream", "strava", "diff");
+    for entry in &flagged {
+        println!(
+            "  {:<10} {:<16} {:>5.0} m {:>5.0} m {:>6}  {}",
+            entry.date,
+            entry.id.to_string(),
+            entry.gain,
+            entry.strava_gain,
+            entry.difference_percent.map_or_else(|| "-".to_string(), |p| format!("{:+.0}%", p)),
+            entry.name.chars().take(40).collect::<String>()
+        );
+    }
+    Ok(())
+}
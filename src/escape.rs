@@ -0,0 +1,22 @@
+//! Escaping for the text formats the exports write: XML and HTML (TCX, ZWO,
+//! the HTML report and S3 responses) and iCalendar TEXT values.
+
+/// Escape text for an XML or HTML element or a double-quoted attribute
+pub fn xml(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Undo `xml`, and the `&apos;` other writers use
+pub fn xml_unescape(s: &str) -> String {
+    s.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+/// iCalendar TEXT value: backslash, semicolon, comma and newline
+pub fn ics_text(s: &str) -> String {
+    s.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
+
+/// Undo `ics_text`, reading escaped newlines as spaces
+pub fn ics_unescape(s: &str) -> String {
+    s.replace("\\n", " ").replace("\\N", " ").replace("\\,", ",").replace("\\;", ";").replace("\\\\", "\\")
+}
@@ -0,0 +1,179 @@
+//! Ride history as time series for Grafana: InfluxDB line protocol pushed to
+//! the v2 write API, or a Prometheus text endpoint scraped from a long-running
+//! `serve-metrics` process, which also serves the calendar feed.
+
+use std::error::Error;
+use std::fmt::Write as _;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::{debug, error, info, warn};
+
+use crate::calendar;
+use crate::config::Config;
+use crate::edit::TagFilter;
+use crate::model::ActivityIndex;
+use crate::training_load::{self, DailyLoad};
+
+pub const DEFAULT_METRICS_PORT: u16 = 9898;
+
+/// Write activity summaries and daily training load to InfluxDB, or print the
+/// line protocol with `dry_run`. Points are keyed by timestamp and tags, so
+/// re-exporting overwrites instead of duplicating.
+pub async fn influx(dry_run: bool, tags: &TagFilter) -> Result<(), Box<dyn Error>> {
+    let config = Config::current()?;
+    let mut index = ActivityIndex::load()?;
+    tags.apply(&mut index);
+    let body = line_protocol(&index, &config);
+    if dry_run {
+        print!("{}", body);
+        return Ok(());
+    }
+
+    let url = std::env::var("INFLUX_URL").map_err(|_| "INFLUX_URL is not set")?;
+    let token = std::env::var("INFLUX_TOKEN").map_err(|_| "INFLUX_TOKEN is not set")?;
+    let org = std::env::var("INFLUX_ORG").unwrap_or_default();
+    let bucket = std::env::var("INFLUX_BUCKET").unwrap_or_else(|_| "watts".to_string());
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/api/v2/write", url.trim_end_matches('/')))
+        .query(&[("org", org.as_str()), ("bucket", bucket.as_str()), ("precision", "s")])
+        .header("Authorization", format!("Token {}", token))
+        .header("Content-Type", "text/plain; charset=utf-8")
+        .body(body.clone())
+        .send()
+        .await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await?;
+        error!("❌ InfluxDB write error ({}): {}", status, text);
+        return Err(format!("InfluxDB returned status {}", status).into());
+    }
+    info!("📈 Wrote {} points to InfluxDB bucket {}", body.lines().count(), bucket);
+    Ok(())
+}
+
+fn line_protocol(index: &ActivityIndex, config: &Config) -> String {
+    let ftp = config.ftp();
+    let mut out = String::new();
+    for summary in index.activities.iter().filter(|a| a.duplicate_of.is_none()) {
+        let start = summary.start_date;
+        let _ = write!(out, "activity,id={}", summary.id);
+        if let Some(workout_type) = &summary.workout_type {
+            let _ = write!(out, ",workout_type={}", escape_tag(workout_type));
+        }
+        let _ = write!(
+            out,
+            " name=\"{}\",distance={},moving_time={}i",
+            summary.name.replace('\\', "\\\\").replace('"', "\\\""),
+            summary.distance,
+            summary.moving_time
+        );
+        if let Some(watts) = summary.average_watts {
+            let _ = write!(out, ",average_watts={}", watts);
+        }
+        if let Some(hr) = summary.average_heartrate {
+            let _ = write!(out, ",average_heartrate={}", hr);
+        }
+        if let Some(tss) = training_load::ride_tss(summary, ftp) {
+            let _ = write!(out, ",tss={:.1}", tss);
+        }
+        if let Some(fit) = summary.heart_rate_fit {
+            let _ = write!(out, ",hr_slope={},hr_intercept={},hr_lag={}i,hr_r2={}", fit.slope, fit.intercept, fit.lag, fit.r2);
+        }
+        for (name, value) in &summary.custom_metrics {
+            let _ = write!(out, ",{}={}", name, value);
+        }
+        if !summary.tags.is_empty() {
+            let _ = write!(out, ",tags=\"{}\"", summary.tags.join(",").replace('\\', "\\\\").replace('"', "\\\""));
+        }
+        let _ = writeln!(out, " {}", start.timestamp());
+    }
+    for day in training_load::daily(index, config) {
+        let timestamp = day.date.and_hms_opt(0, 0, 0).map(|t| t.and_utc().timestamp()).unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "training_load tss={:.1},ctl={:.1},atl={:.1},tsb={:.1} {}",
+            day.tss, day.ctl, day.atl, day.tsb, timestamp
+        );
+    }
+    out
+}
+
+fn escape_tag(value: &str) -> String {
+    value.replace(',', "\\,").replace('=', "\\=").replace(' ', "\\ ")
+}
+
+/// Serve GET /metrics in Prometheus text format, recomputed from the archive
+/// on every scrape so a sync running alongside is picked up without restarts
+pub async fn serve(port: u16) -> Result<(), Box<dyn Error>> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    info!("📡 Serving Prometheus metrics on http://0.0.0.0:{}/metrics", port);
+    info!("   and the activity calendar on http://0.0.0.0:{}/{}", port, calendar::CALENDAR_FILE);
+    loop {
+        let (mut socket, peer) = listener.accept().await?;
+        let mut request = [0u8; 1024];
+        let n = socket.read(&mut request).await.unwrap_or(0);
+        let request_line = String::from_utf8_lossy(&request[..n]).lines().next().unwrap_or_default().to_string();
+        debug!("{} {}", peer, request_line);
+
+        let response = if request_line.starts_with("GET /metrics ") {
+            match prometheus() {
+                Ok(body) => format!("HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}", body.len(), body),
+                Err(e) => {
+                    error!("❌ Could not compute metrics: {}", e);
+                    "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n".to_string()
+                }
+            }
+        } else if request_line.starts_with(&format!("GET /{} ", calendar::CALENDAR_FILE)) {
+            match ActivityIndex::load().and_then(|index| Ok(calendar::render(&index, &Config::current()?))) {
+                Ok(body) => format!("HTTP/1.1 200 OK\r\nContent-Type: text/calendar; charset=utf-8\r\nContent-Length: {}\r\n\r\n{}", body.len(), body),
+                Err(e) => {
+                    error!("❌ Could not render the calendar: {}", e);
+                    "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\n\r\n".to_string()
+                }
+            }
+        } else {
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_string()
+        };
+        if let Err(e) = socket.write_all(response.as_bytes()).await {
+            warn!("   ⚠️  Could not answer {}: {}", peer, e);
+        }
+    }
+}
+
+fn prometheus() -> Result<String, Box<dyn Error>> {
+    let config = Config::current()?;
+    let index = ActivityIndex::load()?;
+    let mut out = String::new();
+
+    gauge(&mut out, "watts_activities", "Activities in the archive", index.activities.len() as f64);
+    gauge(&mut out, "watts_distance_meters", "Distance of all activities", index.activities.iter().map(|a| a.distance).sum());
+    gauge(&mut out, "watts_moving_seconds", "Moving time of all activities",
+        index.activities.iter().map(|a| a.moving_time as f64).sum());
+
+    // Newest first in the index; per-activity series would explode cardinality,
+    // so only the latest ride is exposed (Influx gets the full history)
+    if let Some(last) = index.activities.first() {
+        gauge(&mut out, "watts_last_activity_timestamp_seconds", "Start of the latest activity", last.start_date.timestamp() as f64);
+        gauge(&mut out, "watts_last_activity_distance_meters", "Distance of the latest activity", last.distance);
+        if let Some(watts) = last.average_watts {
+            gauge(&mut out, "watts_last_activity_average_watts", "Average power of the latest activity", watts);
+        }
+        if let Some(tss) = training_load::ride_tss(last, config.ftp()) {
+            gauge(&mut out, "watts_last_activity_tss", "Training stress of the latest activity", tss);
+        }
+    }
+
+    if let Some(DailyLoad { tss, ctl, atl, tsb, .. }) = training_load::daily(&index, &config).pop() {
+        gauge(&mut out, "watts_daily_tss", "Training stress accumulated today", tss);
+        gauge(&mut out, "watts_ctl", "Chronic training load (fitness)", ctl);
+        gauge(&mut out, "watts_atl", "Acute training load (fatigue)", atl);
+        gauge(&mut out, "watts_tsb", "Training stress balance (form)", tsb);
+    }
+    Ok(out)
+}
+
+fn gauge(out: &mut String, name: &str, help: &str, value: f64) {
+    let _ = writeln!(out, "# HELP {} {}\n# TYPE {} gauge\n{} {}", name, help, name, name, value);
+}
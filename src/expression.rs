@@ -0,0 +1,223 @@
+//! Arithmetic over a ride's numbers, for the metrics defined under
+//! "custom_metrics" in watts.json:
+//!
+//! ```json
+//! "custom_metrics": { "kj_per_hour": "kilojoules / (moving_time / 3600)" }
+//! ```
+//!
+//! Expressions combine numbers and the variables in `VARIABLES` with `+ - * /`,
+//! parentheses and `min(a, b)`, `max(a, b)`, `round(x)`. A variable the ride
+//! lacks (no power meter, no FTP) or a division by zero leaves the metric unset
+//! on that ride instead of storing a made-up number.
+
+use std::collections::BTreeMap;
+
+/// What an expression can read, each from the activity file or its index entry
+pub const VARIABLES: [&str; 19] = [
+    "distance",
+    "moving_time",
+    "elapsed_time",
+    "total_elevation_gain",
+    "average_speed",
+    "max_speed",
+    "average_watts",
+    "np",
+    "max_watts",
+    "kilojoules",
+    "average_heartrate",
+    "max_heartrate",
+    "average_cadence",
+    "calories",
+    "suffer_score",
+    "ftp",
+    "tss",
+    "efficiency_factor",
+    "decoupling",
+];
+
+const FUNCTIONS: [(&str, usize); 3] = [("min", 2), ("max", 2), ("round", 1)];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Variable(String),
+    Negate(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+impl Expr {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens: &tokens, pos: 0 };
+        let expr = parser.sum()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected '{}'", token)),
+        }
+    }
+
+    /// The value with `variables`; None when one it reads is missing or the arithmetic is undefined
+    pub fn eval(&self, variables: &BTreeMap<&str, f64>) -> Option<f64> {
+        let value = match self {
+            Expr::Number(n) => *n,
+            Expr::Variable(name) => *variables.get(name.as_str())?,
+            Expr::Negate(inner) => -inner.eval(variables)?,
+            Expr::Binary(op, left, right) => {
+                let (a, b) = (left.eval(variables)?, right.eval(variables)?);
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    _ if b == 0.0 => return None,
+                    _ => a / b,
+                }
+            }
+            Expr::Call(name, args) => {
+                let args: Vec<f64> = args.iter().map(|a| a.eval(variables)).collect::<Option<_>>()?;
+                match name.as_str() {
+                    "min" => args[0].min(args[1]),
+                    "max" => args[0].max(args[1]),
+                    _ => args[0].round(),
+                }
+            }
+        };
+        value.is_finite().then_some(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Symbol(char),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "{}", n),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Symbol(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek().filter(|(_, c)| c.is_ascii_digit() || *c == '.') {
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            let number = &s[start..end];
+            tokens.push(Token::Number(number.parse().map_err(|_| format!("invalid number '{}'", number))?));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(i, c)) = chars.peek().filter(|(_, c)| c.is_ascii_alphanumeric() || *c == '_') {
+                end = i + c.len_utf8();
+                chars.next();
+            }
+            tokens.push(Token::Name(s[start..end].to_string()));
+        } else if "+-*/(),".contains(c) {
+            tokens.push(Token::Symbol(c));
+            chars.next();
+        } else {
+            return Err(format!("unexpected '{}'", c));
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent, one method per precedence level
+struct Parser<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&Token> {
+        self.pos += 1;
+        self.tokens.get(self.pos - 1)
+    }
+
+    fn eat(&mut self, symbol: char) -> bool {
+        let found = self.peek() == Some(&Token::Symbol(symbol));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, symbol: char) -> Result<(), String> {
+        if self.eat(symbol) {
+            Ok(())
+        } else {
+            Err(match self.peek() {
+                Some(token) => format!("expected '{}', found '{}'", symbol, token),
+                None => format!("expected '{}' at the end", symbol),
+            })
+        }
+    }
+
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.product()?;
+        while let Some(op) = ['+', '-'].into_iter().find(|op| self.eat(*op)) {
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.product()?));
+        }
+        Ok(expr)
+    }
+
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.unary()?;
+        while let Some(op) = ['*', '/'].into_iter().find(|op| self.eat(*op)) {
+            expr = Expr::Binary(op, Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat('-') {
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        match self.next().cloned() {
+            Some(Token::Number(n)) => Ok(Expr::Number(n)),
+            Some(Token::Symbol('(')) => {
+                let expr = self.sum()?;
+                self.expect(')')?;
+                Ok(expr)
+            }
+            Some(Token::Name(name)) if self.eat('(') => {
+                let Some(&(_, arity)) = FUNCTIONS.iter().find(|(f, _)| *f == name) else {
+                    return Err(format!("unknown function '{}', expected min, max or round", name));
+                };
+                let mut args = vec![self.sum()?];
+                while self.eat(',') {
+                    args.push(self.sum()?);
+                }
+                self.expect(')')?;
+                if args.len() != arity {
+                    return Err(format!("{} takes {} argument{}", name, arity, if arity == 1 { "" } else { "s" }));
+                }
+                Ok(Expr::Call(name, args))
+            }
+            Some(Token::Name(name)) if VARIABLES.contains(&name.as_str()) => Ok(Expr::Variable(name)),
+            Some(Token::Name(name)) => Err(format!("unknown variable '{}', expected one of {}", name, VARIABLES.join(", "))),
+            Some(token) => Err(format!("unexpected '{}'", token)),
+            None => Err("unexpected end".to_string()),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Exit codes for cron and CI, so a run that did not fetch everything does not
+//! look like a success:
+//!
+//! ```text
+//! 0  everything fetched
+//! 1  error (bad arguments, I/O, unexpected API answer)
+//! 2  partial: the run finished but some activities or parts were not fetched
+//! 3  auth: missing credentials, or Strava rejected them
+//! 4  rate limited: the 15-minute or daily budget ran out, retry later
+//! ```
+//!
+//! After the error itself, the last log line on failure summarizes it in
+//! `failure`, `exit_code` and `activities` fields; `--log-format json` makes it
+//! one JSON object an alerting step can parse.
+
+use std::error::Error;
+use std::fmt;
+use std::process::ExitCode;
+
+use tracing::error;
+
+use crate::activity_id::ActivityId;
+use crate::strava::{self, AuthError};
+
+/// Ordered by how much a person needs to step in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Failure {
+    Partial,
+    RateLimited,
+    Error,
+    Auth,
+}
+
+impl Failure {
+    pub fn code(self) -> u8 {
+        match self {
+            Failure::Error => 1,
+            Failure::Partial => 2,
+            Failure::Auth => 3,
+            Failure::RateLimited => 4,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Failure::Partial => "partial",
+            Failure::RateLimited => "rate_limited",
+            Failure::Error => "error",
+            Failure::Auth => "auth",
+        }
+    }
+
+    /// How a failed Strava request counts
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            401 | 403 => Failure::Auth,
+            429 => Failure::RateLimited,
+            _ => Failure::Error,
+        }
+    }
+
+    /// What kind of failure an error is
+    pub fn of(e: &(dyn Error + 'static)) -> Self {
+        if let Some(incomplete) = e.downcast_ref::<Incomplete>() {
+            incomplete.failure
+        } else if e.downcast_ref::<AuthError>().is_some() {
+            Failure::Auth
+        } else {
+            strava::status_of(e).map_or(Failure::Error, Failure::from_status)
+        }
+    }
+}
+
+/// A run that finished without fetching everything it should have
+#[derive(Debug)]
+pub struct Incomplete {
+    pub failure: Failure,
+    pub message: String,
+    /// Activities left without some of their data
+    pub activities: Vec<ActivityId>,
+}
+
+impl fmt::Display for Incomplete {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for Incomplete {}
+
+/// Log the failure summary and pick the exit code
+pub fn exit(e: &(dyn Error + 'static)) -> ExitCode {
+    let failure = Failure::of(e);
+    let activities = e
+        .downcast_ref::<Incomplete>()
+        .map(|i| i.activities.iter().map(ToString::to_string).collect::<Vec<_>>().join(","))
+        .unwrap_or_default();
+    error!("❌ {}", e);
+    error!(failure = failure.as_str(), exit_code = failure.code(), activities = activities.as_str(), "Failure summary");
+    ExitCode::from(failure.code())
+}
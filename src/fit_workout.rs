@@ -0,0 +1,192 @@
+//! Garmin FIT workout files (file type 5), written by hand as the profile
+//! needs only four messages: file_id, workout and one workout_step per block,
+//! with interval sets as a repeat step. Targets are power as % of FTP, so the
+//! head unit scales them to its own FTP; ramps become a power range.
+
+use chrono::Utc;
+
+use crate::workout::{Segment, Workout};
+
+/// FIT profile version the file claims (21.32)
+const PROFILE_VERSION: u16 = 2132;
+
+/// Seconds between the Unix epoch and the FIT epoch (1989-12-31T00:00:00Z)
+const FIT_EPOCH_OFFSET: i64 = 631_065_600;
+
+/// Steady targets span this many % FTP either side of the block's power
+const TARGET_HALF_RANGE: u32 = 3;
+
+const NAME_SIZE: u8 = 32;
+const STEP_NAME_SIZE: u8 = 16;
+
+// Base types
+const ENUM: u8 = 0x00;
+const STRING: u8 = 0x07;
+const UINT16: u8 = 0x84;
+const UINT32: u8 = 0x86;
+const UINT32Z: u8 = 0x8C;
+
+// Global message numbers
+const FILE_ID: u16 = 0;
+const WORKOUT: u16 = 26;
+const WORKOUT_STEP: u16 = 27;
+
+// workout_step enums
+const DURATION_TIME: u8 = 0;
+const DURATION_REPEAT: u8 = 6;
+const TARGET_POWER: u8 = 4;
+const ACTIVE: u8 = 0;
+const REST: u8 = 1;
+const WARMUP: u8 = 2;
+const COOLDOWN: u8 = 3;
+
+/// Value of a field left unset
+const INVALID: u32 = u32::MAX;
+
+const CRC_TABLE: [u16; 16] =
+    [0x0000, 0xCC01, 0xD801, 0x1400, 0xF001, 0x3C00, 0x2800, 0xE401, 0xA001, 0x6C00, 0x7800, 0xB401, 0x5000, 0x9C01, 0x8801, 0x4400];
+
+fn crc(bytes: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for &byte in bytes {
+        for nibble in [byte & 0x0F, byte >> 4] {
+            let tmp = CRC_TABLE[(crc & 0x0F) as usize];
+            crc = ((crc >> 4) & 0x0FFF) ^ tmp ^ CRC_TABLE[nibble as usize];
+        }
+    }
+    crc
+}
+
+/// One workout_step
+struct Step {
+    name: &'static str,
+    duration_type: u8,
+    /// Milliseconds, or the step to repeat from
+    duration_value: u32,
+    /// 0 for the custom % FTP range, or the repeat count
+    target_value: u32,
+    /// % FTP
+    low: u32,
+    high: u32,
+    intensity: u8,
+}
+
+impl Step {
+    fn timed(name: &'static str, seconds: i32, from: f64, to: f64, intensity: u8) -> Step {
+        let (from, to) = ((from * 100.0).round() as u32, (to * 100.0).round() as u32);
+        let (low, high) = if from == to {
+            (from.saturating_sub(TARGET_HALF_RANGE), to + TARGET_HALF_RANGE)
+        } else {
+            (from.min(to), from.max(to))
+        };
+        Step { name, duration_type: DURATION_TIME, duration_value: seconds.max(1) as u32 * 1000, target_value: 0, low, high, intensity }
+    }
+}
+
+fn steps(workout: &Workout) -> Vec<Step> {
+    let mut steps = Vec::new();
+    for segment in &workout.segments {
+        match *segment {
+            Segment::Warmup { duration, from, to } => steps.push(Step::timed("Warm up", duration, from, to, WARMUP)),
+            Segment::Steady { duration, power } => steps.push(Step::timed("Steady", duration, power, power, ACTIVE)),
+            Segment::Intervals { repeat, on_duration, off_duration, on_power, off_power } => {
+                let first = steps.len() as u32;
+                steps.push(Step::timed("On", on_duration, on_power, on_power, ACTIVE));
+                steps.push(Step::timed("Off", off_duration, off_power, off_power, REST));
+                steps.push(Step {
+                    name: "Repeat",
+                    duration_type: DURATION_REPEAT,
+                    duration_value: first,
+                    target_value: repeat as u32,
+                    low: INVALID,
+                    high: INVALID,
+                    intensity: ACTIVE,
+                });
+            }
+            Segment::Cooldown { duration, from, to } => steps.push(Step::timed("Cool down", duration, from, to, COOLDOWN)),
+        }
+    }
+    steps
+}
+
+/// `s` as a null-terminated field of `size` bytes, cut at a character boundary
+fn string_field(s: &str, size: u8) -> Vec<u8> {
+    let mut end = s.len().min(size as usize - 1);
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    let mut bytes = s.as_bytes()[..end].to_vec();
+    bytes.resize(size as usize, 0);
+    bytes
+}
+
+/// Definition message for local type `local` (little-endian fields)
+fn define(out: &mut Vec<u8>, local: u8, global: u16, fields: &[(u8, u8, u8)]) {
+    out.extend_from_slice(&[0x40 | local, 0, 0]);
+    out.extend_from_slice(&global.to_le_bytes());
+    out.push(fields.len() as u8);
+    for &(number, size, base_type) in fields {
+        out.extend_from_slice(&[number, size, base_type]);
+    }
+}
+
+/// The workout as a FIT file
+pub fn encode(workout: &Workout) -> Vec<u8> {
+    let steps = steps(workout);
+    let mut data = Vec::new();
+
+    define(&mut data, 0, FILE_ID, &[(0, 1, ENUM), (1, 2, UINT16), (2, 2, UINT16), (3, 4, UINT32Z), (4, 4, UINT32)]);
+    let created = (Utc::now().timestamp() - FIT_EPOCH_OFFSET).max(1) as u32;
+    data.push(0);
+    data.push(5); // workout file
+    data.extend_from_slice(&255u16.to_le_bytes()); // development manufacturer
+    data.extend_from_slice(&0u16.to_le_bytes());
+    data.extend_from_slice(&created.to_le_bytes()); // serial number
+    data.extend_from_slice(&created.to_le_bytes());
+
+    define(&mut data, 1, WORKOUT, &[(4, 1, ENUM), (6, 2, UINT16), (8, NAME_SIZE, STRING)]);
+    data.push(1);
+    data.push(2); // cycling
+    data.extend_from_slice(&(steps.len() as u16).to_le_bytes());
+    data.extend_from_slice(&string_field(&workout.name, NAME_SIZE));
+
+    define(
+        &mut data,
+        2,
+        WORKOUT_STEP,
+        &[
+            (254, 2, UINT16),
+            (0, STEP_NAME_SIZE, STRING),
+            (1, 1, ENUM),
+            (2, 4, UINT32),
+            (3, 1, ENUM),
+            (4, 4, UINT32),
+            (5, 4, UINT32),
+            (6, 4, UINT32),
+            (7, 1, ENUM),
+        ],
+    );
+    for (i, step) in steps.iter().enumerate() {
+        data.push(2);
+        data.extend_from_slice(&(i as u16).to_le_bytes());
+        data.extend_from_slice(&string_field(step.name, STEP_NAME_SIZE));
+        data.push(step.duration_type);
+        data.extend_from_slice(&step.duration_value.to_le_bytes());
+        data.push(if step.duration_type == DURATION_REPEAT { INVALID as u8 } else { TARGET_POWER });
+        data.extend_from_slice(&step.target_value.to_le_bytes());
+        data.extend_from_slice(&step.low.to_le_bytes());
+        data.extend_from_slice(&step.high.to_le_bytes());
+        data.push(step.intensity);
+    }
+
+    let mut file = vec![14, 0x20];
+    file.extend_from_slice(&PROFILE_VERSION.to_le_bytes());
+    file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    file.extend_from_slice(b".FIT");
+    let header_crc = crc(&file);
+    file.extend_from_slice(&header_crc.to_le_bytes());
+    file.extend_from_slice(&data);
+    let file_crc = crc(&file);
+    file.extend_from_slice(&file_crc.to_le_bytes());
+    file
+}
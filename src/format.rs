@@ -0,0 +1,372 @@
+//! The on-disk data format, declared in one place.
+//!
+//! Everything the frontend (or any third-party tool) reads from `data/` is
+//! described here: file locations, the version stamped into each file, and the
+//! fields a consumer may rely on. `data/format.json` is written from this
+//! module on every sync so readers can pin against `FORMAT_VERSION` instead of
+//! reverse-engineering the JSON.
+//!
+//! Versioning follows semver:
+//! - major: a breaking change; always equal to `SCHEMA_VERSION`, and shipped
+//!   with a migration in `schema.rs`
+//! - minor: new optional fields or files, old readers keep working
+//! - patch: documentation only
+//!
+//! `fixtures/format/v<N>/` holds an index.json and an activity file as each
+//! schema version wrote them; the tests below read every one with this build.
+
+use std::error::Error;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::athlete;
+use crate::compression::{self, Compression};
+use crate::storage::{list_activity_ids, read_activity_file, write_atomic};
+use crate::store;
+
+pub const FORMAT_VERSION: &str = "1.39.0";
+
+/// Integer stamped as `schema_version` into index.json and activity files
+pub const SCHEMA_VERSION: u32 = 1;
+
+pub const DATA_DIR: &str = "data";
+pub const INDEX_FILE: &str = "index.json";
+pub const ACTIVITIES_DIR: &str = "activities";
+pub const MANIFEST_FILE: &str = "format.json";
+pub const WEEKLY_FILE: &str = "weekly.json";
+pub const LAST_SYNC_FILE: &str = "last_sync.json";
+pub const GEAR_FILE: &str = "gear.json";
+pub const BEST_EFFORTS_FILE: &str = "best_efforts.json";
+pub const PLAN_FILE: &str = "plan.json";
+pub const AGGREGATES_FILE: &str = "aggregates.json";
+pub const CLUB_DIR: &str = "club";
+pub const SEGMENTS_FILE: &str = "segments.json";
+pub const ROUTES_DIR: &str = "routes";
+pub const UPLOADS_FILE: &str = "uploads.json";
+pub const TOKEN_FILE: &str = "strava_token";
+pub const SYNC_JOURNAL_FILE: &str = "sync_journal.json";
+pub const RATE_LIMIT_FILE: &str = "rate_limit.json";
+
+/// Files that only make sense on the machine that wrote them, left out of
+/// backups and archive syncs (as are `*.tmp` files of interrupted writes)
+pub const MACHINE_LOCAL_FILES: [&str; 3] = [TOKEN_FILE, SYNC_JOURNAL_FILE, RATE_LIMIT_FILE];
+
+/// Whether a file named `name` stays on this machine
+pub fn machine_local(name: &str) -> bool {
+    MACHINE_LOCAL_FILES.contains(&name) || name.ends_with(".tmp")
+}
+
+/// Root of the active athlete's archive
+pub fn data_dir() -> PathBuf {
+    match athlete::active() {
+        Some(profile) => profile.data_dir(),
+        None => PathBuf::from(DATA_DIR),
+    }
+}
+
+pub fn index_path() -> PathBuf {
+    data_dir().join(INDEX_FILE)
+}
+
+pub fn weekly_path() -> PathBuf {
+    data_dir().join(WEEKLY_FILE)
+}
+
+pub fn gear_path() -> PathBuf {
+    data_dir().join(GEAR_FILE)
+}
+
+pub fn best_efforts_path() -> PathBuf {
+    data_dir().join(BEST_EFFORTS_FILE)
+}
+
+pub fn plan_path() -> PathBuf {
+    data_dir().join(PLAN_FILE)
+}
+
+pub fn aggregates_path() -> PathBuf {
+    data_dir().join(AGGREGATES_FILE)
+}
+
+pub fn segments_path() -> PathBuf {
+    data_dir().join(SEGMENTS_FILE)
+}
+
+/// The route list; each route's GPX sits beside it
+pub fn routes_path() -> PathBuf {
+    data_dir().join(ROUTES_DIR).join("routes.json")
+}
+
+pub fn uploads_path() -> PathBuf {
+    data_dir().join(UPLOADS_FILE)
+}
+
+pub fn club_path(club_id: u64) -> PathBuf {
+    data_dir().join(CLUB_DIR).join(format!("{}.json", club_id))
+}
+
+/// Key of an activity file in the archive's store
+pub fn activity_key(id: ActivityId, compression: Compression) -> String {
+    format!("{}/{}.json{}", ACTIVITIES_DIR, id, compression.extension())
+}
+
+#[derive(Debug, Serialize)]
+pub struct FormatSpec {
+    pub format_version: &'static str,
+    pub schema_version: u32,
+    pub files: Vec<FileSpec>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileSpec {
+    pub path: &'static str,
+    pub description: &'static str,
+    /// Fields guaranteed to be present for this format major version
+    pub required_fields: &'static [&'static str],
+}
+
+pub fn spec() -> FormatSpec {
+    FormatSpec {
+        format_version: FORMAT_VERSION,
+        schema_version: SCHEMA_VERSION,
+        files: vec![
+            FileSpec {
+                path: "index.json",
+                description: "All activities, newest first, without streams. IDs are Strava numbers, \
+                    or strings prefixed by their source (\"fit-42\") for other imports",
+                required_fields: &["schema_version", "last_updated", "activities[].id", "activities[].name",
+                    "activities[].start_date", "activities[].distance", "activities[].moving_time"],
+            },
+            FileSpec {
+                path: "activities/{id}.json[.gz|.zst]",
+                description: "Strava activity summary flattened with its streams, named by index ID \
+                    (123.json, fit-42.json); optionally compressed",
+                required_fields: &["schema_version", "id", "name", "start_date", "start_date_local",
+                    "sport_type", "distance", "moving_time", "elapsed_time", "streams"],
+            },
+            FileSpec {
+                path: "weekly.json",
+                description: "Per-week totals, TSS, intensity distribution and polarization index, oldest first",
+                required_fields: &["schema_version", "weeks[].week", "weeks[].rides", "weeks[].moving_time",
+                    "weeks[].distance", "weeks[].intensity_distribution"],
+            },
+            FileSpec {
+                path: "last_sync.json",
+                description: "What the latest sync changed: added/updated IDs, their metrics, all-time and season \
+                    power records, lifetime and streak milestones reached, current streaks, rides deferred for the rate limit, fetch errors and the rate-limit budget left",
+                required_fields: &["schema_version", "started_at", "finished_at", "added", "updated", "metrics", "records"],
+            },
+            FileSpec {
+                path: "gear.json",
+                description: "Rides, distance and moving time per bike, trainer and component, with service status",
+                required_fields: &["schema_version", "updated_at", "gear[].id", "gear[].rides", "gear[].distance",
+                    "gear[].moving_time"],
+            },
+            FileSpec {
+                path: "best_efforts.json",
+                description: "All-time and per-season (calendar year) best power per duration, with the activity \
+                    and the offset into it in seconds",
+                required_fields: &["schema_version", "updated_at", "durations[].duration", "durations[].seasons"],
+            },
+            FileSpec {
+                path: "plan.json",
+                description: "Planned workouts imported with `plan import` from a CSV or ICS calendar: date, name, \
+                    planned duration in seconds and TSS",
+                required_fields: &["schema_version", "updated_at", "workouts[].date", "workouts[].name"],
+            },
+            FileSpec {
+                path: "aggregates.json",
+                description: "Rolling 7, 28 and 90-day sums of distance (m), moving time (s), TSS and kJ, one value \
+                    per day from `start` to the sync date, keyed by window length",
+                required_fields: &["schema_version", "updated_at", "start", "windows"],
+            },
+            FileSpec {
+                path: "club/{club_id}.json",
+                description: "A Strava club's feed, newest first: athlete (first name and initial), name, sport type, \
+                    distance, times and climbing of each activity, dated by when a fetch first saw it",
+                required_fields: &["schema_version", "club_id", "updated_at", "activities[].first_seen",
+                    "activities[].athlete", "activities[].distance", "activities[].moving_time"],
+            },
+            FileSpec {
+                path: "segments.json",
+                description: "Starred segments with your PR and the KOM (or QOM) in seconds, and a history entry \
+                    per fetch that saw either change",
+                required_fields: &["schema_version", "updated_at", "segments[].id", "segments[].name", "segments[].history"],
+            },
+            FileSpec {
+                path: "routes/routes.json",
+                description: "Routes planned on Strava: ID (a string, too large for a JSON number), name, sport, \
+                    distance, climbing, estimated time, and whether routes/{id}.gpx holds the track",
+                required_fields: &["schema_version", "updated_at", "routes[].id", "routes[].name", "routes[].distance", "routes[].gpx"],
+            },
+            FileSpec {
+                path: "uploads.json",
+                description: "Files pushed with `upload`, keyed by SHA-256: file name, upload ID, the activity \
+                    created (or duplicated) and the outcome",
+                required_fields: &["schema_version", "uploads"],
+            },
+        ],
+    }
+}
+
+/// Write data/format.json describing the format this build produces
+pub fn write_manifest() -> Result<(), Box<dyn Error>> {
+    let json = serde_json::to_string_pretty(&spec())?;
+    write_atomic(&data_dir().join(MANIFEST_FILE), json.as_bytes())
+}
+
+/// Check every stored file against `spec()`: readable, schema version within
+/// range, required fields present. Returns the number of problems found.
+pub fn check() -> Result<usize, Box<dyn Error>> {
+    let spec = spec();
+    let mut report = CheckReport::default();
+
+    let store = store::current()?;
+    let index = store.get(INDEX_FILE).and_then(|json| json.ok_or_else(|| "not found".into()));
+    check_file(&store.describe(INDEX_FILE), index.map_err(|e| e.to_string()), spec.files[0].required_fields, &mut report);
+    for id in list_activity_ids()? {
+        let json = read_activity_file(id).map(|(json, _)| json).map_err(|e| e.to_string());
+        check_file(&format!("activity {}", id), json, spec.files[1].required_fields, &mut report);
+    }
+    // Derived files, only present once a sync has run
+    for (path, file) in [
+        (weekly_path(), &spec.files[2]),
+        (data_dir().join(LAST_SYNC_FILE), &spec.files[3]),
+        (gear_path(), &spec.files[4]),
+        (best_efforts_path(), &spec.files[5]),
+        (plan_path(), &spec.files[6]),
+        (aggregates_path(), &spec.files[7]),
+        (segments_path(), &spec.files[9]),
+        (routes_path(), &spec.files[10]),
+        (uploads_path(), &spec.files[11]),
+    ]
+    .into_iter()
+    .chain(club_files()?.into_iter().map(|path| (path, &spec.files[8])))
+    {
+        if path.exists() {
+            let json = compression::read_file(&path).map_err(|e| e.to_string());
+            check_file(&path.display().to_string(), json, file.required_fields, &mut report);
+        }
+    }
+
+    if report.outdated > 0 {
+        warn!("   ⚠️  {} files use an older schema, run `migrate` to upgrade them", report.outdated);
+    }
+    info!("🔎 Format v{} check: {} files, {} problems", FORMAT_VERSION, report.files, report.problems);
+    Ok(report.problems)
+}
+
+/// Stored club feeds, one per club fetched
+fn club_files() -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    match std::fs::read_dir(data_dir().join(CLUB_DIR)) {
+        Ok(entries) => Ok(entries.filter_map(|e| e.ok().map(|e| e.path())).filter(|p| p.extension().is_some_and(|e| e == "json")).collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[derive(Default)]
+struct CheckReport {
+    files: usize,
+    problems: usize,
+    outdated: usize,
+}
+
+fn check_file(name: &str, json: Result<Vec<u8>, String>, required: &[&str], report: &mut CheckReport) {
+    report.files += 1;
+    let value: serde_json::Value = match json.and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| e.to_string())) {
+        Ok(value) => value,
+        Err(e) => {
+            warn!("   ❌ {}: unreadable ({})", name, e);
+            report.problems += 1;
+            return;
+        }
+    };
+
+    let version = value.get("schema_version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+    if version > SCHEMA_VERSION {
+        warn!("   ❌ {}: schema v{} is newer than this build (v{})", name, version, SCHEMA_VERSION);
+        report.problems += 1;
+    } else if version < SCHEMA_VERSION {
+        report.outdated += 1;
+    }
+    // Files from before versioning predate schema_version; migrate adds it
+    for field in required.iter().filter(|f| **f != "schema_version") {
+        if !has_field(&value, field) {
+            warn!("   ❌ {}: missing required field '{}'", name, field);
+            report.problems += 1;
+        }
+    }
+}
+
+/// Resolve a spec field path; `list[].field` must exist in every element
+fn has_field(value: &serde_json::Value, field: &str) -> bool {
+    match field.split_once("[].") {
+        Some((list, rest)) => match value.get(list).and_then(|v| v.as_array()) {
+            Some(items) => items.iter().all(|item| has_field(item, rest)),
+            None => false,
+        },
+        None => value.get(field).is_some(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::ActivityIndex;
+    use crate::schema;
+    use crate::storage::parse_activity_file;
+
+    /// Archives as each schema version wrote them, checked in under fixtures/format/
+    const SAMPLES: [(u32, &str, &str); 2] = [
+        (0, include_str!("../fixtures/format/v0/index.json"), include_str!("../fixtures/format/v0/activity.json")),
+        (1, include_str!("../fixtures/format/v1/index.json"), include_str!("../fixtures/format/v1/activity.json")),
+    ];
+
+    #[test]
+    fn format_major_is_schema_version() {
+        let major = FORMAT_VERSION.split('.').next().unwrap();
+        assert_eq!(major, SCHEMA_VERSION.to_string(), "a breaking format change needs a schema version and a migration");
+    }
+
+    #[test]
+    fn every_index_version_migrates_and_parses() {
+        for (version, index, _) in SAMPLES {
+            let mut value: serde_json::Value = serde_json::from_str(index).unwrap();
+            assert_eq!(schema::version_of(&value), version);
+            assert_eq!(schema::migrate_index(&mut value).unwrap(), version != SCHEMA_VERSION);
+            assert_eq!(schema::version_of(&value), SCHEMA_VERSION);
+
+            let mut report = CheckReport::default();
+            check_file("index.json", Ok(value.to_string().into_bytes()), spec().files[0].required_fields, &mut report);
+            assert_eq!(report.problems, 0, "v{} index.json", version);
+            let index: ActivityIndex = serde_json::from_value(value).unwrap();
+            assert_eq!(index.activities.iter().map(|a| a.id).collect::<Vec<_>>(), vec![ActivityId::strava(16087951234)]);
+        }
+    }
+
+    #[test]
+    fn every_activity_version_parses() {
+        for (version, _, activity) in SAMPLES {
+            let mut report = CheckReport::default();
+            check_file("activity", Ok(activity.as_bytes().to_vec()), spec().files[1].required_fields, &mut report);
+            assert_eq!(report.problems, 0, "v{} activity file", version);
+
+            let file = parse_activity_file(activity.as_bytes()).unwrap();
+            assert_eq!(file.schema_version, SCHEMA_VERSION);
+            assert_eq!(file.activity.id, ActivityId::strava(16087951234));
+            let streams = file.streams.unwrap();
+            assert_eq!(streams.watts.map(|w| w.len()), Some(30));
+        }
+    }
+
+    #[test]
+    fn newer_schema_is_refused() {
+        let mut value = serde_json::json!({ "schema_version": SCHEMA_VERSION + 1, "activities": [] });
+        assert!(schema::migrate_index(&mut value).is_err());
+        assert!(parse_activity_file(value.to_string().as_bytes()).is_err());
+    }
+}
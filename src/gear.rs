@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::format;
+use crate::model::{Activity, ActivityIndex};
+use crate::storage::{load_activity_file, write_atomic};
+use crate::strava::{access_token, fetch_gear};
+
+/// A bike, trainer or component in watts.json, with its service interval
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GearConfig {
+    /// Strava gear ID ("b1234"), or any name for trainers and components
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Counts every indoor ride, whatever bike was on it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub trainer: bool,
+    /// Counts the rides of this gear ID (a chain on a bike, a belt on a trainer)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub component_of: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_km: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_hours: Option<f64>,
+    /// Usage counts from this date on, set by `gear serviced`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serviced_at: Option<NaiveDate>,
+}
+
+/// data/gear.json
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GearReport {
+    pub schema_version: u32,
+    pub updated_at: String,
+    pub gear: Vec<GearUsage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GearUsage {
+    pub id: String,
+    pub name: Option<String>,
+    pub rides: usize,
+    /// Metres and seconds over every ride
+    pub distance: f64,
+    pub moving_time: i64,
+    /// Strava's odometer for Strava gear, counting rides outside the archive
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strava_distance: Option<f64>,
+    /// Since `serviced_at`, when a service interval is configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serviced_at: Option<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distance_since_service: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub moving_time_since_service: Option<i64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub service_due: bool,
+}
+
+/// Whether an activity counts towards a gear entry
+fn uses(gear: &GearConfig, activity: &Activity, config: &[GearConfig]) -> bool {
+    if let Some(parent) = &gear.component_of {
+        return config
+            .iter()
+            .find(|g| &g.id == parent)
+            .map_or(activity.gear_id.as_deref() == Some(parent.as_str()), |g| uses(g, activity, config));
+    }
+    (gear.trainer && (activity.trainer || activity.sport_type == "VirtualRide"))
+        || activity.gear_id.as_deref() == Some(gear.id.as_str())
+}
+
+/// Total usage per gear: configured entries first, then any other gear ID seen on a ride
+pub fn usage(index: &ActivityIndex, config: &Config) -> Vec<GearUsage> {
+    let mut gear = config.gear.clone();
+    let mut totals: BTreeMap<String, GearUsage> = BTreeMap::new();
+    // Excluded rides still wore the bike, duplicates did not
+    for summary in index.activities.iter().filter(|a| a.duplicate_of.is_none()) {
+        let file = match load_activity_file(summary.id) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("   ⚠️  Skipping {}: {}", summary.id, e);
+                continue;
+            }
+        };
+        let activity = &file.activity;
+        if let Some(id) = activity.gear_id.as_ref().filter(|id| !gear.iter().any(|g| &g.id == *id)) {
+            gear.push(GearConfig {
+                id: id.clone(),
+                name: None,
+                trainer: false,
+                component_of: None,
+                service_km: None,
+                service_hours: None,
+                serviced_at: None,
+            });
+        }
+        let day = activity.start_date.get(..10).and_then(|d| d.parse::<NaiveDate>().ok());
+        for g in gear.iter().filter(|g| uses(g, activity, &config.gear)) {
+            let entry = totals.entry(g.id.clone()).or_insert_with(|| GearUsage {
+                id: g.id.clone(),
+                name: g.name.clone(),
+                rides: 0,
+                distance: 0.0,
+                moving_time: 0,
+                strava_distance: None,
+                serviced_at: g.serviced_at,
+                distance_since_service: None,
+                moving_time_since_service: None,
+                service_due: false,
+            });
+            entry.rides += 1;
+            entry.distance += activity.distance;
+            entry.moving_time += activity.moving_time as i64;
+            let tracked = g.service_km.is_some() || g.service_hours.is_some();
+            if tracked && g.serviced_at.is_none_or(|s| day.is_some_and(|d| d >= s)) {
+                *entry.distance_since_service.get_or_insert(0.0) += activity.distance;
+                *entry.moving_time_since_service.get_or_insert(0) += activity.moving_time as i64;
+            }
+        }
+    }
+
+    gear.iter()
+        .filter_map(|g| {
+            let mut usage = totals.remove(&g.id)?;
+            usage.service_due = g.service_km.is_some_and(|km| usage.distance_since_service.unwrap_or(0.0) / 1000.0 >= km)
+                || g.service_hours.is_some_and(|h| usage.moving_time_since_service.unwrap_or(0) as f64 / 3600.0 >= h);
+            Some(usage)
+        })
+        .collect()
+}
+
+/// Recompute data/gear.json and warn about gear due for service. Name and
+/// odometer of Strava gear come from the previous report, or from the API when
+/// a token is given and they are missing (or always, with `refresh`).
+pub async fn write_report(
+    index: &ActivityIndex,
+    config: &Config,
+    access_token: Option<&str>,
+    refresh: bool,
+) -> Result<Vec<GearUsage>, Box<dyn Error>> {
+    let path = format::gear_path();
+    let previous: BTreeMap<String, GearUsage> = match fs::read_to_string(&path) {
+        Ok(json) => serde_json::from_str::<GearReport>(&json)?.gear.into_iter().map(|g| (g.id.clone(), g)).collect(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut gear = usage(index, config);
+    for g in gear.iter_mut() {
+        let configured = config.gear.iter().any(|c| c.id == g.id && (c.trainer || c.component_of.is_some()));
+        if let Some(previous) = previous.get(&g.id) {
+            g.name = g.name.take().or(previous.name.clone());
+            g.strava_distance = previous.strava_distance;
+        }
+        // Trainers and components are local names, not Strava gear
+        let missing = g.strava_distance.is_none() || refresh;
+        if let (true, Some(token), false) = (missing, access_token, configured) {
+            match fetch_gear(token, &g.id).await {
+                Ok(strava) => {
+                    g.name = g.name.take().or(Some(strava.name));
+                    g.strava_distance = Some(strava.distance);
+                }
+                Err(e) => warn!("   ⚠️  Could not fetch gear {}: {}", g.id, e),
+            }
+        }
+    }
+    for g in gear.iter().filter(|g| g.service_due) {
+        warn!("🔧 {} is due for service: {:.0} km, {:.0} h since {}",
+            g.name.as_deref().unwrap_or(&g.id),
+            g.distance_since_service.unwrap_or(0.0) / 1000.0,
+            g.moving_time_since_service.unwrap_or(0) as f64 / 3600.0,
+            g.serviced_at.map(|d| d.to_string()).unwrap_or_else(|| "the first ride".into()));
+    }
+
+    let report = GearReport {
+        schema_version: format::SCHEMA_VERSION,
+        updated_at: chrono::Utc::now().to_rfc3339(),
+        gear,
+    };
+    write_atomic(&path, serde_json::to_string_pretty(&report)?.as_bytes())?;
+    Ok(report.gear)
+}
+
+/// Print usage per gear, refreshing names and odometers from Strava with `fetch`
+pub async fn run(fetch: bool) -> Result<(), Box<dyn Error>> {
+    let index = ActivityIndex::load()?;
+    let config = Config::current()?;
+    let token = if fetch { Some(access_token().await?) } else { None };
+    let gear = write_report(&index, &config, token.as_deref(), true).await?;
+
+    info!("🚲 Usage of {} bikes, trainers and components", gear.len());
+    println!("  {:<24} {:>5} {:>9} {:>7}  {:>18}", "gear", "rides", "km", "hours", "since service");
+    for g in &gear {
+        println!(
+            "  {:<24} {:>5} {:>9.0} {:>7.1}  {:>18}",
+            g.name.as_deref().unwrap_or(&g.id),
+            g.rides,
+            g.distance / 1000.0,
+            g.moving_time as f64 / 3600.0,
+            match (g.distance_since_service, g.moving_time_since_service) {
+                (Some(m), Some(s)) => format!("{:.0} km {:.0} h{}", m / 1000.0, s as f64 / 3600.0, if g.service_due { " ⚠" } else { "" }),
+                _ => "-".into(),
+            }
+        );
+    }
+    Ok(())
+}
+
+/// Record a service in watts.json so usage starts counting again
+pub fn serviced(id: &str, date: Option<NaiveDate>) -> Result<(), Box<dyn Error>> {
+    let mut config = Config::load()?;
+    let gear = config
+        .gear
+        .iter_mut()
+        .find(|g| g.id == id)
+        .ok_or_else(|| format!("no gear '{}' under \"gear\" in watts.json", id))?;
+    let date = date.unwrap_or_else(|| chrono::Utc::now().date_naive());
+    gear.serviced_at = Some(date);
+    info!("🔧 {} serviced on {}", gear.name.as_deref().unwrap_or(id), date);
+    config.save()
+}
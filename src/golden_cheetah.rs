@@ -0,0 +1,143 @@
+use std::error::Error;
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+use crate::edit::TagFilter;
+use crate::model::{ActivityIndex, ActivityStreams, ActivityWithStreams};
+use crate::resample::StreamOptions;
+use crate::storage::{load_activity_file, write_atomic};
+
+/// Write activities as Golden Cheetah JSON rides into `<athlete_dir>/activities`,
+/// named the way GC names its own imports so it picks them up on next launch.
+/// Rides already present are left alone unless `overwrite` is set.
+/// Streams are resampled to 1 s first (GC assumes `RECINTSECS` spacing), then
+/// smoothed or decimated as `options` ask.
+pub fn export(
+    athlete_dir: &Path,
+    since: Option<NaiveDate>,
+    overwrite: bool,
+    options: &StreamOptions,
+    tags: &TagFilter,
+) -> Result<(), Box<dyn Error>> {
+    let activities_dir = athlete_dir.join("activities");
+    if !athlete_dir.is_dir() {
+        return Err(format!("{} is not a Golden Cheetah athlete directory", athlete_dir.display()).into());
+    }
+    std::fs::create_dir_all(&activities_dir)?;
+
+    let index = ActivityIndex::load()?;
+    let (mut written, mut existing) = (0, 0);
+    for summary in index.activities.iter().filter(|a| since.is_none_or(|s| a.local_date() >= s) && tags.matches(a)) {
+        let file = match load_activity_file(summary.id) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("   ⚠️  Skipping {}: {}", summary.id, e);
+                continue;
+            }
+        };
+        let options = StreamOptions { resample: true, ..options.clone() };
+        let (name, ride) = match ride(&file, &options) {
+            Ok(ride) => ride,
+            Err(e) => {
+                warn!("   ⚠️  Skipping {}: {}", summary.id, e);
+                continue;
+            }
+        };
+        let path = activities_dir.join(name);
+        if path.exists() && !overwrite {
+            existing += 1;
+            continue;
+        }
+        write_atomic(&path, serde_json::to_string_pretty(&ride)?.as_bytes())?;
+        written += 1;
+    }
+
+    info!("🐆 Exported {} rides to {} ({} already there)", written, activities_dir.display(), existing);
+    Ok(())
+}
+
+/// GC file name (local start time) and ride document for one activity
+fn ride(file: &ActivityWithStreams, options: &StreamOptions) -> Result<(String, Value), String> {
+    let activity = &file.activity;
+    let start: DateTime<Utc> = activity
+        .start_date
+        .parse()
+        .map_err(|e| format!("invalid start_date '{}': {}", activity.start_date, e))?;
+    // Strava suffixes local times with Z even though they carry no zone
+    let local: DateTime<Utc> = activity
+        .start_date_local
+        .parse()
+        .map_err(|e| format!("invalid start_date_local '{}': {}", activity.start_date_local, e))?;
+
+    let mut tags = json!({
+        "Sport": if activity.sport_type.contains("Run") { "Run" } else { "Bike" },
+        "Workout Title": activity.name,
+        "Activity ID": activity.id.to_string(),
+    });
+    if let Some(description) = &activity.description {
+        tags["Notes"] = json!(description);
+    }
+    if let Some(device) = &activity.device_name {
+        tags["Device"] = json!(device);
+    }
+
+    let mut ride = json!({
+        "STARTTIME": start.format("%Y/%m/%d %H:%M:%S UTC ").to_string(),
+        "RECINTSECS": 1,
+        "DEVICETYPE": activity.device_name.as_deref().unwrap_or("Strava"),
+        "IDENTIFIER": "",
+        "TAGS": tags,
+    });
+
+    if let Some(streams) = file.streams.as_ref().map(|s| options.apply(s)) {
+        if let Some(time) = &streams.time {
+            if let Some(laps) = file.laps.as_ref().filter(|laps| laps.len() > 1) {
+                let intervals: Vec<Value> = laps
+                    .iter()
+                    .filter_map(|lap| {
+                        let start = *time.get(lap.start_index)?;
+                        let stop = *time.get(lap.end_index.min(time.len() - 1))?;
+                        Some(json!({ "NAME": lap.name, "START": start, "STOP": stop, "COLOR": "#000000" }))
+                    })
+                    .collect();
+                ride["INTERVALS"] = json!(intervals);
+            }
+            ride["SAMPLES"] = json!(samples(&streams, time));
+        }
+    }
+
+    Ok((local.format("%Y_%m_%d_%H_%M_%S.json").to_string(), json!({ "RIDE": ride })))
+}
+
+/// One GC sample per stream point; distance is integrated from speed as GC expects cumulative km
+fn samples(streams: &ActivityStreams, time: &[i32]) -> Vec<Value> {
+    let mut km = 0.0;
+    let mut samples = Vec::with_capacity(time.len());
+    for (i, &secs) in time.iter().enumerate() {
+        let speed = streams.velocity_smooth.as_ref().and_then(|v| v.get(i)).copied();
+        if i > 0 {
+            km += speed.unwrap_or(0.0) * (secs - time[i - 1]).max(0) as f64 / 1000.0;
+        }
+        let mut sample = json!({ "SECS": secs, "KM": (km * 1e5).round() / 1e5 });
+        if let Some(watts) = streams.watts.as_ref().and_then(|w| w.get(i)) {
+            sample["WATTS"] = json!(watts);
+        }
+        if let Some(hr) = streams.heartrate.as_ref().and_then(|h| h.get(i)) {
+            sample["HR"] = json!(hr);
+        }
+        if let Some(cadence) = streams.cadence.as_ref().and_then(|c| c.get(i)) {
+            sample["CAD"] = json!(cadence);
+        }
+        if let Some(speed) = speed {
+            sample["KPH"] = json!((speed * 3.6 * 100.0).round() / 100.0);
+        }
+        if let Some(altitude) = streams.altitude.as_ref().and_then(|a| a.get(i)) {
+            sample["ALT"] = json!(altitude);
+        }
+        samples.push(sample);
+    }
+    samples
+}
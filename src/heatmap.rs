@@ -0,0 +1,77 @@
+//! Training calendar heatmap, GitHub contribution graph style: one cell per
+//! day with its load and time, shaded by quartile of the active days so a
+//! year of consistency (or the gaps in it) shows at a glance. `report` draws
+//! it, `publish` puts the days in aggregates.json for frontends to draw.
+
+use std::collections::BTreeMap;
+
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::Serialize;
+
+use crate::model::ActivityIndex;
+use crate::training_load::DailyLoad;
+
+/// Shades, rest days included
+pub const LEVELS: usize = 5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HeatmapDay {
+    pub date: NaiveDate,
+    /// Load counted in the PMC that day
+    pub tss: f64,
+    /// Seconds
+    pub moving_time: i32,
+    pub activities: usize,
+    /// 0 on a rest day, else 1 to 4 by quartile of the active days' TSS
+    /// (moving time when no ride has a TSS)
+    pub level: usize,
+}
+
+/// Every day from `since` (the first ride by default) to today, with the
+/// daily TSS of `loads`
+pub fn days(index: &ActivityIndex, loads: &[DailyLoad], since: Option<NaiveDate>) -> Vec<HeatmapDay> {
+    let mut time: BTreeMap<NaiveDate, (i32, usize)> = BTreeMap::new();
+    for summary in index.activities.iter().filter(|a| a.counted()) {
+        let day = time.entry(summary.local_date()).or_default();
+        day.0 += summary.moving_time;
+        day.1 += 1;
+    }
+    let tss: BTreeMap<NaiveDate, f64> = loads.iter().map(|d| (d.date, d.tss)).collect();
+    let Some(first) = since.or_else(|| time.keys().next().copied()) else { return Vec::new() };
+    let today = Utc::now().date_naive();
+
+    let by_tss = tss.range(first..).any(|(_, &t)| t > 0.0);
+    let value = |date: NaiveDate| {
+        if by_tss { tss.get(&date).copied().unwrap_or(0.0) } else { time.get(&date).map_or(0.0, |&(seconds, _)| seconds as f64) }
+    };
+    let mut active: Vec<f64> = first.iter_days().take_while(|d| *d <= today).map(value).filter(|&v| v > 0.0).collect();
+    active.sort_by(f64::total_cmp);
+    let quartiles: Vec<f64> = (1..LEVELS - 1).filter_map(|q| active.get(active.len() * q / (LEVELS - 1)).copied()).collect();
+
+    first
+        .iter_days()
+        .take_while(|d| *d <= today)
+        .map(|date| {
+            let (moving_time, activities) = time.get(&date).copied().unwrap_or_default();
+            let v = value(date);
+            let level = if activities == 0 && v == 0.0 { 0 } else { 1 + quartiles.iter().filter(|&&q| v > q).count() };
+            HeatmapDay { date, tss: (tss.get(&date).copied().unwrap_or(0.0) * 10.0).round() / 10.0, moving_time, activities, level }
+        })
+        .collect()
+}
+
+/// The days laid out in Monday-first weeks: `[week][weekday]`, None before
+/// the first day and after the last
+pub fn weeks(days: &[HeatmapDay]) -> Vec<[Option<&HeatmapDay>; 7]> {
+    let mut weeks: Vec<[Option<&HeatmapDay>; 7]> = Vec::new();
+    for day in days {
+        let weekday = day.date.weekday().num_days_from_monday() as usize;
+        if weekday == 0 || weeks.is_empty() {
+            weeks.push([None; 7]);
+        }
+        if let Some(week) = weeks.last_mut() {
+            week[weekday] = Some(day);
+        }
+    }
+    weeks
+}
@@ -0,0 +1,87 @@
+//! Conditional GETs: Strava responses that carry an ETag are kept, and asked
+//! for again with `If-None-Match`, so re-listing pages or re-checking an
+//! activity that hasn't changed gets a bodyless 304.
+//!
+//! Entries live under `.watts-cache/http/<athlete>/` (`WATTS_HTTP_CACHE` moves
+//! it, `off` disables it) in the cassette layout, each with the URL, query and
+//! ETag it answers. The cache is disposable: a missing or unreadable entry is a
+//! plain request.
+
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::athlete;
+use crate::cassette;
+use crate::storage::write_atomic;
+
+const DEFAULT_DIR: &str = ".watts-cache/http";
+
+/// Responses answered from the cache this run
+static HITS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry {
+    pub url: String,
+    pub query: Vec<(String, String)>,
+    pub etag: String,
+    pub body: String,
+}
+
+impl Entry {
+    fn answers(&self, url: &str, query: &[(&str, &str)]) -> bool {
+        self.url == url && self.query.iter().map(|(k, v)| (k.as_str(), v.as_str())).eq(query.iter().copied())
+    }
+}
+
+/// Where the cache of the active athlete lives, None when disabled
+fn dir() -> Option<PathBuf> {
+    let root = match std::env::var("WATTS_HTTP_CACHE") {
+        Ok(value) if value == "off" => return None,
+        Ok(value) => PathBuf::from(value),
+        Err(_) => PathBuf::from(DEFAULT_DIR),
+    };
+    // The same URL answers differently for each athlete's token
+    Some(root.join(athlete::cache_key()))
+}
+
+fn path_for(url: &str, query: &[(&str, &str)]) -> Option<PathBuf> {
+    let path = reqwest::Url::parse(url).ok()?.path().to_string();
+    cassette::file_for(&dir()?, &path, query)
+}
+
+/// The cached response to `url` with `query`, if there is one to revalidate
+pub fn lookup(url: &str, query: &[(&str, &str)]) -> Option<Entry> {
+    let json = fs::read_to_string(path_for(url, query)?).ok()?;
+    serde_json::from_str::<Entry>(&json).ok().filter(|entry| entry.answers(url, query))
+}
+
+/// Keep a response for the next request to the same URL
+pub fn store(url: &str, query: &[(&str, &str)], etag: &str, body: &str) -> Result<(), Box<dyn Error>> {
+    let Some(path) = path_for(url, query) else { return Ok(()) };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let entry = Entry {
+        url: url.to_string(),
+        query: query.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        etag: etag.to_string(),
+        body: body.to_string(),
+    };
+    write_atomic(&path, serde_json::to_string(&entry)?.as_bytes())
+}
+
+/// A 304 answered `entry`
+pub fn hit(entry: Entry) -> String {
+    debug!("   ♻️  Not modified: {}", entry.url);
+    HITS.fetch_add(1, Ordering::Relaxed);
+    entry.body
+}
+
+pub fn hits() -> usize {
+    HITS.load(Ordering::Relaxed)
+}
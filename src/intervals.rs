@@ -0,0 +1,78 @@
+use crate::metrics;
+use crate::model::ActivityWithStreams;
+use crate::resample;
+
+/// Shortest effort reported when detecting intervals from the power stream
+const MIN_INTERVAL_SECONDS: usize = 60;
+
+/// Smoothing applied before detection so single-second dips don't split efforts
+const SMOOTHING_SECONDS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntervalSource {
+    /// Lap boundaries recorded by the device (Zwift workout blocks)
+    Laps,
+    /// Guessed from sustained efforts in the power stream
+    Detected,
+}
+
+#[derive(Debug, Clone)]
+pub struct Interval {
+    /// Index of the first stream sample
+    pub start: usize,
+    pub duration: i32,
+    pub average_watts: Option<f64>,
+    pub average_heartrate: Option<f64>,
+}
+
+/// Intervals of a ride: its laps when it has a real lap structure, otherwise
+/// sustained efforts detected from the power stream
+pub fn intervals(file: &ActivityWithStreams) -> (IntervalSource, Vec<Interval>) {
+    if let Some(laps) = file.laps.as_ref().filter(|laps| laps.len() > 1) {
+        let intervals = laps
+            .iter()
+            .map(|lap| Interval {
+                start: lap.start_index,
+                duration: lap.moving_time,
+                average_watts: lap.average_watts,
+                average_heartrate: lap.average_heartrate,
+            })
+            .collect();
+        return (IntervalSource::Laps, intervals);
+    }
+    (IntervalSource::Detected, detect_efforts(file))
+}
+
+/// Blocks where smoothed power stays above the ride's normalized power
+pub fn detect_efforts(file: &ActivityWithStreams) -> Vec<Interval> {
+    let Some(streams) = &file.streams else { return Vec::new() };
+    let Some(watts) = streams.watts.as_deref() else { return Vec::new() };
+    let Some(threshold) = metrics::normalized_power(watts) else { return Vec::new() };
+
+    let smoothed = resample::rolling_mean(watts, SMOOTHING_SECONDS);
+    let mut intervals = Vec::new();
+    let mut start = None;
+    for (i, &w) in smoothed.iter().chain(std::iter::once(&0.0)).enumerate() {
+        match (start, w >= threshold) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                if i - s >= MIN_INTERVAL_SECONDS {
+                    let heartrate: Option<Vec<f64>> = streams
+                        .heartrate
+                        .as_ref()
+                        .and_then(|hr| hr.get(s..i))
+                        .map(|hr| hr.iter().map(|&h| h as f64).collect());
+                    intervals.push(Interval {
+                        start: s,
+                        duration: (i - s) as i32,
+                        average_watts: metrics::mean(&watts[s..i]),
+                        average_heartrate: heartrate.as_deref().and_then(metrics::mean),
+                    });
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    intervals
+}
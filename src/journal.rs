@@ -0,0 +1,130 @@
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+
+use chrono::{Datelike, NaiveDate};
+use tracing::{info, warn};
+
+use crate::cli;
+use crate::format;
+use crate::model::{Activity, ActivityIndex, ActivitySummary, Social};
+use crate::storage::{load_activity_file, write_atomic};
+
+/// Compile every activity of a month (YYYY-MM) into data/journal/<month>.md,
+/// leaving out excluded rides and duplicates as the totals elsewhere do
+pub fn run(month: &str) -> Result<(), Box<dyn Error>> {
+    let first_day = NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+        .map_err(|_| format!("invalid month '{}', expected YYYY-MM", month))?;
+
+    let index = ActivityIndex::load()?;
+    let in_month = |s: &&ActivitySummary| {
+        let day = s.local_date();
+        day.year() == first_day.year() && day.month() == first_day.month()
+    };
+    let mut activities: Vec<(&ActivitySummary, Activity, Option<Social>)> = Vec::new();
+    for summary in index.activities.iter().filter(|s| s.counted()).filter(in_month) {
+        match load_activity_file(summary.id) {
+            Ok(file) => activities.push((summary, file.activity, file.social)),
+            Err(e) => warn!("   ⚠️  Skipping {}: {}", summary.id, e),
+        }
+    }
+    activities.sort_by(|(_, a, _), (_, b, _)| a.start_date_local.cmp(&b.start_date_local));
+
+    let markdown = render_month(first_day, &activities);
+    let dir = format::data_dir().join("journal");
+    fs::create_dir_all(&dir)?;
+    let filename = dir.join(format!("{}.md", month));
+    write_atomic(&filename, markdown.as_bytes())?;
+
+    info!("📓 Wrote {} activities to {}", activities.len(), filename.display());
+    Ok(())
+}
+
+fn render_month(first_day: NaiveDate, activities: &[(&ActivitySummary, Activity, Option<Social>)]) -> String {
+    let mut md = String::new();
+    let _ = writeln!(md, "# Training journal - {}\n", first_day.format("%B %Y"));
+
+    if activities.is_empty() {
+        md.push_str("No rides this month.\n");
+        return md;
+    }
+
+    let distance: f64 = activities.iter().map(|(_, a, _)| a.distance).sum();
+    let moving_time: i32 = activities.iter().map(|(_, a, _)| a.moving_time).sum();
+    let energy: f64 = activities.iter().filter_map(|(_, a, _)| a.kilojoules).sum();
+    let elevation: f64 = activities.iter().map(|(_, a, _)| a.total_elevation_gain).sum();
+    let _ = writeln!(
+        md,
+        "**{} rides** · {:.1} km · {} · {:.0} kJ · {:.0} m climbing\n",
+        activities.len(),
+        distance / 1000.0,
+        cli::hours_minutes(moving_time),
+        energy,
+        elevation
+    );
+
+    for (summary, activity, social) in activities {
+        render_activity(&mut md, summary, activity, social.as_ref());
+    }
+    md
+}
+
+fn render_activity(md: &mut String, summary: &ActivitySummary, activity: &Activity, social: Option<&Social>) {
+    let day = chrono::DateTime::parse_from_rfc3339(&activity.start_date_local)
+        .map(|d| d.format("%a %d %b").to_string())
+        .unwrap_or_else(|_| activity.start_date_local.clone());
+    let _ = writeln!(md, "## {} - {}\n", day, activity.name);
+    if let Some(description) = activity.description.as_deref().filter(|d| !d.trim().is_empty()) {
+        for line in description.lines() {
+            let _ = writeln!(md, "> {}", line);
+        }
+        md.push('\n');
+    }
+    if let Some(notes) = summary.notes.as_deref().filter(|n| !n.trim().is_empty()) {
+        for line in notes.lines() {
+            let _ = writeln!(md, "{}", line);
+        }
+        md.push('\n');
+    }
+
+    let _ = writeln!(
+        md,
+        "- Distance {:.1} km · Moving time {} · Elevation {:.0} m",
+        activity.distance / 1000.0,
+        cli::hours_minutes(activity.moving_time),
+        activity.total_elevation_gain
+    );
+    if let Some(avg) = activity.average_watts {
+        let mut line = format!("- Power avg {:.0} W", avg);
+        if let Some(weighted) = activity.weighted_average_watts {
+            let _ = write!(line, " · weighted {:.0} W", weighted);
+        }
+        if let Some(max) = activity.max_watts {
+            let _ = write!(line, " · max {:.0} W", max);
+        }
+        if let Some(kj) = activity.kilojoules {
+            let _ = write!(line, " · {:.0} kJ", kj);
+        }
+        let _ = writeln!(md, "{}", line);
+    }
+    if let Some(avg) = activity.average_heartrate {
+        let max = activity.max_heartrate.map(|m| format!(" · max {:.0} bpm", m)).unwrap_or_default();
+        let _ = writeln!(md, "- Heart rate avg {:.0} bpm{}", avg, max);
+    }
+    if let Some(cadence) = activity.average_cadence {
+        let _ = writeln!(md, "- Cadence avg {:.0} rpm", cadence);
+    }
+    if !summary.tags.is_empty() {
+        let _ = writeln!(md, "- Tags: {}", summary.tags.join(", "));
+    }
+    md.push('\n');
+
+    // Photos fetched by the `social` command, at the largest size stored
+    let photos = social.map(|s| s.photos.as_slice()).unwrap_or_default();
+    for photo in photos {
+        if let Some((_, url)) = photo.urls.iter().max_by_key(|(size, _)| size.parse::<u32>().unwrap_or(0)) {
+            let caption = photo.caption.as_deref().unwrap_or_default().replace(['\n', '[', ']'], " ");
+            let _ = writeln!(md, "![{}]({})\n", caption.trim(), url);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Strava archive and analysis library behind the `strava_fetcher` binary;
+//! integration tests drive it against `mock_strava`.
+
+pub mod activity_id;
+pub mod adjust;
+pub mod aerobic;
+pub mod align;
+pub mod anomalies;
+pub mod archive_sync;
+pub mod ascii_chart;
+pub mod athlete;
+pub mod athlete_history;
+pub mod auth;
+pub mod backup;
+pub mod best_efforts;
+pub mod cadence;
+pub mod calendar;
+pub mod cassette;
+pub mod ci;
+pub mod classify;
+pub mod clean;
+pub mod cli;
+pub mod club;
+pub mod club_activities;
+pub mod compact;
+pub mod compare;
+pub mod compression;
+pub mod config;
+pub mod critical_power;
+pub mod custom_metrics;
+pub mod dfa;
+pub mod duplicates;
+pub mod diff;
+pub mod edit;
+pub mod escape;
+pub mod elevation;
+pub mod exporter;
+pub mod expression;
+pub mod failure;
+pub mod fit_workout;
+pub mod format;
+pub mod gear;
+pub mod golden_cheetah;
+pub mod heatmap;
+pub mod http_cache;
+pub mod intervals;
+pub mod journal;
+pub mod list;
+pub mod locale;
+pub mod logging;
+pub mod mail;
+pub mod matches;
+pub mod metrics;
+pub mod milestones;
+pub mod migrate;
+pub mod mock_strava;
+pub mod model;
+pub mod notify;
+pub mod pace;
+pub mod pacing;
+pub mod pedalling;
+pub mod pipeline;
+pub mod plan;
+pub mod planned_routes;
+pub mod power_estimate;
+pub mod publish;
+pub mod progress;
+pub mod push;
+pub mod recompute;
+pub mod refetch;
+pub mod report;
+pub mod resample;
+pub mod rolling;
+pub mod schema;
+pub mod search;
+pub mod season;
+pub mod segments;
+pub mod show;
+pub mod social;
+pub mod sprints;
+pub mod stats;
+pub mod storage;
+pub mod store;
+pub mod strava;
+pub mod streaks;
+pub mod streamless;
+pub mod sync;
+pub mod sync_journal;
+pub mod sync_report;
+pub mod tcx;
+pub mod training_load;
+pub mod trends;
+pub mod tui;
+pub mod upgrade;
+pub mod upload;
+pub mod verify;
+pub mod w_balance;
+pub mod weekly;
+pub mod wkg;
+pub mod workout;
+pub mod zones;
+pub mod zwift;
@@ -0,0 +1,315 @@
+//! Query the index: filter rides by date, distance, sport, type, tags or any
+//! `--filter` condition, sort them by a stored or derived metric and print
+//! the matches as a table (or JSON), optionally writing them to a CSV file.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::Write as _;
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::cli::{self, OutputFormat};
+use crate::config::Config;
+use crate::edit::{Filter, TagFilter};
+use crate::metrics;
+use crate::model::{ActivityIndex, ActivitySummary};
+use crate::pace;
+use crate::power_estimate::PowerEstimate;
+use crate::storage::{load_activity_file, write_atomic};
+use crate::streamless;
+
+/// What `list` can select on; everything else goes through `--filter`
+#[derive(Debug, Default)]
+pub struct Query {
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+    /// Metres
+    pub min_distance: Option<f64>,
+    pub max_distance: Option<f64>,
+    pub sport: Option<String>,
+    pub workout_type: Option<String>,
+    pub filters: Vec<Filter>,
+    pub tags: TagFilter,
+    /// Also list excluded rides and duplicates
+    pub all: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Date,
+    Distance,
+    Time,
+    Watts,
+    Np,
+    Tss,
+    Ef,
+    Decoupling,
+    Vi,
+}
+
+impl SortKey {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "date" => Ok(SortKey::Date),
+            "distance" => Ok(SortKey::Distance),
+            "time" => Ok(SortKey::Time),
+            "watts" => Ok(SortKey::Watts),
+            "np" => Ok(SortKey::Np),
+            "tss" => Ok(SortKey::Tss),
+            "ef" => Ok(SortKey::Ef),
+            "decoupling" => Ok(SortKey::Decoupling),
+            "vi" => Ok(SortKey::Vi),
+            other => Err(format!(
+                "unknown sort '{}', expected date, distance, time, watts, np, tss, ef, decoupling or vi",
+                other
+            )),
+        }
+    }
+}
+
+/// `40km`, `25mi`, `12000m`, or a bare number of kilometres, in metres
+pub fn parse_distance(s: &str) -> Result<f64, String> {
+    let (number, scale) = if let Some(km) = s.strip_suffix("km") {
+        (km, 1000.0)
+    } else if let Some(mi) = s.strip_suffix("mi") {
+        (mi, 1609.344)
+    } else if let Some(m) = s.strip_suffix('m') {
+        (m, 1.0)
+    } else {
+        (s, 1000.0)
+    };
+    match number.trim().parse::<f64>() {
+        Ok(value) if value >= 0.0 => Ok(value * scale),
+        _ => Err(format!("invalid distance '{}', expected e.g. 40km, 25mi or 12000m", s)),
+    }
+}
+
+/// One listed ride, with what its file adds to the index entry
+#[derive(Debug, Serialize)]
+struct Row {
+    id: ActivityId,
+    start_date: DateTime<Utc>,
+    /// Local day of the start, as listed
+    #[serde(skip)]
+    date: NaiveDate,
+    name: String,
+    sport_type: Option<String>,
+    distance: f64,
+    moving_time: i32,
+    average_watts: Option<f64>,
+    normalized_power: Option<f64>,
+    /// From speed and grade, for rides without a power meter
+    #[serde(skip_serializing_if = "Option::is_none")]
+    estimated_power: Option<PowerEstimate>,
+    tss: Option<f64>,
+    efficiency_factor: Option<f64>,
+    decoupling: Option<f64>,
+    variability_index: Option<f64>,
+    pacing_score: Option<f64>,
+    workout_type: Option<String>,
+    route: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    custom_metrics: BTreeMap<String, f64>,
+}
+
+impl Row {
+    fn key(&self, key: SortKey) -> Option<f64> {
+        match key {
+            SortKey::Date => None,
+            SortKey::Distance => Some(self.distance),
+            SortKey::Time => Some(self.moving_time as f64),
+            SortKey::Watts => self.average_watts,
+            SortKey::Np => self.normalized_power,
+            SortKey::Tss => self.tss,
+            SortKey::Ef => self.efficiency_factor,
+            SortKey::Decoupling => self.decoupling,
+            SortKey::Vi => self.variability_index,
+        }
+    }
+}
+
+impl Query {
+    /// The conditions the index entry alone can answer
+    fn matches(&self, summary: &ActivitySummary) -> bool {
+        let day = summary.local_date();
+        (self.all || summary.counted())
+            && self.since.is_none_or(|s| day >= s)
+            && self.until.is_none_or(|u| day <= u)
+            && self.min_distance.is_none_or(|m| summary.distance >= m)
+            && self.max_distance.is_none_or(|m| summary.distance <= m)
+            && self.workout_type.as_ref().is_none_or(|t| summary.workout_type.as_ref() == Some(t))
+            && self.tags.matches(summary)
+            && self.filters.iter().all(|f| f.matches(summary))
+    }
+}
+
+/// Print the rides matching `query`, best first by `sort` (newest first by
+/// date), or the other way round with `ascending`, and write them to `export` as CSV
+pub fn run(
+    query: &Query,
+    sort: SortKey,
+    ascending: bool,
+    limit: Option<usize>,
+    output: OutputFormat,
+    export: Option<&Path>,
+) -> Result<(), Box<dyn Error>> {
+    let index = ActivityIndex::load()?;
+    let config = Config::current()?;
+
+    let mut rows = Vec::new();
+    for summary in index.activities.iter().filter(|a| query.matches(a)) {
+        // Sport and NP live in the activity file
+        let file = match load_activity_file(summary.id) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                warn!("   ⚠️  {}: {}", summary.id, e);
+                None
+            }
+        };
+        let sport_type = file.as_ref().map(|f| f.activity.sport_type.clone());
+        if let Some(sport) = &query.sport {
+            if !sport_type.as_ref().is_some_and(|s| s.eq_ignore_ascii_case(sport)) {
+                continue;
+            }
+        }
+        // Runs are scored by pace: no NP, rTSS in the TSS column
+        let run = sport_type.as_deref().is_some_and(pace::is_run);
+        let streams = file.as_ref().and_then(|f| f.streams.as_ref());
+        let np = streams.and_then(|s| s.watts.as_deref()).filter(|_| !run).and_then(metrics::normalized_power);
+        let estimated = sport_type.as_deref().and_then(|sport| streamless::estimated_load(sport, summary.moving_time, &config));
+        let tss = if let Some(load) = estimated {
+            Some(load.round())
+        } else if run {
+            streams.and_then(|s| pace::run_tss(s, summary.moving_time, &config)).map(f64::round)
+        } else {
+            np.zip(config.ftp()).map(|(np, ftp)| metrics::training_stress_score(np, summary.moving_time, ftp).round())
+        };
+        rows.push(Row {
+            id: summary.id,
+            start_date: summary.start_date,
+            date: summary.local_date(),
+            name: summary.name.clone(),
+            sport_type,
+            distance: summary.distance,
+            moving_time: summary.moving_time,
+            average_watts: summary.average_watts,
+            normalized_power: np.map(f64::round),
+            estimated_power: summary.estimated_power,
+            tss,
+            efficiency_factor: summary.efficiency_factor,
+            decoupling: summary.decoupling,
+            variability_index: summary.pacing.map(|p| p.variability_index),
+            pacing_score: summary.pacing.map(|p| p.score),
+            workout_type: summary.workout_type.clone(),
+            route: summary.route.clone(),
+            custom_metrics: summary.custom_metrics.clone(),
+        });
+    }
+
+    // Highest first, rides without the metric last either way
+    rows.sort_by(|a, b| {
+        let ordering = match sort {
+            SortKey::Date => b.start_date.cmp(&a.start_date),
+            key => match (a.key(key), b.key(key)) {
+                (Some(x), Some(y)) => y.total_cmp(&x),
+                (Some(_), None) => return Ordering::Less,
+                (None, Some(_)) => return Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        };
+        if ascending { ordering.reverse() } else { ordering }
+    });
+    let matched = rows.len();
+    rows.truncate(limit.unwrap_or(usize::MAX));
+    if let Some(path) = export {
+        write_csv(path, &rows, &config)?;
+    }
+
+    if output == OutputFormat::Json {
+        return cli::print_json(&rows);
+    }
+    info!("🔎 {} rides match{}", matched, if rows.len() < matched { format!(", showing {}", rows.len()) } else { String::new() });
+    if rows.is_empty() {
+        return Ok(());
+    }
+    // Custom metrics from watts.json get a column each, before the type
+    let columns: Vec<(&String, usize)> = config.custom_metrics.keys().map(|name| (name, name.len().max(6))).collect();
+    let custom: String = columns.iter().map(|(name, width)| format!(" {:>width$}", name, width = width)).collect();
+    println!("  {:<10} {:<16} {:<36} {:>6} {:>6} {:>5} {:>5} {:>5}{}  type", "date", "id", "name", "km", "hours", "W", "NP", "TSS", custom);
+    let number = |v: Option<f64>| v.map(|v| format!("{:.0}", v)).unwrap_or_else(|| "-".into());
+    // Estimated power stands in for a missing power meter, marked with ~
+    let watts = |measured: Option<f64>, estimated: Option<f64>| match (measured, estimated) {
+        (None, Some(estimated)) => format!("~{:.0}", estimated),
+        (measured, _) => number(measured),
+    };
+    for row in &rows {
+        let name: String = row.name.chars().take(36).collect();
+        let custom: String = columns
+            .iter()
+            .map(|(metric, width)| {
+                let value = row.custom_metrics.get(*metric).map_or_else(|| "-".to_string(), |v| format!("{:.1}", v));
+                format!(" {:>width$}", value, width = width)
+            })
+            .collect();
+        println!(
+            "  {:<10} {:<16} {:<36} {:>6.1} {:>6.1} {:>5} {:>5} {:>5}{}  {}",
+            row.date,
+            row.id.to_string(),
+            name,
+            row.distance / 1000.0,
+            row.moving_time as f64 / 3600.0,
+            watts(row.average_watts, row.estimated_power.map(|e| e.average_watts)),
+            watts(row.normalized_power, row.estimated_power.and_then(|e| e.normalized_power)),
+            number(row.tss),
+            custom,
+            row.workout_type.as_deref().unwrap_or("-"),
+        );
+    }
+    Ok(())
+}
+
+/// One line per row, custom metrics from watts.json last, empty cells for missing values
+fn write_csv(path: &Path, rows: &[Row], config: &Config) -> Result<(), Box<dyn Error>> {
+    let mut csv = String::from("id,date,name,sport_type,distance,moving_time,average_watts,normalized_power,\
+        estimated_watts,estimated_np,tss,efficiency_factor,decoupling,variability_index,pacing_score,workout_type,route");
+    for name in config.custom_metrics.keys() {
+        let _ = write!(csv, ",{}", name);
+    }
+    csv.push('\n');
+    let cell = |v: Option<f64>| v.map(|v| v.to_string()).unwrap_or_default();
+    // Names and routes are quoted, doubling any quote inside
+    let text = |v: Option<&str>| v.map(|v| format!("\"{}\"", v.replace('"', "\"\""))).unwrap_or_default();
+    for row in rows {
+        let _ = write!(csv, "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            row.id,
+            row.date,
+            text(Some(&row.name)),
+            text(row.sport_type.as_deref()),
+            row.distance,
+            row.moving_time,
+            cell(row.average_watts),
+            cell(row.normalized_power),
+            cell(row.estimated_power.map(|e| e.average_watts)),
+            cell(row.estimated_power.and_then(|e| e.normalized_power)),
+            cell(row.tss),
+            cell(row.efficiency_factor),
+            cell(row.decoupling),
+            cell(row.variability_index),
+            cell(row.pacing_score),
+            text(row.workout_type.as_deref()),
+            text(row.route.as_deref()),
+        );
+        for name in config.custom_metrics.keys() {
+            let _ = write!(csv, ",{}", cell(row.custom_metrics.get(name).copied()));
+        }
+        csv.push('\n');
+    }
+    write_atomic(path, csv.as_bytes())?;
+    info!("📄 Wrote {} rides to {}", rows.len(), path.display());
+    Ok(())
+}
@@ -0,0 +1,85 @@
+//! Number and date formatting for reports shared outside the terminal, set
+//! with `"locale"` in watts.json. English keeps the ISO dates and plain
+//! numbers used everywhere else; French uses day-first dates, French month
+//! names, a decimal comma and spaces between thousands.
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    Fr,
+}
+
+const FRENCH_MONTHS: [&str; 12] = ["janv.", "févr.", "mars", "avr.", "mai", "juin", "juil.", "août", "sept.", "oct.", "nov.", "déc."];
+
+/// Groups thousands in French (U+202F, narrow no-break space)
+const FRENCH_THOUSANDS: char = '\u{202F}';
+
+impl Locale {
+    /// Value of the HTML `lang` attribute
+    pub fn lang(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Fr => "fr",
+        }
+    }
+
+    /// `value` with `decimals` digits after the separator
+    pub fn number(self, value: f64, decimals: usize) -> String {
+        let plain = format!("{:.decimals$}", value);
+        match self {
+            Locale::En => plain,
+            Locale::Fr => {
+                let (sign, digits) = plain.strip_prefix('-').map_or(("", plain.as_str()), |d| ("-", d));
+                let (whole, fraction) = digits.split_once('.').map_or((digits, None), |(w, f)| (w, Some(f)));
+                let mut grouped = String::new();
+                for (i, digit) in whole.chars().enumerate() {
+                    if i > 0 && (whole.len() - i) % 3 == 0 {
+                        grouped.push(FRENCH_THOUSANDS);
+                    }
+                    grouped.push(digit);
+                }
+                match fraction {
+                    Some(fraction) => format!("{}{},{}", sign, grouped, fraction),
+                    None => format!("{}{}", sign, grouped),
+                }
+            }
+        }
+    }
+
+    /// Full date: 2026-02-11 or 11/02/2026
+    pub fn date(self, date: NaiveDate) -> String {
+        match self {
+            Locale::En => date.format("%Y-%m-%d").to_string(),
+            Locale::Fr => date.format("%d/%m/%Y").to_string(),
+        }
+    }
+
+    /// Day and month, for axis labels: 02-11 or 11/02
+    pub fn day_month(self, date: NaiveDate) -> String {
+        match self {
+            Locale::En => date.format("%m-%d").to_string(),
+            Locale::Fr => date.format("%d/%m").to_string(),
+        }
+    }
+
+    /// Abbreviated month name
+    pub fn month(self, date: NaiveDate) -> String {
+        match self {
+            Locale::En => date.format("%b").to_string(),
+            Locale::Fr => FRENCH_MONTHS[date.month0() as usize].to_string(),
+        }
+    }
+
+    /// Date and time of day, in UTC
+    pub fn timestamp(self, time: DateTime<Utc>) -> String {
+        match self {
+            Locale::En => time.format("%Y-%m-%d %H:%M UTC").to_string(),
+            Locale::Fr => time.format("%d/%m/%Y %H:%M UTC").to_string(),
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use std::fmt::Write as _;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::field::{Field, Visit};
+use tracing::span;
+use tracing::{Event, Level, Metadata, Subscriber};
+
+use crate::progress;
+
+/// How log lines are rendered on stderr
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human friendly output, one message per line
+    Pretty,
+    /// One JSON object per line, for systemd/k8s log collectors
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("unknown log format '{}' (expected pretty or json)", other)),
+        }
+    }
+}
+
+pub fn parse_level(s: &str) -> Result<Level, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "error" => Ok(Level::ERROR),
+        "warn" => Ok(Level::WARN),
+        "info" => Ok(Level::INFO),
+        "debug" => Ok(Level::DEBUG),
+        "trace" => Ok(Level::TRACE),
+        other => Err(format!("unknown log level '{}'", other)),
+    }
+}
+
+/// Minimal stderr subscriber - we only need levels and two output formats
+struct StderrSubscriber {
+    max_level: Level,
+    format: LogFormat,
+    next_span_id: AtomicU64,
+}
+
+pub fn init(max_level: Level, format: LogFormat) {
+    let subscriber = StderrSubscriber {
+        max_level,
+        format,
+        next_span_id: AtomicU64::new(1),
+    };
+    // Only fails if a subscriber is already installed, which is harmless
+    let _ = tracing::subscriber::set_global_default(subscriber);
+    // A bar would garble JSON lines and piped output, and --quiet asks for silence
+    progress::enable(format == LogFormat::Pretty && max_level >= Level::INFO && std::io::stderr().is_terminal());
+}
+
+#[derive(Default)]
+struct FieldCollector {
+    message: String,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl Visit for FieldCollector {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.fields.push((field.name(), value.to_string()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{:?}", value);
+        } else {
+            self.fields.push((field.name(), format!("{:?}", value)));
+        }
+    }
+}
+
+impl StderrSubscriber {
+    fn format_pretty(&self, level: &Level, collector: &FieldCollector) -> String {
+        let mut line = match *level {
+            Level::ERROR => "ERROR ".to_string(),
+            Level::WARN => "WARN  ".to_string(),
+            Level::DEBUG => "DEBUG ".to_string(),
+            Level::TRACE => "TRACE ".to_string(),
+            Level::INFO => String::new(),
+        };
+        line.push_str(&collector.message);
+        for (name, value) in &collector.fields {
+            let _ = write!(line, " {}={}", name, value);
+        }
+        line
+    }
+
+    fn format_json(&self, metadata: &Metadata<'_>, collector: &FieldCollector) -> String {
+        let mut object = serde_json::Map::new();
+        object.insert("timestamp".into(), chrono::Utc::now().to_rfc3339().into());
+        object.insert("level".into(), metadata.level().as_str().into());
+        object.insert("target".into(), metadata.target().into());
+        object.insert("message".into(), collector.message.trim().into());
+        for (name, value) in &collector.fields {
+            object.insert((*name).into(), value.clone().into());
+        }
+        serde_json::Value::Object(object).to_string()
+    }
+}
+
+impl Subscriber for StderrSubscriber {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= self.max_level
+    }
+
+    fn new_span(&self, _span: &span::Attributes<'_>) -> span::Id {
+        span::Id::from_u64(self.next_span_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut collector = FieldCollector::default();
+        event.record(&mut collector);
+        let metadata = event.metadata();
+        let line = match self.format {
+            LogFormat::Pretty => self.format_pretty(metadata.level(), &collector),
+            LogFormat::Json => self.format_json(metadata, &collector),
+        };
+        progress::print(&line);
+    }
+
+    fn enter(&self, _span: &span::Id) {}
+
+    fn exit(&self, _span: &span::Id) {}
+}
@@ -0,0 +1,109 @@
+//! Sync summary by email, for installations running headless on a server.
+//!
+//! Sent with lettre: implicit TLS on port 465, STARTTLS otherwise (required, so
+//! credentials never cross a plaintext connection), and SMTP authentication
+//! when a username is configured.
+
+use std::error::Error;
+use std::time::Duration;
+
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::model::{ActivityIndex, ActivityWithStreams};
+use crate::notify;
+use crate::weekly;
+
+const DEFAULT_SUBJECT: &str = "🚴 {count} new rides synced";
+const DEFAULT_BODY: &str = "New activities:\n{activities}\n\nWeek of {week}: {week_rides} rides, {week_hours} h, {week_km} km, TSS {week_tss}\n";
+const DEFAULT_ACTIVITY_LINE: &str = "- {name}: {distance} km in {moving_time}, NP {np} W, TSS {tss}";
+
+/// Longest wait for the whole SMTP exchange, so a stalled server can't hold up the sync
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// SMTP settings under "email" in watts.json; the password comes from SMTP_PASSWORD
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    #[serde(default = "default_port")]
+    pub smtp_port: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Subject with a {count} placeholder
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,
+    /// Body with {activities}, {count}, {week}, {week_rides}, {week_hours}, {week_km} and {week_tss}
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// One line of {activities}, with the same placeholders as webhook templates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activity_template: Option<String>,
+}
+
+fn default_port() -> u16 {
+    587
+}
+
+/// Email the newly synced activities and this week's totals. Like webhooks,
+/// a failure is logged and never fails the sync.
+pub async fn sync_summary(files: &[ActivityWithStreams], index: &ActivityIndex, config: &Config) {
+    let Some(email) = &config.email else { return };
+    if files.is_empty() {
+        return;
+    }
+
+    let line = email.activity_template.as_deref().unwrap_or(DEFAULT_ACTIVITY_LINE);
+    let activities: Vec<String> = files.iter().map(|f| notify::render(line, f, config.ftp())).collect();
+    let this_week = weekly::summarize(index, config).pop();
+    let totals = this_week.as_ref().map(|w| w.totals.clone()).unwrap_or_default();
+    let count = files.len().to_string();
+
+    let subject = email.subject.as_deref().unwrap_or(DEFAULT_SUBJECT).replace("{count}", &count);
+    let body = email
+        .template
+        .as_deref()
+        .unwrap_or(DEFAULT_BODY)
+        .replace("{activities}", &activities.join("\n"))
+        .replace("{count}", &count)
+        .replace("{week}", this_week.as_ref().map(|w| w.week.as_str()).unwrap_or("-"))
+        .replace("{week_rides}", &totals.rides.to_string())
+        .replace("{week_hours}", &format!("{:.1}", totals.moving_time as f64 / 3600.0))
+        .replace("{week_km}", &format!("{:.0}", totals.distance / 1000.0))
+        .replace("{week_tss}", &totals.tss.map(|t| format!("{:.0}", t)).unwrap_or_else(|| "-".into()));
+
+    match send(email, &subject, body).await {
+        Ok(()) => info!("📧 Emailed sync summary to {}", email.to.join(", ")),
+        Err(e) => warn!("   ⚠️  Could not send summary email: {}", e),
+    }
+}
+
+async fn send(email: &EmailConfig, subject: &str, body: String) -> Result<(), Box<dyn Error>> {
+    let mut message = Message::builder().from(email.from.parse::<Mailbox>()?).subject(subject).header(ContentType::TEXT_PLAIN);
+    for recipient in &email.to {
+        message = message.to(recipient.parse::<Mailbox>()?);
+    }
+    let message = message.body(body)?;
+
+    let mut transport = if email.smtp_port == 465 {
+        AsyncSmtpTransport::<Tokio1Executor>::relay(&email.smtp_host)?
+    } else {
+        AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&email.smtp_host)?
+    }
+    .port(email.smtp_port)
+    .timeout(Some(SMTP_TIMEOUT));
+    if let Some(username) = &email.username {
+        let password = std::env::var("SMTP_PASSWORD").map_err(|_| "SMTP_PASSWORD is not set")?;
+        transport = transport.credentials(Credentials::new(username.clone(), password));
+    }
+    // lettre's own timeout only covers connecting; a server that stalls mid-exchange is cut off here
+    tokio::time::timeout(SMTP_TIMEOUT, transport.build().send(message))
+        .await
+        .map_err(|_| format!("{} did not answer within {} s", email.smtp_host, SMTP_TIMEOUT.as_secs()))??;
+    Ok(())
+}
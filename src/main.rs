@@ -1,367 +1,851 @@
-use reqwest;
-use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
 use std::error::Error;
-use std::fs;
+use std::process::ExitCode;
+use tracing::{info, warn};
 
-#[derive(Debug, Deserialize)]
-struct TokenResponse {
-    access_token: String,
-    #[allow(dead_code)]
-    expires_at: i64,
-}
+use strava_fetcher::{
+    activity_id, adjust, aerobic, archive_sync, athlete, athlete_history, auth, backup, best_efforts, cadence,
+    calendar, ci, classify, clean, cli, club, club_activities, compact, compare, compression, config, critical_power,
+    dfa, diff, duplicates, edit, elevation, exporter, failure, format, gear, golden_cheetah, journal, list, logging,
+    migrate, milestones, mock_strava, plan, planned_routes, progress, publish, push, recompute, refetch, report,
+    resample, rolling, search, season, segments, show, social, sprints, stats, strava, sync, trends, tui, upgrade,
+    upload, verify, w_balance, weekly, wkg, workout, zones, zwift,
+};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct Activity {
-    id: i64,
-    name: String,
-    distance: f64,
-    moving_time: i32,
-    elapsed_time: i32,
-    total_elevation_gain: f64,
-    #[serde(rename = "type")]
-    activity_type: String,
-    sport_type: String,
-    start_date: String,
-    start_date_local: String,
-    timezone: String,
-    trainer: bool,
-    commute: bool,
-    average_speed: f64,
-    max_speed: f64,
-    average_watts: Option<f64>,
-    weighted_average_watts: Option<f64>,
-    max_watts: Option<f64>,
-    kilojoules: Option<f64>,
-    device_watts: Option<bool>,
-    has_heartrate: bool,
-    average_heartrate: Option<f64>,
-    max_heartrate: Option<f64>,
-    average_cadence: Option<f64>,
-    suffer_score: Option<f64>,
-    kudos_count: i32,
-    achievement_count: i32,
-    pr_count: i32,
-}
+const USAGE: &str = "usage: strava_fetcher [--quiet|--verbose] [--log-format pretty|json] [--athlete ID|NAME] [--base-url URL] [--ci] [command]
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct ActivityStreams {
-    time: Option<Vec<i32>>,
-    watts: Option<Vec<f64>>,
-    heartrate: Option<Vec<i32>>,
-    cadence: Option<Vec<i32>>,
-    velocity_smooth: Option<Vec<f64>>,
-    altitude: Option<Vec<f64>>,
-}
+commands:
+  sync [--all] [--backfill [--concurrency N]] [--output text|json]
+                      fetch new activities from Strava (default), --all for every athlete,
+                      --backfill lists every page (N at a time, default 4) to fill gaps
+  journal <YYYY-MM>   write a Markdown training journal, with photos, for the month
+  diff <dirA> <dirB>  compare two data directories (added/missing/modified)
+  compact [codec]     re-encode activity files as zstd (default), gzip or none
+  upgrade-streams [--since YYYY-MM-DD] [--budget N]
+                      re-fetch low-resolution streams, N requests per run (default 90)
+  social [--since YYYY-MM-DD] [--budget N]
+                      back up kudos, comments and photo links, N requests per run (default 90)
+  classify [--since YYYY-MM-DD] [--until YYYY-MM-DD]
+                      label rides (recovery/endurance/tempo/vo2/race) and count them
+  compare <id1> <id2> [--by time|distance] [--export FILE]
+                      side-by-side power/HR/speed of two rides, optionally the aligned deltas as CSV
+  stats range [--from YYYY-MM-DD] [--to YYYY-MM-DD | --season NAME] [--group-by week|month|block] [--block-weeks N] [--output text|json]
+                      aggregate rides over any window and grouping (default week, 4-week blocks)
+  aerobic [--since YYYY-MM-DD]
+                      store efficiency factor, Pw:HR decoupling and the heart rate/power fit,
+                      print their monthly trend
+  best-efforts [--rebuild] [--season NAME] [--output text|json]
+                      all-time and season-best power per duration, with the ride and time into it
+                      (data/best_efforts.json, updated by sync; --rebuild rescans the archive)
+  plan import <file.csv|file.ics>
+                      store planned workouts (date, name, duration, TSS) in data/plan.json
+  plan [--since YYYY-MM-DD] [--group-by day|week] [--output text|json]
+                      planned against actual TSS and time, flagging missed and overshot days
+  cadence [--since YYYY-MM-DD] [--output text|json]
+                      store cadence profiles (histogram, coasting, cadence per 50 W band), print their sum
+  sprints [--since YYYY-MM-DD] [--15s] [--limit N] [--output text|json]
+                      leaderboard of each ride's best 5 s (or 15 s) with cadence and speed, and the best per year
+  planned-routes [--fetch] [--output text|json]
+                      routes planned on Strava, with their GPX in data/routes/ (--fetch refreshes them)
+  planned-routes export <route> [--output FILE]
+                      write a route's GPX (by ID or name) for a head unit, to <name>.gpx by default
+  planned-routes compare <route> <id> [--output text|json]
+                      how much of the route a ride covered and how far off it it went
+  upload <file.fit> [--name NAME] [--description TEXT] [--trainer] [--commute] [--force]
+                      upload a FIT, TCX or GPX file (gzipped too) to Strava, waiting for the activity;
+                      files already uploaded (data/uploads.json) are skipped unless --force
+  segments [--fetch] [--output text|json]
+                      starred segments with your PR against the KOM (data/segments.json, refreshed by
+                      sync when segments is set in watts.json; --fetch refreshes now)
+  club-activities [--fetch] [--club ID] [--weeks N] [--output text|json]
+                      weekly volume of a Strava club's members over the last N weeks (default 8), from
+                      the feed sync keeps in data/club/ (--fetch reads it now)
+  leaderboard export [--name ALIAS] [--dir DIR] [--output FILE]
+                      write your anonymous weekly totals (rides, time, km, TSS) for the club folder
+  leaderboard [--weeks N] [--by distance|time|tss|rides] [--dir DIR] [--output text|json]
+                      rank the riders of the club folder (club/ by default) over the last N weeks (default 4)
+  elevation [--since YYYY-MM-DD] [--threshold PCT] [--output text|json]
+                      recompute elevation gain from the altitude streams, list rides where Strava's
+                      total differs by more than PCT% (default 20) and 30 m
+  trends [--since YYYY-MM-DD] [--band LOW-HIGH...] [--group-by week|month] [--output text|json]
+                      heart rate at fixed power bands and cardiac cost over time (bands default
+                      to 60-70, 70-80 and 80-90% of FTP)
+  clean [--since YYYY-MM-DD] [--dry-run]
+                      repair power spikes/dropouts and HR gaps, keeping the raw streams
+  cp [--days N] [--until YYYY-MM-DD]
+                      fit CP and W' to the best 3-20 min efforts of the last N days (default 90)
+  dfa [<id>]          experimental: DFA alpha 1 and HRVT1 from RR intervals (FIT imports only)
+  wbal [<id>]         compute and store W' balance streams, reporting the lowest point of each ride
+  routes [--since YYYY-MM-DD]
+                      detect Zwift world and route of every ride, count rides per route
+  weekly [--since YYYY-MM-DD]
+                      weekly volume, TSS and polarization index (also data/weekly.json)
+  rolling [--output text|json]
+                      rolling 7, 28 and 90-day distance, time, TSS and kJ (also data/aggregates.json)
+  milestones [--output text|json]
+                      Eddington number and lifetime distance and climbing, with the next milestone of each
+  export-influx [--dry-run]
+                      write activities and daily training load to InfluxDB (--dry-run prints them)
+  mock-strava [--port N] [--fixtures DIR] [--rate-limit N]
+                      fake Strava API from recorded JSON (default fixtures/strava, port 9899)
+  serve-metrics [--port N]
+                      serve Prometheus metrics on /metrics and the calendar on /calendar.ics (default port 9898)
+  calendar [--output FILE]
+                      one iCalendar event per ride with its metrics (data/calendar.ics; publish writes it too)
+  export-gc DIR [--since YYYY-MM-DD] [--overwrite] [--smooth N]
+                      write rides into a Golden Cheetah athlete directory
+  export-streams <id> [--resample] [--smooth N] [--points N]
+                      print streams as JSON: 1 s cadence, N-sample rolling average, N points
+  report --html [--output FILE] [--season NAME...]
+                      self-contained HTML power curve, PMC, weekly volume and cadence (data/reports/index.html)
+  report [--output text|json] [--season NAME...]
+                      the same charts drawn in the terminal, or their data as JSON
+  publish [--output DIR] [--points N]
+                      paged index, streams cut to N points per series (LTTB, default 2000) and
+                      aggregates as small JSON files (data/api)
+  push intervals [--dry-run]
+                      upload activities not yet pushed to intervals.icu
+  auth set-token      save a Strava refresh token, read from stdin, to the WATTS_TOKEN_STORE store
+  athlete [--fetch]   weight, Strava FTP and lifetime totals over time (data/athlete_history.json)
+  wkg [--since YYYY-MM-DD]
+                      5s/1m/5m/20m W/kg per ride and the all-time W/kg power curve
+  gear [--fetch]      distance and hours per bike/trainer/component (data/gear.json), --fetch names
+  gear serviced <id> [--date YYYY-MM-DD]
+                      restart the service interval of a gear entry in watts.json
+  zones fetch [--apply]
+                      fetch Strava HR/power zones and seed watts.json
+  format [check]      print the data format spec, or check the archive against it
+  verify [--repair]   check every activity file parses, matches its index entry and has streams of
+                      equal length; --repair rebuilds index entries and cuts streams to their common length
+  migrate [--dry-run] upgrade stored files to the current schema version and fill in index
+                      fields older entries lack (local time offset, sport type)
+  duplicates [--dry-run]
+                      mark rides recorded by two sources, keeping the richer copy (duplicate_of)
+  tag <id> [TAG...] [--remove]
+                      add (or remove) local tags such as race or zrl-round3, list them without TAG
+  edit --filter F... --set|--unset C... [--dry-run]
+                      bulk-edit tags, notes and exclusion (e.g. --filter 'name~=ZRL' --set tag=race)
+  recompute [--metric anomalies,np,wbal,classify,aerobic,cadence,route,pedalling,matches,sprint,pacing,estimated_power,elevation,custom,tss,curve] [--since YYYY-MM-DD] [--force]
+                      rerun analysis over stored rides (all metrics by default), skipping rides whose
+                      file and settings are unchanged since the last run unless --force
+  refetch <id> [--streams] [--detail]
+                      download an activity again, overwriting its file (both parts by default)
+  adjust <id> [--scale F] [--offset W]
+                      correct a mis-calibrated watts stream (scale 1, offset 0 reverts)
+  show <id> [--fetch] [--chart]
+                      summarize an activity (metrics, zones, intervals, power sparkline),
+                      fetching it from Strava if missing; --chart draws power and heart rate
+  workout <id> [--detect] [--ftp W] [--format zwo|erg|mrc|fit] [--output FILE]
+                      rebuild a ride's laps (or detected efforts) as a Zwift .zwo, ERG, MRC or
+                      Garmin FIT workout (data/workouts/<id>.<format>)
+  workout --plan YYYY-MM-DD [--ftp W] [--format zwo|erg|mrc|fit] [--output FILE]
+                      the session planned that day as one block at its TSS's intensity
+  list [--since YYYY-MM-DD] [--until YYYY-MM-DD] [--min-distance D] [--max-distance D] [--sport S]
+       [--type T] [--filter F...] [--sort date|distance|time|watts|np|tss|ef|decoupling|vi] [--asc]
+       [--limit N] [--all] [--output text|json] [--export FILE]
+                      query the index, e.g. --min-distance 40km --sort np (D in km, mi or m)
+  search <words...> [--limit N] [--output text|json]
+                      find rides by name, description, notes, tags or route (best 20 by default)
+  tui                 browse rides in the terminal: list, detail with charts, / filter, s sync
+  sync-archive <push|pull> <remote> [--dry-run]
+                      copy changed archive files to/from rsync or s3:// remotes
+  backup [--to FILE]  snapshot the archive (index, activities, derived files, no tokens) with a
+                      checksum per file, as .tar.zst (default watts-backup-<date>.tar.zst), .tar.gz or .tar
+  restore <FILE> [--verify] [--force]
+                      check a backup's checksums and write it into the archive (--verify only checks;
+                      --force overwrites an archive that already has activities)
 
-/// Combined activity with detailed stream data
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct ActivityWithStreams {
-    #[serde(flatten)]
-    activity: Activity,
-    streams: Option<ActivityStreams>,
-}
+stats range, list, weekly, report, export-influx and export-gc keep only rides with every --tag TAG
+and none of the --without-tag TAG (both repeatable); --trainer/--no-trainer and --commute/--no-commute
+keep only rides with (or without) Strava's trainer or commute flag, virtual rides counting as trainer
 
-/// Index file - just metadata, no streams
-#[derive(Debug, Serialize, Deserialize)]
-struct ActivityIndex {
-    last_updated: String,
-    activities: Vec<ActivitySummary>,
-}
+--season NAME is a season from \"seasons\" in watts.json or a calendar year; report draws the power
+curve of each one given instead of the last 90 days";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct ActivitySummary {
-    id: i64,
-    name: String,
-    start_date: String,
-    distance: f64,
-    moving_time: i32,
-    average_watts: Option<f64>,
-    average_heartrate: Option<f64>,
-}
-
-impl ActivityIndex {
-    fn load() -> Self {
-        fs::read_to_string("data/index.json")
-            .ok()
-            .and_then(|s| serde_json::from_str(&s).ok())
-            .unwrap_or_else(|| ActivityIndex {
-                last_updated: String::new(),
-                activities: Vec::new(),
-            })
-    }
+#[tokio::main]
+async fn main() -> ExitCode {
+    dotenv::dotenv().ok();
     
-    fn save(&self) -> Result<(), Box<dyn Error>> {
-        fs::create_dir_all("data")?;
-        let json = serde_json::to_string_pretty(self)?;
-        fs::write("data/index.json", json)?;
-        Ok(())
+    let cli = match cli::Cli::parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    logging::init(cli.log_level, cli.log_format);
+    if cli.ci {
+        ci::enable();
+        progress::enable(false);
     }
+    let command = cli.args.first().cloned().unwrap_or_else(|| "sync".to_string());
     
-    fn get_known_ids(&self) -> HashSet<i64> {
-        self.activities.iter().map(|a| a.id).collect()
+    strava::load_rate_limit();
+    let result = run(cli).await;
+    if let Err(e) = strava::save_rate_limit() {
+        warn!("⚠️  Could not save the rate-limit budget: {}", e);
     }
-    
-    fn add_activity(&mut self, activity: &Activity) {
-        let summary = ActivitySummary {
-            id: activity.id,
-            name: activity.name.clone(),
-            start_date: activity.start_date.clone(),
-            distance: activity.distance,
-            moving_time: activity.moving_time,
-            average_watts: activity.average_watts,
-            average_heartrate: activity.average_heartrate,
-        };
-        self.activities.insert(0, summary);
-        self.activities.sort_by(|a, b| b.start_date.cmp(&a.start_date));
+    // A failed run may still have stored rides worth committing
+    if ci::enabled() {
+        if let Err(e) = ci::finish(&command) {
+            tracing::error!("❌ Could not write the CI manifest: {}", e);
+        }
+    }
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => failure::exit(e.as_ref()),
     }
 }
 
-fn save_activity_file(activity: &ActivityWithStreams) -> Result<(), Box<dyn Error>> {
-    fs::create_dir_all("data/activities")?;
-    let filename = format!("data/activities/{}.json", activity.activity.id);
-    let json = serde_json::to_string_pretty(activity)?;
-    fs::write(&filename, json)?;
-    Ok(())
-}
-
-fn activity_file_exists(id: i64) -> bool {
-    std::path::Path::new(&format!("data/activities/{}.json", id)).exists()
-}
-
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    dotenv::dotenv().ok();
-    
-    println!("🚴 Watts Happening - Strava Data Fetcher\n");
-    
-    // Load existing index
-    let mut index = ActivityIndex::load();
-    let known_ids = index.get_known_ids();
-    println!("📂 Found {} existing Zwift activities in index", index.activities.len());
-    
-    // Get credentials from environment
-    let client_id = std::env::var("STRAVA_CLIENT_ID")?;
-    let client_secret = std::env::var("STRAVA_CLIENT_SECRET")?;
-    let refresh_token = std::env::var("STRAVA_REFRESH_TOKEN")?;
-    
-    // Get fresh access token
-    println!("📡 Refreshing access token...");
-    let access_token = refresh_access_token(&client_id, &client_secret, &refresh_token).await?;
-    
-    // Fetch activities with pagination
-    println!("📊 Fetching activities from Strava...\n");
+async fn run(cli: cli::Cli) -> Result<(), Box<dyn Error>> {
+    info!("🚴 Watts Happening - Strava Data Fetcher");
     
-    let per_page = 50;
-    let mut page = 1;
-    let mut total_fetched = 0;
-    let mut new_zwift_activities: Vec<Activity> = Vec::new();
-    let mut found_existing = false;
-    
-    // Paginate until we find activities we already have
-    while !found_existing {
-        println!("   Fetching page {} ({} per page)...", page, per_page);
-        
-        let activities = fetch_activities_page(&access_token, page, per_page).await?;
-        
-        if activities.is_empty() {
-            println!("   No more activities found.");
-            break;
-        }
-        
-        total_fetched += activities.len();
-        
-        for activity in activities {
-            // Check if we already have this activity
-            if known_ids.contains(&activity.id) {
-                println!("   ✓ Found existing activity: {} - stopping pagination", activity.name);
-                found_existing = true;
-                break;
-            }
-            
-            // Only keep VirtualRide (Zwift) activities
-            if activity.sport_type == "VirtualRide" {
-                println!("   🆕 New Zwift activity: {}", activity.name);
-                new_zwift_activities.push(activity);
-            } else {
-                println!("   ⏭️  Skipping outdoor activity: {} ({})", activity.name, activity.sport_type);
-            }
-        }
-        
-        page += 1;
-        
-        // Safety limit - don't fetch more than 5 pages (250 activities) in one run
-        if page > 5 {
-            println!("   ⚠️  Reached page limit, stopping pagination");
-            break;
-        }
+    strava::set_base_url(cli.base_url.clone());
+    if let Some(key) = &cli.athlete {
+        let config = config::Config::load()?;
+        let profile = athlete::find(&config, key)?;
+        info!("👤 Athlete {} ({})", profile.name, profile.data_dir().display());
+        athlete::select(Some(profile.clone()));
     }
     
-    println!("\n📈 Summary:");
-    println!("   Total activities fetched from API: {}", total_fetched);
-    println!("   New Zwift activities to process: {}", new_zwift_activities.len());
-    
-    // Fetch detailed streams for new activities
-    if !new_zwift_activities.is_empty() {
-        println!("\n🔍 Fetching detailed streams for new activities...\n");
-        
-        for (i, activity) in new_zwift_activities.iter().enumerate() {
-            println!("   [{}/{}] {} (id: {})", 
-                i + 1, 
-                new_zwift_activities.len(), 
-                activity.name, 
-                activity.id
-            );
-            
-            // Skip if file already exists (safety check)
-            if activity_file_exists(activity.id) {
-                println!("      ⏭️  File already exists, skipping");
-                index.add_activity(activity);
-                continue;
-            }
-            
-            match fetch_activity_streams(&access_token, activity.id).await {
-                Ok(streams) => {
-                    let data_points = streams.time.as_ref().map(|t| t.len()).unwrap_or(0);
-                    println!("      ✅ {} data points", data_points);
-                    
-                    let activity_with_streams = ActivityWithStreams {
-                        activity: activity.clone(),
-                        streams: Some(streams),
-                    };
-                    
-                    // Save individual file
-                    save_activity_file(&activity_with_streams)?;
-                    
-                    // Add to index
-                    index.add_activity(activity);
+    let command = cli.args.first().map(String::as_str).unwrap_or("sync");
+    let mut rest: Vec<String> = cli.args.iter().skip(1).cloned().collect();
+    match command {
+        "sync" => {
+            let all = cli::take_switch(&mut rest, "--all");
+            let output = cli::OutputFormat::take(&mut rest)?;
+            let concurrency = cli::parse_option(&mut rest, "--concurrency")?.unwrap_or(sync::DEFAULT_CONCURRENCY);
+            let backfill = cli::take_switch(&mut rest, "--backfill").then_some(concurrency.max(1));
+            match rest.as_slice() {
+                [] if all => sync_all_athletes(output, backfill).await,
+                [] => {
+                    let report = sync::run(backfill).await?;
+                    ci::describe(sync_subject(report.added.len(), report.updated.len()), report.commit_lines());
+                    if output == cli::OutputFormat::Json {
+                        cli::print_json(&report)?;
+                    }
+                    match report.failure() {
+                        Some(incomplete) => Err(incomplete.into()),
+                        None => Ok(()),
+                    }
                 }
-                Err(e) => {
-                    println!("      ⚠️  Could not fetch streams: {}", e);
-                    // Still save the activity without streams
-                    let activity_with_streams = ActivityWithStreams {
-                        activity: activity.clone(),
-                        streams: None,
-                    };
-                    save_activity_file(&activity_with_streams)?;
-                    index.add_activity(activity);
+                _ => Err(USAGE.into()),
+            }
+        }
+        "journal" => match rest.as_slice() {
+            [month] => journal::run(month),
+            _ => Err(USAGE.into()),
+        },
+        "diff" => match rest.as_slice() {
+            [a, b] => diff::run(a, b),
+            _ => Err(USAGE.into()),
+        },
+        "compact" => match rest.as_slice() {
+            [] => compact::run(compression::Compression::Zstd),
+            [codec] => compact::run(compression::Compression::parse(codec)?),
+            _ => Err(USAGE.into()),
+        },
+        "sync-archive" => {
+            let dry_run = cli::take_switch(&mut rest, "--dry-run");
+            match rest.as_slice() {
+                [direction, remote] => archive_sync::run(archive_sync::Direction::parse(direction)?, remote, dry_run),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "backup" => {
+            let to = cli::take_option(&mut rest, "--to")?.map(std::path::PathBuf::from).unwrap_or_else(backup::default_path);
+            match rest.as_slice() {
+                [] => backup::backup(&to),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "restore" => {
+            let verify = cli::take_switch(&mut rest, "--verify");
+            let force = cli::take_switch(&mut rest, "--force");
+            match rest.as_slice() {
+                [from] => backup::restore(std::path::Path::new(from), verify, force),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "upgrade-streams" => {
+            let since = cli::take_option(&mut rest, "--since")?.map(|d| parse_date(&d)).transpose()?;
+            let budget = cli::parse_option(&mut rest, "--budget")?.unwrap_or(upgrade::DEFAULT_BUDGET);
+            match rest.as_slice() {
+                [] => upgrade::run(since, budget).await,
+                _ => Err(USAGE.into()),
+            }
+        }
+        "social" => {
+            let since = cli::take_option(&mut rest, "--since")?.map(|d| parse_date(&d)).transpose()?;
+            let budget = cli::parse_option(&mut rest, "--budget")?.unwrap_or(upgrade::DEFAULT_BUDGET);
+            match rest.as_slice() {
+                [] => social::run(since, budget).await,
+                _ => Err(USAGE.into()),
+            }
+        }
+        "classify" => {
+            let since = cli::take_option(&mut rest, "--since")?.map(|d| parse_date(&d)).transpose()?;
+            let until = cli::take_option(&mut rest, "--until")?.map(|d| parse_date(&d)).transpose()?;
+            match rest.as_slice() {
+                [] => classify::run(since, until),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "compare" => {
+            let axis = match cli::take_option(&mut rest, "--by")? {
+                Some(axis) => compare::Axis::parse(&axis)?,
+                None => compare::Axis::Time,
+            };
+            let export = cli::take_option(&mut rest, "--export")?;
+            match rest.as_slice() {
+                [first, second] => compare::run(parse_id(first)?, parse_id(second)?, axis, export.as_deref().map(std::path::Path::new)),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "stats" => {
+            let mut from = cli::take_option(&mut rest, "--from")?.map(|d| parse_date(&d)).transpose()?;
+            let mut to = cli::take_option(&mut rest, "--to")?.map(|d| parse_date(&d)).transpose()?;
+            if let Some(name) = cli::take_option(&mut rest, "--season")? {
+                if from.is_some() || to.is_some() {
+                    return Err("--season replaces --from and --to".into());
                 }
+                let season = season::Season::resolve(&config::Config::current()?, &name)?;
+                (from, to) = (Some(season.start), Some(season.end));
+            }
+            let block_weeks = cli::parse_option(&mut rest, "--block-weeks")?.unwrap_or(stats::DEFAULT_BLOCK_WEEKS);
+            let tags = edit::TagFilter::take(&mut rest)?;
+            let group_by = match cli::take_option(&mut rest, "--group-by")? {
+                Some(group) => stats::GroupBy::parse(&group, block_weeks)?,
+                None => stats::GroupBy::Week,
+            };
+            let output = cli::OutputFormat::take(&mut rest)?;
+            match rest.as_slice() {
+                [range] if range == "range" => stats::range(from, to, group_by, &tags, output),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "aerobic" => {
+            let since = cli::take_option(&mut rest, "--since")?.map(|d| parse_date(&d)).transpose()?;
+            match rest.as_slice() {
+                [] => aerobic::run(since),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "best-efforts" => {
+            let rebuild = cli::take_switch(&mut rest, "--rebuild");
+            let season = cli::take_option(&mut rest, "--season")?;
+            let output = cli::OutputFormat::take(&mut rest)?;
+            match rest.as_slice() {
+                [] => best_efforts::run(rebuild, season.as_deref(), output),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "plan" => {
+            let since = cli::take_option(&mut rest, "--since")?.map(|d| parse_date(&d)).transpose()?;
+            let by_week = match cli::take_option(&mut rest, "--group-by")?.as_deref() {
+                None | Some("day") => false,
+                Some("week") => true,
+                Some(other) => return Err(format!("unknown grouping '{}', expected day or week", other).into()),
+            };
+            let output = cli::OutputFormat::take(&mut rest)?;
+            match rest.as_slice() {
+                [import, file] if import == "import" => plan::import(std::path::Path::new(file)),
+                [] => plan::run(since, by_week, output),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "cadence" => {
+            let since = cli::take_option(&mut rest, "--since")?.map(|d| parse_date(&d)).transpose()?;
+            let output = cli::OutputFormat::take(&mut rest)?;
+            match rest.as_slice() {
+                [] => cadence::run(since, output),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "sprints" => {
+            let since = cli::take_option(&mut rest, "--since")?.map(|d| parse_date(&d)).transpose()?;
+            let by_15s = cli::take_switch(&mut rest, "--15s");
+            let limit = cli::parse_option(&mut rest, "--limit")?;
+            let output = cli::OutputFormat::take(&mut rest)?;
+            match rest.as_slice() {
+                [] => sprints::run(since, by_15s, limit, output),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "planned-routes" => {
+            let fetch = cli::take_switch(&mut rest, "--fetch");
+            let output = cli::take_option(&mut rest, "--output")?;
+            let format = || cli::OutputFormat::parse(output.as_deref().unwrap_or("text"));
+            match rest.as_slice() {
+                // For export, --output is the file rather than the format
+                [export, route] if export == "export" => planned_routes::export(route, output.as_deref().map(std::path::Path::new)),
+                [compare, route, id] if compare == "compare" => planned_routes::compare(route, parse_id(id)?, format()?),
+                [] => planned_routes::run(fetch, format()?).await,
+                _ => Err(USAGE.into()),
+            }
+        }
+        "upload" => {
+            let options = upload::UploadOptions {
+                name: cli::take_option(&mut rest, "--name")?,
+                description: cli::take_option(&mut rest, "--description")?,
+                trainer: cli::take_switch(&mut rest, "--trainer"),
+                commute: cli::take_switch(&mut rest, "--commute"),
+                force: cli::take_switch(&mut rest, "--force"),
+            };
+            match rest.as_slice() {
+                [file] => upload::run(std::path::Path::new(file), options).await,
+                _ => Err(USAGE.into()),
+            }
+        }
+        "segments" => {
+            let fetch = cli::take_switch(&mut rest, "--fetch");
+            let output = cli::OutputFormat::take(&mut rest)?;
+            match rest.as_slice() {
+                [] => segments::run(fetch, output).await,
+                _ => Err(USAGE.into()),
+            }
+        }
+        "club-activities" => {
+            let fetch = cli::take_switch(&mut rest, "--fetch");
+            let club = cli::parse_option(&mut rest, "--club")?;
+            let weeks = cli::parse_option(&mut rest, "--weeks")?.unwrap_or(club_activities::DEFAULT_WEEKS);
+            let output = cli::OutputFormat::take(&mut rest)?;
+            match rest.as_slice() {
+                [] => club_activities::run(club, fetch, weeks, output).await,
+                _ => Err(USAGE.into()),
+            }
+        }
+        "leaderboard" => {
+            let name = cli::take_option(&mut rest, "--name")?;
+            let dir = cli::take_option(&mut rest, "--dir")?.map(std::path::PathBuf::from);
+            let weeks = cli::parse_option(&mut rest, "--weeks")?.unwrap_or(club::DEFAULT_WEEKS);
+            let by = cli::take_option(&mut rest, "--by")?.map(|b| club::RankBy::parse(&b)).transpose()?.unwrap_or(club::RankBy::Distance);
+            let output = cli::take_option(&mut rest, "--output")?;
+            match rest.as_slice() {
+                // For export, --output is the file rather than the format
+                [export] if export == "export" => club::export(name, output.as_deref().map(std::path::Path::new), dir.as_deref()),
+                [] => club::leaderboard(weeks, by, dir.as_deref(), cli::OutputFormat::parse(output.as_deref().unwrap_or("text"))?),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "elevation" => {
+            let since = cli::take_option(&mut rest, "--since")?.map(|d| parse_date(&d)).transpose()?;
+            let threshold = cli::parse_option(&mut rest, "--threshold")?;
+            let output = cli::OutputFormat::take(&mut rest)?;
+            match rest.as_slice() {
+                [] => elevation::run(since, threshold, output),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "trends" => {
+            let since = cli::take_option(&mut rest, "--since")?.map(|d| parse_date(&d)).transpose()?;
+            let mut bands = Vec::new();
+            while let Some(band) = cli::take_option(&mut rest, "--band")? {
+                bands.push(trends::Band::parse(&band)?);
+            }
+            let group_by = match cli::take_option(&mut rest, "--group-by")? {
+                Some(group) if group == "week" => stats::GroupBy::Week,
+                Some(group) if group == "month" => stats::GroupBy::Month,
+                Some(other) => return Err(format!("unknown grouping '{}', expected week or month", other).into()),
+                None => stats::GroupBy::Month,
+            };
+            let output = cli::OutputFormat::take(&mut rest)?;
+            match rest.as_slice() {
+                [] => trends::run(since, bands, group_by, output),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "clean" => {
+            let since = cli::take_option(&mut rest, "--since")?.map(|d| parse_date(&d)).transpose()?;
+            let dry_run = cli::take_switch(&mut rest, "--dry-run");
+            match rest.as_slice() {
+                [] => clean::run(since, dry_run),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "cp" => {
+            let days = cli::parse_option(&mut rest, "--days")?.unwrap_or(critical_power::DEFAULT_WINDOW_DAYS);
+            let until = cli::take_option(&mut rest, "--until")?.map(|d| parse_date(&d)).transpose()?;
+            match rest.as_slice() {
+                [] => critical_power::run(days, until),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "dfa" => match rest.as_slice() {
+            [] => dfa::run(None),
+            [id] => dfa::run(Some(parse_id(id)?)),
+            _ => Err(USAGE.into()),
+        },
+        "wbal" => match rest.as_slice() {
+            [] => w_balance::run(None),
+            [id] => w_balance::run(Some(parse_id(id)?)),
+            _ => Err(USAGE.into()),
+        },
+        "routes" => {
+            let since = cli::take_option(&mut rest, "--since")?.map(|d| parse_date(&d)).transpose()?;
+            match rest.as_slice() {
+                [] => zwift::run(since),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "weekly" => {
+            let since = cli::take_option(&mut rest, "--since")?.map(|d| parse_date(&d)).transpose()?;
+            let tags = edit::TagFilter::take(&mut rest)?;
+            match rest.as_slice() {
+                [] => weekly::run(since, &tags),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "rolling" => {
+            let output = cli::OutputFormat::take(&mut rest)?;
+            match rest.as_slice() {
+                [] => rolling::run(output),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "milestones" => {
+            let output = cli::OutputFormat::take(&mut rest)?;
+            match rest.as_slice() {
+                [] => milestones::run(output),
+                _ => Err(USAGE.into()),
             }
-            
-            // Rate limiting - be nice to the API
-            if i < new_zwift_activities.len() - 1 {
-                tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+        }
+        "export-influx" => {
+            let dry_run = cli::take_switch(&mut rest, "--dry-run");
+            let tags = edit::TagFilter::take(&mut rest)?;
+            match rest.as_slice() {
+                [] => exporter::influx(dry_run, &tags).await,
+                _ => Err(USAGE.into()),
+            }
+        }
+        "mock-strava" => {
+            let port = cli::parse_option(&mut rest, "--port")?.unwrap_or(mock_strava::DEFAULT_PORT);
+            let fixtures = cli::take_option(&mut rest, "--fixtures")?.unwrap_or_else(|| mock_strava::DEFAULT_FIXTURES.to_string());
+            let rate_limit = cli::parse_option(&mut rest, "--rate-limit")?;
+            match rest.as_slice() {
+                [] => mock_strava::serve(port, std::path::Path::new(&fixtures), rate_limit).await,
+                _ => Err(USAGE.into()),
+            }
+        }
+        "calendar" => {
+            let output = cli::take_option(&mut rest, "--output")?;
+            match rest.as_slice() {
+                [] => calendar::run(output.as_deref().map(std::path::Path::new)),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "serve-metrics" => {
+            let port = cli::parse_option(&mut rest, "--port")?.unwrap_or(exporter::DEFAULT_METRICS_PORT);
+            match rest.as_slice() {
+                [] => exporter::serve(port).await,
+                _ => Err(USAGE.into()),
+            }
+        }
+        "export-gc" => {
+            let since = cli::take_option(&mut rest, "--since")?.map(|d| parse_date(&d)).transpose()?;
+            let overwrite = cli::take_switch(&mut rest, "--overwrite");
+            let options = resample::StreamOptions::take(&mut rest)?;
+            let tags = edit::TagFilter::take(&mut rest)?;
+            match rest.as_slice() {
+                [dir] => golden_cheetah::export(std::path::Path::new(dir), since, overwrite, &options, &tags),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "export-streams" => {
+            let options = resample::StreamOptions::take(&mut rest)?;
+            match rest.as_slice() {
+                [id] => resample::run(parse_id(id)?, &options),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "push" => {
+            let dry_run = cli::take_switch(&mut rest, "--dry-run");
+            match rest.as_slice() {
+                [target] if target == "intervals" => push::intervals(dry_run).await,
+                _ => Err(USAGE.into()),
+            }
+        }
+        "auth" => match rest.as_slice() {
+            [set] if set == "set-token" => auth::set_token(),
+            _ => Err(USAGE.into()),
+        },
+        "athlete" => {
+            let fetch = cli::take_switch(&mut rest, "--fetch");
+            match rest.as_slice() {
+                [] => athlete_history::run(fetch).await,
+                _ => Err(USAGE.into()),
+            }
+        }
+        "duplicates" => {
+            let dry_run = cli::take_switch(&mut rest, "--dry-run");
+            match rest.as_slice() {
+                [] => duplicates::run(dry_run),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "publish" => {
+            let output = cli::take_option(&mut rest, "--output")?;
+            let points = cli::parse_option(&mut rest, "--points")?.unwrap_or(publish::DEFAULT_POINTS);
+            match rest.as_slice() {
+                [] => publish::run(output.as_deref().map(std::path::Path::new), points),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "report" => {
+            let html = cli::take_switch(&mut rest, "--html");
+            let output = cli::take_option(&mut rest, "--output")?;
+            let mut seasons = Vec::new();
+            while let Some(season) = cli::take_option(&mut rest, "--season")? {
+                seasons.push(season);
+            }
+            let seasons = season::resolve_all(&seasons)?;
+            let tags = edit::TagFilter::take(&mut rest)?;
+            match rest.as_slice() {
+                [] if html => report::run(output.as_deref().map(std::path::Path::new), &seasons, &tags),
+                // Without --html, --output picks the format rather than a file
+                [] => match cli::OutputFormat::parse(output.as_deref().unwrap_or("text"))? {
+                    cli::OutputFormat::Json => report::print_json(&seasons, &tags),
+                    cli::OutputFormat::Text => report::print_charts(&seasons, &tags),
+                },
+                _ => Err(USAGE.into()),
+            }
+        }
+        "wkg" => {
+            let since = cli::take_option(&mut rest, "--since")?.map(|d| parse_date(&d)).transpose()?;
+            match rest.as_slice() {
+                [] => wkg::run(since),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "gear" => {
+            let fetch = cli::take_switch(&mut rest, "--fetch");
+            let date = cli::take_option(&mut rest, "--date")?.map(|d| parse_date(&d)).transpose()?;
+            match rest.as_slice() {
+                [] => gear::run(fetch).await,
+                [serviced, id] if serviced == "serviced" => gear::serviced(id, date),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "zones" => {
+            let apply = cli::take_switch(&mut rest, "--apply");
+            match rest.as_slice() {
+                [fetch] if fetch == "fetch" => zones::fetch(apply).await,
+                _ => Err(USAGE.into()),
+            }
+        }
+        "format" => match rest.as_slice() {
+            [] => {
+                println!("{}", serde_json::to_string_pretty(&format::spec())?);
+                Ok(())
+            }
+            [check] if check == "check" => match format::check()? {
+                0 => Ok(()),
+                problems => Err(format!("{} files are not readable by this build", problems).into()),
+            },
+            _ => Err(USAGE.into()),
+        },
+        "verify" => {
+            let repair = cli::take_switch(&mut rest, "--repair");
+            match rest.as_slice() {
+                [] => match verify::run(repair)? {
+                    0 => Ok(()),
+                    problems => Err(format!("{} problems in the archive", problems).into()),
+                },
+                _ => Err(USAGE.into()),
+            }
+        }
+        "migrate" => {
+            let dry_run = cli::take_switch(&mut rest, "--dry-run");
+            match rest.as_slice() {
+                [] => migrate::run(dry_run),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "show" => {
+            let fetch = cli::take_switch(&mut rest, "--fetch");
+            let chart = cli::take_switch(&mut rest, "--chart");
+            match rest.as_slice() {
+                [id] => show::run(parse_id(id)?, fetch, chart).await,
+                _ => Err(USAGE.into()),
+            }
+        }
+        "workout" => {
+            let detect = cli::take_switch(&mut rest, "--detect");
+            let ftp = cli::parse_option(&mut rest, "--ftp")?;
+            let plan = cli::take_option(&mut rest, "--plan")?.map(|d| parse_date(&d)).transpose()?;
+            let format = workout::WorkoutFormat::parse(cli::take_option(&mut rest, "--format")?.as_deref().unwrap_or("zwo"))?;
+            let output = cli::take_option(&mut rest, "--output")?;
+            let output = output.as_deref().map(std::path::Path::new);
+            match (rest.as_slice(), plan) {
+                ([id], None) => workout::run(workout::Source::Ride(parse_id(id)?), ftp, detect, format, output),
+                ([], Some(date)) => workout::run(workout::Source::Plan(date), ftp, detect, format, output),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "recompute" => {
+            let metrics = cli::take_option(&mut rest, "--metric")?.map(|m| recompute::parse_metrics(&m)).transpose()?;
+            let since = cli::take_option(&mut rest, "--since")?.map(|d| parse_date(&d)).transpose()?;
+            let force = cli::take_switch(&mut rest, "--force");
+            match rest.as_slice() {
+                [] => recompute::run(&metrics.unwrap_or_default(), since, force),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "refetch" => {
+            let streams = cli::take_switch(&mut rest, "--streams");
+            let detail = cli::take_switch(&mut rest, "--detail");
+            match rest.as_slice() {
+                [id] => refetch::run(parse_id(id)?, streams, detail).await,
+                _ => Err(USAGE.into()),
+            }
+        }
+        "adjust" => {
+            let scale = cli::parse_option(&mut rest, "--scale")?.unwrap_or(1.0);
+            let offset = cli::parse_option(&mut rest, "--offset")?.unwrap_or(0.0);
+            match rest.as_slice() {
+                [id] => adjust::run(parse_id(id)?, scale, offset),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "tag" => {
+            let remove = cli::take_switch(&mut rest, "--remove");
+            match rest.as_slice() {
+                [id, tags @ ..] => edit::tag(parse_id(id)?, tags, remove),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "edit" => {
+            let dry_run = cli::take_switch(&mut rest, "--dry-run");
+            let mut filters = Vec::new();
+            while let Some(filter) = cli::take_option(&mut rest, "--filter")? {
+                filters.push(edit::Filter::parse(&filter)?);
+            }
+            let mut changes = Vec::new();
+            while let Some(set) = cli::take_option(&mut rest, "--set")? {
+                changes.push(edit::Change::parse_set(&set)?);
+            }
+            while let Some(unset) = cli::take_option(&mut rest, "--unset")? {
+                changes.push(edit::Change::parse_unset(&unset)?);
+            }
+            match rest.as_slice() {
+                [] => edit::run(&filters, &changes, dry_run),
+                _ => Err(USAGE.into()),
+            }
+        }
+        "list" => {
+            let mut query = list::Query {
+                since: cli::take_option(&mut rest, "--since")?.map(|d| parse_date(&d)).transpose()?,
+                until: cli::take_option(&mut rest, "--until")?.map(|d| parse_date(&d)).transpose()?,
+                min_distance: cli::take_option(&mut rest, "--min-distance")?.map(|d| list::parse_distance(&d)).transpose()?,
+                max_distance: cli::take_option(&mut rest, "--max-distance")?.map(|d| list::parse_distance(&d)).transpose()?,
+                sport: cli::take_option(&mut rest, "--sport")?,
+                workout_type: cli::take_option(&mut rest, "--type")?,
+                tags: edit::TagFilter::take(&mut rest)?,
+                all: cli::take_switch(&mut rest, "--all"),
+                ..Default::default()
+            };
+            while let Some(filter) = cli::take_option(&mut rest, "--filter")? {
+                query.filters.push(edit::Filter::parse(&filter)?);
+            }
+            let sort = match cli::take_option(&mut rest, "--sort")? {
+                Some(key) => list::SortKey::parse(&key)?,
+                None => list::SortKey::Date,
+            };
+            let ascending = cli::take_switch(&mut rest, "--asc");
+            let limit = cli::parse_option(&mut rest, "--limit")?;
+            let output = cli::OutputFormat::take(&mut rest)?;
+            let export = cli::take_option(&mut rest, "--export")?;
+            match rest.as_slice() {
+                [] => list::run(&query, sort, ascending, limit, output, export.as_deref().map(std::path::Path::new)),
+                _ => Err(USAGE.into()),
             }
         }
+        "tui" => match rest.as_slice() {
+            [] => tui::run().await,
+            _ => Err(USAGE.into()),
+        },
+        "search" => {
+            let limit = cli::parse_option(&mut rest, "--limit")?.unwrap_or(search::DEFAULT_LIMIT);
+            let output = cli::OutputFormat::take(&mut rest)?;
+            match rest.as_slice() {
+                [] => Err(USAGE.into()),
+                words => search::run(&words.join(" "), limit, output),
+            }
+        }
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        other => Err(format!("unknown command '{}'\n\n{}", other, USAGE).into()),
     }
-    
-    // Update timestamp and save index
-    index.last_updated = chrono::Utc::now().to_rfc3339();
-    index.save()?;
-    
-    println!("\n💾 Saved {} total Zwift activities", index.activities.len());
-    println!("   📁 Individual files in data/activities/");
-    println!("   📋 Index at data/index.json");
-    println!("🕐 Last updated: {}", index.last_updated);
-    
-    Ok(())
 }
 
-async fn refresh_access_token(
-    client_id: &str,
-    client_secret: &str,
-    refresh_token: &str,
-) -> Result<String, Box<dyn Error>> {
-    let client = reqwest::Client::new();
-    let response = client
-        .post("https://www.strava.com/oauth/token")
-        .form(&[
-            ("client_id", client_id),
-            ("client_secret", client_secret),
-            ("refresh_token", refresh_token),
-            ("grant_type", "refresh_token"),
-        ])
-        .send()
-        .await?
-        .json::<TokenResponse>()
-        .await?;
-    
-    Ok(response.access_token)
+fn parse_id(s: &str) -> Result<activity_id::ActivityId, String> {
+    s.parse()
 }
 
-async fn fetch_activities_page(access_token: &str, page: u32, per_page: u32) -> Result<Vec<Activity>, Box<dyn Error>> {
-    let client = reqwest::Client::new();
-    let response = client
-        .get("https://www.strava.com/api/v3/athlete/activities")
-        .header("Authorization", format!("Bearer {}", access_token))
-        .query(&[
-            ("page", page.to_string()),
-            ("per_page", per_page.to_string()),
-        ])
-        .send()
-        .await?;
-    
-    let status = response.status();
-    let text = response.text().await?;
-    
-    if !status.is_success() {
-        eprintln!("❌ Strava API error ({}): {}", status, text);
-        return Err(format!("API returned status {}", status).into());
-    }
-    
-    let activities: Vec<Activity> = serde_json::from_str(&text)?;
-    Ok(activities)
+fn parse_date(s: &str) -> Result<chrono::NaiveDate, String> {
+    chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|_| format!("invalid date '{}', expected YYYY-MM-DD", s))
 }
 
-async fn fetch_activity_streams(access_token: &str, activity_id: i64) -> Result<ActivityStreams, Box<dyn Error>> {
-    let client = reqwest::Client::new();
-    let url = format!(
-        "https://www.strava.com/api/v3/activities/{}/streams",
-        activity_id
-    );
-    
-    let response = client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", access_token))
-        .query(&[
-            ("keys", "time,watts,heartrate,cadence,velocity_smooth,altitude"),
-            ("key_by_type", "true"),
-        ])
-        .send()
-        .await?;
-    
-    let status = response.status();
-    let text = response.text().await?;
-    
-    if !status.is_success() {
-        eprintln!("❌ Streams API error ({}): {}", status, text);
-        return Err(format!("API returned status {}", status).into());
-    }
-    
-    // Parse the keyed response
-    let streams_map: serde_json::Value = serde_json::from_str(&text)?;
-    
-    let streams = ActivityStreams {
-        time: streams_map.get("time")
-            .and_then(|v| v.get("data"))
-            .and_then(|v| serde_json::from_value(v.clone()).ok()),
-        watts: streams_map.get("watts")
-            .and_then(|v| v.get("data"))
-            .and_then(|v| serde_json::from_value(v.clone()).ok()),
-        heartrate: streams_map.get("heartrate")
-            .and_then(|v| v.get("data"))
-            .and_then(|v| serde_json::from_value(v.clone()).ok()),
-        cadence: streams_map.get("cadence")
-            .and_then(|v| v.get("data"))
-            .and_then(|v| serde_json::from_value(v.clone()).ok()),
-        velocity_smooth: streams_map.get("velocity_smooth")
-            .and_then(|v| v.get("data"))
-            .and_then(|v| serde_json::from_value(v.clone()).ok()),
-        altitude: streams_map.get("altitude")
-            .and_then(|v| v.get("data"))
-            .and_then(|v| serde_json::from_value(v.clone()).ok()),
+/// Commit subject for a sync, such as "sync: 3 new activities"
+fn sync_subject(added: usize, updated: usize) -> String {
+    let mut subject = match added {
+        0 => "sync: no new activities".to_string(),
+        1 => "sync: 1 new activity".to_string(),
+        n => format!("sync: {} new activities", n),
     };
-    
-    Ok(streams)
+    if updated > 0 {
+        subject.push_str(&format!(", {} updated", updated));
+    }
+    subject
 }
 
+/// Sync every athlete configured in watts.json, continuing past failures
+async fn sync_all_athletes(output: cli::OutputFormat, backfill: Option<usize>) -> Result<(), Box<dyn Error>> {
+    let config = config::Config::load()?;
+    if config.athletes.is_empty() {
+        return Err("no athletes configured in watts.json".into());
+    }
+    let mut failed = Vec::new();
+    let mut worst = None;
+    let mut unfetched = Vec::new();
+    let mut reports = std::collections::BTreeMap::new();
+    let (mut added, mut updated, mut lines) = (0, 0, Vec::new());
+    for profile in &config.athletes {
+        info!("👤 Syncing {} ({})", profile.name, profile.data_dir().display());
+        athlete::select(Some(profile.clone()));
+        match sync::run(backfill).await {
+            Ok(report) => {
+                if let Some(incomplete) = report.failure() {
+                    warn!("⚠️  Sync incomplete for {}: {}", profile.name, incomplete);
+                    worst = worst.max(Some(incomplete.failure));
+                    unfetched.extend(incomplete.activities);
+                    failed.push(profile.name.clone());
+                }
+                added += report.added.len();
+                updated += report.updated.len();
+                lines.extend(report.commit_lines().into_iter().map(|line| format!("{} [{}]", line, profile.name)));
+                reports.insert(profile.name.clone(), report);
+            }
+            Err(e) => {
+                tracing::error!("❌ Sync failed for {}: {}", profile.name, e);
+                worst = worst.max(Some(failure::Failure::of(e.as_ref())));
+                failed.push(profile.name.clone());
+            }
+        }
+    }
+    athlete::select(None);
+    ci::describe(sync_subject(added, updated), lines);
+    if output == cli::OutputFormat::Json {
+        cli::print_json(&reports)?;
+    }
+    if let Some(failure) = worst {
+        return Err(failure::Incomplete {
+            failure,
+            message: format!("sync failed for {}", failed.join(", ")),
+            activities: unfetched,
+        }
+        .into());
+    }
+    Ok(())
+}
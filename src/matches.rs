@@ -0,0 +1,126 @@
+//! Matches: hard efforts well above threshold, the attacks and surges a race
+//! is decided by. Each ride's list (where in the ride, how long, how hard) is
+//! stored in its activity file and the count and total kJ on its index entry,
+//! so a race can be read back burn by burn and races compared by how many
+//! matches they took.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::metrics;
+use crate::model::{ActivitySummary, ActivityWithStreams};
+use crate::pipeline::Analyzer;
+
+/// Samples under the threshold for up to this long (a shift, a sprint's lull) don't end a match
+const MAX_DIP_SECONDS: i32 = 2;
+
+/// What counts as a match, under `"matches"` in watts.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchSettings {
+    /// Power at or above this share of FTP, in percent
+    #[serde(default = "default_threshold_percent")]
+    pub threshold_percent: f64,
+    /// Held for at least this many seconds
+    #[serde(default = "default_min_seconds")]
+    pub min_seconds: i32,
+}
+
+fn default_threshold_percent() -> f64 {
+    120.0
+}
+
+fn default_min_seconds() -> i32 {
+    15
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        MatchSettings { threshold_percent: default_threshold_percent(), min_seconds: default_min_seconds() }
+    }
+}
+
+/// One match, stored in the activity file
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Match {
+    /// Index of the first stream sample
+    pub start_index: usize,
+    /// Seconds into the ride
+    pub start: i32,
+    pub duration: i32,
+    pub average_watts: f64,
+    pub kilojoules: f64,
+}
+
+/// Count and work of a ride's matches, on its index entry
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MatchSummary {
+    pub count: usize,
+    pub kilojoules: f64,
+}
+
+/// Match list on the activity file, count and kJ on the index entry
+pub struct Matches;
+
+impl Analyzer for Matches {
+    fn name(&self) -> &'static str {
+        "matches"
+    }
+
+    fn inputs(&self, config: &Config) -> Value {
+        json!([config.ftp(), config.matches])
+    }
+
+    fn analyze(&self, file: &mut ActivityWithStreams, summary: &mut ActivitySummary, config: &Config) {
+        let settings = config.matches.clone().unwrap_or_default();
+        file.matches = if summary.is_run() { None } else { config.ftp().and_then(|ftp| detect(file, ftp, &settings)) };
+        summary.matches = file.matches.as_ref().map(|matches| MatchSummary {
+            count: matches.len(),
+            kilojoules: metrics::round1(matches.iter().fold(0.0, |total, m| total + m.kilojoules)),
+        });
+    }
+}
+
+/// The matches of a ride, None without a power stream
+pub fn detect(file: &ActivityWithStreams, ftp: f64, settings: &MatchSettings) -> Option<Vec<Match>> {
+    let streams = file.streams.as_ref()?;
+    let watts = streams.watts.as_deref().filter(|w| !w.is_empty())?;
+    let time = streams.time.as_deref().filter(|t| t.len() == watts.len());
+    let at = |i: usize| time.map_or(i as i32, |t| t[i]);
+    let threshold = ftp * settings.threshold_percent / 100.0;
+
+    let mut matches = Vec::new();
+    // First and last sample of the effort in progress
+    let mut effort: Option<(usize, usize)> = None;
+    for (i, &power) in watts.iter().enumerate() {
+        if power >= threshold {
+            effort = match effort {
+                Some((first, last)) if at(i) - at(last) <= MAX_DIP_SECONDS + 1 => Some((first, i)),
+                previous => {
+                    matches.extend(previous.and_then(|(first, last)| build(watts, time, first, last, settings)));
+                    Some((i, i))
+                }
+            };
+        }
+    }
+    matches.extend(effort.and_then(|(first, last)| build(watts, time, first, last, settings)));
+    Some(matches)
+}
+
+/// A match over samples `first..=last`, None when too short
+fn build(watts: &[f64], time: Option<&[i32]>, first: usize, last: usize, settings: &MatchSettings) -> Option<Match> {
+    let at = |i: usize| time.map_or(i as i32, |t| t[i]);
+    // The last sample counts for a second
+    let duration = at(last) - at(first) + 1;
+    if duration < settings.min_seconds {
+        return None;
+    }
+    let kilojoules = metrics::kilojoules(&watts[first..=last], time.map(|t| &t[first..=last]));
+    Some(Match {
+        start_index: first,
+        start: at(first) - at(0),
+        duration,
+        average_watts: (kilojoules * 1000.0 / duration as f64).round(),
+        kilojoules: metrics::round1(kilojoules),
+    })
+}
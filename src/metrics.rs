@@ -0,0 +1,211 @@
+use crate::config::Config;
+use crate::model::{Activity, ActivityStreams, ActivitySummary, ActivityWithStreams};
+use crate::pipeline::Analyzer;
+
+/// Rolling window used by Normalized Power, in samples (streams are ~1 Hz)
+const NP_WINDOW: usize = 30;
+
+/// Decoupling needs two halves long enough to settle, 10 minutes in total
+const DECOUPLING_MIN_SAMPLES: usize = 600;
+
+pub fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    Some(values.iter().sum::<f64>() / values.len() as f64)
+}
+
+/// Normalized Power: 4th-power mean of the 30s rolling average
+pub fn normalized_power(watts: &[f64]) -> Option<f64> {
+    if watts.len() < NP_WINDOW {
+        return None;
+    }
+    let mut window_sum: f64 = watts[..NP_WINDOW].iter().sum();
+    let mut fourth_powers = (window_sum / NP_WINDOW as f64).powi(4);
+    let mut count = 1;
+    for i in NP_WINDOW..watts.len() {
+        window_sum += watts[i] - watts[i - NP_WINDOW];
+        fourth_powers += (window_sum / NP_WINDOW as f64).powi(4);
+        count += 1;
+    }
+    Some((fourth_powers / count as f64).powf(0.25))
+}
+
+/// Work in kJ, integrating each sample over the gap to the next timestamp;
+/// the last sample, with no next one, counts for a second
+pub fn kilojoules(watts: &[f64], time: Option<&[i32]>) -> f64 {
+    let joules: f64 = match time {
+        Some(time) if time.len() == watts.len() => watts
+            .iter()
+            .zip(time.windows(2).map(|t| (t[1] - t[0]).max(0)).chain(std::iter::once(1)))
+            .map(|(w, seconds)| w * seconds as f64)
+            .sum(),
+        _ => watts.iter().sum(),
+    };
+    joules / 1000.0
+}
+
+/// Best average power over any `window` consecutive samples (mean-maximal power)
+pub fn mean_maximal_power(watts: &[f64], window: usize) -> Option<f64> {
+    mean_maximal_power_at(watts, window).map(|(power, _)| power)
+}
+
+/// Mean-maximal power over `window` samples, and the sample its window starts at
+pub fn mean_maximal_power_at(watts: &[f64], window: usize) -> Option<(f64, usize)> {
+    if window == 0 || watts.len() < window {
+        return None;
+    }
+    let mut sum: f64 = watts[..window].iter().sum();
+    let (mut best, mut start) = (sum, 0);
+    for i in window..watts.len() {
+        sum += watts[i] - watts[i - window];
+        if sum > best {
+            best = sum;
+            start = i + 1 - window;
+        }
+    }
+    Some((best / window as f64, start))
+}
+
+/// Efficiency Factor: normalized power per heartbeat
+pub fn efficiency_factor(watts: &[f64], heartrate: &[i32]) -> Option<f64> {
+    let hr: Vec<f64> = heartrate.iter().filter(|&&h| h > 0).map(|&h| h as f64).collect();
+    let average_hr = mean(&hr)?;
+    Some(normalized_power(watts)? / average_hr)
+}
+
+/// Pw:HR decoupling in percent: how much the efficiency factor drops from the
+/// first half of the ride to the second. Under 5% means a solid aerobic base.
+pub fn decoupling(watts: &[f64], heartrate: &[i32]) -> Option<f64> {
+    let len = watts.len().min(heartrate.len());
+    if len < DECOUPLING_MIN_SAMPLES {
+        return None;
+    }
+    let mid = len / 2;
+    let first = efficiency_factor(&watts[..mid], &heartrate[..mid])?;
+    let second = efficiency_factor(&watts[mid..len], &heartrate[mid..len])?;
+    Some((first - second) / first * 100.0)
+}
+
+/// W' balance after each sample (Skiba's differential model): W' is spent
+/// above CP and recovers exponentially below it, faster the further below.
+pub fn w_prime_balance(watts: &[f64], time: Option<&[i32]>, cp: f64, w_prime: f64) -> Vec<f64> {
+    let mut balance = w_prime;
+    let mut out = Vec::with_capacity(watts.len());
+    for (i, &power) in watts.iter().enumerate() {
+        let dt = match time {
+            Some(time) if i > 0 && time.len() == watts.len() => (time[i] - time[i - 1]).max(0) as f64,
+            _ => 1.0,
+        };
+        if power > cp {
+            balance -= (power - cp) * dt;
+        } else {
+            balance = w_prime - (w_prime - balance) * (-(cp - power) * dt / w_prime).exp();
+        }
+        out.push(balance);
+    }
+    out
+}
+
+/// Training Stress Score: one hour at FTP scores 100
+pub fn training_stress_score(normalized_power: f64, seconds: i32, ftp: f64) -> f64 {
+    let intensity = normalized_power / ftp;
+    seconds as f64 * normalized_power * intensity / (ftp * 3600.0) * 100.0
+}
+
+/// Banister TRIMP: minutes weighted by heart rate reserve, exponentially so
+/// for hard efforts. Uses the coefficients for men (0.64, 1.92).
+pub fn trimp(heartrate: &[i32], time: Option<&[i32]>, resting_hr: f64, max_hr: f64) -> Option<f64> {
+    if heartrate.is_empty() || max_hr <= resting_hr {
+        return None;
+    }
+    let mut total = 0.0;
+    for (i, &hr) in heartrate.iter().enumerate() {
+        let dt = match time {
+            Some(time) if i > 0 && time.len() == heartrate.len() => (time[i] - time[i - 1]).max(0) as f64,
+            _ => 1.0,
+        };
+        let reserve = ((hr as f64 - resting_hr) / (max_hr - resting_hr)).clamp(0.0, 1.0);
+        total += dt / 60.0 * reserve * 0.64 * (1.92 * reserve).exp();
+    }
+    Some(total)
+}
+
+/// Seconds spent in each zone, given the lower bound of every zone
+pub fn time_in_zones(watts: &[f64], floors: &[f64]) -> Vec<i32> {
+    let mut seconds = vec![0; floors.len()];
+    for &w in watts {
+        if let Some(zone) = floors.iter().rposition(|&floor| w >= floor) {
+            seconds[zone] += 1;
+        }
+    }
+    seconds
+}
+
+/// Polarization index (Treff et al. 2019) from time below LT1, between the
+/// thresholds, and above LT2. Above 2.0 is polarized; None without high-intensity time.
+pub fn polarization_index(low: f64, mid: f64, high: f64) -> Option<f64> {
+    let total = low + mid + high;
+    if total <= 0.0 || high <= 0.0 {
+        return None;
+    }
+    // The paper substitutes 1% when no time was spent between the thresholds
+    let mid = (mid / total).max(0.01);
+    Some(((low / total) / mid * (high / total) * 100.0).log10())
+}
+
+/// Refresh the power summary fields of an activity from its watts stream
+pub fn recompute_power_summary(activity: &mut Activity, streams: &ActivityStreams) {
+    let Some(watts) = streams.watts.as_deref() else { return };
+    activity.average_watts = mean(watts).map(round1);
+    activity.weighted_average_watts = normalized_power(watts).map(|np| np.round());
+    activity.max_watts = watts.iter().cloned().reduce(f64::max);
+    activity.kilojoules = Some(round1(kilojoules(watts, streams.time.as_deref())));
+}
+
+/// Average, normalized and max power and kJ from the watts stream, replacing Strava's
+pub struct NormalizedPower;
+
+impl Analyzer for NormalizedPower {
+    fn name(&self) -> &'static str {
+        "np"
+    }
+
+    fn on_ingest(&self) -> bool {
+        false
+    }
+
+    fn analyze(&self, file: &mut ActivityWithStreams, _summary: &mut ActivitySummary, _config: &Config) {
+        if let Some(streams) = file.streams.as_ref() {
+            recompute_power_summary(&mut file.activity, streams);
+        }
+    }
+}
+
+/// Round to one decimal, as stored metrics are
+pub fn round1(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+/// Least-squares line through (x, y) points: (slope, intercept, r²).
+/// None with fewer than two points or when x doesn't vary; r² is 1 when y doesn't.
+pub fn linear_fit(points: &[(f64, f64)]) -> Option<(f64, f64, f64)> {
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let (mut sxx, mut syy, mut sxy) = (0.0, 0.0, 0.0);
+    for (x, y) in points {
+        sxx += (x - mean_x) * (x - mean_x);
+        syy += (y - mean_y) * (y - mean_y);
+        sxy += (x - mean_x) * (y - mean_y);
+    }
+    if sxx == 0.0 {
+        return None;
+    }
+    let slope = sxy / sxx;
+    let r_squared = if syy > 0.0 { sxy * sxy / (sxx * syy) } else { 1.0 };
+    Some((slope, mean_y - slope * mean_x, r_squared))
+}
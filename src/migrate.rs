@@ -0,0 +1,81 @@
+use std::error::Error;
+use tracing::{info, warn};
+
+use crate::model::{ActivityIndex, ActivitySummary, ActivityWithStreams};
+use crate::format;
+use crate::schema;
+use crate::storage::{list_activity_ids, load_activity_file, read_activity_file, write_activity_file};
+use crate::store;
+
+/// Rewrite the index and every activity file at the current schema version
+pub fn run(dry_run: bool) -> Result<(), Box<dyn Error>> {
+    info!("🧬 Migrating archive to schema v{}{}", schema::CURRENT_VERSION, if dry_run { " (dry run)" } else { "" });
+
+    let store = store::current()?;
+    let json = store.get(format::INDEX_FILE)?.ok_or_else(|| format!("{} not found", store.describe(format::INDEX_FILE)))?;
+    let mut index: serde_json::Value = serde_json::from_slice(&json)?;
+    let index_version = schema::version_of(&index);
+    if schema::migrate_index(&mut index)? {
+        info!("   📋 index.json v{} -> v{}", index_version, schema::CURRENT_VERSION);
+        if !dry_run {
+            let index: ActivityIndex = serde_json::from_value(index)?;
+            index.save()?;
+        }
+    }
+
+    let mut migrated = 0;
+    let mut failed = 0;
+    for id in list_activity_ids()? {
+        let result = read_activity_file(id)
+            .and_then(|(bytes, compression)| Ok((serde_json::from_slice::<serde_json::Value>(&bytes)?, compression)))
+            .and_then(|(mut value, compression)| {
+                let from = schema::version_of(&value);
+                if !schema::migrate_activity(&mut value)? {
+                    return Ok(false);
+                }
+                let activity: ActivityWithStreams = serde_json::from_value(value)?;
+                info!("   {} v{} -> v{}", id, from, schema::CURRENT_VERSION);
+                if !dry_run {
+                    write_activity_file(&activity, compression)?;
+                }
+                Ok(true)
+            });
+        match result {
+            Ok(true) => migrated += 1,
+            Ok(false) => {}
+            Err(e) => {
+                warn!("   ⚠️  Could not migrate {}: {}", id, e);
+                failed += 1;
+            }
+        }
+    }
+
+    // Entries indexed before the sport type, local time offset and work were
+    // recorded get them from their activity file
+    let mut index = ActivityIndex::load()?;
+    let mut filled = 0;
+    let legacy = |a: &ActivitySummary| {
+        a.utc_offset.is_none() || a.sport_type.is_none() || (a.kilojoules.is_none() && a.average_watts.is_some())
+    };
+    for summary in index.activities.iter_mut().filter(|a| legacy(a)) {
+        let Ok(file) = load_activity_file(summary.id) else { continue };
+        summary.utc_offset = summary.utc_offset.or(file.activity.utc_offset());
+        summary.kilojoules = summary.kilojoules.or(file.activity.kilojoules);
+        summary.sport_type = Some(file.activity.sport_type.clone());
+        summary.trainer = file.activity.trainer;
+        summary.commute = file.activity.commute;
+        filled += 1;
+    }
+    if filled > 0 {
+        info!("   📋 Sport, local time and work filled in for {} index entries", filled);
+        if !dry_run {
+            index.save()?;
+        }
+    }
+
+    info!("✅ {} activity files migrated, {} failed", migrated, failed);
+    if failed > 0 {
+        return Err(format!("{} activity files could not be migrated", failed).into());
+    }
+    Ok(())
+}
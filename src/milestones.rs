@@ -0,0 +1,136 @@
+//! Lifetime cycling milestones: the Eddington number (the largest E such
+//! that E days had at least E km of riding), total distance every 1,000 km
+//! and total climbing every 10,000 m. Sync compares the archive before and
+//! after its rides and reports the milestones they crossed in
+//! `last_sync.json`, the log and the webhooks; `milestones` prints where
+//! each stands.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use tracing::info;
+
+use crate::activity_id::ActivityId;
+use crate::cli::{self, OutputFormat};
+use crate::model::{ActivityIndex, ActivitySummary};
+
+/// Lifetime distance milestones, km
+const DISTANCE_STEP_KM: f64 = 1000.0;
+
+/// Lifetime climbing milestones, m
+const CLIMBING_STEP_M: f64 = 10_000.0;
+
+/// Where the archive stands
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Lifetime {
+    pub distance_km: f64,
+    pub climbing_m: f64,
+    pub eddington: usize,
+    /// More days of at least `eddington + 1` km needed to reach it
+    pub eddington_days_needed: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Milestone {
+    /// "distance", "climbing", "eddington", or "day_streak" and "week_streak" from `streaks`
+    pub kind: &'static str,
+    /// km, m, the new Eddington number or the streak's length
+    pub value: f64,
+    pub message: String,
+}
+
+impl Lifetime {
+    /// Totals of the counted bike rides `keep` accepts
+    fn of(index: &ActivityIndex, keep: impl Fn(&ActivitySummary) -> bool) -> Self {
+        let rides: Vec<&ActivitySummary> = index.activities.iter().filter(|a| a.counted() && a.is_ride() && keep(a)).collect();
+        let mut days: BTreeMap<NaiveDate, f64> = BTreeMap::new();
+        for ride in &rides {
+            *days.entry(ride.local_date()).or_default() += ride.distance / 1000.0;
+        }
+        let (eddington, eddington_days_needed) = eddington(&days.into_values().collect::<Vec<_>>());
+        Lifetime {
+            distance_km: (rides.iter().map(|a| a.distance).fold(0.0, |total, d| total + d) / 100.0).round() / 10.0,
+            climbing_m: rides.iter().filter_map(|a| a.total_elevation_gain).fold(0.0, |total, m| total + m),
+            eddington,
+            eddington_days_needed,
+        }
+    }
+
+    pub fn current(index: &ActivityIndex) -> Self {
+        Self::of(index, |_| true)
+    }
+}
+
+/// Eddington number of daily distances in km, and the days still needed for the next one
+pub fn eddington(daily_km: &[f64]) -> (usize, usize) {
+    let mut sorted = daily_km.to_vec();
+    sorted.sort_by(|a, b| b.total_cmp(a));
+    // The nth longest day (1-based) covers n km for every n up to E
+    let e = sorted.iter().enumerate().take_while(|(i, &km)| km >= (i + 1) as f64).count();
+    let next = (e + 1) as f64;
+    (e, (e + 1).saturating_sub(sorted.iter().filter(|&&km| km >= next).count()))
+}
+
+/// The milestones the rides in `added` crossed, logged as they are found
+pub fn reached(index: &ActivityIndex, added: &[ActivityId]) -> Vec<Milestone> {
+    let before = Lifetime::of(index, |a| !added.contains(&a.id));
+    let after = Lifetime::current(index);
+    let mut milestones = Vec::new();
+    let crossed = |before: f64, after: f64, step: f64| (after / step).floor() > (before / step).floor();
+    if crossed(before.distance_km, after.distance_km, DISTANCE_STEP_KM) {
+        let km = (after.distance_km / DISTANCE_STEP_KM).floor() * DISTANCE_STEP_KM;
+        milestones.push(Milestone { kind: "distance", value: km, message: format!("🛣️  {:.0} km ridden in total", km) });
+    }
+    if crossed(before.climbing_m, after.climbing_m, CLIMBING_STEP_M) {
+        let m = (after.climbing_m / CLIMBING_STEP_M).floor() * CLIMBING_STEP_M;
+        milestones.push(Milestone { kind: "climbing", value: m, message: format!("⛰️  {:.0} m climbed in total", m) });
+    }
+    if after.eddington > before.eddington {
+        milestones.push(Milestone {
+            kind: "eddington",
+            value: after.eddington as f64,
+            message: format!("📏 Eddington number up to {} (was {})", after.eddington, before.eddington),
+        });
+    }
+    for milestone in &milestones {
+        info!("🎉 Milestone: {}", milestone.message);
+    }
+    milestones
+}
+
+/// Print the Eddington number and lifetime totals with the next milestone of each
+pub fn run(output: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let index = ActivityIndex::load()?;
+    let lifetime = Lifetime::current(&index);
+    if output == OutputFormat::Json {
+        return cli::print_json(&lifetime);
+    }
+    let next = |value: f64, step: f64| ((value / step).floor() + 1.0) * step;
+    info!("🏅 Lifetime milestones");
+    println!(
+        "  Eddington:  {} ({} more days of {} km for {})",
+        lifetime.eddington,
+        lifetime.eddington_days_needed,
+        lifetime.eddington + 1,
+        lifetime.eddington + 1
+    );
+    println!(
+        "  Distance:   {:.0} km ({:.0} km to {:.0} km)",
+        lifetime.distance_km,
+        next(lifetime.distance_km, DISTANCE_STEP_KM) - lifetime.distance_km,
+        next(lifetime.distance_km, DISTANCE_STEP_KM)
+    );
+    println!(
+        "  Climbing:   {:.0} m ({:.0} m to {:.0} m)",
+        lifetime.climbing_m,
+        next(lifetime.climbing_m, CLIMBING_STEP_M) - lifetime.climbing_m,
+        next(lifetime.climbing_m, CLIMBING_STEP_M)
+    );
+    let missing = index.activities.iter().filter(|a| a.counted() && a.is_ride() && a.total_elevation_gain.is_none()).count();
+    if missing > 0 {
+        info!("   ⚠️  {} rides indexed without their climbing, run `recompute` to count it", missing);
+    }
+    Ok(())
+}
@@ -0,0 +1,146 @@
+//! A fake Strava API serving recorded JSON fixtures, so sync, pagination and
+//! rate-limit handling can be exercised without credentials:
+//!
+//! ```text
+//! cargo run -- mock-strava &
+//! STRAVA_CLIENT_ID=x STRAVA_CLIENT_SECRET=x STRAVA_REFRESH_TOKEN=x \
+//!     cargo run -- --base-url http://127.0.0.1:9899 sync
+//! ```
+//!
+//! A request path maps to `<fixtures>/<path>.json`; paged endpoints map to
+//! `<fixtures>/<path>/page-<n>.json` and answer `[]` past the last page, as
+//! Strava does (the layout `WATTS_CASSETTE=record:DIR` writes). Anything else
+//! is a 404. The tests under `tests/` run it on an ephemeral port with
+//! `serve_on` and point `sync::run` at it.
+
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+use crate::cassette;
+
+pub const DEFAULT_PORT: u16 = 9899;
+pub const DEFAULT_FIXTURES: &str = "fixtures/strava";
+
+/// Strava's short-term limit: requests per 15 minutes
+const SHORT_TERM_LIMIT: u32 = 100;
+
+/// Longest request head accepted, well past anything reqwest sends
+const MAX_HEAD: usize = 64 * 1024;
+
+/// Serve `fixtures` on `port`; with `rate_limit`, requests after the first N get
+/// a 429 as Strava sends once the 15-minute budget is spent
+pub async fn serve(port: u16, fixtures: &Path, rate_limit: Option<u32>) -> Result<(), Box<dyn Error>> {
+    serve_on(TcpListener::bind(("127.0.0.1", port)).await?, fixtures, rate_limit).await
+}
+
+/// `serve` on a listener already bound, such as port 0 in tests
+pub async fn serve_on(listener: TcpListener, fixtures: &Path, rate_limit: Option<u32>) -> Result<(), Box<dyn Error>> {
+    if !fixtures.is_dir() {
+        return Err(format!("fixtures directory {} not found", fixtures.display()).into());
+    }
+    info!("🧪 Mock Strava API on http://{} serving {}", listener.local_addr()?, fixtures.display());
+    let mut served: u32 = 0;
+    loop {
+        let (mut socket, peer) = listener.accept().await?;
+        let request = match read_request(&mut socket).await {
+            Ok(request) => request,
+            Err(e) => {
+                warn!("   ⚠️  Bad request from {}: {}", peer, e);
+                continue;
+            }
+        };
+        let request_line = String::from_utf8_lossy(&request).lines().next().unwrap_or_default().to_string();
+        served += 1;
+
+        let limited = rate_limit.is_some_and(|limit| served > limit);
+        let (status, body) = if limited {
+            ("429 Too Many Requests", r#"{"message":"Rate Limit Exceeded"}"#.to_string())
+        } else {
+            match fixture(fixtures, &request_line) {
+                Some(path) => match std::fs::read_to_string(&path) {
+                    Ok(body) => ("200 OK", body),
+                    Err(e) => {
+                        warn!("   ⚠️  Could not read {}: {}", path.display(), e);
+                        ("500 Internal Server Error", String::new())
+                    }
+                },
+                None if is_paged(&request_line) => ("200 OK", "[]".to_string()),
+                None => ("404 Not Found", r#"{"message":"Record Not Found"}"#.to_string()),
+            }
+        };
+        // Like Strava, a fixture is tagged and a matching If-None-Match gets a 304
+        let etag = format!("\"{:016x}\"", fnv1a(body.as_bytes()));
+        let (status, body) = match header(&request, "if-none-match") {
+            Some(tag) if status == "200 OK" && tag == etag => ("304 Not Modified", String::new()),
+            _ => (status, body),
+        };
+        debug!("{} {} -> {}", peer, request_line, status);
+
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nETag: {}\r\nX-RateLimit-Limit: {},1000\r\nX-RateLimit-Usage: {},{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            etag,
+            rate_limit.unwrap_or(SHORT_TERM_LIMIT),
+            served,
+            served,
+            body.len(),
+            body
+        );
+        if let Err(e) = socket.write_all(response.as_bytes()).await {
+            warn!("   ⚠️  Could not answer {}: {}", peer, e);
+        }
+    }
+}
+
+/// The request head, up to the blank line ending the headers, however the
+/// client split it across packets. A body (the token refresh POST has one) is
+/// drained so closing the socket doesn't reset the connection under the response.
+async fn read_request(socket: &mut TcpStream) -> std::io::Result<Vec<u8>> {
+    let mut request = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let head_len = loop {
+        if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end + 4;
+        }
+        if request.len() > MAX_HEAD {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "request headers too large"));
+        }
+        let n = socket.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        request.extend_from_slice(&chunk[..n]);
+    };
+    let body_len = header(&request[..head_len], "content-length").and_then(|v| v.parse::<usize>().ok()).unwrap_or(0);
+    let mut body = vec![0u8; (head_len + body_len).saturating_sub(request.len())];
+    socket.read_exact(&mut body).await?;
+    request.truncate(head_len);
+    Ok(request)
+}
+
+fn header(request: &[u8], name: &str) -> Option<String> {
+    String::from_utf8_lossy(request).lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.eq_ignore_ascii_case(name).then(|| value.trim().to_string())
+    })
+}
+
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+fn is_paged(request_line: &str) -> bool {
+    request_line.split_whitespace().nth(1).is_some_and(|target| target.contains("page="))
+}
+
+/// The fixture answering "GET /api/v3/athlete/activities?page=2&per_page=30 HTTP/1.1"
+fn fixture(fixtures: &Path, request_line: &str) -> Option<PathBuf> {
+    let target = request_line.split_whitespace().nth(1)?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query: Vec<(&str, &str)> = query.split('&').filter_map(|pair| pair.split_once('=')).collect();
+    cassette::file_for(fixtures, path, &query).filter(|file| file.is_file())
+}
@@ -0,0 +1,381 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::activity_id::ActivityId;
+use crate::aerobic::HeartRateFit;
+use crate::cadence::CadenceProfile;
+use crate::clean::CleaningReport;
+use crate::elevation::ElevationCheck;
+use crate::matches::{Match, MatchSummary};
+use crate::pace;
+use crate::pacing::PacingStats;
+use crate::pedalling::PedallingStats;
+use crate::power_estimate::PowerEstimate;
+use crate::schema;
+use crate::sprints::Sprint;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Activity {
+    pub id: ActivityId,
+    pub name: String,
+    pub distance: f64,
+    pub moving_time: i32,
+    pub elapsed_time: i32,
+    pub total_elevation_gain: f64,
+    #[serde(rename = "type")]
+    pub activity_type: String,
+    pub sport_type: String,
+    pub start_date: String,
+    pub start_date_local: String,
+    pub timezone: String,
+    pub trainer: bool,
+    pub commute: bool,
+    pub average_speed: f64,
+    pub max_speed: f64,
+    pub average_watts: Option<f64>,
+    pub weighted_average_watts: Option<f64>,
+    pub max_watts: Option<f64>,
+    pub kilojoules: Option<f64>,
+    pub device_watts: Option<bool>,
+    pub has_heartrate: bool,
+    pub average_heartrate: Option<f64>,
+    pub max_heartrate: Option<f64>,
+    pub average_cadence: Option<f64>,
+    pub suffer_score: Option<f64>,
+    pub kudos_count: i32,
+    pub achievement_count: i32,
+    pub pr_count: i32,
+    // Only present when the detail endpoint was fetched (WATTS_FETCH_DETAIL)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gear_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub calories: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub splits_metric: Option<Vec<Split>>,
+}
+
+impl Activity {
+    /// `start_date` as a UTC timestamp
+    pub fn start(&self) -> Option<DateTime<Utc>> {
+        self.start_date.parse().ok()
+    }
+
+    /// `start_date_local`, the wall-clock time where the activity was recorded.
+    /// Strava suffixes it with Z although it is not UTC.
+    pub fn local_start(&self) -> Option<NaiveDateTime> {
+        self.start_date_local.parse::<DateTime<Utc>>().ok().map(|t| t.naive_utc())
+    }
+
+    /// Seconds the local start is ahead of UTC
+    pub fn utc_offset(&self) -> Option<i32> {
+        Some((self.local_start()? - self.start()?.naive_utc()).num_seconds() as i32)
+    }
+}
+
+/// Per-kilometre split from the detailed activity endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Split {
+    pub split: i32,
+    pub distance: f64,
+    pub elapsed_time: i32,
+    pub moving_time: i32,
+    pub elevation_difference: Option<f64>,
+    pub average_speed: f64,
+    pub average_heartrate: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityStreams {
+    pub time: Option<Vec<i32>>,
+    pub watts: Option<Vec<f64>>,
+    pub heartrate: Option<Vec<i32>>,
+    pub cadence: Option<Vec<i32>>,
+    pub velocity_smooth: Option<Vec<f64>>,
+    pub altitude: Option<Vec<f64>>,
+    /// [lat, lng] per sample; Zwift maps each world to a fixed real-world location
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latlng: Option<Vec<[f64; 2]>>,
+    /// Watts as recorded, kept when `watts` holds a cleaned or calibration-corrected copy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_watts: Option<Vec<f64>>,
+    /// Heart rate as recorded, kept when `heartrate` holds a cleaned copy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_heartrate: Option<Vec<i32>>,
+    /// Resolution Strava served ("low", "medium" or "high"), the lowest of any
+    /// series; unknown for older files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<String>,
+    /// What the samples are indexed by, "time" or "distance"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub series_type: Option<String>,
+    /// Samples Strava holds for the activity; more than stored means a re-fetch can do better
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_size: Option<usize>,
+    /// Requested series Strava did not return (no power meter, no HR strap, indoor ride)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub missing_series: Vec<String>,
+    /// Beat-to-beat intervals in milliseconds; Strava has none, FIT imports from chest straps do
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rr_intervals: Option<Vec<f64>>,
+    /// Derived: W' balance in joules after each sample, from the CP model at sync time
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub w_balance: Option<Vec<f64>>,
+    /// Derived: watts estimated from speed and grade for outdoor rides without
+    /// a power meter, see `power_estimate`; never merged into `watts`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_watts: Option<Vec<f64>>,
+    /// Series that did not match the time stream's length, with the length they
+    /// had before being cut or padded to it
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub misaligned: BTreeMap<String, usize>,
+}
+
+/// Combined activity with detailed stream data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityWithStreams {
+    #[serde(default)]
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub activity: Activity,
+    pub streams: Option<ActivityStreams>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub power_adjustment: Option<PowerAdjustment>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cleaning: Option<CleaningReport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub laps: Option<Vec<Lap>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub social: Option<Social>,
+    /// Efforts well above FTP, from `matches`; unset without power or FTP
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matches: Option<Vec<Match>>,
+}
+
+/// Kudos, comments and photos, so the archive survives leaving Strava
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Social {
+    pub fetched_at: String,
+    pub kudoers: Vec<AthleteName>,
+    pub comments: Vec<Comment>,
+    pub photos: Vec<Photo>,
+}
+
+/// Strava only shows the initial of other athletes' last names
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AthleteName {
+    #[serde(default)]
+    pub firstname: String,
+    #[serde(default)]
+    pub lastname: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: i64,
+    pub text: String,
+    pub created_at: String,
+    pub athlete: AthleteName,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Photo {
+    pub unique_id: String,
+    /// Image URLs keyed by requested size
+    #[serde(default)]
+    pub urls: BTreeMap<String, String>,
+    #[serde(default)]
+    pub caption: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+/// A lap as recorded by the device; Zwift workouts emit one per workout block
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lap {
+    pub lap_index: i32,
+    pub name: String,
+    /// Stream sample range covered by the lap
+    pub start_index: usize,
+    pub end_index: usize,
+    pub elapsed_time: i32,
+    pub moving_time: i32,
+    pub distance: f64,
+    pub average_speed: f64,
+    pub average_watts: Option<f64>,
+    pub average_heartrate: Option<f64>,
+    pub max_heartrate: Option<f64>,
+    pub average_cadence: Option<f64>,
+}
+
+/// Correction applied to the watts stream: corrected = original * scale + offset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerAdjustment {
+    pub scale: f64,
+    pub offset: f64,
+    pub adjusted_at: String,
+    /// Strava's power summary before the first adjustment, restored on revert
+    pub recorded: PowerSummary,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerSummary {
+    pub average_watts: Option<f64>,
+    pub weighted_average_watts: Option<f64>,
+    pub max_watts: Option<f64>,
+    pub kilojoules: Option<f64>,
+}
+
+/// Index file - just metadata, no streams
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityIndex {
+    #[serde(default)]
+    pub schema_version: u32,
+    pub last_updated: String,
+    pub activities: Vec<ActivitySummary>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivitySummary {
+    pub id: ActivityId,
+    pub name: String,
+    pub start_date: DateTime<Utc>,
+    /// Seconds local time was ahead of UTC at the start; unknown for entries
+    /// indexed before it was recorded (`migrate` fills it in)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utc_offset: Option<i32>,
+    pub distance: f64,
+    pub moving_time: i32,
+    /// Metres climbed as Strava reports it; unset in entries indexed before it
+    /// was recorded (`recompute` fills it in)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub total_elevation_gain: Option<f64>,
+    pub average_watts: Option<f64>,
+    pub average_heartrate: Option<f64>,
+    /// Work in kJ, as on the activity; unset without power, and in entries
+    /// indexed before it was recorded (`migrate` fills it in)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kilojoules: Option<f64>,
+    /// Strava sport type; unset in entries written before other sports could be synced
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sport_type: Option<String>,
+    /// Strava's trainer and commute flags
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub trainer: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub commute: bool,
+    /// Ride type label from `classify` (endurance, tempo, vo2, race, recovery)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workout_type: Option<String>,
+    /// Derived: NP / average HR
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub efficiency_factor: Option<f64>,
+    /// Derived: Pw:HR decoupling between the two halves, in percent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decoupling: Option<f64>,
+    /// Derived: heart rate against power, `slope * watts + intercept` after lag alignment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heart_rate_fit: Option<HeartRateFit>,
+    /// Zwift world and route from `zwift::detect`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub world: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route: Option<String>,
+    /// Derived: cadence histogram, coasting and cadence per power band
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cadence: Option<CadenceProfile>,
+    /// Derived: time at 0 W and below half FTP, longest pedalling block
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pedalling: Option<PedallingStats>,
+    /// Derived: number and total kJ of the matches burned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub matches: Option<MatchSummary>,
+    /// Derived: best 5 s with its cadence and speed, best 15 s
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sprint: Option<Sprint>,
+    /// Derived: variability index and pacing score
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pacing: Option<PacingStats>,
+    /// Derived: average and NP of the estimated watts, for rides without a power meter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_power: Option<PowerEstimate>,
+    /// Derived: gain recomputed from the altitude stream, next to Strava's
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elevation: Option<ElevationCheck>,
+    /// Derived: why the ride's data looks impossible, from `anomalies`; empty when it looks fine
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub anomalies: Vec<String>,
+    /// Derived: values of the `custom_metrics` in watts.json the ride has the inputs for
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub custom_metrics: BTreeMap<String, f64>,
+    /// Local metadata, edited with `edit` and never overwritten by a sync
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+    /// Left out of stats, weekly series and training load
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub excluded: bool,
+    /// Another source's recording of the same ride, kept instead of this one (`duplicates`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<ActivityId>,
+}
+
+impl ActivitySummary {
+    /// Whether the ride counts in stats, weekly series and training load
+    pub fn counted(&self) -> bool {
+        !self.excluded && self.duplicate_of.is_none()
+    }
+
+    /// Flagged by `anomalies`: left out of records unless `include_suspect` is set
+    pub fn suspect(&self) -> bool {
+        !self.anomalies.is_empty()
+    }
+
+    /// Start in the athlete's local time, or UTC when the offset is unknown
+    pub fn local_start(&self) -> NaiveDateTime {
+        self.start_date.naive_utc() + Duration::seconds(self.utc_offset.unwrap_or(0) as i64)
+    }
+
+    /// Local calendar day of the start, what daily, weekly and monthly totals group by
+    pub fn local_date(&self) -> NaiveDate {
+        self.local_start().date()
+    }
+
+    /// Ridden on a trainer: flagged so, or a virtual ride. Entries without a
+    /// sport type predate other sports and are Zwift rides.
+    pub fn indoor(&self) -> bool {
+        self.trainer || self.sport_type.as_deref().is_none_or(|s| s.starts_with("Virtual"))
+    }
+
+    /// Whether this is a bike ride (Ride, VirtualRide, GravelRide, ...). Entries
+    /// without a sport type predate other sports and are Zwift rides.
+    pub fn is_ride(&self) -> bool {
+        self.sport_type.as_deref().is_none_or(|s| s.contains("Ride"))
+    }
+
+    /// Whether this is a run (Run, TrailRun, VirtualRun), scored by pace instead of power
+    pub fn is_run(&self) -> bool {
+        self.sport_type.as_deref().is_some_and(pace::is_run)
+    }
+}
+
+impl ActivityWithStreams {
+    pub fn new(activity: Activity, streams: Option<ActivityStreams>) -> Self {
+        ActivityWithStreams {
+            schema_version: schema::CURRENT_VERSION,
+            activity,
+            streams,
+            power_adjustment: None,
+            cleaning: None,
+            laps: None,
+            social: None,
+            matches: None,
+        }
+    }
+}
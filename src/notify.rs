@@ -0,0 +1,137 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::best_efforts;
+use crate::cli;
+use crate::config::Config;
+use crate::metrics;
+use crate::model::ActivityWithStreams;
+use crate::segments::SegmentChange;
+use crate::sync_report::SyncReport;
+
+const DEFAULT_TEMPLATE: &str = "🚴 {name}: {distance} km in {moving_time}, NP {np} W, TSS {tss}, suffer score {suffer_score}\n{url}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookKind {
+    Discord,
+    Slack,
+    Telegram,
+}
+
+/// A webhook posted to after each sync, configured under "notifications" in watts.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub kind: WebhookKind,
+    /// Webhook URL; for Telegram, `https://api.telegram.org/bot<token>/sendMessage`
+    pub url: String,
+    /// Telegram chat to post into
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_id: Option<String>,
+    /// Message with {name}, {distance}, {moving_time}, {np}, {tss}, {suffer_score}, {url} placeholders
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+}
+
+/// Post one message per newly synced activity to every configured webhook.
+/// Failures are logged but never fail the sync that triggered them.
+pub async fn new_activities(files: &[ActivityWithStreams], config: &Config) {
+    if files.is_empty() || config.notifications.is_empty() {
+        return;
+    }
+    let client = reqwest::Client::new();
+    for file in files {
+        for webhook in &config.notifications {
+            let message = render(webhook.template.as_deref().unwrap_or(DEFAULT_TEMPLATE), file, config.ftp());
+            post(&client, webhook, &message).await;
+        }
+    }
+    info!("🔔 Sent notifications for {} new activities", files.len());
+}
+
+/// Post one message listing the power bests a sync set, all-time first
+pub async fn new_bests(report: &SyncReport, files: &[ActivityWithStreams], config: &Config) {
+    if (report.records.is_empty() && report.season_records.is_empty()) || config.notifications.is_empty() {
+        return;
+    }
+    let mut lines = vec!["🏆 New power bests".to_string()];
+    for best in report.records.iter().chain(&report.season_records) {
+        let name = files.iter().find(|f| f.activity.id == best.id).map_or_else(|| best.id.to_string(), |f| f.activity.name.clone());
+        lines.push(format!(
+            "{} {}: {:.0} W{} on {}",
+            best.season.map_or_else(|| "all-time".to_string(), |year| format!("{} season", year)),
+            best_efforts::label(best.duration),
+            best.watts,
+            best.previous.map(|p| format!(" (was {:.0} W)", p)).unwrap_or_default(),
+            name
+        ));
+    }
+    let message = lines.join("\n");
+    let client = reqwest::Client::new();
+    for webhook in &config.notifications {
+        post(&client, webhook, &message).await;
+    }
+}
+
+/// Post one message listing the lifetime milestones a sync crossed
+pub async fn new_milestones(report: &SyncReport, config: &Config) {
+    if report.milestones.is_empty() || config.notifications.is_empty() {
+        return;
+    }
+    let mut lines = vec!["🎉 Milestones reached".to_string()];
+    lines.extend(report.milestones.iter().map(|m| m.message.clone()));
+    let message = lines.join("\n");
+    let client = reqwest::Client::new();
+    for webhook in &config.notifications {
+        post(&client, webhook, &message).await;
+    }
+}
+
+/// Post one message listing the starred segments whose PR or course record changed
+pub async fn segment_changes(changes: &[SegmentChange], config: &Config) {
+    if changes.is_empty() || config.notifications.is_empty() {
+        return;
+    }
+    let mut lines = vec!["🏔️ Starred segments".to_string()];
+    lines.extend(changes.iter().map(|c| c.message.clone()));
+    let message = lines.join("\n");
+    let client = reqwest::Client::new();
+    for webhook in &config.notifications {
+        post(&client, webhook, &message).await;
+    }
+}
+
+async fn post(client: &reqwest::Client, webhook: &Webhook, message: &str) {
+    let payload = match webhook.kind {
+        WebhookKind::Discord => json!({ "content": message }),
+        WebhookKind::Slack => json!({ "text": message }),
+        WebhookKind::Telegram => json!({ "chat_id": webhook.chat_id, "text": message }),
+    };
+    match client.post(&webhook.url).json(&payload).send().await {
+        Ok(response) if response.status().is_success() => {}
+        Ok(response) => warn!("   ⚠️  {:?} notification failed: {}", webhook.kind, response.status()),
+        Err(e) => warn!("   ⚠️  {:?} notification failed: {}", webhook.kind, e),
+    }
+}
+
+/// Fill an activity template's placeholders
+pub fn render(template: &str, file: &ActivityWithStreams, ftp: Option<f64>) -> String {
+    let activity = &file.activity;
+    let watts = file.streams.as_ref().and_then(|s| s.watts.as_deref());
+    let np = activity.weighted_average_watts.or_else(|| watts.and_then(metrics::normalized_power));
+    let tss = match (np, ftp) {
+        (Some(np), Some(ftp)) => Some(metrics::training_stress_score(np, activity.moving_time, ftp)),
+        _ => None,
+    };
+    let or_dash = |value: Option<f64>| value.map(|v| format!("{:.0}", v)).unwrap_or_else(|| "-".into());
+
+    template
+        .replace("{name}", &activity.name)
+        .replace("{distance}", &format!("{:.1}", activity.distance / 1000.0))
+        .replace("{moving_time}", &cli::hours_minutes(activity.moving_time))
+        .replace("{np}", &or_dash(np))
+        .replace("{tss}", &or_dash(tss))
+        .replace("{suffer_score}", &or_dash(activity.suffer_score))
+        .replace("{url}", &activity.id.strava_id().map(|id| format!("https://www.strava.com/activities/{}", id)).unwrap_or_default())
+}
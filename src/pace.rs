@@ -0,0 +1,99 @@
+//! Pace-based load for runs, which power-based TSS does not fit. Speed is
+//! grade-adjusted with Minetti's energy cost of running on a slope (GAP), the
+//! moving average of that is the normalized graded pace (NGP), and rTSS is
+//! `hours × (NGP / threshold pace)² × 100` with `threshold_pace` from watts.json.
+
+use crate::config::Config;
+use crate::model::ActivityStreams;
+
+/// Samples back over which the grade is measured, to smooth altitude noise
+const GRADE_WINDOW: usize = 10;
+
+/// Grades beyond this are clamped, the cost model is fitted up to ±45%
+const MAX_GRADE: f64 = 0.45;
+
+/// Below this speed (m/s) the athlete is standing, not running
+const MIN_MOVING_SPEED: f64 = 1.0;
+
+/// Energy cost of running on the flat, J/kg/m
+const FLAT_COST: f64 = 3.6;
+
+/// Whether a Strava sport type is a run (Run, TrailRun, VirtualRun)
+pub fn is_run(sport_type: &str) -> bool {
+    sport_type.contains("Run")
+}
+
+/// Minetti et al. (2002): energy cost of running at `grade`, in J/kg/m
+fn cost(grade: f64) -> f64 {
+    let g = grade.clamp(-MAX_GRADE, MAX_GRADE);
+    155.4 * g.powi(5) - 30.4 * g.powi(4) - 43.3 * g.powi(3) + 46.3 * g.powi(2) + 19.5 * g + FLAT_COST
+}
+
+/// Grade of every sample as a fraction, over the last `GRADE_WINDOW` samples.
+/// Without an altitude stream (treadmill, trainer) the grade is taken as zero.
+pub fn grades(streams: &ActivityStreams) -> Option<Vec<f64>> {
+    let speed = streams.velocity_smooth.as_deref()?;
+    let seconds = |i: usize| match streams.time.as_deref() {
+        Some(time) if i > 0 && i < time.len() => (time[i] - time[i - 1]).max(0) as f64,
+        _ => 1.0,
+    };
+    let mut distance = Vec::with_capacity(speed.len());
+    let mut total = 0.0;
+    for (i, &v) in speed.iter().enumerate() {
+        total += v * seconds(i);
+        distance.push(total);
+    }
+    let altitude = streams.altitude.as_deref().filter(|a| a.len() == speed.len());
+    Some(
+        (0..speed.len())
+            .map(|i| {
+                altitude
+                    .and_then(|altitude| {
+                        let from = i.saturating_sub(GRADE_WINDOW);
+                        let run = distance[i] - distance[from];
+                        (run > 1.0).then(|| (altitude[i] - altitude[from]) / run)
+                    })
+                    .unwrap_or(0.0)
+            })
+            .collect(),
+    )
+}
+
+/// Flat-ground equivalent speed of every sample, in m/s
+pub fn grade_adjusted_speed(streams: &ActivityStreams) -> Option<Vec<f64>> {
+    let speed = streams.velocity_smooth.as_deref()?;
+    Some(speed.iter().zip(grades(streams)?).map(|(&v, grade)| v * cost(grade) / FLAT_COST).collect())
+}
+
+/// Average grade-adjusted speed while moving, in m/s
+pub fn normalized_graded_speed(streams: &ActivityStreams) -> Option<f64> {
+    let speed = streams.velocity_smooth.as_deref()?;
+    let graded = grade_adjusted_speed(streams)?;
+    let moving: Vec<f64> = graded.iter().zip(speed).filter(|(_, &v)| v >= MIN_MOVING_SPEED).map(|(&g, _)| g).collect();
+    (!moving.is_empty()).then(|| moving.iter().sum::<f64>() / moving.len() as f64)
+}
+
+/// Threshold pace from watts.json as a speed in m/s
+pub fn threshold_speed(config: &Config) -> Option<f64> {
+    config.threshold_pace.filter(|&pace| pace > 0.0).map(|pace| 1000.0 / pace)
+}
+
+/// rTSS: like TSS with speed in place of power, an hour at threshold pace scoring 100
+pub fn running_stress_score(graded_speed: f64, seconds: i32, threshold_speed: f64) -> f64 {
+    let intensity = graded_speed / threshold_speed;
+    seconds as f64 * intensity * intensity / 3600.0 * 100.0
+}
+
+/// rTSS of a run, None without a velocity stream or threshold pace
+pub fn run_tss(streams: &ActivityStreams, moving_time: i32, config: &Config) -> Option<f64> {
+    Some(running_stress_score(normalized_graded_speed(streams)?, moving_time, threshold_speed(config)?))
+}
+
+/// A speed in m/s as a pace such as "4:35/km"
+pub fn format_pace(speed: f64) -> String {
+    if speed <= 0.0 {
+        return "-".to_string();
+    }
+    let seconds = (1000.0 / speed).round() as i64;
+    format!("{}:{:02}/km", seconds / 60, seconds % 60)
+}
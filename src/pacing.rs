@@ -0,0 +1,68 @@
+//! How evenly a ride was paced: the variability index (NP / average power,
+//! 1.00 for a perfectly steady effort) and a 0-100 score from how much the
+//! 30 s power wanders around its mean. Stored on the index entry; `report`
+//! lists the worst-paced rides and `list --export` writes both for analysis.
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::metrics;
+use crate::model::{ActivityIndex, ActivitySummary, ActivityWithStreams};
+use crate::pipeline::Analyzer;
+
+/// Power is averaged over this many samples before measuring its spread
+const SMOOTHING_SAMPLES: usize = 30;
+
+/// Rides shorter than this (in samples) have no meaningful pacing
+const MIN_SAMPLES: usize = 600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PacingStats {
+    /// Normalized power over average power
+    pub variability_index: f64,
+    /// 100 minus the coefficient of variation of 30 s power in percent, floored at 0
+    pub score: f64,
+}
+
+/// VI and pacing score on the index entry
+pub struct Pacing;
+
+impl Analyzer for Pacing {
+    fn name(&self) -> &'static str {
+        "pacing"
+    }
+
+    fn analyze(&self, file: &mut ActivityWithStreams, summary: &mut ActivitySummary, _config: &Config) {
+        summary.pacing = if summary.is_run() { None } else { pacing(file) };
+    }
+}
+
+/// Pacing of a ride, None without a long enough power stream
+pub fn pacing(file: &ActivityWithStreams) -> Option<PacingStats> {
+    let watts = file.streams.as_ref()?.watts.as_deref().filter(|w| w.len() >= MIN_SAMPLES)?;
+    let average = metrics::mean(watts).filter(|&a| a > 0.0)?;
+    let np = metrics::normalized_power(watts)?;
+
+    let smoothed: Vec<f64> = watts.windows(SMOOTHING_SAMPLES).map(|w| w.iter().sum::<f64>() / SMOOTHING_SAMPLES as f64).collect();
+    let mean = metrics::mean(&smoothed)?;
+    let variance = smoothed.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / smoothed.len() as f64;
+    let variation = variance.sqrt() / mean * 100.0;
+    Some(PacingStats {
+        variability_index: (np / average * 100.0).round() / 100.0,
+        score: (100.0 - variation).max(0.0).round(),
+    })
+}
+
+/// The `limit` rides since `since` with the lowest pacing score, worst first
+pub fn worst_paced(index: &ActivityIndex, since: NaiveDate, limit: usize) -> Vec<(&ActivitySummary, PacingStats)> {
+    let mut rides: Vec<(&ActivitySummary, PacingStats)> = index
+        .activities
+        .iter()
+        .filter(|a| a.counted() && a.local_date() >= since)
+        .filter_map(|a| Some((a, a.pacing?)))
+        .collect();
+    rides.sort_by(|a, b| a.1.score.total_cmp(&b.1.score).then(b.1.variability_index.total_cmp(&a.1.variability_index)));
+    rides.truncate(limit);
+    rides
+}
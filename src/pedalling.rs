@@ -0,0 +1,79 @@
+//! Where the watts stop: the share of a ride at 0 W, the share below half of
+//! FTP and the longest stretch of unbroken pedalling. In a Zwift race they
+//! tell a ride sat in the draft from one spent on the front. Stored on the
+//! index entry at sync, like the cadence profile.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::model::{ActivitySummary, ActivityWithStreams};
+use crate::pipeline::Analyzer;
+
+/// Zero-power gaps up to this long (a dropout, a shift) don't end a pedalling block
+const MAX_GAP_SECONDS: i32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PedallingStats {
+    /// Share of the ride at 0 W, in percent
+    pub zero_percent: f64,
+    /// Share of the ride below 50% FTP (0 W included), in percent; None without an FTP
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub below_half_ftp_percent: Option<f64>,
+    /// Longest block of pedalling in seconds
+    pub longest_block: i32,
+}
+
+/// Pedalling stats on the index entry
+pub struct Pedalling;
+
+impl Analyzer for Pedalling {
+    fn name(&self) -> &'static str {
+        "pedalling"
+    }
+
+    fn inputs(&self, config: &Config) -> Value {
+        json!(config.ftp())
+    }
+
+    fn analyze(&self, file: &mut ActivityWithStreams, summary: &mut ActivitySummary, config: &Config) {
+        // Running power has no freewheel
+        summary.pedalling = if summary.is_run() { None } else { stats(file, config.ftp()) };
+    }
+}
+
+/// Pedalling stats of a ride, None without a power stream
+pub fn stats(file: &ActivityWithStreams, ftp: Option<f64>) -> Option<PedallingStats> {
+    let streams = file.streams.as_ref()?;
+    let watts = streams.watts.as_deref().filter(|w| !w.is_empty())?;
+    // Seconds each sample stands for, the gap to the next timestamp when there is a time stream
+    let time = streams.time.as_deref().filter(|t| t.len() == watts.len());
+    let seconds = |i: usize| time.map_or(1, |t| t.get(i + 1).map_or(1, |next| (next - t[i]).max(0)));
+
+    let (mut total, mut zero, mut below_half) = (0, 0, 0);
+    let (mut block, mut gap, mut longest) = (0, 0, 0);
+    for (i, &power) in watts.iter().enumerate() {
+        let dt = seconds(i);
+        total += dt;
+        if ftp.is_some_and(|ftp| power < ftp / 2.0) {
+            below_half += dt;
+        }
+        if power > 0.0 {
+            block = if block > 0 && gap <= MAX_GAP_SECONDS { block + gap + dt } else { dt };
+            gap = 0;
+            longest = longest.max(block);
+        } else {
+            zero += dt;
+            gap += dt;
+        }
+    }
+    if total == 0 {
+        return None;
+    }
+    let percent = |part: i32| (part as f64 / total as f64 * 1000.0).round() / 10.0;
+    Some(PedallingStats {
+        zero_percent: percent(zero),
+        below_half_ftp_percent: ftp.map(|_| percent(below_half)),
+        longest_block: longest,
+    })
+}
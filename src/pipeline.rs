@@ -0,0 +1,85 @@
+//! The analysis run over every ride: sync and refetch pass each downloaded
+//! activity through it, `recompute` reruns it over the archive.
+//!
+//! Each step is an `Analyzer` living in its own module. A new metric is a
+//! struct implementing the trait plus one line in `ANALYZERS`; sync, refetch
+//! and recompute pick it up from there. Analyzers run in registry order, so
+//! one may read what an earlier one stored.
+
+use serde_json::Value;
+
+use crate::aerobic::Aerobic;
+use crate::anomalies::Anomalies;
+use crate::cadence::Cadence;
+use crate::classify::Classify;
+use crate::config::Config;
+use crate::custom_metrics::CustomMetrics;
+use crate::elevation::Elevation;
+use crate::matches::Matches;
+use crate::metrics::NormalizedPower;
+use crate::model::{ActivityIndex, ActivitySummary, ActivityWithStreams};
+use crate::pacing::Pacing;
+use crate::pedalling::Pedalling;
+use crate::power_estimate::EstimatedPower;
+use crate::sprints::Sprints;
+use crate::w_balance::WBalance;
+use crate::zwift::RouteDetection;
+
+pub trait Analyzer {
+    /// Short name, as `recompute --metric` takes it
+    fn name(&self) -> &'static str;
+
+    /// Settings read besides the ride; `recompute` reruns the analyzer when they change
+    fn inputs(&self, _config: &Config) -> Value {
+        Value::Null
+    }
+
+    /// Whether sync runs it on new rides; false for analyzers redoing what Strava already computed
+    fn on_ingest(&self) -> bool {
+        true
+    }
+
+    /// Derive metrics from the ride, storing series on the file and per-ride values on its index entry
+    fn analyze(&self, file: &mut ActivityWithStreams, summary: &mut ActivitySummary, config: &Config);
+}
+
+/// Every analyzer, in the order they run
+pub const ANALYZERS: &[&dyn Analyzer] = &[
+    &Anomalies,
+    &NormalizedPower,
+    &WBalance,
+    &Classify,
+    &Aerobic,
+    &Cadence,
+    &Pedalling,
+    &Matches,
+    &Sprints,
+    &Pacing,
+    &EstimatedPower,
+    &Elevation,
+    &RouteDetection,
+    &CustomMetrics,
+];
+
+/// The analyzer called `name`
+pub fn find(name: &str) -> Option<&'static dyn Analyzer> {
+    ANALYZERS.iter().copied().find(|a| a.name() == name)
+}
+
+/// Run the analyzers sync applies to a new ride, indexing it
+pub fn ingest(file: &mut ActivityWithStreams, index: &mut ActivityIndex, config: &Config) {
+    let analyzers: Vec<&dyn Analyzer> = ANALYZERS.iter().copied().filter(|a| a.on_ingest()).collect();
+    run(&analyzers, file, index, config);
+}
+
+/// Run `analyzers` over a ride, adding or refreshing its index entry
+pub fn run(analyzers: &[&dyn Analyzer], file: &mut ActivityWithStreams, index: &mut ActivityIndex, config: &Config) {
+    index.add_activity(&file.activity);
+    if let Some(summary) = index.get_mut(file.activity.id) {
+        for analyzer in analyzers {
+            analyzer.analyze(file, summary, config);
+        }
+    }
+    // Picks up summary fields an analyzer changed on the activity, keeping what they stored on the entry
+    index.add_activity(&file.activity);
+}
@@ -0,0 +1,515 @@
+//! Planned workouts imported from a training calendar (CSV or ICS) into
+//! `data/plan.json`, and how the rides compare with them day by day and week
+//! by week: missed days, and days that overshot or fell short of the planned
+//! TSS (or duration, when the plan has no TSS). `report` shows the weekly
+//! comparison.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::cli::{self, OutputFormat};
+use crate::config::Config;
+use crate::escape;
+use crate::format;
+use crate::model::ActivityIndex;
+use crate::stats::GroupBy;
+use crate::storage::write_atomic;
+use crate::training_load::{self, DailyLoad};
+
+/// Actual load within this fraction of the plan counts as done
+const TOLERANCE: f64 = 0.2;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedWorkout {
+    pub date: NaiveDate,
+    pub name: String,
+    /// Seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tss: Option<f64>,
+}
+
+/// data/plan.json
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Plan {
+    pub schema_version: u32,
+    pub updated_at: String,
+    /// Oldest first
+    pub workouts: Vec<PlannedWorkout>,
+}
+
+impl Plan {
+    /// The stored plan, empty when none was imported
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        match fs::read_to_string(format::plan_path()) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Plan::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        self.schema_version = format::SCHEMA_VERSION;
+        self.updated_at = Utc::now().to_rfc3339();
+        write_atomic(&format::plan_path(), serde_json::to_string_pretty(self)?.as_bytes())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// Within `TOLERANCE` of the plan
+    Done,
+    Missed,
+    /// Above the plan by more than `TOLERANCE`
+    Over,
+    /// Ridden, but below the plan by more than `TOLERANCE`
+    Under,
+    /// Ridden on a day without a planned workout
+    Unplanned,
+    /// Planned for today or later, not ridden yet
+    Upcoming,
+}
+
+impl Status {
+    fn label(self) -> &'static str {
+        match self {
+            Status::Done => "done",
+            Status::Missed => "MISSED",
+            Status::Over => "OVER",
+            Status::Under => "under",
+            Status::Unplanned => "unplanned",
+            Status::Upcoming => "upcoming",
+        }
+    }
+}
+
+/// Planned against actual load of one day
+#[derive(Debug, Clone, Serialize)]
+pub struct Day {
+    pub date: NaiveDate,
+    /// Names of the planned workouts
+    pub planned: Vec<String>,
+    pub planned_tss: Option<f64>,
+    pub planned_duration: Option<i32>,
+    pub rides: usize,
+    pub tss: f64,
+    pub moving_time: i32,
+    pub status: Status,
+}
+
+/// Planned against actual load of one week (Monday first)
+#[derive(Debug, Clone, Serialize)]
+pub struct Week {
+    pub week: NaiveDate,
+    pub planned_tss: Option<f64>,
+    pub tss: f64,
+    pub planned_duration: Option<i32>,
+    pub moving_time: i32,
+    /// Actual over planned TSS (or duration), in percent
+    pub compliance: Option<f64>,
+    pub missed: usize,
+    pub over: usize,
+    pub under: usize,
+}
+
+/// Read a CSV or ICS calendar, by extension
+pub fn parse_file(path: &Path) -> Result<Vec<PlannedWorkout>, Box<dyn Error>> {
+    let text = fs::read_to_string(path).map_err(|e| format!("could not read {}: {}", path.display(), e))?;
+    match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+        Some("csv") => parse_csv(&text),
+        Some("ics") => parse_ics(&text),
+        _ => Err(format!("{}: expected a .csv or .ics file", path.display()).into()),
+    }
+}
+
+/// A header row naming a `date` column (YYYY-MM-DD) and any of `name`
+/// (or `title`, `workout`), `duration` (minutes or h:mm[:ss]) and `tss`
+fn parse_csv(text: &str) -> Result<Vec<PlannedWorkout>, Box<dyn Error>> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+    let header: Vec<String> = split_csv_line(lines.next().ok_or("empty CSV file")?).iter().map(|h| h.to_ascii_lowercase()).collect();
+    let column = |names: &[&str]| header.iter().position(|h| names.contains(&h.as_str()));
+    let date = column(&["date", "day"]).ok_or("CSV has no date column")?;
+    let name = column(&["name", "title", "workout"]);
+    let duration = column(&["duration", "planned_duration"]);
+    let tss = column(&["tss", "planned_tss"]);
+
+    let mut workouts = Vec::new();
+    for (n, line) in lines.enumerate() {
+        let fields = split_csv_line(line);
+        let field = |i: Option<usize>| i.and_then(|i| fields.get(i)).map(|f| f.trim()).filter(|f| !f.is_empty());
+        let row = n + 2;
+        let day = field(Some(date)).ok_or_else(|| format!("line {}: missing date", row))?;
+        workouts.push(PlannedWorkout {
+            date: NaiveDate::parse_from_str(day, "%Y-%m-%d").map_err(|_| format!("line {}: invalid date '{}'", row, day))?,
+            name: field(name).unwrap_or("workout").to_string(),
+            duration: field(duration)
+                .map(|d| parse_duration(d).ok_or_else(|| format!("line {}: invalid duration '{}'", row, d)))
+                .transpose()?,
+            tss: field(tss).map(|t| t.parse().map_err(|_| format!("line {}: invalid TSS '{}'", row, t))).transpose()?,
+        });
+    }
+    Ok(workouts)
+}
+
+/// Comma-separated fields, double quotes around fields holding commas
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
+
+/// Minutes ("90"), h:mm ("1:30") or h:mm:ss, in seconds
+fn parse_duration(s: &str) -> Option<i32> {
+    let parts: Vec<i32> = s.split(':').map(|p| p.trim().parse().ok()).collect::<Option<_>>()?;
+    match parts.as_slice() {
+        [minutes] => Some(minutes * 60),
+        [hours, minutes] => Some(hours * 3600 + minutes * 60),
+        [hours, minutes, seconds] => Some(hours * 3600 + minutes * 60 + seconds),
+        _ => None,
+    }
+}
+
+/// VEVENTs of an iCalendar file: the date from DTSTART, the name from
+/// SUMMARY, the duration from DURATION or DTEND, and TSS from "TSS: 65" in
+/// the summary or description
+fn parse_ics(text: &str) -> Result<Vec<PlannedWorkout>, Box<dyn Error>> {
+    // Lines starting with a space or tab continue the previous one
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.trim_end_matches('\r').to_string()),
+        }
+    }
+
+    let mut workouts = Vec::new();
+    let mut event: Option<BTreeMap<String, String>> = None;
+    for line in &lines {
+        match line.as_str() {
+            "BEGIN:VEVENT" => event = Some(BTreeMap::new()),
+            "END:VEVENT" => {
+                let Some(fields) = event.take() else { continue };
+                let start = fields.get("DTSTART").ok_or("VEVENT without DTSTART")?;
+                let (date, start_time) = parse_ics_date(start).ok_or_else(|| format!("invalid DTSTART '{}'", start))?;
+                let summary = fields.get("SUMMARY").map(|s| escape::ics_unescape(s)).unwrap_or_else(|| "workout".to_string());
+                let description = fields.get("DESCRIPTION").map(|d| escape::ics_unescape(d)).unwrap_or_default();
+                let end_time = fields.get("DTEND").and_then(|e| parse_ics_date(e)).and_then(|(_, time)| time);
+                let duration = fields
+                    .get("DURATION")
+                    .and_then(|d| parse_ics_duration(d))
+                    .or_else(|| Some((end_time? - start_time?).num_seconds() as i32).filter(|s| *s > 0));
+                workouts.push(PlannedWorkout {
+                    date,
+                    tss: find_tss(&summary).or_else(|| find_tss(&description)),
+                    name: summary,
+                    duration,
+                });
+            }
+            _ => {
+                if let (Some(fields), Some((key, value))) = (event.as_mut(), line.split_once(':')) {
+                    // DTSTART;VALUE=DATE:20250105 -> DTSTART
+                    let name = key.split(';').next().unwrap_or(key).to_ascii_uppercase();
+                    fields.insert(name, value.to_string());
+                }
+            }
+        }
+    }
+    Ok(workouts)
+}
+
+/// `20250105` or `20250105T063000[Z]`: the day, and the time when given
+fn parse_ics_date(s: &str) -> Option<(NaiveDate, Option<NaiveDateTime>)> {
+    let s = s.trim().trim_end_matches('Z');
+    match s.len() {
+        8 => Some((NaiveDate::parse_from_str(s, "%Y%m%d").ok()?, None)),
+        _ => {
+            let time = NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%S").ok()?;
+            Some((time.date(), Some(time)))
+        }
+    }
+}
+
+/// `PT1H30M`, `PT45M`, `P1D`... in seconds
+fn parse_ics_duration(s: &str) -> Option<i32> {
+    let s = s.trim().strip_prefix('P')?;
+    let mut seconds = 0;
+    let mut number = String::new();
+    for c in s.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            unit => {
+                let n: i32 = number.parse().ok()?;
+                number.clear();
+                seconds += n * match unit {
+                    'W' => 7 * 86400,
+                    'D' => 86400,
+                    'H' => 3600,
+                    'M' => 60,
+                    'S' => 1,
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(seconds)
+}
+
+/// The number after "TSS" in "TSS: 65", "tss=65" or "65 TSS"
+fn find_tss(text: &str) -> Option<f64> {
+    let upper = text.to_ascii_uppercase();
+    let at = upper.find("TSS")?;
+    let after: String = upper[at + 3..]
+        .trim_start_matches(|c: char| c == ':' || c == '=' || c.is_whitespace())
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == '.')
+        .collect();
+    if let Ok(tss) = after.parse() {
+        return Some(tss);
+    }
+    let before: String = upper[..at].trim_end().chars().rev().take_while(|c| c.is_ascii_digit() || *c == '.').collect();
+    before.chars().rev().collect::<String>().parse().ok()
+}
+
+/// Import a calendar into data/plan.json, replacing the workouts planned on
+/// the days it covers
+pub fn import(path: &Path) -> Result<(), Box<dyn Error>> {
+    let imported = parse_file(path)?;
+    if imported.is_empty() {
+        return Err(format!("no workouts in {}", path.display()).into());
+    }
+    let mut plan = Plan::load()?;
+    let replaced = plan.workouts.iter().filter(|w| imported.iter().any(|i| i.date == w.date)).count();
+    plan.workouts.retain(|w| !imported.iter().any(|i| i.date == w.date));
+    let (first, last) = (imported.iter().map(|w| w.date).min(), imported.iter().map(|w| w.date).max());
+    let count = imported.len();
+    plan.workouts.extend(imported);
+    plan.workouts.sort_by_key(|w| w.date);
+    plan.save()?;
+    info!("📅 Imported {} planned workouts from {}", count, path.display());
+    if let (Some(first), Some(last)) = (first, last) {
+        info!("   {} to {}, {} previously planned workouts replaced", first, last, replaced);
+    }
+    Ok(())
+}
+
+/// Each day with a planned workout or a ride since `since` (the first planned
+/// day by default) up to the last planned day, using the PMC's daily loads
+pub fn days(plan: &Plan, index: &ActivityIndex, loads: &[DailyLoad], since: Option<NaiveDate>) -> Vec<Day> {
+    let Some(first) = since.or_else(|| plan.workouts.first().map(|w| w.date)) else { return Vec::new() };
+    let today = Utc::now().date_naive();
+    let last = plan.workouts.last().map_or(today, |w| w.date.max(today));
+
+    let mut days: BTreeMap<NaiveDate, Day> = BTreeMap::new();
+    for workout in plan.workouts.iter().filter(|w| w.date >= first && w.date <= last) {
+        let entry = day(&mut days, workout.date);
+        entry.planned.push(workout.name.clone());
+        if let Some(tss) = workout.tss {
+            *entry.planned_tss.get_or_insert(0.0) += tss;
+        }
+        if let Some(duration) = workout.duration {
+            *entry.planned_duration.get_or_insert(0) += duration;
+        }
+    }
+    for summary in index.activities.iter().filter(|a| a.counted()) {
+        let date = summary.local_date();
+        if date >= first && date <= last {
+            let entry = day(&mut days, date);
+            entry.rides += 1;
+            entry.moving_time += summary.moving_time;
+        }
+    }
+    for load in loads.iter().filter(|l| l.tss > 0.0) {
+        if let Some(entry) = days.get_mut(&load.date) {
+            entry.tss = load.tss.round();
+        }
+    }
+
+    let mut days: Vec<Day> = days.into_values().collect();
+    for day in &mut days {
+        day.status = status(day, today);
+    }
+    days
+}
+
+fn day(days: &mut BTreeMap<NaiveDate, Day>, date: NaiveDate) -> &mut Day {
+    days.entry(date).or_insert_with(|| Day {
+        date,
+        planned: Vec::new(),
+        planned_tss: None,
+        planned_duration: None,
+        rides: 0,
+        tss: 0.0,
+        moving_time: 0,
+        status: Status::Unplanned,
+    })
+}
+
+fn status(day: &Day, today: NaiveDate) -> Status {
+    if day.planned.is_empty() {
+        return Status::Unplanned;
+    }
+    if day.rides == 0 {
+        return if day.date >= today { Status::Upcoming } else { Status::Missed };
+    }
+    let ratio = match (day.planned_tss, day.planned_duration) {
+        (Some(tss), _) if tss > 0.0 && day.tss > 0.0 => day.tss / tss,
+        (_, Some(duration)) if duration > 0 => day.moving_time as f64 / duration as f64,
+        _ => return Status::Done,
+    };
+    if ratio > 1.0 + TOLERANCE {
+        Status::Over
+    } else if ratio < 1.0 - TOLERANCE {
+        Status::Under
+    } else {
+        Status::Done
+    }
+}
+
+/// The days grouped by week, up to the current one
+pub fn weeks(days: &[Day]) -> Vec<Week> {
+    let this_week = GroupBy::Week.start(Utc::now().date_naive(), Utc::now().date_naive());
+    let mut weeks: BTreeMap<NaiveDate, Week> = BTreeMap::new();
+    for day in days {
+        let monday = GroupBy::Week.start(day.date, day.date);
+        if monday > this_week {
+            continue;
+        }
+        let week = weeks.entry(monday).or_insert_with(|| Week {
+            week: monday,
+            planned_tss: None,
+            tss: 0.0,
+            planned_duration: None,
+            moving_time: 0,
+            compliance: None,
+            missed: 0,
+            over: 0,
+            under: 0,
+        });
+        if let Some(tss) = day.planned_tss {
+            *week.planned_tss.get_or_insert(0.0) += tss;
+        }
+        if let Some(duration) = day.planned_duration {
+            *week.planned_duration.get_or_insert(0) += duration;
+        }
+        week.tss += day.tss;
+        week.moving_time += day.moving_time;
+        match day.status {
+            Status::Missed => week.missed += 1,
+            Status::Over => week.over += 1,
+            Status::Under => week.under += 1,
+            _ => {}
+        }
+    }
+    let mut weeks: Vec<Week> = weeks.into_values().collect();
+    for week in &mut weeks {
+        week.compliance = match (week.planned_tss, week.planned_duration) {
+            (Some(tss), _) if tss > 0.0 => Some((week.tss / tss * 100.0).round()),
+            (_, Some(duration)) if duration > 0 => Some((week.moving_time as f64 / duration as f64 * 100.0).round()),
+            _ => None,
+        };
+    }
+    weeks
+}
+
+/// `1:05`
+fn hours_minutes(seconds: i32) -> String {
+    format!("{}:{:02}", seconds / 3600, (seconds % 3600) / 60)
+}
+
+fn optional<T: std::fmt::Display>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".to_string(), |v| v.to_string())
+}
+
+/// Print the weekly comparison lines, as `plan --group-by week` and `report` show them
+pub fn print_weeks(weeks: &[Week]) {
+    println!("  {:<10} {:>9} {:>11} {:>6} {:>6} {:>5} {:>5}", "week", "TSS plan", "TSS actual", "done", "missed", "over", "under");
+    for week in weeks {
+        println!(
+            "  {:<10} {:>9} {:>11.0} {:>6} {:>6} {:>5} {:>5}",
+            week.week.to_string(),
+            optional(week.planned_tss.map(|t| t.round())),
+            week.tss,
+            optional(week.compliance.map(|c| format!("{:.0}%", c))),
+            week.missed,
+            week.over,
+            week.under
+        );
+    }
+}
+
+/// Compare the stored plan with the rides since `since`, by day or by week
+pub fn run(since: Option<NaiveDate>, by_week: bool, output: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let plan = Plan::load()?;
+    if plan.workouts.is_empty() {
+        return Err("no training plan yet, import one with `plan import <file.csv|file.ics>`".into());
+    }
+    let index = ActivityIndex::load()?;
+    let config = Config::current()?;
+    let days = days(&plan, &index, &training_load::daily(&index, &config), since);
+
+    if by_week {
+        let weeks = weeks(&days);
+        if output == OutputFormat::Json {
+            return cli::print_json(&weeks);
+        }
+        info!("📅 Plan vs actual by week ({} weeks)", weeks.len());
+        print_weeks(&weeks);
+        return Ok(());
+    }
+    if output == OutputFormat::Json {
+        return cli::print_json(&days);
+    }
+
+    let count = |status: Status| days.iter().filter(|d| d.status == status).count();
+    info!(
+        "📅 Plan vs actual: {} planned days, {} done, {} missed, {} over, {} under, {} unplanned",
+        days.iter().filter(|d| !d.planned.is_empty()).count(),
+        count(Status::Done),
+        count(Status::Missed),
+        count(Status::Over),
+        count(Status::Under),
+        count(Status::Unplanned)
+    );
+    println!("  {:<10} {:<28} {:>9} {:>11} {:>11}  status", "date", "planned", "TSS plan", "TSS actual", "time");
+    for day in &days {
+        let mut name = day.planned.join(" + ");
+        if name.chars().count() > 28 {
+            name = name.chars().take(27).collect::<String>() + "…";
+        }
+        let time = match day.planned_duration {
+            Some(planned) => format!("{}/{}", hours_minutes(day.moving_time), hours_minutes(planned)),
+            None => hours_minutes(day.moving_time),
+        };
+        println!(
+            "  {:<10} {:<28} {:>9} {:>11.0} {:>11}  {}",
+            day.date.to_string(),
+            name,
+            optional(day.planned_tss.map(|t| t.round())),
+            day.tss,
+            time,
+            day.status.label()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,277 @@
+//! Routes planned on Strava: the list in data/routes/routes.json and each
+//! track as data/routes/{id}.gpx, fetched again only when Strava says the
+//! route changed. `export` copies a track out for a head unit, `compare`
+//! checks how closely a ride followed one.
+
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::cli::{self, OutputFormat};
+use crate::format;
+use crate::storage::{load_activity_file, write_atomic};
+use crate::strava::{self, access_token};
+use crate::zwift;
+
+/// Routes per page
+const PER_PAGE: u32 = 200;
+
+/// How far from the other track a point may be and still count as on it, metres
+const ON_ROUTE_METRES: f64 = 50.0;
+
+/// Track points compared, evenly picked; plenty to tell a detour
+const COMPARE_POINTS: usize = 2000;
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RouteLibrary {
+    pub schema_version: u32,
+    pub updated_at: String,
+    pub routes: Vec<PlannedRoute>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlannedRoute {
+    pub id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// "ride" or "run"
+    pub sport: String,
+    /// Metres
+    pub distance: f64,
+    /// Metres
+    pub elevation_gain: f64,
+    /// Strava's estimate, seconds
+    pub estimated_moving_time: Option<i32>,
+    pub starred: bool,
+    /// As Strava reports it; a change fetches the GPX again
+    pub updated_at: Option<String>,
+    /// Whether data/routes/{id}.gpx is stored
+    pub gpx: bool,
+}
+
+/// How a ride followed a route
+#[derive(Debug, Serialize)]
+pub struct RouteComparison {
+    pub route: String,
+    pub activity: ActivityId,
+    /// Share of the route within `ON_ROUTE_METRES` of the ride, percent
+    pub covered_percent: f64,
+    /// Share of the ride farther than that from the route, percent
+    pub off_route_percent: f64,
+    /// Farthest the ride went from the route, metres
+    pub max_deviation: f64,
+    /// Metres
+    pub planned_distance: f64,
+    pub ridden_distance: f64,
+}
+
+pub fn load() -> Result<RouteLibrary, Box<dyn Error>> {
+    match fs::read_to_string(format::routes_path()) {
+        Ok(json) => Ok(serde_json::from_str(&json)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(RouteLibrary::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn gpx_path(id: &str) -> PathBuf {
+    format::data_dir().join(format::ROUTES_DIR).join(format!("{}.gpx", id))
+}
+
+/// Read the route list and fetch the GPX of new and changed routes, within
+/// the rate-limit budget; routes deleted on Strava are dropped with their track
+pub async fn fetch(access_token: &str) -> Result<(), Box<dyn Error>> {
+    let mut library = load()?;
+    let athlete = strava::fetch_athlete(access_token).await?;
+    let mut fetched = Vec::new();
+    for page in 1.. {
+        let batch = strava::fetch_routes(access_token, athlete.id, page, PER_PAGE).await?;
+        let full = batch.len() == PER_PAGE as usize;
+        fetched.extend(batch);
+        if !full {
+            break;
+        }
+    }
+
+    fs::create_dir_all(format::data_dir().join(format::ROUTES_DIR))?;
+    for gone in library.routes.iter().filter(|r| !fetched.iter().any(|f| f.id_str == r.id)) {
+        info!("   🗑️  {} was deleted on Strava", gone.name);
+        match fs::remove_file(gpx_path(&gone.id)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    let routes: Vec<PlannedRoute> = fetched
+        .into_iter()
+        .map(|r| {
+            let previous = library.routes.iter().find(|p| p.id == r.id_str);
+            let unchanged = previous.is_some_and(|p| p.gpx && p.updated_at == r.updated_at && gpx_path(&p.id).exists());
+            PlannedRoute {
+                gpx: unchanged,
+                id: r.id_str,
+                name: r.name,
+                description: r.description.filter(|d| !d.trim().is_empty()),
+                sport: if r.route_type == 2 { "run" } else { "ride" }.to_string(),
+                distance: r.distance,
+                elevation_gain: r.elevation_gain,
+                estimated_moving_time: r.estimated_moving_time,
+                starred: r.starred,
+                updated_at: r.updated_at,
+            }
+        })
+        .collect();
+    library.routes = routes;
+
+    let stale: Vec<usize> = (0..library.routes.len()).filter(|&i| !library.routes[i].gpx).collect();
+    let batch = strava::within_budget(stale.len());
+    for &i in stale.iter().take(batch) {
+        let route = &mut library.routes[i];
+        match strava::fetch_route_gpx(access_token, &route.id).await {
+            Ok(gpx) => {
+                write_atomic(&gpx_path(&route.id), gpx.as_bytes())?;
+                route.gpx = true;
+            }
+            Err(e) => warn!("   ⚠️  Could not fetch the GPX of {} ({}): {}", route.id, route.name, e),
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    }
+
+    library.schema_version = format::SCHEMA_VERSION;
+    library.updated_at = Utc::now().to_rfc3339();
+    write_atomic(&format::routes_path(), serde_json::to_string_pretty(&library)?.as_bytes())?;
+    info!("🗺️  {} routes, {} tracks fetched", library.routes.len(), batch);
+    Ok(())
+}
+
+/// Print the stored routes, fetching them first with `fetch_first`
+pub async fn run(fetch_first: bool, output: OutputFormat) -> Result<(), Box<dyn Error>> {
+    if fetch_first {
+        fetch(&access_token().await?).await?;
+    }
+    let library = load()?;
+    if output == OutputFormat::Json {
+        return cli::print_json(&library.routes);
+    }
+    if library.routes.is_empty() {
+        info!("🗺️  No routes stored, run `planned-routes --fetch`");
+        return Ok(());
+    }
+    info!("🗺️  {} routes planned on Strava", library.routes.len());
+    println!("  {:<20} {:<30} {:<5} {:>7} {:>6} {:>6}  GPX", "id", "name", "sport", "km", "m up", "est.");
+    for route in &library.routes {
+        println!(
+            "  {:<20} {:<30} {:<5} {:>7.1} {:>6.0} {:>6}  {}",
+            route.id,
+            format!("{}{}", if route.starred { "★ " } else { "" }, route.name).chars().take(30).collect::<String>(),
+            route.sport,
+            route.distance / 1000.0,
+            route.elevation_gain,
+            route.estimated_moving_time.map_or_else(|| "-".to_string(), cli::hours_minutes),
+            if route.gpx { "yes" } else { "-" }
+        );
+    }
+    Ok(())
+}
+
+/// A stored route, by ID or by its name when unambiguous
+fn find<'a>(library: &'a RouteLibrary, key: &str) -> Result<&'a PlannedRoute, Box<dyn Error>> {
+    if let Some(route) = library.routes.iter().find(|r| r.id == key) {
+        return Ok(route);
+    }
+    let named: Vec<&PlannedRoute> = library.routes.iter().filter(|r| r.name.to_lowercase().contains(&key.to_lowercase())).collect();
+    match named.as_slice() {
+        [route] => Ok(route),
+        [] => Err(format!("no stored route '{}', run `planned-routes --fetch`", key).into()),
+        _ => Err(format!("'{}' matches {} routes, use the route ID", key, named.len()).into()),
+    }
+}
+
+fn read_gpx(route: &PlannedRoute) -> Result<String, Box<dyn Error>> {
+    fs::read_to_string(gpx_path(&route.id)).map_err(|e| format!("no GPX stored for {} ({}): {}", route.name, route.id, e).into())
+}
+
+/// Copy a route's GPX to `output`, or `<name>.gpx` in the current directory
+pub fn export(key: &str, output: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let library = load()?;
+    let route = find(&library, key)?;
+    let gpx = read_gpx(route)?;
+    let path = output.map(Path::to_path_buf).unwrap_or_else(|| {
+        let name: String = route.name.chars().map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' }).collect();
+        PathBuf::from(format!("{}.gpx", name))
+    });
+    write_atomic(&path, gpx.as_bytes())?;
+    info!("📤 {} ({:.1} km) written to {}", route.name, route.distance / 1000.0, path.display());
+    Ok(())
+}
+
+/// Latitude and longitude of each track or route point of a GPX document
+fn gpx_points(gpx: &str) -> Vec<[f64; 2]> {
+    let attribute = |tag: &str, name: &str| -> Option<f64> {
+        let start = tag.find(&format!(" {}=", name))? + name.len() + 2;
+        let quote = tag[start..].chars().next()?;
+        let value = &tag[start + 1..];
+        value[..value.find(quote)?].trim().parse().ok()
+    };
+    gpx.split('<')
+        .filter(|tag| tag.starts_with("trkpt ") || tag.starts_with("rtept "))
+        .filter_map(|tag| {
+            let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+            Some([attribute(tag, "lat")?, attribute(tag, "lon")?])
+        })
+        .collect()
+}
+
+/// At most `COMPARE_POINTS` of `points`, evenly spaced
+fn thin(points: &[[f64; 2]]) -> Vec<[f64; 2]> {
+    let step = points.len().div_ceil(COMPARE_POINTS).max(1);
+    points.iter().step_by(step).copied().collect()
+}
+
+/// Metres from `point` to the nearest of `track`
+fn nearest(point: [f64; 2], track: &[[f64; 2]]) -> f64 {
+    track.iter().map(|p| zwift::distance_km(point[0], point[1], p[0], p[1]) * 1000.0).fold(f64::INFINITY, f64::min)
+}
+
+/// Length of a track, metres
+fn length(track: &[[f64; 2]]) -> f64 {
+    track.windows(2).map(|w| zwift::distance_km(w[0][0], w[0][1], w[1][0], w[1][1]) * 1000.0).sum()
+}
+
+/// How closely a stored ride followed a route
+pub fn compare(key: &str, id: ActivityId, output: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let library = load()?;
+    let route = find(&library, key)?;
+    let planned = gpx_points(&read_gpx(route)?);
+    if planned.len() < 2 {
+        return Err(format!("the GPX of {} has no track points", route.name).into());
+    }
+    let file = load_activity_file(id)?;
+    let ridden = file.streams.as_ref().and_then(|s| s.latlng.clone()).filter(|l| l.len() >= 2).ok_or_else(|| format!("activity {} has no GPS track", id))?;
+
+    let (planned_thin, ridden_thin) = (thin(&planned), thin(&ridden));
+    let share = |from: &[[f64; 2]], to: &[[f64; 2]], on: bool| {
+        from.iter().filter(|&&p| (nearest(p, to) <= ON_ROUTE_METRES) == on).count() as f64 / from.len() as f64 * 100.0
+    };
+    let comparison = RouteComparison {
+        route: route.name.clone(),
+        activity: id,
+        covered_percent: (share(&planned_thin, &ridden_thin, true) * 10.0).round() / 10.0,
+        off_route_percent: (share(&ridden_thin, &planned_thin, false) * 10.0).round() / 10.0,
+        max_deviation: ridden_thin.iter().map(|&p| nearest(p, &planned_thin)).fold(0.0, f64::max).round(),
+        planned_distance: if route.distance > 0.0 { route.distance } else { length(&planned) }.round(),
+        ridden_distance: file.activity.distance.round(),
+    };
+    if output == OutputFormat::Json {
+        return cli::print_json(&comparison);
+    }
+    info!("🗺️  {} against {} ({})", file.activity.name, route.name, id);
+    println!("  Covered:    {:.1}% of the route within {:.0} m of the ride", comparison.covered_percent, ON_ROUTE_METRES);
+    println!("  Off route:  {:.1}% of the ride, at most {:.0} m away", comparison.off_route_percent, comparison.max_deviation);
+    println!("  Distance:   {:.1} km planned, {:.1} km ridden", comparison.planned_distance / 1000.0, comparison.ridden_distance / 1000.0);
+    Ok(())
+}
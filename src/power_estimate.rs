@@ -0,0 +1,133 @@
+//! Power for outdoor rides without a power meter, estimated from speed and
+//! grade with the usual road model: rolling resistance, gravity, air drag and
+//! acceleration over rider and bike mass. Stored as `estimated_watts` next to
+//! the recorded streams, never in `watts`, so records, curves and TSS keep
+//! counting measured power only while HR-only rides still get a power trace.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::metrics;
+use crate::model::{ActivityStreams, ActivitySummary, ActivityWithStreams};
+use crate::pace;
+use crate::pipeline::Analyzer;
+
+/// m/s²
+const GRAVITY: f64 = 9.81;
+
+/// kg/m³, sea level at 15 °C
+const AIR_DENSITY: f64 = 1.225;
+
+/// Share of the rider's power reaching the wheel
+const DRIVETRAIN_EFFICIENCY: f64 = 0.976;
+
+/// Grades beyond this are altitude noise
+const MAX_GRADE: f64 = 0.25;
+
+/// Below this speed (m/s) the bike is stopped
+const MIN_MOVING_SPEED: f64 = 1.0;
+
+/// Rider, bike and position, under `"power_model"` in watts.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PowerModel {
+    /// kg
+    #[serde(default = "default_rider_mass")]
+    pub rider_mass: f64,
+    /// kg, with bottles and bags
+    #[serde(default = "default_bike_mass")]
+    pub bike_mass: f64,
+    /// Drag area in m², about 0.25 in the drops and 0.40 sitting up
+    #[serde(default = "default_cda")]
+    pub cda: f64,
+    /// Rolling resistance coefficient, about 0.004 on good tarmac and 0.008 on gravel
+    #[serde(default = "default_crr")]
+    pub crr: f64,
+}
+
+fn default_rider_mass() -> f64 {
+    75.0
+}
+
+fn default_bike_mass() -> f64 {
+    9.0
+}
+
+fn default_cda() -> f64 {
+    0.32
+}
+
+fn default_crr() -> f64 {
+    0.005
+}
+
+impl Default for PowerModel {
+    fn default() -> Self {
+        PowerModel { rider_mass: default_rider_mass(), bike_mass: default_bike_mass(), cda: default_cda(), crr: default_crr() }
+    }
+}
+
+/// Average and normalized estimated power, on the index entry
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PowerEstimate {
+    pub average_watts: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalized_power: Option<f64>,
+}
+
+/// Estimated watts stream on the activity file, its average and NP on the index entry
+pub struct EstimatedPower;
+
+impl Analyzer for EstimatedPower {
+    fn name(&self) -> &'static str {
+        "estimated_power"
+    }
+
+    fn inputs(&self, config: &Config) -> Value {
+        json!(config.power_model)
+    }
+
+    fn analyze(&self, file: &mut ActivityWithStreams, summary: &mut ActivitySummary, config: &Config) {
+        // Indoors the speed comes from the trainer or the game, not from the road
+        let unmeasured = file.activity.device_watts == Some(false) || file.streams.as_ref().is_none_or(|s| s.watts.is_none());
+        let wanted = unmeasured && !summary.is_run() && !summary.indoor();
+        let model = config.power_model.clone().unwrap_or_default();
+        let Some(streams) = file.streams.as_mut() else { return };
+        streams.estimated_watts = if wanted { estimate(streams, &model) } else { None };
+        summary.estimated_power = streams.estimated_watts.as_deref().and_then(|watts| {
+            Some(PowerEstimate {
+                average_watts: metrics::mean(watts)?.round(),
+                normalized_power: metrics::normalized_power(watts).map(f64::round),
+            })
+        });
+    }
+}
+
+/// Watts the rider must have pushed at every sample, None without a speed stream
+pub fn estimate(streams: &ActivityStreams, model: &PowerModel) -> Option<Vec<f64>> {
+    let speed = streams.velocity_smooth.as_deref().filter(|v| !v.is_empty())?;
+    let grades = pace::grades(streams)?;
+    let mass = model.rider_mass + model.bike_mass;
+    let seconds = |i: usize| match streams.time.as_deref() {
+        Some(time) if i > 0 && i < time.len() => (time[i] - time[i - 1]).max(1) as f64,
+        _ => 1.0,
+    };
+    Some(
+        speed
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| {
+                if v < MIN_MOVING_SPEED {
+                    return 0.0;
+                }
+                let angle = grades[i].clamp(-MAX_GRADE, MAX_GRADE).atan();
+                let rolling = mass * GRAVITY * model.crr * angle.cos();
+                let climbing = mass * GRAVITY * angle.sin();
+                let drag = 0.5 * AIR_DENSITY * model.cda * v * v;
+                let acceleration = if i > 0 { mass * (v - speed[i - 1]) / seconds(i) } else { 0.0 };
+                // Freewheeling when the slope and momentum do the work
+                ((rolling + climbing + drag + acceleration) * v / DRIVETRAIN_EFFICIENCY).max(0.0).round()
+            })
+            .collect(),
+    )
+}
@@ -0,0 +1,102 @@
+//! A progress bar for long per-activity loops (sync backfills, upgrade-streams,
+//! social): an indicatif bar on stderr with ETA, and as its message the Strava
+//! rate-limit budget left, the current activity and its status.
+//!
+//! Only drawn on an interactive terminal with pretty logs; otherwise `Progress`
+//! logs the same items and statuses as plain lines, so CI output is unchanged.
+//! Log lines printed while a bar is up go above it (see `logging`).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use indicatif::{ProgressBar, ProgressStyle};
+use tracing::info;
+
+use crate::strava;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The bar being drawn, which log lines are printed above
+static BAR: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+const TEMPLATE: &str = "{prefix} [{bar:20}] {pos}/{len} ETA {eta} {wide_msg}";
+
+pub fn enable(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Write a log line to stderr, keeping the bar below it
+pub fn print(line: &str) {
+    let bar = BAR.lock().unwrap_or_else(|e| e.into_inner());
+    match bar.as_ref() {
+        Some(bar) => bar.suspend(|| eprintln!("{}", line)),
+        None => eprintln!("{}", line),
+    }
+}
+
+pub struct Progress {
+    total: usize,
+    item: String,
+    status: String,
+    /// None when not interactive
+    bar: Option<ProgressBar>,
+}
+
+impl Progress {
+    pub fn new(label: &'static str, total: usize) -> Self {
+        let bar = ENABLED.load(Ordering::Relaxed).then(|| {
+            let style = ProgressStyle::with_template(TEMPLATE).unwrap_or_else(|_| ProgressStyle::default_bar()).progress_chars("█░");
+            let bar = ProgressBar::new(total as u64).with_style(style).with_prefix(label);
+            *BAR.lock().unwrap_or_else(|e| e.into_inner()) = Some(bar.clone());
+            bar
+        });
+        let progress = Progress { total, item: String::new(), status: String::new(), bar };
+        progress.draw();
+        progress
+    }
+
+    /// Start on item `index` (from 0)
+    pub fn item(&mut self, index: usize, name: &str) {
+        match &self.bar {
+            Some(bar) => {
+                bar.set_position(index as u64);
+                self.item = name.to_string();
+                self.status.clear();
+                self.draw();
+            }
+            None => info!("   [{}/{}] {}", index + 1, self.total, name),
+        }
+    }
+
+    /// What happened to the current item
+    pub fn status(&mut self, status: &str) {
+        if self.bar.is_some() {
+            self.status = status.to_string();
+            self.draw();
+        } else {
+            info!("      {}", status);
+        }
+    }
+
+    /// Refresh the message: budget left, then the current item and its status
+    fn draw(&self) {
+        let Some(bar) = &self.bar else { return };
+        let mut message = Vec::new();
+        if let Some(remaining) = strava::rate_limit_remaining() {
+            message.push(format!("{} API calls left", remaining));
+        }
+        if !self.item.is_empty() {
+            message.push(format!("{} {}", self.item, self.status).trim_end().to_string());
+        }
+        bar.set_message(message.join(" · "));
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if let Some(bar) = self.bar.take() {
+            bar.finish_and_clear();
+            *BAR.lock().unwrap_or_else(|e| e.into_inner()) = None;
+        }
+    }
+}
@@ -0,0 +1,302 @@
+//! A static JSON API for frontends served from GitHub Pages: everything a page
+//! needs is a small file it can `fetch()`, instead of the full index and
+//! activity files with their 1 Hz streams.
+//!
+//! ```text
+//! api/manifest.json           counts, page size, generation time
+//! api/activities/{n}.json     index pages, newest first
+//! api/streams/{id}.json       each stream reduced to the point budget with LTTB
+//! api/aggregates.json         weekly and monthly totals, PMC, power curves (also per season), calendar heatmap, streaks
+//! api/calendar.ics            one calendar event per ride, to subscribe to
+//! ```
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::aerobic::HeartRateFit;
+use crate::cadence::{self, CadenceProfile};
+use crate::calendar;
+use crate::config::Config;
+use crate::format;
+use crate::heatmap::{self, HeatmapDay};
+use crate::model::{ActivityIndex, ActivityStreams, ActivitySummary};
+use crate::plan::{self, Plan};
+use crate::report;
+use crate::resample;
+use crate::season::Season;
+use crate::stats::{GroupBy, Totals};
+use crate::storage::{load_activity_file, write_atomic};
+use crate::streaks::{self, Streaks};
+use crate::training_load;
+use crate::weekly::{self, WeekSummary};
+
+const API_DIR: &str = "api";
+
+/// Activities per index page
+const PAGE_SIZE: usize = 50;
+
+/// Samples per published stream by default, plenty for a chart
+pub const DEFAULT_POINTS: usize = 2000;
+
+#[derive(Debug, Serialize)]
+struct Manifest {
+    schema_version: u32,
+    format_version: &'static str,
+    generated_at: String,
+    activities: usize,
+    page_size: usize,
+    pages: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct Page<'a> {
+    page: usize,
+    pages: usize,
+    activities: &'a [ActivitySummary],
+}
+
+/// Streams as published: each series picks its own samples, so it carries its own time
+#[derive(Debug, Serialize)]
+struct ChartStreams {
+    /// Samples in the stored streams
+    samples: usize,
+    series: BTreeMap<&'static str, Series>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latlng: Option<Vec<[f64; 2]>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Series {
+    time: Vec<i32>,
+    values: Vec<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Aggregates {
+    weeks: Vec<WeekSummary>,
+    months: Vec<MonthSummary>,
+    pmc: Vec<PmcDay>,
+    /// Every day since the first ride, for a calendar heatmap
+    heatmap: Vec<HeatmapDay>,
+    /// Current and longest day and week streaks
+    streaks: Streaks,
+    power_curve: Vec<CurvePoint>,
+    /// All counted rides with a cadence stream
+    cadence: Option<CadenceProfile>,
+    /// Heart rate against power of each ride, oldest first
+    heart_rate_fits: Vec<RideFit>,
+    /// Planned against actual load per week, when a plan was imported
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    plan: Vec<plan::Week>,
+    /// Power curve of each requested season
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    seasons: Vec<SeasonCurve>,
+}
+
+#[derive(Debug, Serialize)]
+struct SeasonCurve {
+    #[serde(flatten)]
+    season: Season,
+    power_curve: Vec<SeasonPoint>,
+}
+
+#[derive(Debug, Serialize)]
+struct SeasonPoint {
+    /// Seconds
+    duration: usize,
+    watts: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RideFit {
+    pub id: ActivityId,
+    pub start_date: DateTime<Utc>,
+    #[serde(skip)]
+    pub date: NaiveDate,
+    #[serde(flatten)]
+    pub fit: HeartRateFit,
+}
+
+#[derive(Debug, Serialize)]
+struct MonthSummary {
+    /// First day of the month
+    month: String,
+    #[serde(flatten)]
+    totals: Totals,
+}
+
+#[derive(Debug, Serialize)]
+struct PmcDay {
+    date: String,
+    tss: f64,
+    ctl: f64,
+    atl: f64,
+    tsb: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct CurvePoint {
+    /// Seconds
+    duration: usize,
+    all_time: Option<f64>,
+    last_90_days: Option<f64>,
+}
+
+pub fn default_dir() -> PathBuf {
+    format::data_dir().join(API_DIR)
+}
+
+fn write_json(path: &Path, value: &impl Serialize) -> Result<(), Box<dyn Error>> {
+    write_atomic(path, serde_json::to_string(value)?.as_bytes())
+}
+
+/// Write the API into `output` (data/api by default), replacing what was there;
+/// streams are reduced to at most `points` samples per series
+pub fn run(output: Option<&Path>, points: usize) -> Result<(), Box<dyn Error>> {
+    let dir = output.map(Path::to_path_buf).unwrap_or_else(default_dir);
+    let index = ActivityIndex::load()?;
+    let config = Config::current()?;
+    let activities: Vec<ActivitySummary> = index.activities.iter().filter(|a| a.duplicate_of.is_none()).cloned().collect();
+
+    // Stale pages and streams of deleted rides must not linger
+    for sub in ["activities", "streams"] {
+        match fs::remove_dir_all(dir.join(sub)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        fs::create_dir_all(dir.join(sub))?;
+    }
+
+    let pages = activities.len().div_ceil(PAGE_SIZE).max(1);
+    for page in 0..pages {
+        let chunk = activities.chunks(PAGE_SIZE).nth(page).unwrap_or_default();
+        write_json(&dir.join("activities").join(format!("{}.json", page + 1)), &Page { page: page + 1, pages, activities: chunk })?;
+    }
+
+    let mut streams = 0;
+    for summary in &activities {
+        let file = match load_activity_file(summary.id) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("   ⚠️  Skipping {}: {}", summary.id, e);
+                continue;
+            }
+        };
+        let Some(full) = &file.streams else { continue };
+        write_json(&dir.join("streams").join(format!("{}.json", summary.id)), &chart_streams(full, points))?;
+        streams += 1;
+    }
+
+    write_json(&dir.join("aggregates.json"), &aggregates(&index, &config, &config.seasons))?;
+    write_atomic(&dir.join(calendar::CALENDAR_FILE), calendar::render(&index, &config).as_bytes())?;
+    write_json(&dir.join("manifest.json"), &Manifest {
+        schema_version: format::SCHEMA_VERSION,
+        format_version: format::FORMAT_VERSION,
+        generated_at: Utc::now().to_rfc3339(),
+        activities: activities.len(),
+        page_size: PAGE_SIZE,
+        pages,
+    })?;
+    info!("🌐 Published {} activities ({} pages, {} streams) to {}", activities.len(), pages, streams, dir.display());
+    Ok(())
+}
+
+/// Reduce every series with LTTB, keeping the peaks and drops a chart should show
+fn chart_streams(streams: &ActivityStreams, points: usize) -> ChartStreams {
+    let time = streams.time.as_deref().unwrap_or_default();
+    let x: Vec<f64> = time.iter().map(|&t| t as f64).collect();
+    let ints = |values: &Option<Vec<i32>>| values.as_ref().map(|v| v.iter().map(|&v| v as f64).collect::<Vec<f64>>());
+    let all = [
+        ("watts", streams.watts.clone()),
+        ("heartrate", ints(&streams.heartrate)),
+        ("cadence", ints(&streams.cadence)),
+        ("velocity_smooth", streams.velocity_smooth.clone()),
+        ("altitude", streams.altitude.clone()),
+        ("w_balance", streams.w_balance.clone()),
+        ("estimated_watts", streams.estimated_watts.clone()),
+    ];
+    let series = all
+        .into_iter()
+        .filter_map(|(name, values)| {
+            let values = values?;
+            let picked = resample::lttb(&x, &values, points);
+            Some((name, Series {
+                time: picked.iter().map(|&i| time[i]).collect(),
+                values: picked.iter().map(|&i| values[i]).collect(),
+            }))
+        })
+        .collect();
+    // A route trace has no y to rank samples by; an even stride keeps its shape
+    let latlng = streams.latlng.as_ref().map(|l| {
+        let step = l.len().div_ceil(points.max(1)).max(1);
+        l.iter().step_by(step).copied().collect()
+    });
+    ChartStreams { samples: time.len(), series, latlng }
+}
+
+/// The stored heart rate fits of counted rides, oldest first
+pub fn heart_rate_fits(index: &ActivityIndex) -> Vec<RideFit> {
+    index
+        .activities
+        .iter()
+        .rev()
+        .filter(|a| a.counted())
+        .filter_map(|a| Some(RideFit { id: a.id, start_date: a.start_date, date: a.local_date(), fit: a.heart_rate_fit? }))
+        .collect()
+}
+
+/// Totals, PMC and curves of the archive, with the power curve of each of `seasons`
+pub fn aggregates(index: &ActivityIndex, config: &Config, seasons: &[Season]) -> Aggregates {
+    let mut months: BTreeMap<_, Totals> = BTreeMap::new();
+    for summary in index.activities.iter().filter(|a| a.counted()) {
+        let day = summary.local_date();
+        months.entry(GroupBy::Month.start(day, day)).or_default().add(summary, config);
+    }
+    let loads = training_load::daily(index, config);
+    let plan = Plan::load().unwrap_or_else(|e| {
+        warn!("   ⚠️  Skipping the training plan: {}", e);
+        Plan::default()
+    });
+    let mut periods = vec![Season::last_days(report::RECENT_DAYS)];
+    periods.extend_from_slice(seasons);
+    let (all_time, curves) = report::power_curves(index, config, &periods);
+    let watts = |p: f64| (p > 0.0).then(|| p.round());
+    let round = |v: f64| (v * 10.0).round() / 10.0;
+    Aggregates {
+        weeks: weekly::summarize(index, config),
+        months: months.into_iter().map(|(month, totals)| MonthSummary { month: month.to_string(), totals }).collect(),
+        pmc: loads
+            .iter()
+            .map(|d| PmcDay { date: d.date.to_string(), tss: round(d.tss), ctl: round(d.ctl), atl: round(d.atl), tsb: round(d.tsb) })
+            .collect(),
+        heatmap: heatmap::days(index, &loads, None),
+        streaks: streaks::compute(index, config, &loads),
+        power_curve: report::CURVE_DURATIONS
+            .iter()
+            .enumerate()
+            .map(|(i, &duration)| CurvePoint { duration, all_time: watts(all_time[i]), last_90_days: watts(curves[0][i]) })
+            .collect(),
+        seasons: seasons
+            .iter()
+            .zip(&curves[1..])
+            .map(|(season, curve)| SeasonCurve {
+                season: season.clone(),
+                power_curve: report::CURVE_DURATIONS
+                    .iter()
+                    .zip(curve)
+                    .map(|(&duration, &power)| SeasonPoint { duration, watts: watts(power) })
+                    .collect(),
+            })
+            .collect(),
+        cadence: cadence::aggregate(index, None).0,
+        heart_rate_fits: heart_rate_fits(index),
+        plan: plan::weeks(&plan::days(&plan, index, &loads, None)),
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use tracing::{info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::config::Config;
+use crate::format;
+use crate::metrics;
+use crate::model::ActivityIndex;
+use crate::storage::{load_activity_file, write_atomic};
+use crate::tcx;
+
+const INTERVALS_API: &str = "https://intervals.icu/api/v1";
+
+/// Archive activity ID -> intervals.icu activity ID, so reruns skip what was pushed
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PushedLog {
+    activities: BTreeMap<ActivityId, String>,
+}
+
+impl PushedLog {
+    fn path() -> std::path::PathBuf {
+        format::data_dir().join("pushed_intervals.json")
+    }
+
+    fn load() -> Result<Self, Box<dyn Error>> {
+        match fs::read_to_string(Self::path()) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(PushedLog::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self) -> Result<(), Box<dyn Error>> {
+        write_atomic(&Self::path(), serde_json::to_string_pretty(self)?.as_bytes())
+    }
+}
+
+/// Upload activities not yet on intervals.icu as TCX, then set their training load.
+///
+/// Needs INTERVALS_API_KEY; INTERVALS_ATHLETE_ID defaults to the key's own athlete.
+pub async fn intervals(dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let api_key = match std::env::var("INTERVALS_API_KEY") {
+        Ok(key) => key,
+        Err(_) if dry_run => String::new(),
+        Err(_) => return Err("INTERVALS_API_KEY is not set".into()),
+    };
+    let athlete_id = std::env::var("INTERVALS_ATHLETE_ID").unwrap_or_else(|_| "0".to_string());
+    let ftp = Config::current()?.ftp();
+
+    let index = ActivityIndex::load()?;
+    let mut pushed = PushedLog::load()?;
+    let pending: Vec<_> = index.activities.iter().filter(|a| !pushed.activities.contains_key(&a.id)).collect();
+    info!("📤 {} activities to push to intervals.icu{}", pending.len(), if dry_run { " (dry run)" } else { "" });
+
+    let client = reqwest::Client::new();
+    for summary in pending.iter().rev() {
+        let file = match load_activity_file(summary.id) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("   ⚠️  Skipping {}: {}", summary.id, e);
+                continue;
+            }
+        };
+        info!("   {} {}", summary.start_date, summary.name);
+        if dry_run {
+            continue;
+        }
+
+        let boundary = format!("watts-happening-{}", summary.id);
+        let mut body = Vec::new();
+        body.extend_from_slice(format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}.tcx\"\r\nContent-Type: application/xml\r\n\r\n",
+            boundary, summary.id
+        ).as_bytes());
+        body.extend_from_slice(tcx::render(&file)?.as_bytes());
+        body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+        let response = client
+            .post(format!("{}/athlete/{}/activities", INTERVALS_API, athlete_id))
+            .basic_auth("API_KEY", Some(&api_key))
+            .query(&[("name", summary.name.as_str()), ("external_id", &summary.id.to_string())])
+            .header("Content-Type", format!("multipart/form-data; boundary={}", boundary))
+            .body(body)
+            .send()
+            .await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            warn!("   ❌ Upload failed ({}): {}", status, text);
+            continue;
+        }
+        let uploaded: serde_json::Value = serde_json::from_str(&text)?;
+        let Some(remote_id) = uploaded.get("id").and_then(|v| v.as_str()).map(String::from) else {
+            warn!("   ❌ Unexpected upload response: {}", text);
+            continue;
+        };
+
+        // intervals.icu computes its own load; align it with ours when FTP is known
+        let watts = file.streams.as_ref().and_then(|s| s.watts.as_deref());
+        if let (Some(ftp), Some(np)) = (ftp, watts.and_then(metrics::normalized_power)) {
+            let tss = metrics::training_stress_score(np, file.activity.moving_time, ftp).round();
+            let response = client
+                .put(format!("{}/activity/{}", INTERVALS_API, remote_id))
+                .basic_auth("API_KEY", Some(&api_key))
+                .json(&serde_json::json!({ "icu_training_load": tss }))
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                warn!("   ⚠️  Could not set training load: {}", response.status());
+            }
+        }
+
+        info!("      ✅ Pushed as {}", remote_id);
+        pushed.activities.insert(summary.id, remote_id);
+        pushed.save()?;
+    }
+    Ok(())
+}
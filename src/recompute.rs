@@ -0,0 +1,157 @@
+//! `recompute`: run the analysis pipeline over activities already stored, so
+//! a new or fixed analyzer (or a changed FTP) needs no download.
+//!
+//! Analyzers are skipped when nothing they read has changed: the cache in
+//! `.watts-cache/recompute/` keeps, for each ride, the SHA-256 of its file and
+//! a fingerprint of the settings each analyzer last ran with. Archive-wide outputs
+//! (weekly TSS, the power curve table) are rebuilt every time they are asked for.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::athlete;
+use crate::best_efforts::BestEfforts;
+use crate::config::Config;
+use crate::model::ActivityIndex;
+use crate::pipeline::{self, Analyzer};
+use crate::rolling;
+use crate::storage::{parse_activity_file, read_activity_file, sha256, write_activity_file, write_atomic};
+use crate::training_load;
+use crate::weekly;
+
+const CACHE_DIR: &str = ".watts-cache/recompute";
+
+/// Outputs computed over the whole archive rather than ride by ride
+const ARCHIVE_METRICS: [&str; 2] = ["tss", "curve"];
+
+/// A comma-separated list of analyzer and archive metric names such as "np,tss,curve"
+pub fn parse_metrics(s: &str) -> Result<Vec<String>, String> {
+    s.split(',')
+        .map(|name| {
+            let name = name.trim();
+            if pipeline::find(name).is_some() || ARCHIVE_METRICS.contains(&name) {
+                Ok(name.to_string())
+            } else {
+                let known: Vec<&str> = pipeline::ANALYZERS.iter().map(|a| a.name()).chain(ARCHIVE_METRICS).collect();
+                Err(format!("unknown metric '{}' (expected {})", name, known.join(", ")))
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Cache {
+    /// By activity ID
+    rides: BTreeMap<String, CachedRide>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CachedRide {
+    /// SHA-256 of the activity file as last written or read
+    file: String,
+    /// Fingerprint of each analyzer's settings when it last ran on this file
+    metrics: BTreeMap<String, String>,
+}
+
+fn cache_path() -> PathBuf {
+    PathBuf::from(CACHE_DIR).join(format!("{}.json", athlete::cache_key()))
+}
+
+fn load_cache() -> Cache {
+    let path = cache_path();
+    match fs::read_to_string(&path).map(|json| serde_json::from_str(&json)) {
+        Ok(Ok(cache)) => cache,
+        Ok(Err(e)) => {
+            debug!("   Ignoring {}: {}", path.display(), e);
+            Cache::default()
+        }
+        Err(_) => Cache::default(),
+    }
+}
+
+/// Rerun `metrics` (all of them when empty) on rides since `since`, skipping
+/// unchanged ones unless `force`
+pub fn run(metrics: &[String], since: Option<NaiveDate>, force: bool) -> Result<(), Box<dyn Error>> {
+    let wanted = |name: &str| metrics.is_empty() || metrics.iter().any(|m| m == name);
+    let config = Config::current()?;
+    let mut index = ActivityIndex::load()?;
+    let mut cache = if force { Cache::default() } else { load_cache() };
+
+    let analyzers: Vec<(&dyn Analyzer, String)> = pipeline::ANALYZERS
+        .iter()
+        .filter(|a| wanted(a.name()))
+        .map(|a| (*a, sha256(a.inputs(&config).to_string().as_bytes())))
+        .collect();
+    let ids: Vec<_> = index.activities.iter().filter(|a| since.is_none_or(|s| a.local_date() >= s)).map(|a| a.id).collect();
+    if !analyzers.is_empty() {
+        let names: Vec<&str> = analyzers.iter().map(|(a, _)| a.name()).collect();
+        info!("🔁 Recomputing {} over {} rides", names.join(", "), ids.len());
+    }
+
+    let (mut computed, mut unchanged, mut failed) = (0, 0, 0);
+    for id in ids.iter().copied().filter(|_| !analyzers.is_empty()) {
+        let (json, compression) = match read_activity_file(id) {
+            Ok(read) => read,
+            Err(e) => {
+                warn!("   ⚠️  Skipping {}: {}", id, e);
+                failed += 1;
+                continue;
+            }
+        };
+        let hash = sha256(&json);
+        let cached = cache.rides.entry(id.to_string()).or_default();
+        let due: Vec<&(&dyn Analyzer, String)> = analyzers
+            .iter()
+            .filter(|(analyzer, inputs)| cached.file != hash || cached.metrics.get(analyzer.name()) != Some(inputs))
+            .collect();
+        if due.is_empty() {
+            unchanged += 1;
+            continue;
+        }
+
+        let mut file = match parse_activity_file(&json) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("   ⚠️  Skipping {}: {}", id, e);
+                failed += 1;
+                continue;
+            }
+        };
+        let before = serde_json::to_value(&file)?;
+        let due_analyzers: Vec<&dyn Analyzer> = due.iter().map(|(analyzer, _)| *analyzer).collect();
+        pipeline::run(&due_analyzers, &mut file, &mut index, &config);
+        cached.file = if serde_json::to_value(&file)? == before {
+            hash
+        } else {
+            write_activity_file(&file, compression)?;
+            sha256(&read_activity_file(id)?.0)
+        };
+        for (analyzer, inputs) in due {
+            cached.metrics.insert(analyzer.name().to_string(), inputs.clone());
+        }
+        computed += 1;
+    }
+    if !analyzers.is_empty() {
+        index.save()?;
+        fs::create_dir_all(CACHE_DIR)?;
+        write_atomic(&cache_path(), serde_json::to_string(&cache)?.as_bytes())?;
+        info!("💾 {} rides recomputed, {} unchanged, {} failed", computed, unchanged, failed);
+    }
+
+    if wanted("tss") {
+        weekly::write_series(&index)?;
+        rolling::write_series(&index, &training_load::daily(&index, &config))?;
+        info!("📊 Weekly and rolling TSS and totals rewritten (data/weekly.json, data/aggregates.json)");
+    }
+    if wanted("curve") {
+        BestEfforts::rebuild(&index, &config, &[]).save()?;
+        info!("🏆 Power curve rebuilt (data/best_efforts.json)");
+    }
+    Ok(())
+}
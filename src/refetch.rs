@@ -0,0 +1,78 @@
+use std::error::Error;
+
+use tracing::{info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::adjust;
+use crate::clean;
+use crate::config::Config;
+use crate::metrics;
+use crate::model::{ActivityIndex, PowerSummary};
+use crate::pipeline;
+use crate::storage::{load_activity_file, stored_compression, write_activity_file};
+use crate::strava::{access_token, fetch_activity, fetch_activity_laps, fetch_activity_social, fetch_activity_streams};
+
+/// Download an archived activity again and overwrite its file: the detail
+/// (summary, laps, kudos/comments/photos) with `detail`, the streams with
+/// `streams`, both when neither is given. Local edits survive: cleaning and
+/// power adjustments are re-applied to the new streams, tags and notes stay
+/// in the index.
+pub async fn run(id: ActivityId, streams: bool, detail: bool) -> Result<(), Box<dyn Error>> {
+    let (streams, detail) = if streams || detail { (streams, detail) } else { (true, true) };
+    let compression = stored_compression(id)?
+        .ok_or_else(|| format!("activity {} is not in the archive, `show {} --fetch` downloads it", id, id))?;
+    let mut file = load_activity_file(id)?;
+    let config = Config::current()?;
+    let access_token = access_token().await?;
+
+    if detail {
+        file.activity = fetch_activity(&access_token, id).await?;
+        // Strava's own figures are what a revert goes back to
+        if let Some(adjustment) = file.power_adjustment.as_mut() {
+            adjustment.recorded = PowerSummary {
+                average_watts: file.activity.average_watts,
+                weighted_average_watts: file.activity.weighted_average_watts,
+                max_watts: file.activity.max_watts,
+                kilojoules: file.activity.kilojoules,
+            };
+        }
+        match fetch_activity_laps(&access_token, id).await {
+            Ok(laps) => file.laps = Some(laps),
+            Err(e) => warn!("   ⚠️  Could not fetch laps: {}", e),
+        }
+        match fetch_activity_social(&access_token, id).await {
+            Ok(social) => file.social = Some(social),
+            Err(e) => warn!("   ⚠️  Could not fetch kudos, comments and photos: {}", e),
+        }
+        info!("📝 Refetched details of {}", file.activity.name);
+    }
+
+    if streams {
+        let old_points = file.streams.as_ref().and_then(|s| s.time.as_ref()).map_or(0, |t| t.len());
+        let new = fetch_activity_streams(&access_token, id).await?;
+        info!("📈 Refetched streams of {}: {} -> {} points", file.activity.name,
+            old_points, new.time.as_ref().map_or(0, |t| t.len()));
+        let was_cleaned = file.cleaning.take().is_some();
+        file.streams = Some(new);
+
+        let cleaning = config.cleaning.clone().unwrap_or_default();
+        if cleaning.enabled || was_cleaned {
+            // Also re-applies a power adjustment on top of the cleaned stream
+            clean::apply(&mut file, &cleaning);
+        } else if let (Some(adjustment), Some(s)) = (&file.power_adjustment, file.streams.as_mut()) {
+            if let Some(recorded) = s.watts.take() {
+                s.watts = Some(adjust::corrected(&recorded, adjustment.scale, adjustment.offset));
+                s.original_watts = Some(recorded);
+            }
+        }
+    }
+    // The adjusted summary follows whichever part changed
+    if let (Some(_), Some(s)) = (&file.power_adjustment, &file.streams) {
+        metrics::recompute_power_summary(&mut file.activity, s);
+    }
+
+    let mut index = ActivityIndex::load()?;
+    pipeline::ingest(&mut file, &mut index, &config);
+    write_activity_file(&file, compression)?;
+    index.save()
+}
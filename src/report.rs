@@ -0,0 +1,608 @@
+//! Static HTML report: power curve, PMC, weekly volume, training calendar,
+//! heart rate against power and cadence drawn as inline SVG, plus plan compliance and the
+//! worst-paced rides as tables, so the file has no dependencies and can be published as is (GitHub Pages).
+//! Without `--html` the same charts are drawn in the terminal.
+
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use tracing::{info, warn};
+
+use crate::aerobic::HeartRateFit;
+use crate::ascii_chart;
+use crate::best_efforts;
+use crate::cadence;
+use crate::cli;
+use crate::config::Config;
+use crate::edit::TagFilter;
+use crate::escape;
+use crate::format;
+use crate::heatmap::{self, HeatmapDay};
+use crate::locale::Locale;
+use crate::metrics;
+use crate::model::ActivityIndex;
+use crate::pacing;
+use crate::plan::{self, Plan};
+use crate::publish;
+use crate::season::Season;
+use crate::storage::{load_activity_file, write_atomic};
+use crate::streaks::{self, Streaks};
+use crate::training_load::{self, DailyLoad};
+use crate::weekly;
+
+const REPORTS_DIR: &str = "reports";
+
+/// Power curve durations, log-spaced
+pub const CURVE_DURATIONS: [usize; 12] = [1, 5, 10, 30, 60, 120, 300, 600, 1200, 1800, 3600, 7200];
+
+/// The curve is drawn all-time and over this recent window
+pub const RECENT_DAYS: i64 = 90;
+
+/// Colours of the period curves drawn over the all-time one
+const CURVE_COLORS: [&str; 5] = ["#ff6b6b", "#ffd93d", "#6bcb77", "#c77dff", "#ff9f1c"];
+
+/// PMC and weekly volume cover this many days
+const HISTORY_DAYS: i64 = 365;
+
+/// Weeks of the terminal volume chart, one line each
+const TERMINAL_WEEKS: usize = 26;
+
+/// Rides in the terminal heart rate fit table
+const TERMINAL_FITS: usize = 10;
+
+/// Rides in the worst-paced table
+const WORST_PACED: usize = 10;
+
+/// Rows of the terminal fitness chart
+const TERMINAL_HEIGHT: usize = 10;
+
+/// Heatmap shades from rest day to the top quartile, and the terminal's
+const HEATMAP_COLORS: [&str; heatmap::LEVELS] = ["#2a2a4e", "#004d66", "#007a99", "#00a8cc", "#00d4ff"];
+const HEATMAP_BLOCKS: [char; heatmap::LEVELS] = ['·', '░', '▒', '▓', '█'];
+
+const WIDTH: f64 = 900.0;
+const HEIGHT: f64 = 320.0;
+const MARGIN: f64 = 48.0;
+
+pub fn default_path() -> PathBuf {
+    format::data_dir().join(REPORTS_DIR).join("index.html")
+}
+
+/// Render the report into `output` (data/reports/index.html by default),
+/// drawing the power curve of each of `seasons` instead of the last 90 days
+pub fn run(output: Option<&Path>, seasons: &[Season], tags: &TagFilter) -> Result<(), Box<dyn Error>> {
+    let mut index = ActivityIndex::load()?;
+    tags.apply(&mut index);
+    let config = Config::current()?;
+    let path = output.map(Path::to_path_buf).unwrap_or_else(default_path);
+    let html = render(&index, &config, seasons)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    write_atomic(&path, html.as_bytes())?;
+    info!("📄 Report written to {}", path.display());
+    Ok(())
+}
+
+/// Print the report's data (power curve, PMC, weekly and monthly totals) as
+/// JSON, in the shape `publish` writes to aggregates.json
+pub fn print_json(seasons: &[Season], tags: &TagFilter) -> Result<(), Box<dyn Error>> {
+    let mut index = ActivityIndex::load()?;
+    tags.apply(&mut index);
+    let config = Config::current()?;
+    cli::print_json(&publish::aggregates(&index, &config, seasons))
+}
+
+/// Print the power curves, fitness and recent weekly volume as terminal charts
+pub fn print_charts(seasons: &[Season], tags: &TagFilter) -> Result<(), Box<dyn Error>> {
+    let mut index = ActivityIndex::load()?;
+    tags.apply(&mut index);
+    let config = Config::current()?;
+    let locale = config.locale.unwrap_or_default();
+    let width = ascii_chart::width();
+
+    let periods = curve_periods(seasons);
+    let (all_time, curves) = power_curves(&index, &config, &periods);
+    let titles = std::iter::once("all time".to_string()).chain(periods.iter().map(|s| season_title(s, locale)));
+    for (title, best) in titles.zip(std::iter::once(all_time).chain(curves)) {
+        let bars: Vec<(String, f64)> = CURVE_DURATIONS
+            .iter()
+            .zip(best)
+            .filter(|(_, power)| *power > 0.0)
+            .map(|(&seconds, power)| (best_efforts::label(seconds), power))
+            .collect();
+        if bars.is_empty() {
+            continue;
+        }
+        println!("⚡ Power curve, {}", title);
+        for line in ascii_chart::localized_bar_chart(&bars, width, 0, "W", locale) {
+            println!("  {}", line);
+        }
+        println!();
+    }
+
+    let since = Utc::now().date_naive() - Duration::days(HISTORY_DAYS);
+    let loads = training_load::daily(&index, &config);
+    let ctl: Vec<f64> = loads.iter().filter(|d| d.date >= since).map(|d| d.ctl).collect();
+    if !ctl.is_empty() {
+        println!("📈 Fitness (CTL) since {}", locale.date(since));
+        for line in ascii_chart::area_chart(&ctl, width, TERMINAL_HEIGHT) {
+            println!("  {}", line);
+        }
+        println!();
+    }
+
+    let weeks = weekly::summarize(&index, &config);
+    let recent_weeks = &weeks[weeks.len().saturating_sub(TERMINAL_WEEKS)..];
+    let bars: Vec<(String, f64)> =
+        recent_weeks.iter().map(|w| (week_label(&w.week, |d| locale.date(d)), w.totals.moving_time as f64 / 3600.0)).collect();
+    println!("📊 Weekly volume, last {} weeks", bars.len());
+    for line in ascii_chart::localized_bar_chart(&bars, width, 1, "h", locale) {
+        println!("  {}", line);
+    }
+
+    // As many weeks as fit the terminal, up to the report's year
+    let days = heatmap::days(&index, &loads, Some(since));
+    let weeks = heatmap::weeks(&days);
+    let weeks = &weeks[weeks.len().saturating_sub(width.saturating_sub(4))..];
+    if days.iter().any(|d| d.level > 0) {
+        println!();
+        println!("🗓️  Training calendar, last {} weeks", weeks.len());
+        for (weekday, label) in ["M", "T", "W", "T", "F", "S", "S"].iter().enumerate() {
+            let row: String = weeks.iter().map(|week| week[weekday].map_or(' ', |d| HEATMAP_BLOCKS[d.level])).collect();
+            println!("  {} {}", label, row.trim_end());
+        }
+    }
+
+    let rows = streak_rows(&streaks::compute(&index, &config, &loads), locale);
+    if !rows.is_empty() {
+        println!();
+        println!("🔥 Streaks");
+        for [label, length, start, end] in rows {
+            println!("  {:<8} {:>9}  {} to {}", label, length, start, end);
+        }
+    }
+
+    let fits = publish::heart_rate_fits(&index);
+    let recent_fits = &fits[fits.len().saturating_sub(TERMINAL_FITS)..];
+    if !recent_fits.is_empty() {
+        println!();
+        println!("💓 Heart rate against power, last {} rides", recent_fits.len());
+        println!("  {:<10} {:<16} {:>9} {:>7} {:>4} {:>5}", "date", "id", "bpm/100W", "bpm@0W", "lag", "r²");
+        for ride in recent_fits {
+            println!(
+                "  {:<10} {:<16} {:>9} {:>7} {:>3}s {:>5}",
+                locale.date(ride.date),
+                ride.id.to_string(),
+                locale.number(ride.fit.slope * 100.0, 1),
+                locale.number(ride.fit.intercept, 0),
+                ride.fit.lag,
+                locale.number(ride.fit.r2, 2)
+            );
+        }
+    }
+
+    let weeks = plan_weeks(&index, &loads, since)?;
+    if !weeks.is_empty() {
+        println!();
+        println!("📅 Plan vs actual by week");
+        plan::print_weeks(&weeks);
+    }
+
+    if let (Some(profile), rides) = cadence::aggregate(&index, None) {
+        println!();
+        println!("🦵 Cadence over {} rides, {} rpm average, {}% coasting",
+            rides,
+            locale.number(profile.average, 0),
+            locale.number(profile.coasting_percent().unwrap_or(0.0), 1)
+        );
+        for line in ascii_chart::localized_bar_chart(&cadence::histogram_bars(&profile), width, 0, "min", locale) {
+            println!("  {}", line);
+        }
+    }
+
+    let worst = pacing::worst_paced(&index, since, WORST_PACED);
+    if !worst.is_empty() {
+        println!();
+        println!("🎢 Worst-paced rides, last {} days", HISTORY_DAYS);
+        println!("  {:<10} {:<16} {:>5} {:>5}  name", "date", "id", "VI", "score");
+        for (ride, stats) in worst {
+            println!(
+                "  {:<10} {:<16} {:>5} {:>5}  {}",
+                locale.date(ride.local_date()),
+                ride.id.to_string(),
+                locale.number(stats.variability_index, 2),
+                locale.number(stats.score, 0),
+                ride.name.chars().take(40).collect::<String>()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Label, length, first and last day of each streak there is
+fn streak_rows(streaks: &Streaks, locale: Locale) -> Vec<[String; 4]> {
+    [
+        ("current", "day", streaks.current_days),
+        ("longest", "day", streaks.longest_days),
+        ("current", "week", streaks.current_weeks),
+        ("longest", "week", streaks.longest_weeks),
+    ]
+    .into_iter()
+    .filter_map(|(label, unit, streak)| {
+        let streak = streak?;
+        // Weekly streaks end on the Sunday of their last week
+        let end = if unit == "week" { streak.end + Duration::days(6) } else { streak.end };
+        Some([
+            label.to_string(),
+            format!("{} {}{}", streak.length, unit, if streak.length == 1 { "" } else { "s" }),
+            locale.date(streak.start),
+            locale.date(end),
+        ])
+    })
+    .collect()
+}
+
+/// Weekly plan compliance since `since`, empty without an imported plan
+fn plan_weeks(index: &ActivityIndex, loads: &[DailyLoad], since: NaiveDate) -> Result<Vec<plan::Week>, Box<dyn Error>> {
+    let plan = Plan::load()?;
+    let first = plan.workouts.first().map(|w| w.date.max(since));
+    Ok(first.map(|first| plan::weeks(&plan::days(&plan, index, loads, Some(first)))).unwrap_or_default())
+}
+
+/// A season's name, with its dates unless it is a calendar year
+fn season_title(season: &Season, locale: Locale) -> String {
+    if season.year().is_some() {
+        season.name.clone()
+    } else {
+        format!("{} ({} to {})", season.name, locale.date(season.start), locale.date(season.end))
+    }
+}
+
+/// A weekly summary's Monday through `format`, as stored when it doesn't parse
+fn week_label(week: &str, format: impl Fn(NaiveDate) -> String) -> String {
+    week.parse().map_or_else(|_| week.to_string(), format)
+}
+
+/// Best mean-maximal power at each of `CURVE_DURATIONS`, 0 where no ride is long enough
+pub type Curve = [f64; CURVE_DURATIONS.len()];
+
+/// The all-time power curve and that of each of `periods`, over the rides counting for records
+pub fn power_curves(index: &ActivityIndex, config: &Config, periods: &[Season]) -> (Curve, Vec<Curve>) {
+    let mut all_time = [0.0f64; CURVE_DURATIONS.len()];
+    let mut curves = vec![[0.0f64; CURVE_DURATIONS.len()]; periods.len()];
+    for summary in index.activities.iter().filter(|a| config.counts_for_records(a) && !a.is_run()) {
+        let file = match load_activity_file(summary.id) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("   ⚠️  Skipping {}: {}", summary.id, e);
+                continue;
+            }
+        };
+        let Some(watts) = file.streams.as_ref().and_then(|s| s.watts.as_deref()) else { continue };
+        let within: Vec<usize> = (0..periods.len()).filter(|&p| periods[p].includes(summary)).collect();
+        for (i, &duration) in CURVE_DURATIONS.iter().enumerate() {
+            if let Some(power) = metrics::mean_maximal_power(watts, duration) {
+                all_time[i] = all_time[i].max(power);
+                for &p in &within {
+                    curves[p][i] = curves[p][i].max(power);
+                }
+            }
+        }
+    }
+    (all_time, curves)
+}
+
+/// The periods the power curve is drawn over besides all-time: the given
+/// seasons, or the last `RECENT_DAYS`
+fn curve_periods(seasons: &[Season]) -> Vec<Season> {
+    if seasons.is_empty() { vec![Season::last_days(RECENT_DAYS)] } else { seasons.to_vec() }
+}
+
+fn render(index: &ActivityIndex, config: &Config, seasons: &[Season]) -> Result<String, Box<dyn Error>> {
+    let locale = config.locale.unwrap_or_default();
+    let periods = curve_periods(seasons);
+    let (all_time, curves) = power_curves(index, config, &periods);
+    let curve = |best: &[f64]| -> Vec<(f64, f64)> {
+        CURVE_DURATIONS.iter().zip(best).filter(|(_, p)| **p > 0.0).map(|(&d, &p)| ((d as f64).ln(), p)).collect()
+    };
+    let curve_ticks: Vec<(f64, String)> = [(1, "1s"), (5, "5s"), (30, "30s"), (60, "1m"), (300, "5m"), (1200, "20m"), (3600, "1h"), (7200, "2h")]
+        .iter()
+        .map(|&(d, label)| ((d as f64).ln(), label.to_string()))
+        .collect();
+    let titles: Vec<String> = periods.iter().map(|s| season_title(s, locale)).collect();
+    let mut series: Vec<Series> = vec![("all time", "#00d4ff", curve(&all_time))];
+    for ((title, best), color) in titles.iter().zip(&curves).zip(CURVE_COLORS.iter().cycle()) {
+        series.push((title, color, curve(best)));
+    }
+    let power_curve = line_chart(&series, &curve_ticks, "W", locale);
+
+    let since = Utc::now().date_naive() - Duration::days(HISTORY_DAYS);
+    let all_loads = training_load::daily(index, config);
+    let loads: Vec<_> = all_loads.iter().filter(|d| d.date >= since).collect();
+    let day = |d: chrono::NaiveDate| (d - since).num_days() as f64;
+    let month_ticks: Vec<(f64, String)> = loads
+        .iter()
+        .filter(|d| d.date.format("%d").to_string() == "01")
+        .map(|d| (day(d.date), locale.month(d.date)))
+        .collect();
+    let pmc = line_chart(
+        &[
+            ("CTL (fitness)", "#00d4ff", loads.iter().map(|d| (day(d.date), d.ctl)).collect()),
+            ("ATL (fatigue)", "#ff6b6b", loads.iter().map(|d| (day(d.date), d.atl)).collect()),
+            ("TSB (form)", "#ffd93d", loads.iter().map(|d| (day(d.date), d.tsb)).collect()),
+        ],
+        &month_ticks,
+        "TSS/d",
+        locale,
+    );
+
+    let weeks: Vec<_> = weekly::summarize(index, config).into_iter().filter(|w| w.week >= since.to_string()).collect();
+    let volume = bar_chart(
+        &weeks.iter().map(|w| (week_label(&w.week, |d| locale.day_month(d)), w.totals.moving_time as f64 / 3600.0)).collect::<Vec<_>>(),
+        "h",
+        locale,
+    );
+
+    let calendar = heatmap_chart(&heatmap::days(index, &all_loads, Some(since)), locale);
+    let rows = streak_rows(&streaks::compute(index, config, &all_loads), locale);
+    let streaks = if rows.is_empty() {
+        String::new()
+    } else {
+        let mut table = String::from("<table>\n<tr><th>streak</th><th>length</th><th>from</th><th>to</th></tr>\n");
+        for [label, length, start, end] in rows {
+            let _ = writeln!(table, "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>", label, length, start, end);
+        }
+        table.push_str("</table>");
+        table
+    };
+
+    let fits: Vec<(f64, HeartRateFit)> = publish::heart_rate_fits(index)
+        .into_iter()
+        .filter(|f| f.date >= since)
+        .map(|f| (day(f.date), f.fit))
+        .collect();
+    let heart_rate = line_chart(
+        &[
+            ("bpm per 100 W", "#ff6b6b", fits.iter().map(|(x, fit)| (*x, fit.slope * 100.0)).collect()),
+            ("bpm at 0 W", "#00d4ff", fits.iter().map(|(x, fit)| (*x, fit.intercept)).collect()),
+        ],
+        &month_ticks,
+        "bpm",
+        locale,
+    );
+
+    let cadence = match cadence::aggregate(index, None).0 {
+        Some(profile) => format!(
+            "<h2>Cadence (minutes per rpm, {} rpm average, {}% coasting)</h2>\n{}",
+            locale.number(profile.average, 0),
+            locale.number(profile.coasting_percent().unwrap_or(0.0), 1),
+            bar_chart(&cadence::histogram_bars(&profile), "min", locale)
+        ),
+        None => String::new(),
+    };
+
+    let weeks = plan_weeks(index, &all_loads, since)?;
+    let plan = if weeks.is_empty() {
+        String::new()
+    } else {
+        let mut table = String::from("<h2>Plan vs actual</h2>\n<table>\n<tr><th>week</th><th>planned TSS</th><th>actual TSS</th><th>done</th><th>missed</th><th>over</th><th>under</th></tr>\n");
+        for week in &weeks {
+            let flag = |count: usize| if count > 0 { format!(r#"<td class="flag">{}</td>"#, count) } else { "<td>0</td>".to_string() };
+            let _ = writeln!(
+                table,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td>{}{}<td>{}</td></tr>",
+                locale.date(week.week),
+                week.planned_tss.map_or_else(|| "-".to_string(), |t| locale.number(t, 0)),
+                locale.number(week.tss, 0),
+                week.compliance.map_or_else(|| "-".to_string(), |c| format!("{}%", locale.number(c, 0))),
+                flag(week.missed),
+                flag(week.over),
+                week.under
+            );
+        }
+        table.push_str("</table>");
+        table
+    };
+
+    // One chart per custom metric from watts.json, a point per ride
+    let mut custom = String::new();
+    for name in config.custom_metrics.keys() {
+        let points: Vec<(f64, f64)> = index
+            .activities
+            .iter()
+            .filter(|a| a.counted() && a.local_date() >= since)
+            .filter_map(|a| a.custom_metrics.get(name).map(|v| (day(a.local_date()), *v)))
+            .rev()
+            .collect();
+        if !points.is_empty() {
+            let _ = writeln!(custom, "<h2>{} per ride (last {} days)</h2>", name, HISTORY_DAYS);
+            custom.push_str(&line_chart(&[(name, "#6bcb77", points)], &month_ticks, "", locale));
+        }
+    }
+
+    let worst = pacing::worst_paced(index, since, WORST_PACED);
+    let pacing = if worst.is_empty() {
+        String::new()
+    } else {
+        let mut table = format!(
+            "<h2>Worst-paced rides (last {} days)</h2>\n<table>\n<tr><th>date</th><th>ride</th><th>VI</th><th>pacing score</th></tr>\n",
+            HISTORY_DAYS
+        );
+        for (ride, stats) in worst {
+            let _ = writeln!(
+                table,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                locale.date(ride.local_date()),
+                escape::xml(&ride.name),
+                locale.number(stats.variability_index, 2),
+                locale.number(stats.score, 0)
+            );
+        }
+        table.push_str("</table>");
+        table
+    };
+
+    let rides = index.activities.iter().filter(|a| a.counted()).count();
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html lang="{lang}">
+<head>
+<meta charset="UTF-8">
+<meta name="viewport" content="width=device-width, initial-scale=1.0">
+<title>Watts Happening? ⚡🚴 - Report</title>
+<style>
+body {{ font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif; background: #0f0f23; color: #eee; max-width: 960px; margin: 0 auto; padding: 20px; }}
+h1 {{ color: #00d4ff; text-align: center; }}
+h2 {{ color: #00d4ff; font-size: 1.2em; margin-top: 32px; }}
+.subtitle {{ text-align: center; color: #888; }}
+svg {{ width: 100%; height: auto; background: #1a1a3e; border-radius: 8px; }}
+svg text {{ fill: #aaa; font-size: 12px; }}
+table {{ width: 100%; border-collapse: collapse; background: #1a1a3e; border-radius: 8px; }}
+th, td {{ padding: 6px 10px; text-align: right; }}
+th {{ color: #888; font-weight: normal; }}
+td.flag {{ color: #ff6b6b; font-weight: bold; }}
+</style>
+</head>
+<body>
+<h1>Watts Happening? ⚡🚴</h1>
+<p class="subtitle">{rides} rides, generated {generated}</p>
+<h2>Power curve</h2>
+{power_curve}
+<h2>Performance management (last {HISTORY_DAYS} days)</h2>
+{pmc}
+<h2>Weekly volume (hours)</h2>
+{volume}
+<h2>Training calendar (last {HISTORY_DAYS} days)</h2>
+{calendar}
+{streaks}
+<h2>Heart rate against power, per ride (last {HISTORY_DAYS} days)</h2>
+{heart_rate}
+{plan}
+{cadence}
+{pacing}
+{custom}
+</body>
+</html>
+"#,
+        lang = locale.lang(),
+        generated = locale.timestamp(Utc::now()),
+    ))
+}
+
+/// Map `value` from [min, max] onto [from, to]
+fn scale(value: f64, min: f64, max: f64, from: f64, to: f64) -> f64 {
+    if max > min { from + (value - min) / (max - min) * (to - from) } else { from }
+}
+
+/// Y axis with ~5 gridlines; returns (min, max)
+fn y_axis(svg: &mut String, min: f64, max: f64, unit: &str, locale: Locale) -> (f64, f64) {
+    let step = nice_step((max - min) / 5.0);
+    let (min, max) = ((min / step).floor() * step, (max / step).ceil() * step);
+    let mut tick = min;
+    while tick <= max + step / 2.0 {
+        let y = scale(tick, min, max, HEIGHT - MARGIN, MARGIN / 2.0);
+        let _ = write!(svg, r##"<line x1="{MARGIN}" x2="{}" y1="{y:.1}" y2="{y:.1}" stroke="#333"/><text x="{}" y="{:.1}" text-anchor="end">{}</text>"##,
+            WIDTH - MARGIN / 2.0, MARGIN - 6.0, y + 4.0, locale.number(tick, 0));
+        tick += step;
+    }
+    let _ = write!(svg, r#"<text x="4" y="14">{unit}</text>"#);
+    (min, max)
+}
+
+fn nice_step(raw: f64) -> f64 {
+    if raw <= 0.0 {
+        return 1.0;
+    }
+    let magnitude = 10f64.powf(raw.log10().floor());
+    [1.0, 2.0, 5.0, 10.0].into_iter().map(|m| m * magnitude).find(|s| *s >= raw).unwrap_or(10.0 * magnitude)
+}
+
+/// Name, colour and points of a line
+type Series<'a> = (&'a str, &'a str, Vec<(f64, f64)>);
+
+fn line_chart(series: &[Series], x_ticks: &[(f64, String)], unit: &str, locale: Locale) -> String {
+    let points = series.iter().flat_map(|(_, _, p)| p.iter());
+    let (x_min, x_max) = points.clone().fold((f64::MAX, f64::MIN), |(lo, hi), (x, _)| (lo.min(*x), hi.max(*x)));
+    let (y_min, y_max) = points.fold((0.0f64, f64::MIN), |(lo, hi), (_, y)| (lo.min(*y), hi.max(*y)));
+    let mut svg = format!(r#"<svg viewBox="0 0 {WIDTH} {HEIGHT}" xmlns="http://www.w3.org/2000/svg">"#);
+    if x_min > x_max {
+        svg.push_str(r#"<text x="50%" y="50%" text-anchor="middle">no data</text></svg>"#);
+        return svg;
+    }
+    let (y_min, y_max) = y_axis(&mut svg, y_min, y_max, unit, locale);
+    let x = |v: f64| scale(v, x_min, x_max, MARGIN, WIDTH - MARGIN / 2.0);
+    let y = |v: f64| scale(v, y_min, y_max, HEIGHT - MARGIN, MARGIN / 2.0);
+    for (value, label) in x_ticks.iter().filter(|(v, _)| (x_min..=x_max).contains(v)) {
+        let _ = write!(svg, r#"<text x="{:.1}" y="{}" text-anchor="middle">{label}</text>"#, x(*value), HEIGHT - MARGIN + 18.0);
+    }
+    for (i, (name, color, points)) in series.iter().enumerate() {
+        let path: Vec<String> = points.iter().map(|(px, py)| format!("{:.1},{:.1}", x(*px), y(*py))).collect();
+        let _ = write!(svg, r#"<polyline fill="none" stroke="{color}" stroke-width="2" points="{}"/>"#, path.join(" "));
+        let legend_x = MARGIN + 10.0 + i as f64 * 150.0;
+        let _ = write!(svg, r#"<rect x="{legend_x}" y="{}" width="12" height="3" fill="{color}"/><text x="{}" y="{}">{name}</text>"#,
+            HEIGHT - 14.0, legend_x + 16.0, HEIGHT - 10.0);
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+/// One square per day in Monday-first week columns, shaded by level, months along the top
+fn heatmap_chart(days: &[HeatmapDay], locale: Locale) -> String {
+    const CELL: f64 = 13.0;
+    const GAP: f64 = 3.0;
+    let weeks = heatmap::weeks(days);
+    let left = 24.0;
+    let top = 20.0;
+    let width = left + weeks.len() as f64 * (CELL + GAP);
+    let height = top + 7.0 * (CELL + GAP);
+    let mut svg = format!(r#"<svg viewBox="0 0 {width} {height}" xmlns="http://www.w3.org/2000/svg">"#);
+    if weeks.is_empty() {
+        svg.push_str(r#"<text x="50%" y="50%" text-anchor="middle">no data</text></svg>"#);
+        return svg;
+    }
+    for (weekday, label) in [(0, "M"), (2, "W"), (4, "F")] {
+        let _ = write!(svg, r#"<text x="4" y="{:.1}">{label}</text>"#, top + weekday as f64 * (CELL + GAP) + CELL - 2.0);
+    }
+    for (column, week) in weeks.iter().enumerate() {
+        let x = left + column as f64 * (CELL + GAP);
+        if let Some(first) = week.iter().flatten().find(|d| d.date.day() == 1) {
+            let _ = write!(svg, r#"<text x="{x:.1}" y="12">{}</text>"#, locale.month(first.date));
+        }
+        for (weekday, day) in week.iter().enumerate() {
+            let Some(day) = day else { continue };
+            let y = top + weekday as f64 * (CELL + GAP);
+            let _ = write!(svg, r#"<rect x="{x:.1}" y="{y:.1}" width="{CELL}" height="{CELL}" rx="2" fill="{}"><title>{}: {} TSS, {}</title></rect>"#,
+                HEATMAP_COLORS[day.level], locale.date(day.date), locale.number(day.tss, 0), cli::hours_minutes(day.moving_time));
+        }
+    }
+    svg.push_str("</svg>");
+    svg
+}
+
+fn bar_chart(bars: &[(String, f64)], unit: &str, locale: Locale) -> String {
+    let mut svg = format!(r#"<svg viewBox="0 0 {WIDTH} {HEIGHT}" xmlns="http://www.w3.org/2000/svg">"#);
+    if bars.is_empty() {
+        svg.push_str(r#"<text x="50%" y="50%" text-anchor="middle">no data</text></svg>"#);
+        return svg;
+    }
+    let y_max = bars.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+    let (y_min, y_max) = y_axis(&mut svg, 0.0, y_max, unit, locale);
+    let slot = (WIDTH - MARGIN * 1.5) / bars.len() as f64;
+    // Label about every month
+    let every = (bars.len() / 12).max(1);
+    for (i, (label, value)) in bars.iter().enumerate() {
+        let top = scale(*value, y_min, y_max, HEIGHT - MARGIN, MARGIN / 2.0);
+        let left = MARGIN + i as f64 * slot;
+        let _ = write!(svg, r##"<rect x="{:.1}" y="{top:.1}" width="{:.1}" height="{:.1}" fill="#00d4ff"><title>{label}: {} {unit}</title></rect>"##,
+            left + slot * 0.1, slot * 0.8, HEIGHT - MARGIN - top, locale.number(*value, 1));
+        if i % every == 0 {
+            let _ = write!(svg, r#"<text x="{:.1}" y="{}" text-anchor="middle">{label}</text>"#, left + slot / 2.0, HEIGHT - MARGIN + 18.0);
+        }
+    }
+    svg.push_str("</svg>");
+    svg
+}
@@ -0,0 +1,205 @@
+use std::error::Error;
+
+use crate::activity_id::ActivityId;
+use crate::cli;
+use crate::model::ActivityStreams;
+use crate::storage::load_activity_file;
+
+/// Gaps longer than this (in seconds) are pauses, not missed samples
+const MAX_INTERPOLATED_GAP: i32 = 10;
+
+/// What a pause holds once resampled: nothing was pedalled, but heart rate and
+/// altitude carry on from their last value
+#[derive(Debug, Clone, Copy)]
+enum Fill {
+    Zero,
+    Hold,
+}
+
+/// Stream post-processing for exports, from the `--resample`, `--smooth N` and
+/// `--points N` flags, applied in that order
+#[derive(Debug, Clone, Default)]
+pub struct StreamOptions {
+    pub resample: bool,
+    pub smooth: Option<usize>,
+    pub points: Option<usize>,
+}
+
+impl StreamOptions {
+    /// Remove the stream flags from a command's arguments
+    pub fn take(args: &mut Vec<String>) -> Result<Self, String> {
+        Ok(StreamOptions {
+            resample: cli::take_switch(args, "--resample"),
+            smooth: cli::parse_option(args, "--smooth")?,
+            points: cli::parse_option(args, "--points")?,
+        })
+    }
+
+    pub fn apply(&self, streams: &ActivityStreams) -> ActivityStreams {
+        let mut streams = streams.clone();
+        if self.resample {
+            streams = resample(&streams);
+        }
+        if let Some(window) = self.smooth {
+            streams = smooth(&streams, window);
+        }
+        if let Some(points) = self.points {
+            streams = decimate(&streams, points);
+        }
+        streams
+    }
+}
+
+/// Print an activity's streams as JSON, processed for charting or analysis elsewhere
+pub fn run(id: ActivityId, options: &StreamOptions) -> Result<(), Box<dyn Error>> {
+    let file = load_activity_file(id)?;
+    let streams = file.streams.as_ref().ok_or("activity has no streams")?;
+    println!("{}", serde_json::to_string(&options.apply(streams))?);
+    Ok(())
+}
+
+/// Resample every time-indexed stream to one sample per second
+pub fn resample(streams: &ActivityStreams) -> ActivityStreams {
+    let Some(time) = streams.time.as_deref().filter(|t| !t.is_empty()) else { return streams.clone() };
+    let seconds: Vec<i32> = (time[0]..=time[time.len() - 1]).collect();
+    map(streams, Some(seconds), |values, fill| {
+        if values.len() == time.len() {
+            resample_values(time, values, fill)
+        } else {
+            values.to_vec()
+        }
+    })
+}
+
+/// Trailing rolling average over `window` samples of every stream but time
+pub fn smooth(streams: &ActivityStreams, window: usize) -> ActivityStreams {
+    map(streams, streams.time.clone(), |values, _| rolling_mean(values, window))
+}
+
+/// Reduce every stream to at most `points` samples by averaging equal buckets,
+/// for charting; time keeps the start of each bucket
+pub fn decimate(streams: &ActivityStreams, points: usize) -> ActivityStreams {
+    let time = streams.time.as_ref().map(|time| {
+        time.chunks(bucket_size(time.len(), points)).map(|bucket| bucket[0]).collect()
+    });
+    map(streams, time, |values, _| decimate_values(values, points))
+}
+
+fn resample_values(time: &[i32], values: &[f64], fill: Fill) -> Vec<f64> {
+    let mut out = Vec::with_capacity((time[time.len() - 1] - time[0] + 1).max(0) as usize);
+    for i in 0..time.len() {
+        // Of duplicate timestamps, keep the last sample
+        if time.get(i + 1).is_some_and(|&next| next <= time[i]) {
+            continue;
+        }
+        out.push(values[i]);
+        let Some(&next) = time.get(i + 1) else { break };
+        let gap = next - time[i];
+        for step in 1..gap {
+            out.push(if gap <= MAX_INTERPOLATED_GAP {
+                values[i] + (values[i + 1] - values[i]) * step as f64 / gap as f64
+            } else {
+                match fill {
+                    Fill::Zero => 0.0,
+                    Fill::Hold => values[i],
+                }
+            });
+        }
+    }
+    out
+}
+
+/// Trailing rolling mean over `window` samples (at least one)
+pub fn rolling_mean(values: &[f64], window: usize) -> Vec<f64> {
+    let window = window.max(1);
+    let mut sum = 0.0;
+    let mut out = Vec::with_capacity(values.len());
+    for (i, &value) in values.iter().enumerate() {
+        sum += value;
+        if i >= window {
+            sum -= values[i - window];
+        }
+        out.push(sum / (i + 1).min(window) as f64);
+    }
+    out
+}
+
+pub fn decimate_values(values: &[f64], points: usize) -> Vec<f64> {
+    values
+        .chunks(bucket_size(values.len(), points))
+        .map(|bucket| bucket.iter().sum::<f64>() / bucket.len() as f64)
+        .collect()
+}
+
+/// Indices of at most `points` samples that keep the visual shape of a series
+/// (largest-triangle-three-buckets); the first and last samples are always kept
+pub fn lttb(x: &[f64], y: &[f64], points: usize) -> Vec<usize> {
+    let len = x.len().min(y.len());
+    if points >= len || points < 3 {
+        return (0..len).step_by(bucket_size(len, points)).collect();
+    }
+    let every = (len - 2) as f64 / (points - 2) as f64;
+    let mut selected = Vec::with_capacity(points);
+    let mut a = 0;
+    selected.push(a);
+    for bucket in 0..points - 2 {
+        let start = (bucket as f64 * every) as usize + 1;
+        let end = (((bucket + 1) as f64 * every) as usize + 1).min(len - 1);
+        // The next bucket's average is the third corner of the triangle
+        let next_end = (((bucket + 2) as f64 * every) as usize + 1).min(len);
+        let next = end..next_end.max(end + 1);
+        let (avg_x, avg_y) = (
+            x[next.clone()].iter().sum::<f64>() / next.len() as f64,
+            y[next.clone()].iter().sum::<f64>() / next.len() as f64,
+        );
+        a = (start..end.max(start + 1))
+            .max_by(|&i, &j| {
+                let area = |k: usize| ((x[a] - avg_x) * (y[k] - y[a]) - (x[a] - x[k]) * (avg_y - y[a])).abs();
+                area(i).total_cmp(&area(j))
+            })
+            .unwrap_or(start);
+        selected.push(a);
+    }
+    selected.push(len - 1);
+    selected
+}
+
+fn bucket_size(len: usize, points: usize) -> usize {
+    len.div_ceil(points.max(1)).max(1)
+}
+
+/// Rebuild streams with `f` applied to each time-indexed one; integer streams
+/// go through f64 and are rounded back
+fn map(streams: &ActivityStreams, time: Option<Vec<i32>>, f: impl Fn(&[f64], Fill) -> Vec<f64>) -> ActivityStreams {
+    let floats = |values: &Option<Vec<f64>>, fill| values.as_deref().map(|v| f(v, fill));
+    let ints = |values: &Option<Vec<i32>>, fill| {
+        values.as_deref().map(|v| {
+            let v: Vec<f64> = v.iter().map(|&x| x as f64).collect();
+            f(&v, fill).iter().map(|x| x.round() as i32).collect()
+        })
+    };
+    ActivityStreams {
+        time,
+        watts: floats(&streams.watts, Fill::Zero),
+        heartrate: ints(&streams.heartrate, Fill::Hold),
+        cadence: ints(&streams.cadence, Fill::Zero),
+        velocity_smooth: floats(&streams.velocity_smooth, Fill::Zero),
+        altitude: floats(&streams.altitude, Fill::Hold),
+        latlng: streams.latlng.as_ref().map(|latlng| {
+            let lat: Vec<f64> = latlng.iter().map(|p| p[0]).collect();
+            let lng: Vec<f64> = latlng.iter().map(|p| p[1]).collect();
+            f(&lat, Fill::Hold).into_iter().zip(f(&lng, Fill::Hold)).map(|(lat, lng)| [lat, lng]).collect()
+        }),
+        original_watts: floats(&streams.original_watts, Fill::Zero),
+        original_heartrate: ints(&streams.original_heartrate, Fill::Hold),
+        resolution: streams.resolution.clone(),
+        series_type: streams.series_type.clone(),
+        original_size: streams.original_size,
+        missing_series: streams.missing_series.clone(),
+        // Beat-to-beat, not time-indexed
+        rr_intervals: streams.rr_intervals.clone(),
+        w_balance: floats(&streams.w_balance, Fill::Hold),
+        estimated_watts: floats(&streams.estimated_watts, Fill::Zero),
+        misaligned: streams.misaligned.clone(),
+    }
+}
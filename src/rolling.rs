@@ -0,0 +1,154 @@
+//! Rolling 7, 28 and 90-day totals of distance, moving time, TSS and kJ for
+//! every day since the first ride, kept in `data/aggregates.json` and
+//! rewritten on each sync. The file is columnar (one array per window and
+//! metric, a value per day from `start`), so a chart reads a few thousand
+//! integers instead of opening every activity file.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::cli::{self, OutputFormat};
+use crate::config::Config;
+use crate::format;
+use crate::model::ActivityIndex;
+use crate::storage::write_atomic;
+use crate::training_load::{self, DailyLoad};
+
+/// Days in each rolling window
+pub const WINDOWS: [usize; 3] = [7, 28, 90];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RollingSeries {
+    pub schema_version: u32,
+    pub updated_at: String,
+    /// Day of the first value in every array; the last is today
+    pub start: Option<NaiveDate>,
+    /// Keyed by window length in days
+    pub windows: BTreeMap<usize, Window>,
+}
+
+/// Trailing sums ending on each day, that day included
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Window {
+    /// Metres
+    pub distance: Vec<i64>,
+    /// Seconds
+    pub moving_time: Vec<i64>,
+    /// PMC load (TSS, rTSS, TRIMP or the moving-time estimate)
+    pub tss: Vec<i64>,
+    pub kilojoules: Vec<i64>,
+}
+
+/// Daily totals, indexed by days since the first ride
+#[derive(Debug, Default, Clone, Copy)]
+struct Day {
+    distance: f64,
+    moving_time: i64,
+    tss: f64,
+    kilojoules: f64,
+}
+
+/// The rolling series of the whole archive, its TSS from the PMC's daily `loads`
+pub fn compute(index: &ActivityIndex, loads: &[DailyLoad]) -> RollingSeries {
+    let counted: Vec<_> = index.activities.iter().filter(|a| a.counted()).collect();
+    let today = Utc::now().date_naive();
+    let start = counted.iter().map(|a| a.local_date()).min();
+    let mut windows: BTreeMap<usize, Window> = WINDOWS.iter().map(|&w| (w, Window::default())).collect();
+    let Some(first) = start else {
+        return RollingSeries { schema_version: format::SCHEMA_VERSION, updated_at: Utc::now().to_rfc3339(), start, windows };
+    };
+
+    let mut days = vec![Day::default(); ((today - first).num_days() + 1).max(0) as usize];
+    for summary in counted {
+        let Some(day) = days.get_mut((summary.local_date() - first).num_days() as usize) else { continue };
+        day.distance += summary.distance;
+        day.moving_time += summary.moving_time as i64;
+        day.kilojoules += summary.kilojoules.unwrap_or(0.0);
+    }
+    // The PMC's daily load, so the 7 and 28-day TSS match ATL and CTL's inputs
+    for load in loads.iter().filter(|d| d.date >= first) {
+        if let Some(day) = days.get_mut((load.date - first).num_days() as usize) {
+            day.tss = load.tss;
+        }
+    }
+
+    for (&length, window) in windows.iter_mut() {
+        let mut sum = Day::default();
+        for i in 0..days.len() {
+            sum.distance += days[i].distance;
+            sum.moving_time += days[i].moving_time;
+            sum.tss += days[i].tss;
+            sum.kilojoules += days[i].kilojoules;
+            if let Some(old) = i.checked_sub(length).map(|j| days[j]) {
+                sum.distance -= old.distance;
+                sum.moving_time -= old.moving_time;
+                sum.tss -= old.tss;
+                sum.kilojoules -= old.kilojoules;
+            }
+            window.distance.push(sum.distance.round() as i64);
+            window.moving_time.push(sum.moving_time);
+            window.tss.push(sum.tss.round() as i64);
+            window.kilojoules.push(sum.kilojoules.round() as i64);
+        }
+    }
+    RollingSeries { schema_version: format::SCHEMA_VERSION, updated_at: Utc::now().to_rfc3339(), start, windows }
+}
+
+/// Write data/aggregates.json from the current index and its daily load
+pub fn write_series(index: &ActivityIndex, loads: &[DailyLoad]) -> Result<RollingSeries, Box<dyn Error>> {
+    let series = compute(index, loads);
+    write_atomic(&format::aggregates_path(), serde_json::to_string(&series)?.as_bytes())?;
+    Ok(series)
+}
+
+#[derive(Debug, Serialize)]
+struct Row {
+    days: usize,
+    distance: i64,
+    moving_time: i64,
+    tss: i64,
+    kilojoules: i64,
+}
+
+/// Refresh data/aggregates.json and print the windows ending today
+pub fn run(output: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let index = ActivityIndex::load()?;
+    let series = write_series(&index, &training_load::daily(&index, &Config::current()?))?;
+    let rows: Vec<Row> = series
+        .windows
+        .iter()
+        .filter_map(|(&days, w)| {
+            Some(Row {
+                days,
+                distance: *w.distance.last()?,
+                moving_time: *w.moving_time.last()?,
+                tss: *w.tss.last()?,
+                kilojoules: *w.kilojoules.last()?,
+            })
+        })
+        .collect();
+    if output == OutputFormat::Json {
+        return cli::print_json(&rows);
+    }
+    let Some(start) = series.start else {
+        info!("📈 No rides yet");
+        return Ok(());
+    };
+    info!("📈 Rolling totals up to today ({} days since {}, data/aggregates.json)", (Utc::now().date_naive() - start).num_days() + 1, start);
+    println!("  {:>7} {:>8} {:>7} {:>6} {:>7}", "window", "km", "hours", "TSS", "kJ");
+    for row in &rows {
+        println!(
+            "  {:>6}d {:>8.1} {:>7.1} {:>6} {:>7}",
+            row.days,
+            row.distance as f64 / 1000.0,
+            row.moving_time as f64 / 3600.0,
+            row.tss,
+            row.kilojoules
+        );
+    }
+    Ok(())
+}
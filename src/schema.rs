@@ -0,0 +1,53 @@
+use std::error::Error;
+
+use serde_json::Value;
+
+use crate::format;
+
+/// Version written into index.json and every activity file.
+/// Bump `format::SCHEMA_VERSION` together with a new entry in the migration tables below.
+pub const CURRENT_VERSION: u32 = format::SCHEMA_VERSION;
+
+type Migration = fn(&mut Value);
+
+/// `ACTIVITY_MIGRATIONS[n]` upgrades an activity file from version n to n + 1
+const ACTIVITY_MIGRATIONS: [Migration; CURRENT_VERSION as usize] = [
+    // v0 -> v1: files written before versioning only lack the version field
+    |_| {},
+];
+
+/// `INDEX_MIGRATIONS[n]` upgrades index.json from version n to n + 1
+const INDEX_MIGRATIONS: [Migration; CURRENT_VERSION as usize] = [
+    |_| {},
+];
+
+pub fn version_of(value: &Value) -> u32 {
+    value.get("schema_version").and_then(|v| v.as_u64()).unwrap_or(0) as u32
+}
+
+/// Upgrade a raw activity file in place. Returns true when anything changed.
+pub fn migrate_activity(value: &mut Value) -> Result<bool, Box<dyn Error>> {
+    migrate(value, &ACTIVITY_MIGRATIONS)
+}
+
+/// Upgrade a raw index.json in place. Returns true when anything changed.
+pub fn migrate_index(value: &mut Value) -> Result<bool, Box<dyn Error>> {
+    migrate(value, &INDEX_MIGRATIONS)
+}
+
+fn migrate(value: &mut Value, migrations: &[Migration]) -> Result<bool, Box<dyn Error>> {
+    let from = version_of(value);
+    if from > CURRENT_VERSION {
+        return Err(format!(
+            "data written by a newer release (schema v{}, this build reads up to v{})",
+            from, CURRENT_VERSION
+        ).into());
+    }
+    for migration in &migrations[from as usize..] {
+        migration(value);
+    }
+    if let Some(object) = value.as_object_mut() {
+        object.insert("schema_version".into(), CURRENT_VERSION.into());
+    }
+    Ok(from != CURRENT_VERSION)
+}
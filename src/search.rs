@@ -0,0 +1,191 @@
+//! Full-text search over ride names, descriptions, notes, tags and Zwift
+//! routes, through a small inverted index in `.watts-cache/search/`.
+//!
+//! The index is rebuilt whenever index.json has changed since it was built
+//! (a sync, an edit, a refetch), so it never needs managing. Words are
+//! lowercased and stripped of accents; every query word must start some word
+//! of a ride ("alp" finds "Alpe d'Huez"), and rides rank by where they matched.
+
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::athlete;
+use crate::cli::{self, OutputFormat};
+use crate::format;
+use crate::model::{ActivityIndex, ActivitySummary};
+use crate::storage::{load_activity_file, write_atomic};
+
+const SEARCH_DIR: &str = ".watts-cache/search";
+
+/// Hits printed unless `--limit` says otherwise
+pub const DEFAULT_LIMIT: usize = 20;
+
+/// How much a match in each field counts
+const NAME_WEIGHT: u32 = 4;
+const TAG_WEIGHT: u32 = 3;
+const ROUTE_WEIGHT: u32 = 2;
+const TEXT_WEIGHT: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SearchIndex {
+    /// Modification time of the index.json this was built from, in milliseconds
+    source_modified: u64,
+    /// Word -> rides containing it, with the weight of the best field it appears in
+    terms: BTreeMap<String, Vec<(ActivityId, u32)>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Hit {
+    id: ActivityId,
+    start_date: DateTime<Utc>,
+    #[serde(skip)]
+    date: NaiveDate,
+    name: String,
+    score: u32,
+}
+
+fn path() -> PathBuf {
+    PathBuf::from(SEARCH_DIR).join(format!("{}.json", athlete::cache_key()))
+}
+
+fn source_modified() -> u64 {
+    fs::metadata(format::index_path())
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Lowercase words without accents, so "Alpe d'Huez" gives alpe, d, huez
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).map(|w| w.chars().map(fold).collect())
+}
+
+fn fold(c: char) -> char {
+    match c.to_lowercase().next().unwrap_or(c) {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => 'a',
+        'ç' => 'c',
+        'è' | 'é' | 'ê' | 'ë' => 'e',
+        'ì' | 'í' | 'î' | 'ï' => 'i',
+        'ñ' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => 'o',
+        'ù' | 'ú' | 'û' | 'ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        lower => lower,
+    }
+}
+
+fn build(index: &ActivityIndex) -> SearchIndex {
+    let mut terms: BTreeMap<String, HashMap<ActivityId, u32>> = BTreeMap::new();
+    let mut add = |id: ActivityId, text: &str, weight: u32| {
+        for word in words(text) {
+            let best = terms.entry(word).or_default().entry(id).or_insert(0);
+            *best = (*best).max(weight);
+        }
+    };
+    for summary in &index.activities {
+        add(summary.id, &summary.name, NAME_WEIGHT);
+        for tag in &summary.tags {
+            add(summary.id, tag, TAG_WEIGHT);
+        }
+        for route in summary.route.iter().chain(&summary.world) {
+            add(summary.id, route, ROUTE_WEIGHT);
+        }
+        if let Some(notes) = &summary.notes {
+            add(summary.id, notes, TEXT_WEIGHT);
+        }
+        // Descriptions are only in the activity files
+        match load_activity_file(summary.id) {
+            Ok(file) => {
+                if let Some(description) = &file.activity.description {
+                    add(summary.id, description, TEXT_WEIGHT);
+                }
+            }
+            Err(e) => warn!("   ⚠️  {}: {}", summary.id, e),
+        }
+    }
+    SearchIndex {
+        source_modified: source_modified(),
+        terms: terms.into_iter().map(|(word, ids)| (word, ids.into_iter().collect())).collect(),
+    }
+}
+
+/// The search index, rebuilt first if index.json changed since
+fn load(index: &ActivityIndex) -> Result<SearchIndex, Box<dyn Error>> {
+    let path = path();
+    if let Ok(json) = fs::read_to_string(&path) {
+        match serde_json::from_str::<SearchIndex>(&json) {
+            // An index kept in a remote store has no modification time to compare
+            Ok(search) if search.source_modified != 0 && search.source_modified == source_modified() => return Ok(search),
+            Ok(_) => debug!("   Search index is stale"),
+            Err(e) => debug!("   Ignoring {}: {}", path.display(), e),
+        }
+    }
+    info!("🗂️  Indexing {} activities for search...", index.activities.len());
+    let search = build(index);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_atomic(&path, serde_json::to_string(&search)?.as_bytes())?;
+    Ok(search)
+}
+
+/// Print the rides matching every word of `query`, best first
+pub fn run(query: &str, limit: usize, output: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let query: Vec<String> = words(query).collect();
+    if query.is_empty() {
+        return Err("nothing to search for".into());
+    }
+    let index = ActivityIndex::load()?;
+    let search = load(&index)?;
+
+    // Per query word, the best weight each ride reaches over the words it prefixes
+    let mut scores: Option<HashMap<ActivityId, u32>> = None;
+    for word in &query {
+        let mut matched: HashMap<ActivityId, u32> = HashMap::new();
+        for (_, postings) in search.terms.range(word.clone()..).take_while(|(term, _)| term.starts_with(word.as_str())) {
+            for &(id, weight) in postings {
+                let best = matched.entry(id).or_insert(0);
+                *best = (*best).max(weight);
+            }
+        }
+        scores = Some(match scores {
+            None => matched,
+            Some(scores) => scores
+                .into_iter()
+                .filter_map(|(id, score)| matched.get(&id).map(|weight| (id, score + weight)))
+                .collect(),
+        });
+    }
+
+    let summaries: HashMap<ActivityId, &ActivitySummary> = index.activities.iter().map(|a| (a.id, a)).collect();
+    let mut hits: Vec<Hit> = scores
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(id, score)| {
+            let summary = summaries.get(&id)?;
+            Some(Hit { id, start_date: summary.start_date, date: summary.local_date(), name: summary.name.clone(), score })
+        })
+        .collect();
+    // Best match first, then newest
+    hits.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| b.start_date.cmp(&a.start_date)));
+    let found = hits.len();
+    hits.truncate(limit);
+
+    if output == OutputFormat::Json {
+        return cli::print_json(&hits);
+    }
+    info!("🔎 {} rides match \"{}\"", found, query.join(" "));
+    for hit in &hits {
+        println!("  {:<10} {:<16} {}", hit.date, hit.id.to_string(), hit.name);
+    }
+    Ok(())
+}
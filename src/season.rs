@@ -0,0 +1,81 @@
+//! Named training periods ("2024-25 winter block") listed under `"seasons"` in
+//! watts.json. `report`, `best-efforts` and `stats range` take `--season NAME`
+//! to compare power curves, bests and totals between them instead of between
+//! arbitrary dates. A bare year such as `2024` is the calendar year.
+
+use std::error::Error;
+
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::model::ActivitySummary;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Season {
+    pub name: String,
+    pub start: NaiveDate,
+    /// Last day, included
+    pub end: NaiveDate,
+}
+
+impl Season {
+    /// The configured season called `name` (case-insensitive), or the calendar year `name`
+    pub fn resolve(config: &Config, name: &str) -> Result<Season, String> {
+        if let Some(season) = config.seasons.iter().find(|s| s.name.eq_ignore_ascii_case(name.trim())) {
+            if season.end < season.start {
+                return Err(format!("season '{}' in watts.json ends before it starts", season.name));
+            }
+            return Ok(season.clone());
+        }
+        match name.trim().parse::<i32>() {
+            Ok(year) => Season::calendar_year(year).ok_or_else(|| format!("invalid year '{}'", name)),
+            Err(_) => {
+                let known: Vec<&str> = config.seasons.iter().map(|s| s.name.as_str()).collect();
+                Err(if known.is_empty() {
+                    format!("unknown season '{}': add it under \"seasons\" in watts.json or pass a year", name)
+                } else {
+                    format!("unknown season '{}', expected a year or one of: {}", name, known.join(", "))
+                })
+            }
+        }
+    }
+
+    pub fn calendar_year(year: i32) -> Option<Season> {
+        Some(Season {
+            name: year.to_string(),
+            start: NaiveDate::from_ymd_opt(year, 1, 1)?,
+            end: NaiveDate::from_ymd_opt(year, 12, 31)?,
+        })
+    }
+
+    /// The last `days` days up to today
+    pub fn last_days(days: i64) -> Season {
+        let today = Utc::now().date_naive();
+        Season { name: format!("last {} days", days), start: today - Duration::days(days), end: today }
+    }
+
+    /// The year this season is, when it is exactly a calendar year
+    pub fn year(&self) -> Option<i32> {
+        let year = Season::calendar_year(self.start.year())?;
+        (year.start == self.start && year.end == self.end).then(|| self.start.year())
+    }
+
+    pub fn contains(&self, day: NaiveDate) -> bool {
+        day >= self.start && day <= self.end
+    }
+
+    /// Whether a ride started within the season, by its local start date
+    pub fn includes(&self, summary: &ActivitySummary) -> bool {
+        self.contains(summary.local_date())
+    }
+}
+
+/// Resolve every `--season` given, against the active athlete's watts.json
+pub fn resolve_all(names: &[String]) -> Result<Vec<Season>, Box<dyn Error>> {
+    if names.is_empty() {
+        return Ok(Vec::new());
+    }
+    let config = Config::current()?;
+    Ok(names.iter().map(|name| Season::resolve(&config, name)).collect::<Result<_, _>>()?)
+}
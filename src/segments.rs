@@ -0,0 +1,276 @@
+//! Starred segments: your PR and the KOM (or QOM) time of each, refreshed from
+//! Strava at most every `refresh_hours` by sync, with every change kept in
+//! data/segments.json. A faster PR or a new course record is logged and
+//! posted to the webhooks, for picking which Zwift segment to hunt next.
+
+use std::error::Error;
+use std::fs;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::cli::{self, clock, OutputFormat};
+use crate::config::Config;
+use crate::format;
+use crate::storage::write_atomic;
+use crate::strava::{self, access_token};
+
+/// Starred segments per page
+const PER_PAGE: u32 = 200;
+
+/// Pause between segment requests
+const REQUEST_DELAY_MS: u64 = 500;
+
+/// Segment tracking, under `"segments"` in watts.json
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentSettings {
+    /// Hours between two refreshes by sync, each costing a request per starred segment
+    #[serde(default = "default_refresh_hours")]
+    pub refresh_hours: u32,
+    /// Follow the QOM rather than the KOM
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub qom: bool,
+}
+
+fn default_refresh_hours() -> u32 {
+    24
+}
+
+impl Default for SegmentSettings {
+    fn default() -> Self {
+        SegmentSettings { refresh_hours: default_refresh_hours(), qom: false }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SegmentArchive {
+    pub schema_version: u32,
+    /// When the starred list was last read; empty before the first fetch
+    pub updated_at: String,
+    pub segments: Vec<TrackedSegment>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackedSegment {
+    pub id: i64,
+    pub name: String,
+    /// Metres
+    pub distance: f64,
+    /// Percent
+    pub average_grade: f64,
+    /// False once unstarred; the history stays
+    pub starred: bool,
+    pub fetched_at: Option<DateTime<Utc>>,
+    /// Your best elapsed time, seconds
+    pub pr: Option<i32>,
+    pub pr_date: Option<String>,
+    pub efforts: Option<i64>,
+    /// KOM, or QOM with `"qom": true`, seconds
+    pub record: Option<i32>,
+    /// One entry per fetch that saw the PR or the record change, oldest first
+    pub history: Vec<SegmentSnapshot>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentSnapshot {
+    pub fetched_at: DateTime<Utc>,
+    pub pr: Option<i32>,
+    pub record: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SegmentChange {
+    pub id: i64,
+    pub name: String,
+    /// "pr", "kom" or "qom"
+    pub kind: &'static str,
+    /// Seconds; None on a first effort
+    pub previous: Option<i32>,
+    pub current: Option<i32>,
+    pub message: String,
+}
+
+pub fn load() -> Result<SegmentArchive, Box<dyn Error>> {
+    match fs::read_to_string(format::segments_path()) {
+        Ok(json) => Ok(serde_json::from_str(&json)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SegmentArchive::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn save(archive: &mut SegmentArchive) -> Result<(), Box<dyn Error>> {
+    archive.schema_version = format::SCHEMA_VERSION;
+    write_atomic(&format::segments_path(), serde_json::to_string_pretty(archive)?.as_bytes())
+}
+
+/// Whether sync should refresh the segments: tracking is on and the last
+/// refresh is older than `refresh_hours`
+pub fn due(config: &Config) -> bool {
+    let Some(settings) = &config.segments else { return false };
+    let Ok(archive) = load() else { return true };
+    DateTime::parse_from_rfc3339(&archive.updated_at)
+        .map_or(true, |last| Utc::now() - last.with_timezone(&Utc) >= Duration::hours(settings.refresh_hours as i64))
+}
+
+/// Seconds of a Strava course record time: "45s", "5:02" or "1:02:03"
+fn parse_clock(s: &str) -> Option<i32> {
+    let s = s.trim();
+    if let Some(seconds) = s.strip_suffix('s') {
+        return seconds.trim().parse().ok();
+    }
+    s.split(':').try_fold(0, |total, part| Some(total * 60 + part.trim().parse::<i32>().ok()?))
+}
+
+/// Read the starred list and refresh the segments, least recently fetched
+/// first, within the rate-limit budget; returns the PRs and records that changed
+pub async fn fetch(access_token: &str, config: &Config) -> Result<Vec<SegmentChange>, Box<dyn Error>> {
+    let settings = config.segments.clone().unwrap_or_default();
+    let record_kind = if settings.qom { "qom" } else { "kom" };
+    let mut archive = load()?;
+
+    let mut starred = Vec::new();
+    for page in 1.. {
+        let batch = strava::fetch_starred_segments(access_token, page, PER_PAGE).await?;
+        let full = batch.len() == PER_PAGE as usize;
+        starred.extend(batch);
+        if !full {
+            break;
+        }
+    }
+    for segment in &mut archive.segments {
+        segment.starred = starred.iter().any(|s| s.id == segment.id);
+    }
+    for segment in &starred {
+        if !archive.segments.iter().any(|s| s.id == segment.id) {
+            archive.segments.push(TrackedSegment {
+                id: segment.id,
+                name: segment.name.clone(),
+                distance: 0.0,
+                average_grade: 0.0,
+                starred: true,
+                fetched_at: None,
+                pr: None,
+                pr_date: None,
+                efforts: None,
+                record: None,
+                history: Vec::new(),
+            });
+        }
+    }
+
+    // None sorts first
+    let mut order: Vec<usize> = (0..archive.segments.len()).filter(|&i| archive.segments[i].starred).collect();
+    order.sort_by_key(|&i| archive.segments[i].fetched_at);
+    let batch = strava::within_budget(order.len());
+    let now = Utc::now();
+    let mut changes = Vec::new();
+    for (n, &i) in order.iter().take(batch).enumerate() {
+        let tracked = &mut archive.segments[i];
+        let segment = match strava::fetch_segment(access_token, tracked.id).await {
+            Ok(segment) => segment,
+            Err(e) => {
+                warn!("   ⚠️  Could not fetch segment {} ({}): {}", tracked.id, tracked.name, e);
+                continue;
+            }
+        };
+        let stats = segment.athlete_segment_stats;
+        let pr = stats.as_ref().and_then(|s| s.pr_elapsed_time);
+        let xoms = segment.xoms.as_ref();
+        let record = xoms.and_then(|x| if settings.qom { x.qom.as_deref() } else { x.kom.as_deref() }).and_then(parse_clock);
+
+        // A segment's first fetch sets the baseline, nothing changed yet
+        if tracked.fetched_at.is_some() {
+            if pr.is_some() && pr.zip(tracked.pr).is_none_or(|(new, old)| new < old) {
+                changes.push(SegmentChange {
+                    id: tracked.id,
+                    name: segment.name.clone(),
+                    kind: "pr",
+                    previous: tracked.pr,
+                    current: pr,
+                    message: format!(
+                        "⭐ New PR on {}: {}{}",
+                        segment.name,
+                        pr.map(clock).unwrap_or_default(),
+                        tracked.pr.map(|old| format!(" (was {})", clock(old))).unwrap_or_default()
+                    ),
+                });
+            }
+            if record.is_some() && tracked.record.is_some() && record != tracked.record {
+                changes.push(SegmentChange {
+                    id: tracked.id,
+                    name: segment.name.clone(),
+                    kind: record_kind,
+                    previous: tracked.record,
+                    current: record,
+                    message: format!(
+                        "👑 {} on {} now {} (was {})",
+                        record_kind.to_uppercase(),
+                        segment.name,
+                        record.map(clock).unwrap_or_default(),
+                        tracked.record.map(clock).unwrap_or_default()
+                    ),
+                });
+            }
+        }
+        if tracked.history.last().is_none_or(|last| last.pr != pr || last.record != record) {
+            tracked.history.push(SegmentSnapshot { fetched_at: now, pr, record });
+        }
+        tracked.name = segment.name;
+        tracked.distance = segment.distance;
+        tracked.average_grade = segment.average_grade;
+        tracked.fetched_at = Some(now);
+        tracked.pr = pr;
+        tracked.pr_date = stats.as_ref().and_then(|s| s.pr_date.clone());
+        tracked.efforts = stats.and_then(|s| s.effort_count);
+        tracked.record = record;
+        if n + 1 < batch {
+            tokio::time::sleep(tokio::time::Duration::from_millis(REQUEST_DELAY_MS)).await;
+        }
+    }
+    archive.updated_at = now.to_rfc3339();
+    save(&mut archive)?;
+
+    info!("🏔️  Refreshed {} of {} starred segments", batch, order.len());
+    for change in &changes {
+        info!("   {}", change.message);
+    }
+    Ok(changes)
+}
+
+/// Print the starred segments with your PR against the course record,
+/// fetching them first with `fetch_first`
+pub async fn run(fetch_first: bool, output: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let config = Config::current()?;
+    if fetch_first {
+        fetch(&access_token().await?, &config).await?;
+    }
+    let archive = load()?;
+    let starred: Vec<&TrackedSegment> = archive.segments.iter().filter(|s| s.starred).collect();
+    if output == OutputFormat::Json {
+        return cli::print_json(&starred);
+    }
+    if starred.is_empty() {
+        info!("🏔️  No starred segments stored, run `segments --fetch`");
+        return Ok(());
+    }
+    let record_label = if config.segments.as_ref().is_some_and(|s| s.qom) { "QOM" } else { "KOM" };
+    info!("🏔️  {} starred segments (last fetched {})", starred.len(), archive.updated_at.get(..10).unwrap_or("never"));
+    println!("  {:<30} {:>6} {:>6} {:>8} {:>8} {:>7} {:>7}  PR date", "segment", "km", "grade", "PR", record_label, "behind", "efforts");
+    for s in starred {
+        let or_dash = |v: Option<i32>| v.map_or_else(|| "-".to_string(), clock);
+        let behind = s.pr.zip(s.record).filter(|(_, record)| *record > 0).map(|(pr, record)| (pr - record) as f64 / record as f64 * 100.0);
+        println!(
+            "  {:<30} {:>6.2} {:>5.1}% {:>8} {:>8} {:>7} {:>7}  {}",
+            s.name.chars().take(30).collect::<String>(),
+            s.distance / 1000.0,
+            s.average_grade,
+            or_dash(s.pr),
+            or_dash(s.record),
+            behind.map_or_else(|| "-".to_string(), |b| format!("{:.1}%", b)),
+            s.efforts.map_or_else(|| "-".to_string(), |e| e.to_string()),
+            s.pr_date.as_deref().and_then(|d| d.get(..10)).unwrap_or("-")
+        );
+    }
+    Ok(())
+}
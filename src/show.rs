@@ -0,0 +1,213 @@
+//! `show <id>`: one ride at a glance in the terminal, from its metadata and
+//! derived metrics down to the zone split, the intervals and a sparkline of
+//! its power (full power and heart rate charts with `--chart`).
+
+use std::error::Error;
+
+use crate::activity_id::ActivityId;
+use crate::anomalies;
+use crate::ascii_chart;
+use crate::athlete_history;
+use crate::cli::{clock, hours_minutes};
+use crate::config::{self, Config};
+use crate::elevation;
+use crate::intervals::{self, IntervalSource};
+use crate::matches;
+use crate::metrics;
+use crate::pace;
+use crate::pacing;
+use crate::pedalling;
+use crate::storage::load_or_fetch_activity;
+use crate::streamless;
+use crate::training_load;
+use crate::w_balance;
+use crate::wkg;
+use crate::zwift;
+
+/// Characters of a zone bar at 100%
+const ZONE_BAR_WIDTH: usize = 30;
+
+/// Rows of the `--chart` power and heart rate charts
+const CHART_HEIGHT: usize = 8;
+
+/// Print a summary of one activity, fetching it on demand when allowed
+pub async fn run(id: ActivityId, fetch_missing: bool, chart: bool) -> Result<(), Box<dyn Error>> {
+    let file = load_or_fetch_activity(id, fetch_missing || config::flag("WATTS_READ_THROUGH")).await?;
+    let activity = &file.activity;
+    let config = Config::current()?;
+
+    println!("{} (id: {})", activity.name, activity.id);
+    println!("  Date:       {}", activity.start_date_local);
+    println!("  Sport:      {}", activity.sport_type);
+    let anomalies = anomalies::detect(&file);
+    if !anomalies.is_empty() {
+        println!("  Suspect:    {}{}", anomalies.join(", "), if config.include_suspect { "" } else { " (left out of records)" });
+    }
+    if let (world, Some(route)) = zwift::detect(&file) {
+        println!("  Route:      {}{}", route, world.map(|w| format!(" ({})", w)).unwrap_or_default());
+    }
+    println!("  Distance:   {:.1} km", activity.distance / 1000.0);
+    println!("  Moving:     {}", hours_minutes(activity.moving_time));
+    let recomputed = elevation::check(&file).map(|check| {
+        let flag = if check.flagged(elevation::DEFAULT_THRESHOLD_PERCENT) { " ⚠️" } else { "" };
+        format!(", {:.0} m from the altitude stream{}", check.gain, flag)
+    });
+    println!("  Elevation:  {:.0} m{}", activity.total_elevation_gain, recomputed.unwrap_or_default());
+    let history = athlete_history::load()?;
+    if let Some(watts) = activity.average_watts {
+        let weighted = activity.weighted_average_watts.unwrap_or(watts);
+        let weight = activity.local_start().and_then(|start| athlete_history::weight_at(&history, start.date()));
+        println!("  Power:      {:.0} W avg, {:.0} W weighted{}",
+            watts,
+            weighted,
+            weight.map(|kg| format!(" ({:.2} W/kg)", weighted / kg)).unwrap_or_default()
+        );
+    }
+    if let Some(hr) = activity.average_heartrate {
+        println!("  Heart rate: {:.0} bpm avg", hr);
+    }
+    if let Some(load) = streamless::estimated_load(&activity.sport_type, activity.moving_time, &config) {
+        println!("  Load:       ~{:.0} TSS, estimated from moving time", load);
+    }
+    let streams = file.streams.as_ref();
+    let watts = streams.and_then(|s| s.watts.as_deref());
+    let run = pace::is_run(&activity.sport_type);
+    if let Some(graded) = streams.and_then(pace::normalized_graded_speed).filter(|_| run) {
+        let load = pace::threshold_speed(&config).map(|threshold| {
+            format!(", IF {:.2}, rTSS {:.0}", graded / threshold, pace::running_stress_score(graded, activity.moving_time, threshold))
+        });
+        let average = activity.distance / activity.moving_time.max(1) as f64;
+        println!("  Pace:       {} avg, {} graded{}", pace::format_pace(average), pace::format_pace(graded), load.unwrap_or_default());
+    }
+    if let Some(np) = watts.filter(|_| !run).and_then(metrics::normalized_power) {
+        let load = config.ftp().map(|ftp| {
+            format!(", IF {:.2}, TSS {:.0}", np / ftp, metrics::training_stress_score(np, activity.moving_time, ftp))
+        });
+        let variability = activity.average_watts.filter(|&w| w > 0.0).map(|w| format!(", VI {:.2}", np / w));
+        println!("  Load:       NP {:.0} W{}{}", np, load.unwrap_or_default(), variability.unwrap_or_default());
+    }
+    let estimated = streams.and_then(|s| s.estimated_watts.as_deref()).filter(|w| !w.is_empty());
+    if let Some(average) = estimated.and_then(metrics::mean) {
+        let np = estimated.and_then(metrics::normalized_power).map(|np| format!(", ~{:.0} W NP", np));
+        println!("  Estimated:  ~{:.0} W avg{} from speed and grade (no power meter)", average, np.unwrap_or_default());
+    }
+    if let (Some(watts), Some(hr)) = (watts.filter(|_| !run), streams.and_then(|s| s.heartrate.as_deref())) {
+        if let Some(ef) = metrics::efficiency_factor(watts, hr) {
+            println!("  Efficiency: {:.2} W/bpm{}", ef,
+                metrics::decoupling(watts, hr).map(|d| format!(", {:.1}% Pw:HR decoupling", d)).unwrap_or_default());
+        }
+    }
+    if let Some(stats) = pedalling::stats(&file, config.ftp()).filter(|_| !run) {
+        let below_half = stats.below_half_ftp_percent.map(|p| format!(", {:.1}% below 50% FTP", p));
+        println!("  Pedalling:  {:.1}% at 0 W{}, longest block {}", stats.zero_percent, below_half.unwrap_or_default(),
+            clock(stats.longest_block));
+    }
+    if let Some(stats) = pacing::pacing(&file).filter(|_| !run) {
+        println!("  Pacing:     score {:.0}/100", stats.score);
+    }
+    let settings = config.matches.clone().unwrap_or_default();
+    if let Some(burned) = config.ftp().filter(|_| !run).and_then(|ftp| matches::detect(&file, ftp, &settings)) {
+        println!("  Matches:    {} over {:.0}% FTP for {} s+, {:.1} kJ", burned.len(), settings.threshold_percent,
+            settings.min_seconds, burned.iter().fold(0.0, |total, m| total + m.kilojoules));
+        for m in &burned {
+            println!("              {} for {} s at {:.0} W ({:.1} kJ)", clock(m.start), m.duration, m.average_watts, m.kilojoules);
+        }
+    }
+    let peaks: Vec<String> = wkg::DURATIONS
+        .iter()
+        .zip(wkg::peaks(&file, &history))
+        .filter_map(|((_, label), peak)| {
+            let (watts, wkg) = peak?;
+            Some(format!("{} {:.0} W{}", label, watts, wkg.map(|w| format!(" ({:.2} W/kg)", w)).unwrap_or_default()))
+        })
+        .collect();
+    if !peaks.is_empty() {
+        println!("  Peaks:      {}", peaks.join(", "));
+    }
+    if let Some(trimp) = streams.and_then(|s| training_load::streams_trimp(s, &config)) {
+        println!("  TRIMP:      {:.0}", trimp);
+    }
+    if let Some(social) = &file.social {
+        println!("  Social:     {} kudos, {} comments, {} photos",
+            social.kudoers.len(), social.comments.len(), social.photos.len());
+    }
+    let points = file.streams.as_ref().and_then(|s| s.time.as_ref()).map(|t| t.len()).unwrap_or(0);
+    println!("  Streams:    {} data points", points);
+    if let Some((_, w_prime)) = w_balance::model(&config) {
+        if let Some((minimum, below_half)) = w_balance::summary(&file, w_prime) {
+            println!("  W'bal:      {:.1} kJ min ({:.0}%), {} below 50%",
+                minimum / 1000.0, minimum / w_prime * 100.0, clock(below_half));
+        }
+    }
+
+    if let (Some(watts), Some(floors)) = (watts, config.power_zone_floors()) {
+        let seconds = metrics::time_in_zones(watts, &floors);
+        let total: i32 = seconds.iter().sum();
+        if total > 0 {
+            println!();
+            println!("  Power zones");
+            for (zone, &spent) in seconds.iter().enumerate() {
+                let range = match floors.get(zone + 1) {
+                    Some(next) => format!("{:.0}-{:.0} W", floors[zone], next - 1.0),
+                    None => format!("{:.0}+ W", floors[zone]),
+                };
+                let share = spent as f64 / total as f64;
+                let line = format!("  Z{:<2} {:>11}  {:>8}  {:>3.0}%  {}",
+                    zone + 1,
+                    range,
+                    clock(spent),
+                    share * 100.0,
+                    "█".repeat((share * ZONE_BAR_WIDTH as f64).round() as usize)
+                );
+                println!("{}", line.trim_end());
+            }
+        }
+    }
+    let measured = watts.filter(|w| !w.is_empty());
+    if let Some(watts) = measured.or(estimated) {
+        println!();
+        println!("  {} ({} samples, peak {:.0} W)",
+            if measured.is_some() { "Power" } else { "Estimated power" },
+            watts.len(),
+            watts.iter().copied().fold(0.0, f64::max)
+        );
+        if chart {
+            for line in ascii_chart::area_chart(watts, ascii_chart::width(), CHART_HEIGHT) {
+                println!("  {}", line);
+            }
+        } else {
+            println!("  {}", ascii_chart::sparkline(watts, ascii_chart::width()));
+        }
+    }
+    let heartrate = streams.and_then(|s| s.heartrate.as_deref()).filter(|h| !h.is_empty());
+    if let (true, Some(heartrate)) = (chart, heartrate) {
+        let bpm: Vec<f64> = heartrate.iter().map(|&h| h as f64).collect();
+        println!();
+        println!("  Heart rate ({} samples, max {} bpm)", bpm.len(), heartrate.iter().max().unwrap_or(&0));
+        for line in ascii_chart::area_chart(&bpm, ascii_chart::width(), CHART_HEIGHT) {
+            println!("  {}", line);
+        }
+    }
+
+    let (source, intervals) = intervals::intervals(&file);
+    if !intervals.is_empty() {
+        let time = file.streams.as_ref().and_then(|s| s.time.as_ref());
+        println!();
+        println!("  Intervals ({})", match source {
+            IntervalSource::Laps => "from laps",
+            IntervalSource::Detected => "detected from power",
+        });
+        println!("  {:>3}  {:>6}  {:>6}  {:>6}  {:>5}", "#", "Start", "Time", "Power", "HR");
+        for (i, interval) in intervals.iter().enumerate() {
+            let start = time.and_then(|t| t.get(interval.start)).copied().unwrap_or(interval.start as i32);
+            println!("  {:>3}  {:>6}  {:>6}  {:>6}  {:>5}",
+                i + 1,
+                format!("{}:{:02}", start / 60, start % 60),
+                format!("{}:{:02}", interval.duration / 60, interval.duration % 60),
+                interval.average_watts.map(|w| format!("{:.0}W", w)).unwrap_or_default(),
+                interval.average_heartrate.map(|h| format!("{:.0}", h)).unwrap_or_default()
+            );
+        }
+    }
+    Ok(())
+}
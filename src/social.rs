@@ -0,0 +1,60 @@
+use std::error::Error;
+
+use chrono::NaiveDate;
+use tracing::{info, warn};
+
+use crate::model::ActivityIndex;
+use crate::progress::Progress;
+use crate::storage::{load_activity_file, stored_compression, write_activity_file};
+use crate::strava::{self, access_token, fetch_activity_social};
+
+/// Requests per activity: kudos, comments and photos
+const REQUESTS_PER_ACTIVITY: usize = 3;
+
+/// Refresh kudos, comments and photos of stored activities, never-fetched and
+/// least recently fetched first, within `budget` requests
+pub async fn run(since: Option<NaiveDate>, budget: usize) -> Result<(), Box<dyn Error>> {
+    let index = ActivityIndex::load()?;
+    let budget = strava::within_budget(budget);
+
+    let mut candidates = Vec::new();
+    for summary in index.activities.iter().filter(|a| since.is_none_or(|s| a.local_date() >= s)) {
+        match load_activity_file(summary.id) {
+            Ok(file) => candidates.push(file),
+            Err(e) => warn!("   ⚠️  Skipping {}: {}", summary.id, e),
+        }
+    }
+    // None sorts first
+    candidates.sort_by_key(|f| f.social.as_ref().map(|s| s.fetched_at.clone()));
+    let batch = (budget / REQUESTS_PER_ACTIVITY).min(candidates.len());
+    info!("💬 Fetching kudos, comments and photos for {} of {} activities", batch, candidates.len());
+    if batch == 0 {
+        return Ok(());
+    }
+
+    let access_token = access_token().await?;
+    let (mut kudos, mut comments, mut photos) = (0, 0, 0);
+    let mut progress = Progress::new("💬 Fetching", batch);
+    for (i, mut file) in candidates.into_iter().take(batch).enumerate() {
+        let id = file.activity.id;
+        progress.item(i, &id.to_string());
+        match fetch_activity_social(&access_token, id).await {
+            Ok(social) => {
+                progress.status(&format!("{} kudos, {} comments, {} photos",
+                    social.kudoers.len(), social.comments.len(), social.photos.len()));
+                kudos += social.kudoers.len();
+                comments += social.comments.len();
+                photos += social.photos.len();
+                let compression = stored_compression(id)?.ok_or("activity file disappeared")?;
+                file.social = Some(social);
+                write_activity_file(&file, compression)?;
+            }
+            Err(e) => warn!("   ⚠️  Could not fetch kudos/comments/photos for {}: {}", id, e),
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    }
+    drop(progress);
+
+    info!("✅ Stored {} kudos, {} comments and {} photo links", kudos, comments, photos);
+    Ok(())
+}
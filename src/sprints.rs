@@ -0,0 +1,154 @@
+//! Peak sprint of each ride: the best 5 s of power with the cadence and speed
+//! it was ridden at, and the best 15 s. Stored on the index entry at sync, so
+//! `sprints` ranks every finish in the archive without reading the streams.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::cli::{self, OutputFormat};
+use crate::config::Config;
+use crate::metrics;
+use crate::model::{ActivityIndex, ActivitySummary, ActivityWithStreams};
+use crate::pipeline::Analyzer;
+use crate::storage::load_activity_file;
+
+/// Samples in the sprint itself, and in the longer effort around it
+const SPRINT_SAMPLES: usize = 5;
+const LONG_SPRINT_SAMPLES: usize = 15;
+
+/// Rides listed by default
+const DEFAULT_LIMIT: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Sprint {
+    /// Seconds into the ride the best 5 s start
+    pub start: i32,
+    pub watts_5s: f64,
+    /// Best 15 s anywhere in the ride
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watts_15s: Option<f64>,
+    /// Highest cadence during the best 5 s, rpm
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_cadence: Option<i32>,
+    /// Top speed during the best 5 s, m/s
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f64>,
+}
+
+/// Peak sprint on the index entry
+pub struct Sprints;
+
+impl Analyzer for Sprints {
+    fn name(&self) -> &'static str {
+        "sprint"
+    }
+
+    fn analyze(&self, file: &mut ActivityWithStreams, summary: &mut ActivitySummary, _config: &Config) {
+        summary.sprint = if summary.is_run() { None } else { peak(file) };
+    }
+}
+
+/// The peak sprint of a ride, None without enough power data
+pub fn peak(file: &ActivityWithStreams) -> Option<Sprint> {
+    let streams = file.streams.as_ref()?;
+    let watts = streams.watts.as_deref()?;
+    let (watts_5s, first) = metrics::mean_maximal_power_at(watts, SPRINT_SAMPLES)?;
+    let window = first..first + SPRINT_SAMPLES;
+    let time = streams.time.as_deref().filter(|t| t.len() == watts.len());
+    Some(Sprint {
+        start: time.map_or(first as i32, |t| t[first] - t[0]),
+        watts_5s: watts_5s.round(),
+        watts_15s: metrics::mean_maximal_power(watts, LONG_SPRINT_SAMPLES).map(f64::round),
+        peak_cadence: streams.cadence.as_deref().and_then(|c| c.get(window.clone())).and_then(|c| c.iter().copied().max()),
+        speed: streams
+            .velocity_smooth
+            .as_deref()
+            .and_then(|v| v.get(window))
+            .and_then(|v| v.iter().copied().reduce(f64::max))
+            .map(|v| (v * 100.0).round() / 100.0),
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Entry {
+    id: ActivityId,
+    date: NaiveDate,
+    name: String,
+    #[serde(flatten)]
+    sprint: Sprint,
+}
+
+#[derive(Debug, Serialize)]
+struct Leaderboard {
+    /// Best first
+    rides: Vec<Entry>,
+    /// Best sprint of each calendar year
+    years: BTreeMap<i32, Entry>,
+}
+
+/// Recompute the sprints of the archive and print the best since `since`,
+/// ranked by 5 s power (15 s with `by_15s`), with each year's best
+pub fn run(since: Option<NaiveDate>, by_15s: bool, limit: Option<usize>, output: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let config = Config::current()?;
+    let mut index = ActivityIndex::load()?;
+    for summary in index.activities.iter_mut().filter(|a| !a.is_run()) {
+        match load_activity_file(summary.id) {
+            Ok(file) => summary.sprint = peak(&file),
+            Err(e) => warn!("   ⚠️  Skipping {}: {}", summary.id, e),
+        }
+    }
+    index.save()?;
+
+    let score = |sprint: &Sprint| if by_15s { sprint.watts_15s.unwrap_or(0.0) } else { sprint.watts_5s };
+    let mut rides: Vec<Entry> = index
+        .activities
+        .iter()
+        .filter(|a| config.counts_for_records(a) && since.is_none_or(|s| a.local_date() >= s))
+        .filter_map(|a| Some(Entry { id: a.id, date: a.local_date(), name: a.name.clone(), sprint: a.sprint? }))
+        .collect();
+    rides.sort_by(|a, b| score(&b.sprint).total_cmp(&score(&a.sprint)));
+    let mut years: BTreeMap<i32, Entry> = BTreeMap::new();
+    for ride in &rides {
+        years.entry(ride.date.year()).or_insert_with(|| ride.clone());
+    }
+    rides.truncate(limit.unwrap_or(DEFAULT_LIMIT));
+
+    if output == OutputFormat::Json {
+        return cli::print_json(&Leaderboard { rides, years });
+    }
+    if rides.is_empty() {
+        info!("🏁 No rides with power");
+        return Ok(());
+    }
+    info!("🏁 Best sprints by {} power", if by_15s { "15 s" } else { "5 s" });
+    print_entries(&rides);
+    println!();
+    info!("📅 Best sprint per year");
+    print_entries(&years.into_values().rev().collect::<Vec<_>>());
+    Ok(())
+}
+
+fn print_entries(entries: &[Entry]) {
+    println!("  {:<10} {:<16} {:>6} {:>6} {:>5} {:>6} {:>8}  name", "date", "id", "5s", "15s", "rpm", "km/h", "at");
+    let optional = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+    for entry in entries {
+        let sprint = &entry.sprint;
+        let name: String = entry.name.chars().take(40).collect();
+        println!(
+            "  {:<10} {:<16} {:>6.0} {:>6} {:>5} {:>6} {:>8}  {}",
+            entry.date,
+            entry.id.to_string(),
+            sprint.watts_5s,
+            optional(sprint.watts_15s.map(|w| format!("{:.0}", w))),
+            optional(sprint.peak_cadence.map(|c| c.to_string())),
+            optional(sprint.speed.map(|s| format!("{:.1}", s * 3.6))),
+            format!("{}:{:02}:{:02}", sprint.start / 3600, (sprint.start % 3600) / 60, sprint.start % 60),
+            name
+        );
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::cli::{self, OutputFormat};
+use crate::config::Config;
+use crate::edit::TagFilter;
+use crate::metrics;
+use crate::model::{ActivityIndex, ActivitySummary};
+use crate::pace;
+use crate::storage::load_activity_file;
+use crate::streamless;
+
+/// Training block length used by `--group-by block` unless overridden
+pub const DEFAULT_BLOCK_WEEKS: i64 = 4;
+
+/// Aggregates over a set of rides
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Totals {
+    pub rides: usize,
+    pub moving_time: i32,
+    pub distance: f64,
+    /// Sum of ride TSS; None without an FTP
+    pub tss: Option<f64>,
+    /// Seconds below LT1, between LT1 and LT2, and above LT2 (Coggan Z1-2, Z3-4, Z5+)
+    pub intensity_distribution: [i32; 3],
+}
+
+impl Totals {
+    /// Count a ride, reading its streams for TSS and intensity distribution
+    pub fn add(&mut self, summary: &ActivitySummary, config: &Config) {
+        self.rides += 1;
+        self.moving_time += summary.moving_time;
+        self.distance += summary.distance;
+
+        let Ok(file) = load_activity_file(summary.id) else { return };
+        if let Some(load) = streamless::estimated_load(&file.activity.sport_type, summary.moving_time, config) {
+            *self.tss.get_or_insert(0.0) += load;
+            return;
+        }
+        if pace::is_run(&file.activity.sport_type) {
+            if let Some(rtss) = file.streams.as_ref().and_then(|s| pace::run_tss(s, summary.moving_time, config)) {
+                *self.tss.get_or_insert(0.0) += rtss;
+            }
+            return;
+        }
+        let Some(watts) = file.streams.as_ref().and_then(|s| s.watts.as_deref()) else { return };
+        if let (Some(ftp), Some(np)) = (config.ftp(), metrics::normalized_power(watts)) {
+            *self.tss.get_or_insert(0.0) += metrics::training_stress_score(np, summary.moving_time, ftp);
+        }
+        if let Some(floors) = config.power_zone_floors() {
+            for (zone, seconds) in metrics::time_in_zones(watts, &floors).into_iter().enumerate() {
+                self.intensity_distribution[(zone / 2).min(2)] += seconds;
+            }
+        }
+    }
+
+    pub fn polarization_index(&self) -> Option<f64> {
+        let [low, mid, high] = self.intensity_distribution.map(|s| s as f64);
+        metrics::polarization_index(low, mid, high)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Week,
+    Month,
+    /// Consecutive blocks of N weeks counted from the start of the range
+    Block(i64),
+}
+
+impl GroupBy {
+    pub fn parse(s: &str, block_weeks: i64) -> Result<Self, String> {
+        match s {
+            "week" => Ok(GroupBy::Week),
+            "month" => Ok(GroupBy::Month),
+            "block" if block_weeks > 0 => Ok(GroupBy::Block(block_weeks)),
+            "block" => Err("--block-weeks must be at least 1".into()),
+            other => Err(format!("unknown grouping '{}', expected week, month or block", other)),
+        }
+    }
+
+    /// First day of the group containing `day`
+    pub fn start(self, day: NaiveDate, from: NaiveDate) -> NaiveDate {
+        match self {
+            GroupBy::Week => day - Duration::days(day.weekday().num_days_from_monday() as i64),
+            GroupBy::Month => day.with_day(1).unwrap_or(day),
+            GroupBy::Block(weeks) => {
+                let length = weeks * 7;
+                from + Duration::days((day - from).num_days().div_euclid(length) * length)
+            }
+        }
+    }
+}
+
+/// `stats range --output json`
+#[derive(Debug, Serialize)]
+struct RangeResult {
+    groups: Vec<GroupTotals>,
+    total: GroupTotals,
+}
+
+#[derive(Debug, Serialize)]
+struct GroupTotals {
+    /// First day of the group, None for the total
+    from: Option<NaiveDate>,
+    #[serde(flatten)]
+    totals: Totals,
+    polarization_index: Option<f64>,
+}
+
+impl GroupTotals {
+    fn new(from: Option<NaiveDate>, totals: Totals) -> Self {
+        let polarization_index = totals.polarization_index();
+        GroupTotals { from, totals, polarization_index }
+    }
+}
+
+/// Group the rides between `from` and `to` (inclusive) and print one line per group plus a total
+pub fn range(
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    group_by: GroupBy,
+    tags: &TagFilter,
+    output: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let config = Config::current()?;
+    let mut index = ActivityIndex::load()?;
+    tags.apply(&mut index);
+
+    let mut rides: Vec<(NaiveDate, &ActivitySummary)> = Vec::new();
+    for summary in index.activities.iter().filter(|a| a.counted()) {
+        let day = summary.local_date();
+        if from.is_none_or(|f| day >= f) && to.is_none_or(|t| day <= t) {
+            rides.push((day, summary));
+        }
+    }
+    let Some(first) = from.or_else(|| rides.iter().map(|(day, _)| *day).min()) else {
+        info!("📊 No rides in range");
+        if output == OutputFormat::Json {
+            cli::print_json(&RangeResult { groups: Vec::new(), total: GroupTotals::new(None, Totals::default()) })?;
+        }
+        return Ok(());
+    };
+
+    let mut groups: BTreeMap<NaiveDate, Totals> = BTreeMap::new();
+    let mut total = Totals::default();
+    for (day, summary) in rides {
+        groups.entry(group_by.start(day, first)).or_default().add(summary, &config);
+        total.add(summary, &config);
+    }
+
+    info!("📊 {} rides in {} groups", total.rides, groups.len());
+    if output == OutputFormat::Json {
+        return cli::print_json(&RangeResult {
+            groups: groups.into_iter().map(|(start, totals)| GroupTotals::new(Some(start), totals)).collect(),
+            total: GroupTotals::new(None, total),
+        });
+    }
+    print_header("from");
+    for (start, totals) in &groups {
+        print_row(&start.to_string(), totals);
+    }
+    print_row("total", &total);
+    Ok(())
+}
+
+pub fn print_header(label: &str) {
+    println!("  {:<10} {:>5} {:>6} {:>7} {:>5}  {:>14}  {:>5}", label, "rides", "hours", "km", "tss", "low/mid/high %", "PI");
+}
+
+pub fn print_row(label: &str, totals: &Totals) {
+    let seconds = totals.intensity_distribution.iter().sum::<i32>().max(1) as f64;
+    let [low, mid, high] = totals.intensity_distribution.map(|s| (s as f64 / seconds * 100.0).round());
+    println!(
+        "  {:<10} {:>5} {:>6.1} {:>7.0} {:>5} {:>5}/{:>3}/{:>3}  {:>5}",
+        label,
+        totals.rides,
+        totals.moving_time as f64 / 3600.0,
+        totals.distance / 1000.0,
+        totals.tss.map(|t| format!("{:.0}", t)).unwrap_or_else(|| "-".into()),
+        low,
+        mid,
+        high,
+        totals.polarization_index().map(|pi| format!("{:.2}", pi)).unwrap_or_else(|| "-".into()),
+    );
+}
@@ -0,0 +1,320 @@
+use std::collections::{BTreeMap, HashSet};
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use ring::digest;
+use tracing::{info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::ci;
+use crate::compression::Compression;
+use crate::config::{self, Config};
+use crate::format;
+use crate::model::{Activity, ActivityIndex, ActivitySummary, ActivityWithStreams};
+use crate::pipeline;
+use crate::progress::Progress;
+use crate::schema;
+use crate::store;
+use crate::strava;
+use crate::sync;
+
+impl ActivityIndex {
+    /// Load the index, starting empty only when it does not exist yet.
+    /// A corrupt index is an error: silently resetting it would forget every known ID.
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let store = store::current()?;
+        let path = store.describe(format::INDEX_FILE);
+        let Some(json): Option<Vec<u8>> = store.get(format::INDEX_FILE)? else {
+            return Ok(ActivityIndex {
+                schema_version: schema::CURRENT_VERSION,
+                last_updated: String::new(),
+                activities: Vec::new(),
+            });
+        };
+        let mut value: serde_json::Value = serde_json::from_slice(&json).map_err(|e| {
+            format!("{} is corrupt ({}); restore it from git or a backup before syncing", path, e)
+        })?;
+        schema::migrate_index(&mut value).map_err(|e| format!("{}: {}", path, e))?;
+        serde_json::from_value(value).map_err(|e| {
+            format!("{} is corrupt ({}); restore it from git or a backup before syncing", path, e).into()
+        })
+    }
+    
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let json = serde_json::to_string_pretty(self)?;
+        store::current()?.put(format::INDEX_FILE, json.as_bytes())
+    }
+    
+    pub fn get_known_ids(&self) -> HashSet<ActivityId> {
+        self.activities.iter().map(|a| a.id).collect()
+    }
+    
+    pub fn get_mut(&mut self, id: ActivityId) -> Option<&mut ActivitySummary> {
+        self.activities.iter_mut().find(|a| a.id == id)
+    }
+    
+    /// Add entries from another index that this one lacks, returning how many were added
+    pub fn merge(&mut self, other: &ActivityIndex) -> usize {
+        let known_ids = self.get_known_ids();
+        let missing: Vec<ActivitySummary> = other.activities.iter()
+            .filter(|a| !known_ids.contains(&a.id))
+            .cloned()
+            .collect();
+        let added = missing.len();
+        self.activities.extend(missing);
+        self.activities.sort_by_key(|a| std::cmp::Reverse(a.start_date));
+        added
+    }
+    
+    /// Add an activity, replacing its existing entry if there is one
+    pub fn add_activity(&mut self, activity: &Activity) {
+        let Some(start_date) = activity.start() else {
+            warn!("   ⚠️  Not indexing {}: invalid start_date '{}'", activity.id, activity.start_date);
+            return;
+        };
+        let summary = ActivitySummary {
+            id: activity.id,
+            name: activity.name.clone(),
+            start_date,
+            utc_offset: activity.utc_offset(),
+            distance: activity.distance,
+            moving_time: activity.moving_time,
+            total_elevation_gain: Some(activity.total_elevation_gain),
+            average_watts: activity.average_watts,
+            average_heartrate: activity.average_heartrate,
+            kilojoules: activity.kilojoules,
+            sport_type: Some(activity.sport_type.clone()),
+            trainer: activity.trainer,
+            commute: activity.commute,
+            workout_type: None,
+            efficiency_factor: None,
+            decoupling: None,
+            heart_rate_fit: None,
+            world: None,
+            route: None,
+            cadence: None,
+            pedalling: None,
+            matches: None,
+            sprint: None,
+            pacing: None,
+            estimated_power: None,
+            elevation: None,
+            anomalies: Vec::new(),
+            custom_metrics: BTreeMap::new(),
+            tags: Vec::new(),
+            notes: None,
+            excluded: false,
+            duplicate_of: None,
+        };
+        self.replace_summary(summary);
+    }
+    
+    /// Insert a summary, carrying over derived fields from the entry it replaces
+    fn replace_summary(&mut self, mut summary: ActivitySummary) {
+        if let Some(previous) = self.activities.iter().find(|a| a.id == summary.id) {
+            summary.workout_type = summary.workout_type.or(previous.workout_type.clone());
+            summary.efficiency_factor = summary.efficiency_factor.or(previous.efficiency_factor);
+            summary.decoupling = summary.decoupling.or(previous.decoupling);
+            summary.heart_rate_fit = summary.heart_rate_fit.or(previous.heart_rate_fit);
+            summary.world = summary.world.or(previous.world.clone());
+            summary.route = summary.route.or(previous.route.clone());
+            summary.cadence = summary.cadence.or(previous.cadence.clone());
+            summary.pedalling = summary.pedalling.or(previous.pedalling);
+            summary.matches = summary.matches.or(previous.matches);
+            summary.sprint = summary.sprint.or(previous.sprint);
+            summary.pacing = summary.pacing.or(previous.pacing);
+            summary.estimated_power = summary.estimated_power.or(previous.estimated_power);
+            summary.elevation = summary.elevation.or(previous.elevation);
+            if summary.anomalies.is_empty() {
+                summary.anomalies = previous.anomalies.clone();
+            }
+            if summary.custom_metrics.is_empty() {
+                summary.custom_metrics = previous.custom_metrics.clone();
+            }
+            summary.tags = previous.tags.clone();
+            summary.notes = previous.notes.clone();
+            summary.excluded = previous.excluded;
+            summary.duplicate_of = previous.duplicate_of;
+        }
+        self.activities.retain(|a| a.id != summary.id);
+        self.activities.insert(0, summary);
+        self.activities.sort_by_key(|a| std::cmp::Reverse(a.start_date));
+    }
+}
+
+/// The encoding an activity file was stored in, None when it is not in the archive
+pub fn stored_compression(id: ActivityId) -> Result<Option<Compression>, Box<dyn Error>> {
+    let store = store::current()?;
+    for compression in Compression::ALL {
+        if store.exists(&format::activity_key(id, compression))? {
+            return Ok(Some(compression));
+        }
+    }
+    Ok(None)
+}
+
+pub fn save_activity_file(activity: &ActivityWithStreams) -> Result<(), Box<dyn Error>> {
+    write_activity_file(activity, Compression::from_env()?)
+}
+
+/// Store an activity with the given encoding, removing copies in any other encoding
+pub fn write_activity_file(activity: &ActivityWithStreams, compression: Compression) -> Result<(), Box<dyn Error>> {
+    let store = store::current()?;
+    let id = activity.activity.id;
+    let bytes = match compression {
+        Compression::None => serde_json::to_string_pretty(activity)?.into_bytes(),
+        _ => compression.compress(&serde_json::to_vec(activity)?)?,
+    };
+    store.put(&format::activity_key(id, compression), &bytes)?;
+    for other in Compression::ALL.into_iter().filter(|c| *c != compression) {
+        let key = format::activity_key(id, other);
+        if store.exists(&key)? {
+            store.delete(&key)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn activity_file_exists(id: ActivityId) -> Result<bool, Box<dyn Error>> {
+    Ok(stored_compression(id)?.is_some())
+}
+
+/// An activity file's decompressed JSON, with the encoding it was stored in
+pub fn read_activity_file(id: ActivityId) -> Result<(Vec<u8>, Compression), Box<dyn Error>> {
+    let store = store::current()?;
+    let compression = stored_compression(id)?
+        .ok_or_else(|| format!("no file for activity {} in {}", id, store.describe(format::ACTIVITIES_DIR)))?;
+    let key = format::activity_key(id, compression);
+    let bytes = store.get(&key)?.ok_or_else(|| format!("{} disappeared", store.describe(&key)))?;
+    let json = compression.decompress(&bytes).map_err(|e| format!("could not read {}: {}", store.describe(&key), e))?;
+    Ok((json, compression))
+}
+
+pub fn load_activity_file(id: ActivityId) -> Result<ActivityWithStreams, Box<dyn Error>> {
+    let (json, _) = read_activity_file(id)?;
+    parse_activity_file(&json).map_err(|e| format!("activity {}: {}", id, e).into())
+}
+
+/// An activity file's JSON, migrated to the current schema
+pub fn parse_activity_file(json: &[u8]) -> Result<ActivityWithStreams, Box<dyn Error>> {
+    let mut value: serde_json::Value = serde_json::from_slice(json)?;
+    schema::migrate_activity(&mut value)?;
+    Ok(serde_json::from_value(value)?)
+}
+
+/// IDs of every stored activity file, whatever its encoding
+pub fn list_activity_ids() -> Result<Vec<ActivityId>, Box<dyn Error>> {
+    let mut ids = Vec::new();
+    for name in store::current()?.list(format::ACTIVITIES_DIR)? {
+        if let Some(id) = name.split('.').next().and_then(|s| s.parse().ok()) {
+            if name.ends_with(".json") || name.ends_with(".json.gz") || name.ends_with(".json.zst") {
+                ids.push(id);
+            }
+        }
+    }
+    ids.sort();
+    ids.dedup();
+    Ok(ids)
+}
+
+/// WATTS_FSYNC=1 flushes every write to disk before returning, for archives on
+/// storage that may lose power mid-write (NAS, SD cards). Since the index and
+/// journal are always written after the activity files they reference, this
+/// also guarantees they never point at a file that was not durably stored.
+pub fn durable() -> bool {
+    config::flag("WATTS_FSYNC")
+}
+
+/// Write to a temporary sibling then rename over the target, so readers
+/// (and a crash mid-write) only ever see the old or the new contents
+pub fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    replace(path, contents, &options)
+}
+
+/// [`write_atomic`] for secrets: the file is created owner-only before the
+/// first byte goes in, so it is never readable by anyone else
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    // A leftover temporary file would keep whatever permissions it had
+    match fs::remove_file(tmp_path(path)) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+    replace(path, contents, &options)
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    PathBuf::from(tmp)
+}
+
+fn replace(path: &Path, contents: &[u8], options: &OpenOptions) -> Result<(), Box<dyn Error>> {
+    ci::record_write(path, contents);
+    let tmp = tmp_path(path);
+    let mut file = options.open(&tmp)?;
+    file.write_all(contents)?;
+    if durable() {
+        file.sync_all()?;
+    }
+    drop(file);
+    fs::rename(&tmp, path)?;
+    if durable() {
+        sync_parent(path)?;
+    }
+    Ok(())
+}
+
+/// fsync the directory holding `path`, making a rename, creation or removal in it durable
+pub fn sync_parent(path: &Path) -> std::io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+/// Lowercase hex SHA-256 of `data`, as checksums and cache keys are stored
+pub fn sha256(data: &[u8]) -> String {
+    hex(digest::digest(&digest::SHA256, data).as_ref())
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Load an activity, optionally fetching it from Strava when it is not on disk.
+///
+/// This turns the archive into a read-through cache: the fetched activity is
+/// stored and indexed exactly as a sync would have done.
+pub async fn load_or_fetch_activity(id: ActivityId, fetch_missing: bool) -> Result<ActivityWithStreams, Box<dyn Error>> {
+    if activity_file_exists(id)? {
+        return load_activity_file(id);
+    }
+    if !fetch_missing {
+        return Err(format!("activity {} is not in the archive (use --fetch or WATTS_READ_THROUGH=1)", id).into());
+    }
+
+    info!("📡 Activity {} is not in the archive, fetching it from Strava", id);
+    let access_token = strava::access_token().await?;
+    let activity = strava::fetch_activity(&access_token, id).await?;
+    let config = Config::current()?;
+    let mut progress = Progress::new("📡 Fetching", 1);
+    progress.item(0, &format!("{} (id: {})", activity.name, id));
+    // A part that fails is logged and left out, as sync does
+    let mut activity_with_streams = sync::fetch_parts(&access_token, activity, &config, &mut progress, &mut Vec::new()).await;
+
+    let mut index = ActivityIndex::load()?;
+    pipeline::ingest(&mut activity_with_streams, &mut index, &config);
+    save_activity_file(&activity_with_streams)?;
+    index.save()?;
+    Ok(activity_with_streams)
+}
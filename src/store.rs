@@ -0,0 +1,336 @@
+//! Where the archive's index and activity files live.
+//!
+//! Everything goes through a [`Store`] addressed by keys relative to the data
+//! directory (`index.json`, `activities/123.json.gz`). The filesystem is the
+//! default; with `"store": { "kind": "s3", "bucket": ... }` in watts.json the
+//! archive lives in an S3-compatible bucket instead, for runs on machines
+//! without a persistent disk. Credentials come from `AWS_ACCESS_KEY_ID`,
+//! `AWS_SECRET_ACCESS_KEY` and, for temporary ones, `AWS_SESSION_TOKEN`.
+//!
+//! Derived files (weekly.json, gear.json, reports, the published API) are
+//! still written to the local data directory: they are rebuilt from the index
+//! and activities on every sync.
+
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::Utc;
+use reqwest::blocking::Client;
+use reqwest::{Method, StatusCode, Url};
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::ci;
+use crate::config::Config;
+use crate::escape;
+use crate::format;
+use crate::storage::{durable, hex, sha256, sync_parent, write_atomic};
+
+/// Archive storage, keyed by paths relative to the data directory
+pub trait Store {
+    /// Contents of `key`, None when it does not exist
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>>;
+    /// Create or replace `key`; readers never see a partial write
+    fn put(&self, key: &str, contents: &[u8]) -> Result<(), Box<dyn Error>>;
+    fn delete(&self, key: &str) -> Result<(), Box<dyn Error>>;
+    fn exists(&self, key: &str) -> Result<bool, Box<dyn Error>>;
+    /// Names of the entries directly under `dir`
+    fn list(&self, dir: &str) -> Result<Vec<String>, Box<dyn Error>>;
+    /// Where `key` lives, for messages
+    fn describe(&self, key: &str) -> String;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKind {
+    File,
+    S3,
+}
+
+/// Storage backend under "store" in watts.json; the data directory when unset
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoreConfig {
+    pub kind: StoreKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bucket: Option<String>,
+    /// AWS region, AWS_REGION or us-east-1 by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Endpoint of another S3-compatible service (MinIO, R2, ...), addressed path-style
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    /// Key prefix before the data directory, e.g. "watts" for watts/data/index.json
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+}
+
+/// The configured store, rooted at the active athlete's data directory
+pub fn current() -> Result<Box<dyn Store>, Box<dyn Error>> {
+    let config = Config::load()?;
+    match config.store {
+        Some(StoreConfig { kind: StoreKind::S3, bucket, region, endpoint, prefix }) => {
+            let bucket = bucket.ok_or("\"store\" of kind s3 needs a \"bucket\"")?;
+            Ok(Box::new(S3Store::new(bucket, region, endpoint, prefix)?))
+        }
+        _ => Ok(Box::new(FileStore { root: format::data_dir() })),
+    }
+}
+
+/// The data directory on local disk
+pub struct FileStore {
+    root: PathBuf,
+}
+
+impl Store for FileStore {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        match fs::read(self.root.join(key)) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("could not read {}: {}", self.describe(key), e).into()),
+        }
+    }
+
+    fn put(&self, key: &str, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+        let path = self.root.join(key);
+        if let Some(dir) = path.parent() {
+            if !dir.exists() {
+                fs::create_dir_all(dir)?;
+                if durable() {
+                    sync_parent(dir)?;
+                }
+            }
+        }
+        write_atomic(&path, contents)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), Box<dyn Error>> {
+        let path = self.root.join(key);
+        ci::record_removal(&path);
+        fs::remove_file(&path)?;
+        if durable() {
+            sync_parent(&path)?;
+        }
+        Ok(())
+    }
+
+    fn exists(&self, key: &str) -> Result<bool, Box<dyn Error>> {
+        Ok(self.root.join(key).exists())
+    }
+
+    fn list(&self, dir: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut names = Vec::new();
+        let entries = match fs::read_dir(self.root.join(dir)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(names),
+            Err(e) => return Err(format!("could not read {}: {}", self.describe(dir), e).into()),
+        };
+        for entry in entries {
+            names.push(entry?.file_name().to_string_lossy().into_owned());
+        }
+        Ok(names)
+    }
+
+    fn describe(&self, key: &str) -> String {
+        self.root.join(key).display().to_string()
+    }
+}
+
+/// A bucket on S3 or a compatible service, requests signed with AWS Signature V4
+pub struct S3Store {
+    bucket: String,
+    region: String,
+    /// Bucket URL that keys are appended to
+    base: Url,
+    /// Prefix and data directory, ending with '/'
+    root: String,
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+}
+
+impl S3Store {
+    fn new(bucket: String, region: Option<String>, endpoint: Option<String>, prefix: Option<String>) -> Result<Self, Box<dyn Error>> {
+        let region = region.or_else(|| std::env::var("AWS_REGION").ok()).unwrap_or_else(|| "us-east-1".to_string());
+        let base = match endpoint {
+            Some(endpoint) => format!("{}/{}/", endpoint.trim_end_matches('/'), bucket),
+            None => format!("https://{}.s3.{}.amazonaws.com/", bucket, region),
+        };
+        let data_dir = format::data_dir().to_string_lossy().trim_matches('/').to_string();
+        let root = match prefix.as_deref().map(|p| p.trim_matches('/')).filter(|p| !p.is_empty()) {
+            Some(prefix) => format!("{}/{}/", prefix, data_dir),
+            None => format!("{}/", data_dir),
+        };
+        Ok(S3Store {
+            bucket,
+            region,
+            base: Url::parse(&base)?,
+            root,
+            access_key: std::env::var("AWS_ACCESS_KEY_ID").map_err(|_| "AWS_ACCESS_KEY_ID is not set")?,
+            secret_key: std::env::var("AWS_SECRET_ACCESS_KEY").map_err(|_| "AWS_SECRET_ACCESS_KEY is not set")?,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+
+    /// Send a signed request for `object` (a full key, or "" for the bucket)
+    fn request(&self, method: Method, object: &str, query: &[(&str, &str)], body: &[u8]) -> Result<(StatusCode, Vec<u8>), Box<dyn Error>> {
+        let mut url = self.base.clone();
+        url.set_path(&format!("{}{}", url.path(), uri_encode(object, false)));
+        let mut query: Vec<(String, String)> = query.iter().map(|(k, v)| (uri_encode(k, true), uri_encode(v, true))).collect();
+        query.sort();
+        let canonical_query = query.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&");
+        url.set_query((!canonical_query.is_empty()).then_some(canonical_query.as_str()));
+
+        let now = Utc::now();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = sha256(body);
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let mut headers = vec![
+            ("host", host),
+            ("x-amz-content-sha256", payload_hash.clone()),
+            ("x-amz-date", timestamp.clone()),
+        ];
+        if let Some(token) = &self.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+        let signed_headers = headers.iter().map(|(name, _)| *name).collect::<Vec<_>>().join(";");
+        let canonical_headers: String = headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect();
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method,
+            url.path(),
+            canonical_query,
+            canonical_headers,
+            signed_headers,
+            payload_hash
+        );
+
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            timestamp,
+            scope,
+            sha256(canonical_request.as_bytes())
+        );
+        let mut key = format!("AWS4{}", self.secret_key).into_bytes();
+        for part in [date.as_str(), self.region.as_str(), "s3", "aws4_request"] {
+            key = hmac_sha256(&key, part.as_bytes());
+        }
+        let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes()));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        );
+
+        debug!("   {} {}", method, url);
+        // The blocking client must be created, used and dropped off the async worker threads
+        let (status, body) = tokio::task::block_in_place(|| -> Result<_, reqwest::Error> {
+            let mut request = Client::new().request(method, url).header("authorization", authorization).body(body.to_vec());
+            for (name, value) in headers.into_iter().filter(|(name, _)| *name != "host") {
+                request = request.header(name, value);
+            }
+            let response = request.send()?;
+            let status = response.status();
+            Ok((status, response.bytes()?.to_vec()))
+        })?;
+        Ok((status, body))
+    }
+
+    /// Fail with S3's error message unless the status is a success
+    fn check(&self, key: &str, status: StatusCode, body: &[u8]) -> Result<(), Box<dyn Error>> {
+        if status.is_success() {
+            return Ok(());
+        }
+        let body = String::from_utf8_lossy(body);
+        let message = xml_values(&body, "Message").into_iter().next().unwrap_or_else(|| body.trim().to_string());
+        Err(format!("{}: {} {}", self.describe(key), status, message).into())
+    }
+}
+
+impl Store for S3Store {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        let (status, body) = self.request(Method::GET, &format!("{}{}", self.root, key), &[], &[])?;
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        self.check(key, status, &body)?;
+        Ok(Some(body))
+    }
+
+    fn put(&self, key: &str, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+        let (status, body) = self.request(Method::PUT, &format!("{}{}", self.root, key), &[], contents)?;
+        self.check(key, status, &body)
+    }
+
+    fn delete(&self, key: &str) -> Result<(), Box<dyn Error>> {
+        let (status, body) = self.request(Method::DELETE, &format!("{}{}", self.root, key), &[], &[])?;
+        self.check(key, status, &body)
+    }
+
+    fn exists(&self, key: &str) -> Result<bool, Box<dyn Error>> {
+        let (status, body) = self.request(Method::HEAD, &format!("{}{}", self.root, key), &[], &[])?;
+        if status == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        self.check(key, status, &body)?;
+        Ok(true)
+    }
+
+    fn list(&self, dir: &str) -> Result<Vec<String>, Box<dyn Error>> {
+        let prefix = format!("{}{}/", self.root, dir.trim_end_matches('/'));
+        let mut names = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", prefix.as_str()), ("delimiter", "/")];
+            if let Some(token) = &token {
+                query.push(("continuation-token", token));
+            }
+            let (status, body) = self.request(Method::GET, "", &query, &[])?;
+            self.check(dir, status, &body)?;
+            let body = String::from_utf8_lossy(&body);
+            names.extend(xml_values(&body, "Key").into_iter().filter_map(|key| key.strip_prefix(&prefix).map(str::to_string)));
+            token = xml_values(&body, "NextContinuationToken").into_iter().next();
+            if token.is_none() {
+                return Ok(names);
+            }
+        }
+    }
+
+    fn describe(&self, key: &str) -> String {
+        format!("s3://{}/{}{}", self.bucket, self.root, key)
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data).as_ref().to_vec()
+}
+
+/// Percent-encode everything but unreserved characters, and '/' unless `slash`
+fn uri_encode(s: &str, slash: bool) -> String {
+    let mut encoded = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            b'/' if !slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Text of every `<tag>` element in an S3 XML response
+fn xml_values(xml: &str, tag: &str) -> Vec<String> {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+    xml.split(&open)
+        .skip(1)
+        .filter_map(|rest| rest.split_once(&close))
+        .map(|(value, _)| escape::xml_unescape(value))
+        .collect()
+}
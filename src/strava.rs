@@ -0,0 +1,620 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock};
+use tracing::{debug, error, info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::align;
+use crate::athlete;
+use crate::auth;
+use crate::cassette::{self, Mode};
+use crate::format;
+use crate::http_cache;
+use crate::athlete_history::AthleteStats;
+use crate::model::{Activity, ActivityStreams, Lap, Social};
+use crate::storage::write_atomic;
+
+const DEFAULT_BASE_URL: &str = "https://www.strava.com";
+
+static BASE_URL: RwLock<Option<String>> = RwLock::new(None);
+
+/// Point the client at another server, such as `mock-strava` (`--base-url`)
+pub fn set_base_url(url: Option<String>) {
+    *BASE_URL.write().unwrap_or_else(|e| e.into_inner()) = url.map(|u| u.trim_end_matches('/').to_string());
+}
+
+/// Scheme and host every request goes to: `--base-url`, `STRAVA_BASE_URL` or Strava itself
+pub fn base_url() -> String {
+    BASE_URL
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .or_else(|| std::env::var("STRAVA_BASE_URL").ok().map(|u| u.trim_end_matches('/').to_string()))
+        .unwrap_or_else(|| DEFAULT_BASE_URL.to_string())
+}
+
+/// Strava answered with an error status
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: reqwest::StatusCode,
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "API returned status {}", self.status)
+    }
+}
+
+impl Error for ApiError {}
+
+/// No usable credentials: missing settings, or a refresh token Strava rejected
+#[derive(Debug)]
+pub struct AuthError(pub String);
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for AuthError {}
+
+/// The HTTP status behind an error, if Strava sent one
+pub fn status_of(e: &(dyn Error + 'static)) -> Option<u16> {
+    e.downcast_ref::<ApiError>().map(|api| api.status.as_u16())
+}
+
+/// Limits and usage from the last response's X-RateLimit headers, or from the
+/// state file until the first response of this run
+static RATE_LIMIT: Mutex<Option<RateLimit>> = Mutex::new(None);
+
+/// Whether this run heard from Strava, so the state file needs rewriting
+static RATE_LIMIT_SEEN: AtomicBool = AtomicBool::new(false);
+
+fn note_rate_limit(headers: &reqwest::header::HeaderMap) {
+    let pair = |name: &str| -> Option<(u32, u32)> {
+        let (short, daily) = headers.get(name)?.to_str().ok()?.split_once(',')?;
+        Some((short.trim().parse().ok()?, daily.trim().parse().ok()?))
+    };
+    if let (Some((limit, daily_limit)), Some((usage, daily_usage))) = (pair("x-ratelimit-limit"), pair("x-ratelimit-usage")) {
+        *RATE_LIMIT.lock().unwrap_or_else(|e| e.into_inner()) =
+            Some(RateLimit { limit, usage, daily_limit, daily_usage, observed_at: Utc::now() });
+        RATE_LIMIT_SEEN.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Strava's budget as of one response: a 15-minute window starting on the
+/// quarter hour and a day starting at midnight UTC
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// Requests allowed per 15 minutes
+    pub limit: u32,
+    /// Requests made in that 15-minute window
+    pub usage: u32,
+    pub daily_limit: u32,
+    pub daily_usage: u32,
+    pub observed_at: DateTime<Utc>,
+}
+
+impl RateLimit {
+    /// Requests that can still be made at `now`, counting windows that reset since
+    pub fn left(&self, now: DateTime<Utc>) -> u32 {
+        let quarter = |t: DateTime<Utc>| t.timestamp().div_euclid(15 * 60);
+        let short = if quarter(now) == quarter(self.observed_at) { self.limit.saturating_sub(self.usage) } else { self.limit };
+        let daily = if now.date_naive() == self.observed_at.date_naive() {
+            self.daily_limit.saturating_sub(self.daily_usage)
+        } else {
+            self.daily_limit
+        };
+        short.min(daily)
+    }
+}
+
+/// The budget as last seen, by this run or a previous one
+pub fn rate_limit() -> Option<RateLimit> {
+    *RATE_LIMIT.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Requests that can be made right now, None before Strava has ever told us
+pub fn rate_limit_remaining() -> Option<u32> {
+    rate_limit().map(|r| r.left(Utc::now()))
+}
+
+/// `requested` requests, or fewer if the budget left can't take them
+pub fn within_budget(requested: usize) -> usize {
+    // One request goes to the token refresh
+    let Some(left) = rate_limit_remaining().map(|left| (left as usize).saturating_sub(1)) else { return requested };
+    if left < requested {
+        warn!("⏸️  {} requests left in the rate-limit budget, doing {} of {}", left, left, requested);
+    }
+    requested.min(left)
+}
+
+/// Where the budget is kept between runs: Strava counts per application, so one
+/// file for every athlete
+fn rate_limit_path() -> PathBuf {
+    Path::new(format::DATA_DIR).join(format::RATE_LIMIT_FILE)
+}
+
+/// Pick up the budget a previous run left, so the first requests are planned too
+pub fn load_rate_limit() {
+    let Ok(json) = std::fs::read_to_string(rate_limit_path()) else { return };
+    match serde_json::from_str(&json) {
+        Ok(state) => *RATE_LIMIT.lock().unwrap_or_else(|e| e.into_inner()) = Some(state),
+        Err(e) => warn!("⚠️  Ignoring {}: {}", rate_limit_path().display(), e),
+    }
+}
+
+/// Keep the budget for the next run, if this one heard from Strava
+pub fn save_rate_limit() -> Result<(), Box<dyn Error>> {
+    let Some(state) = rate_limit().filter(|_| RATE_LIMIT_SEEN.load(Ordering::Relaxed)) else { return Ok(()) };
+    let path = rate_limit_path();
+    if !path.parent().is_some_and(Path::exists) {
+        return Ok(());
+    }
+    write_atomic(&path, serde_json::to_string_pretty(&state)?.as_bytes())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    #[allow(dead_code)]
+    pub expires_at: i64,
+    /// Strava may rotate the refresh token; the previous one stops working soon after
+    #[serde(default)]
+    pub refresh_token: Option<String>,
+}
+
+/// Get a fresh access token, reading the refresh token from the configured store
+/// and writing back a rotated one
+pub async fn access_token() -> Result<String, Box<dyn Error>> {
+    // A replayed session needs no credentials
+    if let Some(Mode::Replay(_)) = cassette::mode()? {
+        return Ok(refresh_access_token("", "", "").await?.access_token);
+    }
+    let client_id = std::env::var("STRAVA_CLIENT_ID").map_err(|_| AuthError("STRAVA_CLIENT_ID is not set".into()))?;
+    let client_secret = std::env::var("STRAVA_CLIENT_SECRET").map_err(|_| AuthError("STRAVA_CLIENT_SECRET is not set".into()))?;
+    let store = auth::token_store()?;
+    let refresh_token = store.load().map_err(|e| AuthError(match athlete::active() {
+        Some(profile) => format!("{} (athlete {})", e, profile.name),
+        None => e.to_string(),
+    }))?;
+    
+    info!("📡 Refreshing access token...");
+    let response = match refresh_access_token(&client_id, &client_secret, &refresh_token).await {
+        Ok(response) => response,
+        // A rate limit or an outage is not a credentials problem
+        Err(e) if status_of(e.as_ref()).is_some_and(|s| s != 429 && s < 500) => {
+            return Err(AuthError(format!("Strava rejected the refresh token: {}", e)).into())
+        }
+        Err(e) => return Err(e),
+    };
+    if let Some(rotated) = response.refresh_token.filter(|t| *t != refresh_token) {
+        store.save(&rotated)?;
+    }
+    Ok(response.access_token)
+}
+
+pub async fn refresh_access_token(
+    client_id: &str,
+    client_secret: &str,
+    refresh_token: &str,
+) -> Result<TokenResponse, Box<dyn Error>> {
+    let url = format!("{}/oauth/token", base_url());
+    let cassette = cassette::mode()?;
+    if let Some(Mode::Replay(dir)) = &cassette {
+        return Ok(serde_json::from_str(&cassette::replay(dir, &url, &[])?)?);
+    }
+    let client = reqwest::Client::new();
+    let response = client
+        .post(&url)
+        .form(&[
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("refresh_token", refresh_token),
+            ("grant_type", "refresh_token"),
+        ])
+        .send()
+        .await?;
+    let status = response.status();
+    note_rate_limit(response.headers());
+    let text = response.text().await?;
+    if !status.is_success() {
+        error!("❌ Token API error ({}): {}", status, text);
+        return Err(ApiError { status }.into());
+    }
+    let response = serde_json::from_str::<TokenResponse>(&text)?;
+    if let Some(Mode::Record(dir)) = &cassette {
+        cassette::record(dir, &url, &[], &text)?;
+    }
+    
+    Ok(response)
+}
+
+pub async fn fetch_activities_page(access_token: &str, page: u32, per_page: u32) -> Result<Vec<Activity>, Box<dyn Error>> {
+    let (page, per_page) = (page.to_string(), per_page.to_string());
+    get_json(
+        access_token,
+        &format!("{}/api/v3/athlete/activities", base_url()),
+        &[("page", &page), ("per_page", &per_page)],
+        "Strava",
+    )
+    .await
+}
+
+pub async fn fetch_activity(access_token: &str, activity_id: ActivityId) -> Result<Activity, Box<dyn Error>> {
+    let activity_id = activity_id.strava_id()?;
+    get_json(access_token, &format!("{}/api/v3/activities/{}", base_url(), activity_id), &[], "Activity").await
+}
+
+/// Heart rate and power zones configured on Strava (needs the profile:read_all scope)
+pub async fn fetch_athlete_zones(access_token: &str) -> Result<serde_json::Value, Box<dyn Error>> {
+    get_json(access_token, &format!("{}/api/v3/athlete/zones", base_url()), &[], "Zones").await
+}
+
+pub async fn fetch_activity_laps(access_token: &str, activity_id: ActivityId) -> Result<Vec<Lap>, Box<dyn Error>> {
+    let activity_id = activity_id.strava_id()?;
+    get_json(access_token, &format!("{}/api/v3/activities/{}/laps", base_url(), activity_id), &[], "Laps").await
+}
+
+/// The authenticated athlete's profile, reduced to what is tracked over time
+#[derive(Debug, Deserialize)]
+pub struct StravaAthlete {
+    pub id: i64,
+    #[serde(default)]
+    pub weight: Option<f64>,
+    #[serde(default)]
+    pub ftp: Option<f64>,
+}
+
+pub async fn fetch_athlete(access_token: &str) -> Result<StravaAthlete, Box<dyn Error>> {
+    get_json(access_token, &format!("{}/api/v3/athlete", base_url()), &[], "Athlete").await
+}
+
+pub async fn fetch_athlete_stats(access_token: &str, athlete_id: i64) -> Result<AthleteStats, Box<dyn Error>> {
+    get_json(access_token, &format!("{}/api/v3/athletes/{}/stats", base_url(), athlete_id), &[], "Stats").await
+}
+
+/// An entry of a club's feed: Strava leaves out its ID and date, and shortens
+/// the athlete's last name to an initial
+#[derive(Debug, Deserialize)]
+pub struct ClubActivity {
+    pub athlete: ClubAthlete,
+    pub name: String,
+    pub distance: f64,
+    pub moving_time: i32,
+    pub elapsed_time: i32,
+    #[serde(default)]
+    pub total_elevation_gain: f64,
+    pub sport_type: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ClubAthlete {
+    pub firstname: String,
+    pub lastname: String,
+}
+
+/// Recent activities of a club's members, newest first
+pub async fn fetch_club_activities(access_token: &str, club_id: u64, page: u32, per_page: u32) -> Result<Vec<ClubActivity>, Box<dyn Error>> {
+    let (page, per_page) = (page.to_string(), per_page.to_string());
+    get_json(
+        access_token,
+        &format!("{}/api/v3/clubs/{}/activities", base_url(), club_id),
+        &[("page", &page), ("per_page", &per_page)],
+        "Club",
+    )
+    .await
+}
+
+/// A segment from `/segments/starred`
+#[derive(Debug, Deserialize)]
+pub struct StarredSegment {
+    pub id: i64,
+    pub name: String,
+}
+
+pub async fn fetch_starred_segments(access_token: &str, page: u32, per_page: u32) -> Result<Vec<StarredSegment>, Box<dyn Error>> {
+    let (page, per_page) = (page.to_string(), per_page.to_string());
+    get_json(access_token, &format!("{}/api/v3/segments/starred", base_url()), &[("page", &page), ("per_page", &per_page)], "Segments").await
+}
+
+/// `/segments/{id}`, reduced to the PR and KOM times
+#[derive(Debug, Deserialize)]
+pub struct Segment {
+    pub name: String,
+    /// Metres
+    pub distance: f64,
+    /// Percent
+    #[serde(default)]
+    pub average_grade: f64,
+    #[serde(default)]
+    pub xoms: Option<Xoms>,
+    #[serde(default)]
+    pub athlete_segment_stats: Option<SegmentStats>,
+}
+
+/// Course record times, as Strava formats them ("5:02", "1:02:03" or "45s")
+#[derive(Debug, Deserialize)]
+pub struct Xoms {
+    #[serde(default)]
+    pub kom: Option<String>,
+    #[serde(default)]
+    pub qom: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SegmentStats {
+    /// Seconds
+    #[serde(default)]
+    pub pr_elapsed_time: Option<i32>,
+    #[serde(default)]
+    pub pr_date: Option<String>,
+    #[serde(default)]
+    pub effort_count: Option<i64>,
+}
+
+pub async fn fetch_segment(access_token: &str, segment_id: i64) -> Result<Segment, Box<dyn Error>> {
+    get_json(access_token, &format!("{}/api/v3/segments/{}", base_url(), segment_id), &[], "Segment").await
+}
+
+/// A route planned on Strava, from `/athletes/{id}/routes`
+#[derive(Debug, Deserialize)]
+pub struct Route {
+    /// Route IDs overflow a JSON number; `id_str` keeps them exact
+    pub id_str: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    /// Metres
+    pub distance: f64,
+    #[serde(default)]
+    pub elevation_gain: f64,
+    /// 1 for a ride, 2 for a run
+    #[serde(rename = "type", default)]
+    pub route_type: i32,
+    #[serde(default)]
+    pub starred: bool,
+    /// Seconds
+    #[serde(default)]
+    pub estimated_moving_time: Option<i32>,
+    #[serde(default)]
+    pub updated_at: Option<String>,
+}
+
+pub async fn fetch_routes(access_token: &str, athlete_id: i64, page: u32, per_page: u32) -> Result<Vec<Route>, Box<dyn Error>> {
+    let (page, per_page) = (page.to_string(), per_page.to_string());
+    get_json(
+        access_token,
+        &format!("{}/api/v3/athletes/{}/routes", base_url(), athlete_id),
+        &[("page", &page), ("per_page", &per_page)],
+        "Routes",
+    )
+    .await
+}
+
+/// A route's track as a GPX document
+pub async fn fetch_route_gpx(access_token: &str, route_id: &str) -> Result<String, Box<dyn Error>> {
+    get_text(access_token, &format!("{}/api/v3/routes/{}/export_gpx", base_url(), route_id), &[], "Route GPX").await
+}
+
+/// Strava's answer to an upload, and to each poll of its status
+#[derive(Debug, Deserialize)]
+pub struct Upload {
+    pub id_str: String,
+    #[serde(default)]
+    pub status: Option<String>,
+    /// Set when processing failed, duplicates included
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Set once processing is done
+    #[serde(default)]
+    pub activity_id: Option<i64>,
+}
+
+/// Start an upload (needs the activity:write scope); `fields` are the form
+/// fields besides the file, such as data_type, name and external_id
+pub async fn create_upload(access_token: &str, file_name: &str, contents: &[u8], fields: &[(&str, String)]) -> Result<Upload, Box<dyn Error>> {
+    let url = format!("{}/api/v3/uploads", base_url());
+    let cassette = cassette::mode()?;
+    if let Some(Mode::Replay(dir)) = &cassette {
+        return Ok(serde_json::from_str(&cassette::replay(dir, &url, &[])?)?);
+    }
+    let boundary = format!("watts-happening-{}", Utc::now().timestamp_millis());
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", boundary, name, value).as_bytes());
+    }
+    body.extend_from_slice(format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+        boundary, file_name
+    ).as_bytes());
+    body.extend_from_slice(contents);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+    let response = reqwest::Client::new()
+        .post(&url)
+        .header("Authorization", format!("Bearer {}", access_token))
+        .header("Content-Type", format!("multipart/form-data; boundary={}", boundary))
+        .body(body)
+        .send()
+        .await?;
+    let status = response.status();
+    note_rate_limit(response.headers());
+    let text = response.text().await?;
+    if !status.is_success() {
+        error!("❌ Upload API error ({}): {}", status, text);
+        return Err(ApiError { status }.into());
+    }
+    if let Some(Mode::Record(dir)) = &cassette {
+        cassette::record(dir, &url, &[], &text)?;
+    }
+    Ok(serde_json::from_str(&text)?)
+}
+
+pub async fn fetch_upload(access_token: &str, upload_id: &str) -> Result<Upload, Box<dyn Error>> {
+    get_json(access_token, &format!("{}/api/v3/uploads/{}", base_url(), upload_id), &[], "Upload").await
+}
+
+/// A bike or pair of shoes, as `/gear/{id}` describes it
+#[derive(Debug, Deserialize)]
+pub struct Gear {
+    pub name: String,
+    /// Odometer in metres, outdoor rides included
+    #[serde(default)]
+    pub distance: f64,
+}
+
+pub async fn fetch_gear(access_token: &str, gear_id: &str) -> Result<Gear, Box<dyn Error>> {
+    get_json(access_token, &format!("{}/api/v3/gear/{}", base_url(), gear_id), &[], "Gear").await
+}
+
+/// Kudoers, comments and photos of an activity: three requests
+pub async fn fetch_activity_social(access_token: &str, activity_id: ActivityId) -> Result<Social, Box<dyn Error>> {
+    let activity_id = activity_id.strava_id()?;
+    let base = format!("{}/api/v3/activities/{}", base_url(), activity_id);
+    Ok(Social {
+        fetched_at: chrono::Utc::now().to_rfc3339(),
+        kudoers: get_json(access_token, &format!("{}/kudos", base), &[("per_page", "200")], "Kudos").await?,
+        comments: get_json(access_token, &format!("{}/comments", base), &[("per_page", "200")], "Comments").await?,
+        photos: get_json(access_token, &format!("{}/photos", base), &[("size", "2048"), ("photo_sources", "true")], "Photos").await?,
+    })
+}
+
+async fn get_json<T: serde::de::DeserializeOwned>(
+    access_token: &str,
+    url: &str,
+    query: &[(&str, &str)],
+    label: &str,
+) -> Result<T, Box<dyn Error>> {
+    Ok(serde_json::from_str(&get_text(access_token, url, query, label).await?)?)
+}
+
+/// Body of a successful GET, going through the cassette when `WATTS_CASSETTE` is set
+async fn get_text(access_token: &str, url: &str, query: &[(&str, &str)], label: &str) -> Result<String, Box<dyn Error>> {
+    let cassette = cassette::mode()?;
+    if let Some(Mode::Replay(dir)) = &cassette {
+        return cassette::replay(dir, url, query);
+    }
+
+    let cached = http_cache::lookup(url, query);
+    let mut request = reqwest::Client::new()
+        .get(url)
+        .header("Authorization", format!("Bearer {}", access_token))
+        .query(query);
+    if let Some(entry) = &cached {
+        request = request.header(reqwest::header::IF_NONE_MATCH, &entry.etag);
+    }
+    let response = request.send().await?;
+
+    let status = response.status();
+    note_rate_limit(response.headers());
+    let etag = response.headers().get(reqwest::header::ETAG).and_then(|v| v.to_str().ok()).map(String::from);
+    let text = match (status, cached) {
+        (reqwest::StatusCode::NOT_MODIFIED, Some(entry)) => http_cache::hit(entry),
+        _ => {
+            let text = response.text().await?;
+            if !status.is_success() {
+                error!("❌ {} API error ({}): {}", label, status, text);
+                return Err(ApiError { status }.into());
+            }
+            if let Some(etag) = etag {
+                if let Err(e) = http_cache::store(url, query, &etag, &text) {
+                    debug!("   Could not cache {}: {}", url, e);
+                }
+            }
+            text
+        }
+    };
+    if let Some(Mode::Record(dir)) = &cassette {
+        cassette::record(dir, url, query, &text)?;
+    }
+
+    Ok(text)
+}
+
+/// Series requested for every activity
+const STREAM_KEYS: [&str; 7] = ["time", "watts", "heartrate", "cadence", "velocity_smooth", "altitude", "latlng"];
+
+pub async fn fetch_activity_streams(access_token: &str, activity_id: ActivityId) -> Result<ActivityStreams, Box<dyn Error>> {
+    let activity_id = activity_id.strava_id()?;
+    let text = get_text(
+        access_token,
+        &format!("{}/api/v3/activities/{}/streams", base_url(), activity_id),
+        &[
+            ("keys", &STREAM_KEYS.join(",")),
+            ("key_by_type", "true"),
+            // Honoured for some activities, ignored for others; `resolution` below says what came back
+            ("resolution", "high"),
+            ("series_type", "time"),
+        ],
+        "Streams",
+    )
+    .await?;
+    
+    // Parse the keyed response
+    let streams_map: serde_json::Value = serde_json::from_str(&text)?;
+    let present: Vec<&serde_json::Value> = STREAM_KEYS.iter().filter_map(|key| streams_map.get(*key)).collect();
+    
+    let mut streams = ActivityStreams {
+        time: streams_map.get("time")
+            .and_then(|v| v.get("data"))
+            .and_then(|v| serde_json::from_value(v.clone()).ok()),
+        watts: streams_map.get("watts")
+            .and_then(|v| v.get("data"))
+            .and_then(|v| serde_json::from_value(v.clone()).ok()),
+        heartrate: streams_map.get("heartrate")
+            .and_then(|v| v.get("data"))
+            .and_then(|v| serde_json::from_value(v.clone()).ok()),
+        cadence: streams_map.get("cadence")
+            .and_then(|v| v.get("data"))
+            .and_then(|v| serde_json::from_value(v.clone()).ok()),
+        velocity_smooth: streams_map.get("velocity_smooth")
+            .and_then(|v| v.get("data"))
+            .and_then(|v| serde_json::from_value(v.clone()).ok()),
+        altitude: streams_map.get("altitude")
+            .and_then(|v| v.get("data"))
+            .and_then(|v| serde_json::from_value(v.clone()).ok()),
+        latlng: streams_map.get("latlng")
+            .and_then(|v| v.get("data"))
+            .and_then(|v| serde_json::from_value(v.clone()).ok()),
+        original_watts: None,
+        original_heartrate: None,
+        resolution: present
+            .iter()
+            .filter_map(|s| s.get("resolution").and_then(|v| v.as_str()))
+            .min_by_key(|r| ["low", "medium", "high"].iter().position(|known| known == r).unwrap_or(0))
+            .map(String::from),
+        series_type: present
+            .iter()
+            .find_map(|s| s.get("series_type").and_then(|v| v.as_str()))
+            .map(String::from),
+        original_size: present
+            .iter()
+            .filter_map(|s| s.get("original_size").and_then(|v| v.as_u64()))
+            .max()
+            .map(|n| n as usize),
+        missing_series: STREAM_KEYS
+            .iter()
+            .filter(|key| streams_map.get(**key).and_then(|v| v.get("data")).is_none())
+            .map(|key| key.to_string())
+            .collect(),
+        rr_intervals: None,
+        w_balance: None,
+        estimated_watts: None,
+        misaligned: BTreeMap::new(),
+    };
+    let misaligned = align::align(&mut streams);
+    if !misaligned.is_empty() {
+        let time = streams.time.as_ref().map_or(0, Vec::len);
+        let listed: Vec<String> = misaligned.iter().map(|(name, len)| format!("{} {}", name, len)).collect();
+        warn!("   ⚠️  Streams of {} differ in length ({}), aligned to {} time samples", activity_id, listed.join(", "), time);
+    }
+    
+    Ok(streams)
+}
@@ -0,0 +1,148 @@
+//! Activity streaks: runs of consecutive days, and of consecutive Monday-first
+//! weeks, with at least one counted activity and, when watts.json sets one, a
+//! minimum TSS. A streak is still current while its last day is today or
+//! yesterday (its last week this week or last week), as the day isn't over.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::activity_id::ActivityId;
+use crate::config::Config;
+use crate::milestones::Milestone;
+use crate::model::ActivityIndex;
+use crate::training_load::{self, DailyLoad};
+
+/// Current day streaks are announced at every multiple of this many days, and when they set a record
+const DAY_STEP: usize = 7;
+
+/// Current week streaks are announced at every multiple of this many weeks, and when they set a record
+const WEEK_STEP: usize = 4;
+
+/// What keeps a streak going, under `"streaks"` in watts.json
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreakSettings {
+    /// Least PMC load for a day to count; any counted activity when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub daily_tss: Option<f64>,
+    /// Least PMC load for a week to count; any counted activity when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weekly_tss: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Streak {
+    /// Days, or weeks
+    pub length: usize,
+    pub start: NaiveDate,
+    /// Last day, or Monday of the last week
+    pub end: NaiveDate,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Streaks {
+    pub current_days: Option<Streak>,
+    pub longest_days: Option<Streak>,
+    pub current_weeks: Option<Streak>,
+    pub longest_weeks: Option<Streak>,
+}
+
+/// The streaks of the archive as of today. `loads` is the PMC's daily load,
+/// only read when watts.json sets a TSS threshold.
+pub fn compute(index: &ActivityIndex, config: &Config, loads: &[DailyLoad]) -> Streaks {
+    let settings = config.streaks.clone().unwrap_or_default();
+    let tss: BTreeMap<NaiveDate, f64> =
+        if settings.thresholds() { loads.iter().map(|d| (d.date, d.tss)).collect() } else { BTreeMap::new() };
+    from_days(index, &settings, &tss)
+}
+
+impl StreakSettings {
+    fn thresholds(&self) -> bool {
+        self.daily_tss.is_some() || self.weekly_tss.is_some()
+    }
+}
+
+fn from_days(index: &ActivityIndex, settings: &StreakSettings, tss: &BTreeMap<NaiveDate, f64>) -> Streaks {
+    let active: BTreeSet<NaiveDate> = index.activities.iter().filter(|a| a.counted()).map(|a| a.local_date()).collect();
+    let monday = |d: NaiveDate| d - Duration::days(d.weekday().num_days_from_monday() as i64);
+
+    let days: BTreeSet<NaiveDate> =
+        active.iter().copied().filter(|d| tss.get(d).copied().unwrap_or(0.0) >= settings.daily_tss.unwrap_or(0.0)).collect();
+    let mut week_tss: BTreeMap<NaiveDate, f64> = active.iter().map(|&d| (monday(d), 0.0)).collect();
+    for (&date, &load) in tss {
+        if let Some(total) = week_tss.get_mut(&monday(date)) {
+            *total += load;
+        }
+    }
+    let weeks: BTreeSet<NaiveDate> =
+        week_tss.into_iter().filter(|&(_, load)| load >= settings.weekly_tss.unwrap_or(0.0)).map(|(week, _)| week).collect();
+
+    let today = Utc::now().date_naive();
+    let (current_days, longest_days) = runs(&days, 1, today);
+    let (current_weeks, longest_weeks) = runs(&weeks, 7, monday(today));
+    Streaks { current_days, longest_days, current_weeks, longest_weeks }
+}
+
+/// The current and longest runs of `dates` spaced `step` days apart; the
+/// current one must reach `now` or the step before it. Ties go to the latest.
+fn runs(dates: &BTreeSet<NaiveDate>, step: i64, now: NaiveDate) -> (Option<Streak>, Option<Streak>) {
+    let mut all: Vec<Streak> = Vec::new();
+    for &date in dates {
+        match all.last_mut() {
+            Some(run) if date - run.end == Duration::days(step) => {
+                run.end = date;
+                run.length += 1;
+            }
+            _ => all.push(Streak { length: 1, start: date, end: date }),
+        }
+    }
+    let current = all.last().copied().filter(|run| run.end >= now - Duration::days(step));
+    let longest = all.iter().max_by_key(|run| run.length).copied();
+    (current, longest)
+}
+
+/// Streak milestones the rides in `added` reached: a current streak growing to
+/// a multiple of a week (of four weeks), or past the longest one. `after` is
+/// `compute` over `index`, from the same `loads`.
+pub fn reached(index: &ActivityIndex, added: &[ActivityId], config: &Config, loads: &[DailyLoad], after: &Streaks) -> Vec<Milestone> {
+    let settings = config.streaks.clone().unwrap_or_default();
+    let mut previous = index.clone();
+    previous.activities.retain(|a| !added.contains(&a.id));
+    // The day loads without the added rides, which spares reloading every other file
+    let mut tss: BTreeMap<NaiveDate, f64> = BTreeMap::new();
+    if settings.thresholds() {
+        tss = loads.iter().map(|d| (d.date, d.tss)).collect();
+        for summary in index.activities.iter().filter(|a| added.contains(&a.id) && a.counted() && !config.excluded_from_load(a)) {
+            if let (Some(load), Some(day)) = (training_load::ride_load(summary, config), tss.get_mut(&summary.local_date())) {
+                *day -= load;
+            }
+        }
+    }
+    let before = from_days(&previous, &settings, &tss);
+    let mut milestones = Vec::new();
+    let checks = [
+        ("day_streak", "day", DAY_STEP, before.current_days, before.longest_days, after.current_days),
+        ("week_streak", "week", WEEK_STEP, before.current_weeks, before.longest_weeks, after.current_weeks),
+    ];
+    for (kind, unit, step, current, longest, now) in checks {
+        let Some(now) = now else { continue };
+        if now.length <= current.map_or(0, |c| c.length) {
+            continue;
+        }
+        // Once the current streak is the longest, each day would beat it again
+        let record = current != longest && now.length > longest.map_or(0, |l| l.length) && now.length > 1;
+        if record || now.length % step == 0 {
+            milestones.push(Milestone {
+                kind,
+                value: now.length as f64,
+                message: format!("🔥 {}-{} streak{}", now.length, unit, if record { ", your longest yet" } else { "" }),
+            });
+        }
+    }
+    for milestone in &milestones {
+        info!("🎉 Milestone: {}", milestone.message);
+    }
+    milestones
+}
@@ -0,0 +1,40 @@
+//! Swims, weight training and other activities Strava has no useful streams
+//! for. Sync stores only their summary, saving the streams request, and their
+//! training load is estimated from moving time at a per-sport TSS per hour,
+//! overridable with `"load_per_hour"` in watts.json.
+
+use crate::config::Config;
+
+/// Sport types stored without streams, with their default TSS per hour
+const DEFAULT_LOAD_PER_HOUR: &[(&str, f64)] = &[
+    ("Swim", 60.0),
+    ("WeightTraining", 40.0),
+    ("Workout", 40.0),
+    ("Crossfit", 60.0),
+    ("Yoga", 20.0),
+    ("Pilates", 25.0),
+    ("RockClimbing", 45.0),
+];
+
+/// Whether activities of this sport type are synced without streams
+pub fn is_streamless(sport_type: &str) -> bool {
+    DEFAULT_LOAD_PER_HOUR.iter().any(|(sport, _)| *sport == sport_type)
+}
+
+/// TSS per hour counted for a streamless sport, None for other sports
+pub fn load_per_hour(sport_type: &str, config: &Config) -> Option<f64> {
+    if !is_streamless(sport_type) {
+        return None;
+    }
+    config
+        .load_per_hour
+        .iter()
+        .find(|(sport, _)| sport.eq_ignore_ascii_case(sport_type))
+        .map(|(_, &load)| load)
+        .or_else(|| DEFAULT_LOAD_PER_HOUR.iter().find(|(sport, _)| *sport == sport_type).map(|&(_, load)| load))
+}
+
+/// Training load of a streamless activity from its moving time
+pub fn estimated_load(sport_type: &str, moving_time: i32, config: &Config) -> Option<f64> {
+    Some(load_per_hour(sport_type, config)? * moving_time as f64 / 3600.0)
+}
@@ -0,0 +1,404 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use tokio::task::{JoinSet, LocalSet};
+use tracing::{debug, info, warn};
+
+use crate::model::{Activity, ActivityIndex, ActivityWithStreams};
+use crate::storage::{activity_file_exists, save_activity_file};
+use crate::athlete_history;
+use crate::clean;
+use crate::club_activities;
+use crate::config::{self, Config};
+use crate::duplicates;
+use crate::failure::{Failure, Incomplete};
+use crate::format;
+use crate::gear;
+use crate::http_cache;
+use crate::mail;
+use crate::notify;
+use crate::pipeline;
+use crate::progress::Progress;
+use crate::rolling;
+use crate::segments;
+use crate::store;
+use crate::strava::{
+    self, access_token, fetch_activities_page, fetch_activity, fetch_activity_laps, fetch_activity_social, fetch_activity_streams,
+};
+use crate::streamless;
+use crate::sync_journal::SyncJournal;
+use crate::sync_report::{self, SyncError, SyncReport};
+use crate::training_load;
+use crate::weekly;
+
+/// Requests for one new activity: streams and laps (none for streamless
+/// sports), plus the detail and three social requests with `WATTS_FETCH_DETAIL`
+const PART_REQUESTS: usize = 2;
+const DETAIL_REQUESTS: usize = 4;
+
+/// Strava's largest page, so a backfill lists thousands of rides in few requests
+const BACKFILL_PER_PAGE: u32 = 200;
+
+/// Pages listed at once by `sync --backfill` unless overridden
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Kept back for the athlete snapshot and gear names after the activities
+const TAIL_REQUESTS: usize = 5;
+
+/// Fetch new activities from Strava and store them under data/, returning
+/// what the run changed (as written to last_sync.json). With `backfill`, every
+/// page is listed, that many at a time, instead of stopping at the first known ride.
+pub async fn run(backfill: Option<usize>) -> Result<SyncReport, Box<dyn Error>> {
+    // Load existing index
+    let mut index = ActivityIndex::load()?;
+    info!("📂 Found {} existing activities in index", index.activities.len());
+    
+    // Pick up where an interrupted run left off
+    let mut journal = match SyncJournal::load()? {
+        Some(mut journal) => {
+            let recovered = journal.reconcile(&mut index);
+            info!("♻️  Resuming interrupted sync from {}: {} recovered, {} still pending",
+                journal.started_at,
+                recovered,
+                journal.pending.len()
+            );
+            journal
+        }
+        None => SyncJournal {
+            started_at: chrono::Utc::now().to_rfc3339(),
+            pending: Vec::new(),
+        },
+    };
+    let known_ids = index.get_known_ids();
+    let config = Config::current()?;
+    let started_at = chrono::Utc::now().to_rfc3339();
+
+    // Listing needs a couple of requests; without them, wait for the window to reset
+    if let Some(left) = strava::rate_limit_remaining().filter(|&left| (left as usize) < TAIL_REQUESTS) {
+        return Err(Incomplete {
+            failure: Failure::RateLimited,
+            message: format!("only {} requests left in the rate-limit budget, sync deferred to the next run", left),
+            activities: journal.pending.iter().map(|a| a.id).collect(),
+        }
+        .into());
+    }
+    
+    // Get fresh access token
+    let access_token = access_token().await?;
+    
+    // Fetch activities with pagination
+    info!("📊 Fetching activities from Strava...");
+    
+    let per_page = 50;
+    let mut page = 1;
+    let mut total_fetched = 0;
+    let mut new_activities: Vec<Activity> = Vec::new();
+    let mut found_existing = false;
+    
+    if let Some(concurrency) = backfill {
+        // Everything is listed, so rides missing from the middle of the archive are found too
+        let activities = list_all_pages(&access_token, concurrency).await?;
+        total_fetched = activities.len();
+        for activity in activities.into_iter().filter(|a| !known_ids.contains(&a.id)) {
+            if config.syncs(&activity.sport_type) {
+                debug!("   🆕 New activity: {}", activity.name);
+                new_activities.push(activity);
+            }
+        }
+    } else {
+        // Paginate until we find activities we already have
+        while !found_existing {
+            debug!("   Fetching page {} ({} per page)...", page, per_page);
+        
+            let activities = fetch_activities_page(&access_token, page, per_page).await?;
+        
+            if activities.is_empty() {
+                info!("   No more activities found.");
+                break;
+            }
+        
+            total_fetched += activities.len();
+        
+            for activity in activities {
+                // Check if we already have this activity
+                if known_ids.contains(&activity.id) {
+                    info!("   ✓ Found existing activity: {} - stopping pagination", activity.name);
+                    found_existing = true;
+                    break;
+                }
+            
+                // Only keep VirtualRide (Zwift) activities, or the sports listed in watts.json
+                if config.syncs(&activity.sport_type) {
+                    info!("   🆕 New activity: {}", activity.name);
+                    new_activities.push(activity);
+                } else {
+                    debug!("   ⏭️  Skipping activity: {} ({})", activity.name, activity.sport_type);
+                }
+            }
+        
+            page += 1;
+        
+            // Safety limit - don't fetch more than 5 pages (250 activities) in one run
+            if page > 5 {
+                warn!("   ⚠️  Reached page limit, stopping pagination");
+                break;
+            }
+        }
+    
+    }
+    
+    info!("📈 Summary:");
+    info!("   Total activities fetched from API: {}", total_fetched);
+    info!("   New activities to process: {}", new_activities.len());
+    if http_cache::hits() > 0 {
+        info!("   Unchanged pages answered from cache: {}", http_cache::hits());
+    }
+    
+    // Record the work before doing it, so a crash can be resumed
+    journal.extend(&new_activities);
+    let pending = journal.pending.clone();
+    let mut synced = Vec::new();
+    let mut updated = Vec::new();
+    let mut errors = Vec::new();
+    let mut deferred = Vec::new();
+    if !pending.is_empty() {
+        journal.save()?;
+    }
+    
+    // Fetch detailed streams for new activities
+    if !pending.is_empty() {
+        info!("🔍 Fetching detailed streams for new activities...");
+        let fetch_detail = config::flag("WATTS_FETCH_DETAIL");
+        if let Some(left) = strava::rate_limit_remaining() {
+            let planned: usize = pending.iter().map(|a| requests_for(a, fetch_detail)).sum();
+            info!("   ~{} requests planned, {} left in the rate-limit budget", planned + TAIL_REQUESTS, left);
+        }
+        let mut progress = Progress::new("🔍 Syncing", pending.len());
+        
+        for (i, activity) in pending.iter().enumerate() {
+            // Checked before every ride: other clients of the same app spend the budget too
+            if strava::rate_limit_remaining().is_some_and(|left| (left as usize) < requests_for(activity, fetch_detail) + TAIL_REQUESTS) {
+                deferred = pending[i..].iter().map(|a| a.id).collect();
+                warn!("⏸️  Rate-limit budget spent, {} activities queued for the next run", deferred.len());
+                break;
+            }
+            progress.item(i, &format!("{} (id: {})", activity.name, activity.id));
+            
+            // Skip if file already exists (safety check)
+            if activity_file_exists(activity.id)? {
+                progress.status("⏭️  File already exists, skipping");
+                index.add_activity(activity);
+                journal.complete(activity.id)?;
+                updated.push(activity.id);
+                continue;
+            }
+            
+            // The list endpoint omits description, gear, calories and splits
+            let activity = if fetch_detail {
+                match fetch_activity(&access_token, activity.id).await {
+                    Ok(detailed) => detailed,
+                    Err(e) => {
+                        warn!("      ⚠️  Could not fetch details: {}", e);
+                        errors.push(SyncError::new(Some(activity.id), "details", e.as_ref()));
+                        activity.clone()
+                    }
+                }
+            } else {
+                activity.clone()
+            };
+            
+            let mut activity_with_streams = fetch_parts(&access_token, activity, &config, &mut progress, &mut errors).await;
+            
+            // Analyze and index, then save individual file
+            pipeline::ingest(&mut activity_with_streams, &mut index, &config);
+            save_activity_file(&activity_with_streams)?;
+            journal.complete(activity_with_streams.activity.id)?;
+            synced.push(activity_with_streams);
+            
+            // Rate limiting - be nice to the API
+            if i < pending.len() - 1 {
+                tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+            }
+        }
+    }
+    
+    // A ride uploaded to Strava may already be in the archive from a FIT import
+    for d in duplicates::detect(&mut index).iter().filter(|d| synced.iter().any(|f| f.activity.id == d.kept || f.activity.id == d.dropped)) {
+        info!("👯 {} duplicates {}, keeping {} ({})", d.dropped, d.kept, d.kept, d.reason);
+    }
+
+    // Update timestamp and save index
+    index.last_updated = chrono::Utc::now().to_rfc3339();
+    index.save()?;
+    weekly::write_series(&index)?;
+    // Computed once: the rolling TSS and the streak thresholds both read it
+    let loads = training_load::daily(&index, &config);
+    rolling::write_series(&index, &loads)?;
+    format::write_manifest()?;
+    // Deferred rides stay in the journal, which the next run resumes first
+    if deferred.is_empty() {
+        SyncJournal::clear()?;
+    }
+    if let Err(e) = athlete_history::snapshot(&access_token).await {
+        warn!("⚠️  Could not snapshot athlete profile: {}", e);
+        errors.push(SyncError::new(None, "athlete profile", e.as_ref()));
+    }
+    if let Some(club_id) = config.strava_club {
+        if let Err(e) = club_activities::fetch(&access_token, club_id).await {
+            warn!("⚠️  Could not fetch club {} activities: {}", club_id, e);
+            errors.push(SyncError::new(None, "club activities", e.as_ref()));
+        }
+    }
+    if segments::due(&config) {
+        match segments::fetch(&access_token, &config).await {
+            Ok(changes) => notify::segment_changes(&changes, &config).await,
+            Err(e) => {
+                warn!("⚠️  Could not refresh starred segments: {}", e);
+                errors.push(SyncError::new(None, "starred segments", e.as_ref()));
+            }
+        }
+    }
+    let report = sync_report::write(started_at, &synced, updated, deferred, errors, &index, &config, &loads)?;
+    notify::new_bests(&report, &synced, &config).await;
+    notify::new_milestones(&report, &config).await;
+    if !config.gear.is_empty() || synced.iter().any(|f| f.activity.gear_id.is_some()) {
+        gear::write_report(&index, &config, Some(&access_token), false).await?;
+    }
+    notify::new_activities(&synced, &config).await;
+    mail::sync_summary(&synced, &index, &config).await;
+    
+    info!("💾 Saved {} total activities", index.activities.len());
+    let store = store::current()?;
+    info!("   📁 Individual files in {}", store.describe(format::ACTIVITIES_DIR));
+    info!("   📋 Index at {}", store.describe(format::INDEX_FILE));
+    info!("🕐 Last updated: {}", index.last_updated);
+    
+    Ok(report)
+}
+
+/// Requests `run` spends on one new activity
+fn requests_for(activity: &Activity, fetch_detail: bool) -> usize {
+    let parts = if streamless::is_streamless(&activity.sport_type) { 0 } else { PART_REQUESTS };
+    parts + if fetch_detail { DETAIL_REQUESTS } else { 0 }
+}
+
+/// Download the streams and laps of an activity (neither for streamless sports;
+/// kudos, comments and photos too with WATTS_FETCH_DETAIL) and clean its
+/// streams, as sync stores every ride. A part that fails is left out and
+/// recorded in `errors`.
+pub async fn fetch_parts(
+    access_token: &str,
+    activity: Activity,
+    config: &Config,
+    progress: &mut Progress,
+    errors: &mut Vec<SyncError>,
+) -> ActivityWithStreams {
+    let id = activity.id;
+    // Swims and gym sessions have nothing worth a request
+    let streamless = streamless::is_streamless(&activity.sport_type);
+    let streams = if streamless {
+        progress.status(&format!("⏭️  {}, no streams or laps fetched", activity.sport_type));
+        None
+    } else {
+        match fetch_activity_streams(access_token, activity.id).await {
+            Ok(streams) => {
+                let data_points = streams.time.as_ref().map(|t| t.len()).unwrap_or(0);
+                progress.status(&format!("✅ {} data points", data_points));
+                let partial = streams.original_size.is_some_and(|size| size > data_points);
+                if partial || streams.resolution.as_deref().is_some_and(|r| r != "high") {
+                    warn!("      ⚠️  {} of {} samples at {} resolution, `upgrade-streams` can re-fetch them later",
+                        data_points, streams.original_size.unwrap_or(data_points), streams.resolution.as_deref().unwrap_or("reduced"));
+                }
+                if streams.time.is_none() {
+                    warn!("      ⚠️  No time series, time-based metrics will be skipped");
+                }
+                if !streams.missing_series.is_empty() {
+                    debug!("      Missing series: {}", streams.missing_series.join(", "));
+                }
+                Some(streams)
+            }
+            Err(e) => {
+                warn!("      ⚠️  Could not fetch streams: {}", e);
+                errors.push(SyncError::new(Some(activity.id), "streams", e.as_ref()));
+                // Still save the activity without streams
+                None
+            }
+        }
+    };
+    let mut activity_with_streams = ActivityWithStreams::new(activity, streams);
+    let cleaning = config.cleaning.clone().unwrap_or_default();
+    if cleaning.enabled && clean::apply(&mut activity_with_streams, &cleaning) {
+        debug!("      🧹 Repaired spikes or dropouts in streams");
+    }
+    
+    // Laps carry the block structure of Zwift workouts
+    if !streamless {
+        match fetch_activity_laps(access_token, id).await {
+            Ok(laps) => {
+                debug!("      {} laps", laps.len());
+                activity_with_streams.laps = Some(laps);
+            }
+            Err(e) => {
+                warn!("      ⚠️  Could not fetch laps: {}", e);
+                errors.push(SyncError::new(Some(id), "laps", e.as_ref()));
+            }
+        }
+    }
+    if config::flag("WATTS_FETCH_DETAIL") {
+        match fetch_activity_social(access_token, id).await {
+            Ok(social) => activity_with_streams.social = Some(social),
+            Err(e) => {
+                warn!("      ⚠️  Could not fetch kudos, comments and photos: {}", e);
+                errors.push(SyncError::new(Some(id), "social", e.as_ref()));
+            }
+        }
+    }
+    activity_with_streams
+}
+
+/// Every page of the activity list, newest first: `concurrency` pages are
+/// requested at a time and merged in page order until one comes back empty
+async fn list_all_pages(access_token: &str, concurrency: usize) -> Result<Vec<Activity>, Box<dyn Error>> {
+    let mut activities = Vec::new();
+    let mut next = 1;
+    // Requests share this thread, so their errors needn't be Send
+    let local = LocalSet::new();
+    loop {
+        let width = match strava::rate_limit_remaining() {
+            Some(left) => concurrency.min((left as usize).saturating_sub(TAIL_REQUESTS)),
+            None => concurrency,
+        };
+        if width == 0 {
+            return Err(Incomplete {
+                failure: Failure::RateLimited,
+                message: format!("rate-limit budget spent after listing {} pages, backfill again later", next - 1),
+                activities: Vec::new(),
+            }
+            .into());
+        }
+        debug!("   Fetching pages {}-{} ({} per page)...", next, next + width as u32 - 1, BACKFILL_PER_PAGE);
+
+        let mut batch = JoinSet::new();
+        for page in next..next + width as u32 {
+            let access_token = access_token.to_string();
+            batch.spawn_local_on(
+                async move { (page, fetch_activities_page(&access_token, page, BACKFILL_PER_PAGE).await) },
+                &local,
+            );
+        }
+        let mut pages = BTreeMap::new();
+        while let Some(joined) = local.run_until(batch.join_next()).await {
+            let (page, result) = joined?;
+            pages.insert(page, result?);
+        }
+
+        for (page, listed) in pages {
+            if listed.is_empty() {
+                info!("   Listed {} pages, {} activities", page - 1, activities.len());
+                return Ok(activities);
+            }
+            activities.extend(listed);
+        }
+        next += width as u32;
+    }
+}
@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+
+use crate::activity_id::ActivityId;
+use crate::ci;
+use crate::format;
+use crate::model::{Activity, ActivityIndex};
+use crate::storage::{activity_file_exists, write_atomic};
+
+
+/// Write-ahead record of activities a sync has committed to fetching.
+///
+/// It is written before any stream is fetched and trimmed after each activity
+/// file lands on disk, so a run that dies halfway leaves behind exactly the
+/// work that is still outstanding.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncJournal {
+    pub started_at: String,
+    pub pending: Vec<Activity>,
+}
+
+impl SyncJournal {
+    /// Load the journal left by an interrupted run, if any
+    pub fn load() -> Result<Option<Self>, Box<dyn Error>> {
+        let path = format::data_dir().join(format::SYNC_JOURNAL_FILE);
+        match fs::read_to_string(&path) {
+            Ok(json) => Ok(Some(serde_json::from_str(&json)
+                .map_err(|e| format!("{} is unreadable ({}), delete it to start over", path.display(), e))?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(format::data_dir())?;
+        write_atomic(&format::data_dir().join(format::SYNC_JOURNAL_FILE), serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+
+    /// Remove the journal once every pending activity has been stored
+    pub fn clear() -> Result<(), Box<dyn Error>> {
+        let path = format::data_dir().join(format::SYNC_JOURNAL_FILE);
+        ci::record_removal(&path);
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Queue activities, ignoring ones that are already pending
+    pub fn extend(&mut self, activities: &[Activity]) {
+        for activity in activities {
+            if !self.pending.iter().any(|p| p.id == activity.id) {
+                self.pending.push(activity.clone());
+            }
+        }
+    }
+
+    /// Mark an activity as stored and persist the shorter journal
+    pub fn complete(&mut self, id: ActivityId) -> Result<(), Box<dyn Error>> {
+        self.pending.retain(|a| a.id != id);
+        self.save()
+    }
+
+    /// Bring the index in line with files written before the interruption.
+    /// Returns how many activities were recovered this way.
+    pub fn reconcile(&mut self, index: &mut ActivityIndex) -> usize {
+        let known_ids = index.get_known_ids();
+        let mut recovered = 0;
+        self.pending.retain(|activity| {
+            if !activity_file_exists(activity.id).unwrap_or(false) {
+                return true;
+            }
+            if !known_ids.contains(&activity.id) {
+                index.add_activity(activity);
+                recovered += 1;
+            }
+            false
+        });
+        recovered
+    }
+}
@@ -0,0 +1,186 @@
+//! `last_sync.json`: what one sync run changed, for CI jobs downstream of it
+//! (site rebuilds, notifications) that shouldn't have to diff the archive.
+
+use std::error::Error;
+
+use serde::Serialize;
+
+use crate::activity_id::ActivityId;
+use crate::best_efforts::{self, NewBest};
+use crate::config::Config;
+use crate::failure::{Failure, Incomplete};
+use crate::format;
+use crate::metrics;
+use crate::milestones::{self, Milestone};
+use crate::model::{ActivityIndex, ActivityWithStreams};
+use crate::storage::write_atomic;
+use crate::streaks::{self, Streaks};
+use crate::strava::{self, RateLimit};
+use crate::training_load::DailyLoad;
+use crate::w_balance;
+
+#[derive(Debug, Serialize)]
+pub struct SyncReport {
+    pub schema_version: u32,
+    pub started_at: String,
+    pub finished_at: String,
+    /// Activities stored by this run
+    pub added: Vec<ActivityId>,
+    /// Activities already on disk whose index entry was refreshed
+    pub updated: Vec<ActivityId>,
+    pub metrics: Vec<RideMetrics>,
+    /// All-time mean-maximal power records set by the added rides
+    pub records: Vec<PowerRecord>,
+    /// Season (calendar year) bests set by the added rides
+    pub season_records: Vec<PowerRecord>,
+    /// Lifetime distance, climbing, Eddington and streak milestones the added rides crossed
+    pub milestones: Vec<Milestone>,
+    /// Current and longest day and week streaks after the run
+    pub streaks: Streaks,
+    /// New activities left in the sync journal for the next run, the rate-limit budget being spent
+    pub deferred: Vec<ActivityId>,
+    /// What could not be fetched; the run still completed
+    pub errors: Vec<SyncError>,
+    /// Strava's 15-minute budget when the run finished
+    pub rate_limit: Option<RateLimit>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncError {
+    /// None when not about one activity
+    pub id: Option<ActivityId>,
+    pub message: String,
+    /// HTTP status when Strava answered with one
+    pub status: Option<u16>,
+}
+
+impl SyncError {
+    /// `what` could not be fetched for activity `id`
+    pub fn new(id: Option<ActivityId>, what: &str, e: &(dyn Error + 'static)) -> Self {
+        SyncError { id, message: format!("{}: {}", what, e), status: strava::status_of(e) }
+    }
+}
+
+impl SyncReport {
+    /// One line per added ride, for a commit message
+    pub fn commit_lines(&self) -> Vec<String> {
+        self.metrics.iter().map(|m| format!("- {} ({})", m.name, m.id)).collect()
+    }
+
+    /// Why the run counts as failed despite finishing, None if everything was fetched
+    pub fn failure(&self) -> Option<Incomplete> {
+        let failure = self
+            .errors
+            .iter()
+            .map(|e| match e.status.map(Failure::from_status) {
+                Some(failure @ (Failure::Auth | Failure::RateLimited)) => failure,
+                _ => Failure::Partial,
+            })
+            .chain((!self.deferred.is_empty()).then_some(Failure::RateLimited))
+            .max()?;
+        let mut activities: Vec<ActivityId> = self.errors.iter().filter_map(|e| e.id).collect();
+        activities.dedup();
+        activities.extend(&self.deferred);
+        let message = match self.errors.first() {
+            Some(first) => format!("sync incomplete: {} fetch errors, first: {}", self.errors.len(), first.message),
+            None => format!("sync incomplete: {} activities deferred to the next run", self.deferred.len()),
+        };
+        Some(Incomplete { failure, message, activities })
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RideMetrics {
+    pub id: ActivityId,
+    pub name: String,
+    pub normalized_power: Option<f64>,
+    pub tss: Option<f64>,
+    pub workout_type: Option<String>,
+    pub route: Option<String>,
+    pub efficiency_factor: Option<f64>,
+    pub decoupling: Option<f64>,
+    /// Lowest W'bal in joules
+    pub min_w_balance: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PowerRecord {
+    pub id: ActivityId,
+    pub duration: usize,
+    pub watts: f64,
+    /// Season of a season best
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub season: Option<i32>,
+    /// Best of the rest of the archive, None if no earlier ride was long enough
+    pub previous: Option<f64>,
+}
+
+impl From<NewBest> for PowerRecord {
+    fn from(best: NewBest) -> Self {
+        PowerRecord { id: best.id, duration: best.duration, watts: best.watts, season: best.season, previous: best.previous }
+    }
+}
+
+/// Describe a finished sync run, add its rides to the best-efforts table and
+/// write it to data/last_sync.json. `loads` is the daily PMC load sync already
+/// computed for the rolling totals, reused for the streaks.
+#[allow(clippy::too_many_arguments)]
+pub fn write(
+    started_at: String,
+    added: &[ActivityWithStreams],
+    updated: Vec<ActivityId>,
+    deferred: Vec<ActivityId>,
+    errors: Vec<SyncError>,
+    index: &ActivityIndex,
+    config: &Config,
+    loads: &[DailyLoad],
+) -> Result<SyncReport, Box<dyn Error>> {
+    let w_prime = w_balance::model(config).map(|(_, w_prime)| w_prime);
+    let metrics = added
+        .iter()
+        .map(|file| {
+            let summary = index.activities.iter().find(|a| a.id == file.activity.id);
+            let watts = file.streams.as_ref().and_then(|s| s.watts.as_deref());
+            let np = watts.and_then(metrics::normalized_power);
+            RideMetrics {
+                id: file.activity.id,
+                name: file.activity.name.clone(),
+                normalized_power: np.map(f64::round),
+                tss: np
+                    .zip(config.ftp())
+                    .map(|(np, ftp)| metrics::training_stress_score(np, file.activity.moving_time, ftp).round()),
+                workout_type: summary.and_then(|s| s.workout_type.clone()),
+                route: summary.and_then(|s| s.route.clone()),
+                efficiency_factor: summary.and_then(|s| s.efficiency_factor),
+                decoupling: summary.and_then(|s| s.decoupling),
+                min_w_balance: w_prime.and_then(|w| w_balance::summary(file, w)).map(|(minimum, _)| minimum),
+            }
+        })
+        .collect();
+
+    let bests = best_efforts::update(index, added, config)?;
+    let (records, season_records): (Vec<PowerRecord>, Vec<PowerRecord>) =
+        bests.into_iter().map(PowerRecord::from).partition(|r| r.season.is_none());
+    let added: Vec<ActivityId> = added.iter().map(|f| f.activity.id).collect();
+    let mut milestones = milestones::reached(index, &added);
+    let streaks = streaks::compute(index, config, loads);
+    milestones.extend(streaks::reached(index, &added, config, loads, &streaks));
+
+    let report = SyncReport {
+        schema_version: format::SCHEMA_VERSION,
+        started_at,
+        finished_at: chrono::Utc::now().to_rfc3339(),
+        added,
+        updated,
+        metrics,
+        records,
+        season_records,
+        milestones,
+        streaks,
+        deferred,
+        errors,
+        rate_limit: strava::rate_limit(),
+    };
+    write_atomic(&format::data_dir().join(format::LAST_SYNC_FILE), serde_json::to_string_pretty(&report)?.as_bytes())?;
+    Ok(report)
+}
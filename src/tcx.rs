@@ -0,0 +1,69 @@
+use std::fmt::Write as _;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::escape;
+use crate::model::ActivityWithStreams;
+
+/// Render an activity as a Garmin TCX document, the most widely accepted
+/// upload format that can be produced from Strava streams alone
+pub fn render(file: &ActivityWithStreams) -> Result<String, String> {
+    let activity = &file.activity;
+    let start: DateTime<Utc> = activity
+        .start_date
+        .parse()
+        .map_err(|e| format!("invalid start_date '{}': {}", activity.start_date, e))?;
+    let sport = if activity.sport_type.contains("Run") { "Running" } else { "Biking" };
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<TrainingCenterDatabase xmlns=\"http://www.garmin.com/xmlschemas/TrainingCenterDatabase/v2\" ");
+    xml.push_str("xmlns:ns3=\"http://www.garmin.com/xmlschemas/ActivityExtension/v2\">\n");
+    let _ = writeln!(xml, "  <Activities>\n    <Activity Sport=\"{}\">", sport);
+    let _ = writeln!(xml, "      <Id>{}</Id>", timestamp(start));
+    let _ = writeln!(xml, "      <Notes>{}</Notes>", escape::xml(&activity.name));
+    let _ = writeln!(xml, "      <Lap StartTime=\"{}\">", timestamp(start));
+    let _ = writeln!(xml, "        <TotalTimeSeconds>{}</TotalTimeSeconds>", activity.elapsed_time);
+    let _ = writeln!(xml, "        <DistanceMeters>{:.1}</DistanceMeters>", activity.distance);
+    if let Some(kj) = activity.kilojoules {
+        // TCX wants kcal; human efficiency makes kJ of work ≈ kcal burned
+        let _ = writeln!(xml, "        <Calories>{:.0}</Calories>", kj);
+    }
+    xml.push_str("        <Intensity>Active</Intensity>\n        <TriggerMethod>Manual</TriggerMethod>\n");
+    xml.push_str("        <Track>\n");
+
+    if let Some(streams) = &file.streams {
+        if let Some(time) = &streams.time {
+            let mut distance = 0.0;
+            for (i, &t) in time.iter().enumerate() {
+                if i > 0 {
+                    let dt = (t - time[i - 1]).max(0) as f64;
+                    distance += streams.velocity_smooth.as_ref().and_then(|v| v.get(i)).copied().unwrap_or(0.0) * dt;
+                }
+                let _ = writeln!(xml, "          <Trackpoint>");
+                let _ = writeln!(xml, "            <Time>{}</Time>", timestamp(start + Duration::seconds(t as i64)));
+                if let Some(altitude) = streams.altitude.as_ref().and_then(|a| a.get(i)) {
+                    let _ = writeln!(xml, "            <AltitudeMeters>{:.1}</AltitudeMeters>", altitude);
+                }
+                let _ = writeln!(xml, "            <DistanceMeters>{:.1}</DistanceMeters>", distance);
+                if let Some(hr) = streams.heartrate.as_ref().and_then(|h| h.get(i)) {
+                    let _ = writeln!(xml, "            <HeartRateBpm><Value>{}</Value></HeartRateBpm>", hr);
+                }
+                if let Some(cadence) = streams.cadence.as_ref().and_then(|c| c.get(i)) {
+                    let _ = writeln!(xml, "            <Cadence>{}</Cadence>", cadence);
+                }
+                if let Some(watts) = streams.watts.as_ref().and_then(|w| w.get(i)) {
+                    let _ = writeln!(xml, "            <Extensions><ns3:TPX><ns3:Watts>{:.0}</ns3:Watts></ns3:TPX></Extensions>", watts);
+                }
+                let _ = writeln!(xml, "          </Trackpoint>");
+            }
+        }
+    }
+
+    xml.push_str("        </Track>\n      </Lap>\n    </Activity>\n  </Activities>\n</TrainingCenterDatabase>\n");
+    Ok(xml)
+}
+
+fn timestamp(t: DateTime<Utc>) -> String {
+    t.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
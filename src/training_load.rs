@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use chrono::{Duration, NaiveDate, Utc};
+
+use crate::config::Config;
+use crate::metrics;
+use crate::model::{ActivityIndex, ActivityStreams, ActivitySummary};
+use crate::pace;
+use crate::storage::load_activity_file;
+use crate::streamless;
+
+/// Time constants of the impulse-response model, in days
+const CTL_DAYS: f64 = 42.0;
+const ATL_DAYS: f64 = 7.0;
+
+#[derive(Debug, Clone)]
+pub struct DailyLoad {
+    pub date: NaiveDate,
+    /// TSS of the day's rides, counting TRIMP for rides without power
+    pub tss: f64,
+    /// Chronic training load ("fitness")
+    pub ctl: f64,
+    /// Acute training load ("fatigue")
+    pub atl: f64,
+    /// Training stress balance ("form"): yesterday's CTL minus ATL
+    pub tsb: f64,
+}
+
+/// TSS of a stored ride, None without power data or FTP
+pub fn ride_tss(summary: &ActivitySummary, ftp: Option<f64>) -> Option<f64> {
+    let file = load_activity_file(summary.id).ok()?;
+    streams_tss(file.streams.as_ref()?, summary.moving_time, ftp?)
+}
+
+/// Load counted in the PMC: TSS when the ride has power (rTSS from pace for
+/// runs), heart-rate TRIMP otherwise, and an estimate from moving time for
+/// activities without streams
+pub fn ride_load(summary: &ActivitySummary, config: &Config) -> Option<f64> {
+    let file = load_activity_file(summary.id).ok()?;
+    if let Some(load) = streamless::estimated_load(&file.activity.sport_type, summary.moving_time, config) {
+        return Some(load);
+    }
+    let streams = file.streams.as_ref()?;
+    if pace::is_run(&file.activity.sport_type) {
+        return pace::run_tss(streams, summary.moving_time, config).or_else(|| streams_trimp(streams, config));
+    }
+    config
+        .ftp()
+        .and_then(|ftp| streams_tss(streams, summary.moving_time, ftp))
+        .or_else(|| streams_trimp(streams, config))
+}
+
+/// TSS from the watts stream against `ftp`
+pub fn streams_tss(streams: &ActivityStreams, moving_time: i32, ftp: f64) -> Option<f64> {
+    let watts = streams.watts.as_deref()?;
+    Some(metrics::training_stress_score(metrics::normalized_power(watts)?, moving_time, ftp))
+}
+
+/// TRIMP from the heartrate stream, None unless resting and max HR are configured
+pub fn streams_trimp(streams: &ActivityStreams, config: &Config) -> Option<f64> {
+    let heartrate = streams.heartrate.as_deref()?;
+    metrics::trimp(heartrate, streams.time.as_deref(), config.resting_hr?, config.max_hr?)
+}
+
+/// One entry per day from the first ride to today, with CTL/ATL as exponentially
+/// weighted averages of daily load
+pub fn daily(index: &ActivityIndex, config: &Config) -> Vec<DailyLoad> {
+    let mut tss_by_day: BTreeMap<NaiveDate, f64> = BTreeMap::new();
+    for summary in index.activities.iter().filter(|a| a.counted() && !config.excluded_from_load(a)) {
+        if let Some(tss) = ride_load(summary, config) {
+            *tss_by_day.entry(summary.local_date()).or_default() += tss;
+        }
+    }
+    let Some(&first) = tss_by_day.keys().next() else { return Vec::new() };
+    let today = Utc::now().date_naive();
+
+    let (mut ctl, mut atl) = (0.0, 0.0);
+    let mut days = Vec::new();
+    let mut date = first;
+    while date <= today {
+        let tss = tss_by_day.get(&date).copied().unwrap_or(0.0);
+        let tsb = ctl - atl;
+        ctl += (tss - ctl) / CTL_DAYS;
+        atl += (tss - atl) / ATL_DAYS;
+        days.push(DailyLoad { date, tss, ctl, atl, tsb });
+        date += Duration::days(1);
+    }
+    days
+}
@@ -0,0 +1,210 @@
+//! Heart rate at fixed power over the months: for each power band (e.g.
+//! 180-200 W), the average heart rate of every second ridden in it, so a
+//! falling line shows aerobic adaptation and a rising one fatigue or
+//! overtraining. Cardiac cost (heartbeats per kJ of work) sums it up across
+//! all power.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::cli::{self, OutputFormat};
+use crate::config::Config;
+use crate::model::ActivityIndex;
+use crate::stats::GroupBy;
+use crate::storage::load_activity_file;
+
+/// Power is smoothed over this many samples before banding, as heart rate lags it
+const SMOOTHING: usize = 30;
+
+/// Default bands, as fractions of FTP: endurance to tempo
+const DEFAULT_BANDS: [(f64, f64); 3] = [(0.6, 0.7), (0.7, 0.8), (0.8, 0.9)];
+
+/// A power range in watts, upper bound excluded
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Band {
+    pub low: f64,
+    pub high: f64,
+}
+
+impl Band {
+    /// `180-200`, in watts
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let invalid = || format!("invalid power band '{}', expected e.g. 180-200", s);
+        let (low, high) = s.trim_end_matches('W').split_once('-').ok_or_else(invalid)?;
+        let low: f64 = low.trim().parse().map_err(|_| invalid())?;
+        let high: f64 = high.trim().parse().map_err(|_| invalid())?;
+        if low < 0.0 || high <= low {
+            return Err(invalid());
+        }
+        Ok(Band { low, high })
+    }
+
+    fn label(&self) -> String {
+        format!("{:.0}-{:.0}W", self.low, self.high)
+    }
+}
+
+/// Seconds and heartbeats accumulated in one band
+#[derive(Debug, Default, Clone, Copy)]
+struct Accumulator {
+    seconds: usize,
+    heartrate_sum: f64,
+}
+
+#[derive(Debug, Default)]
+struct Group {
+    rides: usize,
+    bands: Vec<Accumulator>,
+    beats: f64,
+    kilojoules: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct BandHeartRate {
+    #[serde(flatten)]
+    band: Band,
+    seconds: usize,
+    heartrate: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+struct Period {
+    from: NaiveDate,
+    rides: usize,
+    bands: Vec<BandHeartRate>,
+    /// Heartbeats per kJ of work
+    cardiac_cost: Option<f64>,
+}
+
+/// FTP-relative bands rounded to 5 W, when no `--band` was given
+fn default_bands(config: &Config) -> Result<Vec<Band>, Box<dyn Error>> {
+    let ftp = config.ftp().ok_or("no FTP in watts.json to derive power bands from, pass --band LOW-HIGH")?;
+    let round = |w: f64| (w / 5.0).round() * 5.0;
+    Ok(DEFAULT_BANDS.iter().map(|&(low, high)| Band { low: round(low * ftp), high: round(high * ftp) }).collect())
+}
+
+/// Add one ride's seconds in each band to `group`
+fn accumulate(group: &mut Group, bands: &[Band], watts: &[f64], heartrate: &[i32]) {
+    let len = watts.len().min(heartrate.len());
+    if len < SMOOTHING {
+        return;
+    }
+    group.rides += 1;
+    let mut window: f64 = watts[..SMOOTHING].iter().sum();
+    for i in SMOOTHING - 1..len {
+        if i >= SMOOTHING {
+            window += watts[i] - watts[i - SMOOTHING];
+        }
+        let hr = heartrate[i];
+        if hr <= 0 {
+            continue;
+        }
+        if watts[i] > 0.0 {
+            group.beats += hr as f64 / 60.0;
+            group.kilojoules += watts[i] / 1000.0;
+        }
+        let power = window / SMOOTHING as f64;
+        if let Some(b) = bands.iter().position(|b| power >= b.low && power < b.high) {
+            group.bands[b].seconds += 1;
+            group.bands[b].heartrate_sum += hr as f64;
+        }
+    }
+}
+
+/// Print heart rate per power band for each week or month since `since`
+pub fn run(since: Option<NaiveDate>, bands: Vec<Band>, group_by: GroupBy, output: OutputFormat) -> Result<(), Box<dyn Error>> {
+    let index = ActivityIndex::load()?;
+    let config = Config::current()?;
+    let bands = if bands.is_empty() { default_bands(&config)? } else { bands };
+
+    let mut groups: BTreeMap<NaiveDate, Group> = BTreeMap::new();
+    for summary in index.activities.iter().filter(|a| a.counted() && a.average_heartrate.is_some()) {
+        let day = summary.local_date();
+        if since.is_some_and(|s| day < s) {
+            continue;
+        }
+        let file = match load_activity_file(summary.id) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("   ⚠️  Skipping {}: {}", summary.id, e);
+                continue;
+            }
+        };
+        let streams = file.streams.as_ref();
+        let (Some(watts), Some(heartrate)) = (streams.and_then(|s| s.watts.as_deref()), streams.and_then(|s| s.heartrate.as_deref())) else {
+            continue;
+        };
+        let group = groups
+            .entry(group_by.start(day, day))
+            .or_insert_with(|| Group { bands: vec![Accumulator::default(); bands.len()], ..Group::default() });
+        accumulate(group, &bands, watts, heartrate);
+    }
+
+    let periods: Vec<Period> = groups
+        .into_iter()
+        .filter(|(_, g)| g.rides > 0)
+        .map(|(from, g)| Period {
+            from,
+            rides: g.rides,
+            bands: bands
+                .iter()
+                .zip(&g.bands)
+                .map(|(&band, a)| BandHeartRate {
+                    band,
+                    seconds: a.seconds,
+                    heartrate: (a.seconds > 0).then(|| (a.heartrate_sum / a.seconds as f64 * 10.0).round() / 10.0),
+                })
+                .collect(),
+            cardiac_cost: (g.kilojoules > 0.0).then(|| (g.beats / g.kilojoules * 100.0).round() / 100.0),
+        })
+        .collect();
+
+    if output == OutputFormat::Json {
+        return cli::print_json(&periods);
+    }
+    let unit = if group_by == GroupBy::Week { "week" } else { "month" };
+    info!("💓 Heart rate at power by {} ({} rides with power and heart rate)", unit, periods.iter().map(|p| p.rides).sum::<usize>());
+    let mut header = format!("  {:<10} {:>5}", unit, "rides");
+    for band in &bands {
+        header.push_str(&format!(" {:>15}", band.label()));
+    }
+    println!("{} {:>9}", header, "beats/kJ");
+    for period in &periods {
+        let mut line = format!(
+            "  {:<10} {:>5}",
+            if group_by == GroupBy::Week { period.from.to_string() } else { period.from.format("%Y-%m").to_string() },
+            period.rides
+        );
+        for band in &period.bands {
+            let cell = match band.heartrate {
+                Some(hr) => format!("{:.0} bpm {:>3}m", hr, band.seconds / 60),
+                None => "-".to_string(),
+            };
+            line.push_str(&format!(" {:>15}", cell));
+        }
+        println!("{} {:>9}", line, period.cardiac_cost.map(|c| format!("{:.2}", c)).unwrap_or_else(|| "-".into()));
+    }
+
+    // Drift from the first to the last period with time in each band
+    let mut change = format!("  {:<10} {:>5}", "change", "");
+    for b in 0..bands.len() {
+        let mut rates = periods.iter().filter_map(|p| p.bands[b].heartrate);
+        let first = rates.next();
+        let cell = match (first, rates.next_back()) {
+            (Some(first), Some(last)) => format!("{:+.0} bpm", last - first),
+            _ => "-".to_string(),
+        };
+        change.push_str(&format!(" {:>15}", cell));
+    }
+    let mut costs = periods.iter().filter_map(|p| p.cardiac_cost);
+    let cost_change = match (costs.next(), costs.next_back()) {
+        (Some(first), Some(last)) => format!("{:+.2}", last - first),
+        _ => "-".to_string(),
+    };
+    println!("{} {:>9}", change, cost_change);
+    Ok(())
+}
@@ -0,0 +1,442 @@
+//! `tui`: browse the archive in the terminal. The ride list scrolls on the
+//! left and the selected ride's metrics and charts show on the right; `/`
+//! filters by name, tag or route, `o` changes the order and `s` syncs without
+//! leaving.
+//!
+//! Drawn with ratatui on crossterm, so it runs in any terminal those support.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, IsTerminal, Write};
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListState, Padding, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use tracing::debug;
+
+use crate::activity_id::ActivityId;
+use crate::ascii_chart;
+use crate::config::Config;
+use crate::metrics;
+use crate::model::{ActivityIndex, ActivitySummary};
+use crate::storage::load_activity_file;
+use crate::sync;
+
+/// Width of the ride list, at most
+const LIST_WIDTH: u16 = 60;
+
+/// Rows of the power and heart rate charts
+const CHART_HEIGHT: usize = 6;
+
+const HELP: &str = "j/k ↑/↓ move  PgUp/PgDn page  g/G first/last  / filter  a all  o order  s sync  q quit";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Esc,
+    Backspace,
+    Interrupt,
+    Char(char),
+}
+
+/// One of our keys, None when the key isn't one of ours
+fn key(event: KeyEvent) -> Option<Key> {
+    if event.kind != KeyEventKind::Press {
+        return None;
+    }
+    let key = match event.code {
+        KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::CONTROL) => Key::Interrupt,
+        KeyCode::Up => Key::Up,
+        KeyCode::Down => Key::Down,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::Enter => Key::Enter,
+        KeyCode::Esc => Key::Esc,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Char(c) => Key::Char(c),
+        _ => return None,
+    };
+    Some(key)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Order {
+    Date,
+    Distance,
+    Time,
+    Watts,
+}
+
+impl Order {
+    fn next(self) -> Self {
+        match self {
+            Order::Date => Order::Distance,
+            Order::Distance => Order::Time,
+            Order::Time => Order::Watts,
+            Order::Watts => Order::Date,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Order::Date => "date",
+            Order::Distance => "distance",
+            Order::Time => "time",
+            Order::Watts => "watts",
+        }
+    }
+}
+
+/// What the detail pane shows beyond the index entry, read from the activity file
+#[derive(Debug, Default)]
+struct Detail {
+    sport: Option<String>,
+    elevation: Option<f64>,
+    description: Option<String>,
+    normalized_power: Option<f64>,
+    watts: Vec<f64>,
+    heartrate: Vec<f64>,
+    /// Seconds in each power zone
+    zones: Vec<i32>,
+}
+
+impl Detail {
+    fn load(id: ActivityId, config: &Config) -> Self {
+        let file = match load_activity_file(id) {
+            Ok(file) => file,
+            Err(e) => {
+                debug!("   {}: {}", id, e);
+                return Detail::default();
+            }
+        };
+        let streams = file.streams.as_ref();
+        let watts = streams.and_then(|s| s.watts.clone()).unwrap_or_default();
+        let heartrate = streams.and_then(|s| s.heartrate.as_ref()).map(|h| h.iter().map(|&b| b as f64).collect()).unwrap_or_default();
+        Detail {
+            sport: Some(file.activity.sport_type.clone()),
+            elevation: Some(file.activity.total_elevation_gain),
+            description: file.activity.description.clone().filter(|d| !d.trim().is_empty()),
+            normalized_power: metrics::normalized_power(&watts),
+            zones: config.power_zone_floors().map(|floors| metrics::time_in_zones(&watts, &floors)).unwrap_or_default(),
+            watts,
+            heartrate,
+        }
+    }
+}
+
+struct App {
+    index: ActivityIndex,
+    config: Config,
+    /// Positions in `index.activities` of the listed rides, in order
+    rows: Vec<usize>,
+    selected: usize,
+    /// Scroll position of the ride list
+    list: ListState,
+    filter: String,
+    /// The filter being typed after `/`
+    editing: Option<String>,
+    order: Order,
+    /// Also list excluded rides and duplicates
+    all: bool,
+    status: String,
+    details: HashMap<ActivityId, Detail>,
+}
+
+impl App {
+    fn new() -> Result<Self, Box<dyn Error>> {
+        let mut app = App {
+            index: ActivityIndex::load()?,
+            config: Config::current()?,
+            rows: Vec::new(),
+            selected: 0,
+            list: ListState::default(),
+            filter: String::new(),
+            editing: None,
+            order: Order::Date,
+            all: false,
+            status: String::new(),
+            details: HashMap::new(),
+        };
+        app.refresh();
+        Ok(app)
+    }
+
+    fn matches(&self, summary: &ActivitySummary) -> bool {
+        if !self.all && !summary.counted() {
+            return false;
+        }
+        let needle = self.filter.to_lowercase();
+        needle.split_whitespace().all(|word| {
+            summary.name.to_lowercase().contains(word)
+                || summary.tags.iter().any(|t| t.to_lowercase().contains(word))
+                || summary.route.iter().chain(&summary.world).any(|r| r.to_lowercase().contains(word))
+                || summary.workout_type.as_ref().is_some_and(|t| t.to_lowercase().contains(word))
+        })
+    }
+
+    /// Rebuild the listed rows after the filter, order or index changed, keeping the selection if it's still listed
+    fn refresh(&mut self) {
+        let selected_id = self.current().map(|s| s.id);
+        let activities = &self.index.activities;
+        let mut rows: Vec<usize> = (0..activities.len()).filter(|&i| self.matches(&activities[i])).collect();
+        // Highest first, newest first on ties
+        rows.sort_by(|&a, &b| {
+            let (a, b) = (&activities[a], &activities[b]);
+            let by = match self.order {
+                Order::Date => std::cmp::Ordering::Equal,
+                Order::Distance => b.distance.total_cmp(&a.distance),
+                Order::Time => b.moving_time.cmp(&a.moving_time),
+                Order::Watts => b.average_watts.unwrap_or(0.0).total_cmp(&a.average_watts.unwrap_or(0.0)),
+            };
+            by.then_with(|| b.start_date.cmp(&a.start_date))
+        });
+        self.rows = rows;
+        self.selected = selected_id
+            .and_then(|id| self.rows.iter().position(|&i| activities[i].id == id))
+            .unwrap_or(0);
+    }
+
+    fn current(&self) -> Option<&ActivitySummary> {
+        self.rows.get(self.selected).map(|&i| &self.index.activities[i])
+    }
+
+    fn select(&mut self, delta: isize) {
+        let last = self.rows.len().saturating_sub(1) as isize;
+        self.selected = (self.selected as isize + delta).clamp(0, last.max(0)) as usize;
+    }
+
+    /// Handle a key; false once the user quits
+    fn handle(&mut self, key: Key, page: usize) -> bool {
+        if let Some(editing) = &mut self.editing {
+            match key {
+                Key::Enter => {
+                    self.filter = self.editing.take().unwrap_or_default();
+                    self.refresh();
+                }
+                Key::Esc => self.editing = None,
+                Key::Interrupt => return false,
+                Key::Backspace => {
+                    editing.pop();
+                }
+                Key::Char(c) => editing.push(c),
+                _ => {}
+            }
+            return true;
+        }
+        let page = page.max(1) as isize;
+        match key {
+            Key::Char('q') | Key::Interrupt => return false,
+            Key::Up | Key::Char('k') => self.select(-1),
+            Key::Down | Key::Char('j') => self.select(1),
+            Key::PageUp => self.select(-page),
+            Key::PageDown | Key::Char(' ') => self.select(page),
+            Key::Home | Key::Char('g') => self.selected = 0,
+            Key::End | Key::Char('G') => self.select(isize::MAX / 2),
+            Key::Char('/') => self.editing = Some(self.filter.clone()),
+            Key::Esc if !self.filter.is_empty() => {
+                self.filter.clear();
+                self.refresh();
+            }
+            Key::Char('a') => {
+                self.all = !self.all;
+                self.refresh();
+            }
+            Key::Char('o') => {
+                self.order = self.order.next();
+                self.refresh();
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn list_line(&self, row: usize) -> String {
+        let summary = &self.index.activities[self.rows[row]];
+        format!(
+            "{:<10} {:>5.1} {:>4} {}",
+            summary.local_date(),
+            summary.distance / 1000.0,
+            summary.average_watts.map(|w| format!("{:.0}", w)).unwrap_or_else(|| "-".into()),
+            summary.name
+        )
+    }
+
+    fn detail_lines(&mut self, width: usize) -> Vec<String> {
+        let Some(summary) = self.current().cloned() else {
+            return vec!["No rides match".to_string()];
+        };
+        let detail = self.details.entry(summary.id).or_insert_with(|| Detail::load(summary.id, &self.config));
+        let mut lines = vec![
+            summary.name.clone(),
+            [
+                Some(summary.local_start().format("%Y-%m-%d %H:%M").to_string()),
+                detail.sport.clone(),
+                summary.route.clone(),
+                summary.workout_type.clone(),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" · "),
+            String::new(),
+            format!(
+                "{:.1} km  {}:{:02}:{:02}{}",
+                summary.distance / 1000.0,
+                summary.moving_time / 3600,
+                (summary.moving_time % 3600) / 60,
+                summary.moving_time % 60,
+                detail.elevation.map(|e| format!("  {:.0} m up", e)).unwrap_or_default()
+            ),
+        ];
+        let mut power = Vec::new();
+        if let Some(watts) = summary.average_watts {
+            power.push(format!("{:.0} W avg", watts));
+        }
+        if let Some(np) = detail.normalized_power {
+            power.push(format!("NP {:.0} W", np));
+            if let Some(ftp) = self.config.ftp() {
+                power.push(format!("IF {:.2}", np / ftp));
+                power.push(format!("TSS {:.0}", metrics::training_stress_score(np, summary.moving_time, ftp)));
+            }
+        }
+        if !power.is_empty() {
+            lines.push(power.join("  "));
+        }
+        let mut heart = Vec::new();
+        if let Some(hr) = summary.average_heartrate {
+            heart.push(format!("{:.0} bpm avg", hr));
+        }
+        if let Some(ef) = summary.efficiency_factor {
+            heart.push(format!("EF {:.2}", ef));
+        }
+        if let Some(decoupling) = summary.decoupling {
+            heart.push(format!("{:.1}% decoupling", decoupling));
+        }
+        if !heart.is_empty() {
+            lines.push(heart.join("  "));
+        }
+        if !summary.tags.is_empty() {
+            lines.push(format!("tags: {}", summary.tags.join(", ")));
+        }
+        if !summary.counted() {
+            lines.push(if summary.excluded { "excluded".to_string() } else { "duplicate".to_string() });
+        }
+        for text in summary.notes.iter().chain(&detail.description) {
+            lines.push(String::new());
+            lines.extend(text.lines().map(str::to_string));
+        }
+        let chart_width = width.saturating_sub(10);
+        for (title, values) in [("Power (W)", &detail.watts), ("Heart rate (bpm)", &detail.heartrate)] {
+            if !values.is_empty() {
+                lines.push(String::new());
+                lines.push(title.to_string());
+                lines.extend(ascii_chart::area_chart(values, chart_width, CHART_HEIGHT));
+            }
+        }
+        if detail.zones.iter().any(|&s| s > 0) {
+            let bars: Vec<(String, f64)> =
+                detail.zones.iter().enumerate().map(|(zone, &s)| (format!("Z{}", zone + 1), s as f64 / 60.0)).collect();
+            lines.push(String::new());
+            lines.push("Power zones".to_string());
+            lines.extend(ascii_chart::bar_chart(&bars, width.saturating_sub(16), 0, "min"));
+        }
+        lines
+    }
+
+    /// The whole screen: header, ride list beside the detail pane, and footer
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header_area, body, footer_area] = Layout::vertical([Constraint::Length(1), Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [list_area, detail_area] =
+            Layout::horizontal([Constraint::Length((body.width * 45 / 100).min(LIST_WIDTH)), Constraint::Min(0)]).areas(body);
+
+        let mut header = format!(" Watts Happening  {} of {} rides  order: {}", self.rows.len(), self.index.activities.len(), self.order.as_str());
+        if !self.filter.is_empty() {
+            header.push_str(&format!("  filter: {}", self.filter));
+        }
+        if self.all {
+            header.push_str("  (all)");
+        }
+        frame.render_widget(Paragraph::new(header).reversed(), header_area);
+
+        let list = List::new((0..self.rows.len()).map(|row| self.list_line(row))).highlight_style(Style::new().reversed());
+        self.list.select(Some(self.selected).filter(|_| !self.rows.is_empty()));
+        frame.render_stateful_widget(list, list_area, &mut self.list);
+
+        // Less the divider and its padding
+        let detail = self.detail_lines(detail_area.width.saturating_sub(2) as usize);
+        let detail = Paragraph::new(detail.into_iter().map(Line::from).collect::<Vec<_>>());
+        frame.render_widget(detail.block(Block::new().borders(Borders::LEFT).padding(Padding::left(1))), detail_area);
+
+        let footer = match &self.editing {
+            Some(text) => format!("/{}█", text),
+            None if !self.status.is_empty() => format!("{}  ({})", self.status, HELP),
+            None => HELP.to_string(),
+        };
+        frame.render_widget(Paragraph::new(footer), footer_area);
+    }
+}
+
+/// Sync with the terminal back in normal mode so its logs and progress show
+async fn sync_from_tui(app: &mut App) -> Result<(), Box<dyn Error>> {
+    println!();
+    app.status = match sync::run(None).await {
+        Ok(report) => match report.failure() {
+            Some(incomplete) => format!("Sync incomplete: {}", incomplete),
+            None => format!("Synced: {} new, {} updated", report.added.len(), report.updated.len()),
+        },
+        Err(e) => format!("Sync failed: {}", e),
+    };
+    print!("{} - press Enter to go back", app.status);
+    io::stdout().flush()?;
+    io::stdin().read_line(&mut String::new())?;
+    app.index = ActivityIndex::load()?;
+    app.details.clear();
+    app.refresh();
+    Ok(())
+}
+
+/// Draw and handle keys until the user quits; `s` leaves the terminal for the sync
+async fn browse(terminal: &mut DefaultTerminal, app: &mut App) -> Result<(), Box<dyn Error>> {
+    loop {
+        terminal.draw(|frame| app.draw(frame))?;
+        // Resizes and keys that aren't ours only redraw
+        let Event::Key(event) = event::read()? else { continue };
+        let Some(key) = key(event) else { continue };
+        if key == Key::Char('s') && app.editing.is_none() {
+            ratatui::restore();
+            let synced = sync_from_tui(app).await;
+            *terminal = ratatui::try_init()?;
+            synced?;
+            continue;
+        }
+        app.status.clear();
+        // Less the header and footer
+        let page = terminal.size()?.height.saturating_sub(2) as usize;
+        if !app.handle(key, page) {
+            return Ok(());
+        }
+    }
+}
+
+/// Browse the active athlete's rides until `q`
+pub async fn run() -> Result<(), Box<dyn Error>> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err("tui needs a terminal".into());
+    }
+    let mut app = App::new()?;
+    let mut terminal = ratatui::try_init()?;
+    let browsed = browse(&mut terminal, &mut app).await;
+    ratatui::restore();
+    browsed
+}
@@ -0,0 +1,86 @@
+use std::error::Error;
+
+use chrono::NaiveDate;
+use tracing::{info, warn};
+
+use crate::model::{ActivityIndex, ActivityWithStreams};
+use crate::progress::Progress;
+use crate::storage::{load_activity_file, stored_compression, write_activity_file};
+use crate::strava::{self, access_token, fetch_activity_streams};
+use crate::streamless;
+
+/// Default number of stream requests per run, under Strava's 100 per 15 minutes
+pub const DEFAULT_BUDGET: usize = 90;
+
+/// Whether an activity's stored streams are below full resolution
+pub fn needs_upgrade(file: &ActivityWithStreams) -> bool {
+    if streamless::is_streamless(&file.activity.sport_type) {
+        return false;
+    }
+    let Some(streams) = &file.streams else { return true };
+    let points = streams.time.as_ref().map(|t| t.len()).unwrap_or(0);
+    if streams.original_size.is_some_and(|size| points < size) {
+        return true;
+    }
+    match streams.resolution.as_deref() {
+        Some(resolution) => resolution != "high",
+        // Files from before resolution was recorded: full resolution is ~1 point per second
+        None => (points as i32) < file.activity.moving_time / 2,
+    }
+}
+
+/// Re-fetch streams for low-resolution activities, at most `budget` requests per run
+pub async fn run(since: Option<NaiveDate>, budget: usize) -> Result<(), Box<dyn Error>> {
+    let index = ActivityIndex::load()?;
+    let budget = strava::within_budget(budget);
+
+    let mut candidates = Vec::new();
+    for summary in index.activities.iter().filter(|a| since.is_none_or(|s| a.local_date() >= s)) {
+        match load_activity_file(summary.id) {
+            Ok(file) if needs_upgrade(&file) => candidates.push(file),
+            Ok(_) => {}
+            Err(e) => warn!("   ⚠️  Skipping {}: {}", summary.id, e),
+        }
+    }
+    info!("📉 {} activities need full-resolution streams", candidates.len());
+    if candidates.is_empty() {
+        return Ok(());
+    }
+
+    let access_token = access_token().await?;
+    let total = candidates.len();
+    let batch = budget.min(total);
+    let mut upgraded = 0;
+    let mut progress = Progress::new("📉 Upgrading", batch);
+    for (i, mut file) in candidates.into_iter().take(budget).enumerate() {
+        let id = file.activity.id;
+        progress.item(i, &id.to_string());
+        if file.power_adjustment.is_some() {
+            warn!("   ⏭️  {} has a power adjustment, revert it before upgrading", id);
+            continue;
+        }
+        let old_points = file.streams.as_ref().and_then(|s| s.time.as_ref()).map(|t| t.len()).unwrap_or(0);
+        match fetch_activity_streams(&access_token, id).await {
+            Ok(streams) => {
+                let new_points = streams.time.as_ref().map(|t| t.len()).unwrap_or(0);
+                progress.status(&format!("{} -> {} points", old_points, new_points));
+                if new_points >= old_points {
+                    let compression = stored_compression(id)?.ok_or("activity file disappeared")?;
+                    file.streams = Some(streams);
+                    write_activity_file(&file, compression)?;
+                    upgraded += 1;
+                }
+            }
+            Err(e) => warn!("   ⚠️  Could not fetch streams for {}: {}", id, e),
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    }
+    drop(progress);
+
+    let remaining = total - upgraded;
+    info!("✅ Upgraded {} activities, {} still need upgrading", upgraded, remaining);
+    if total > budget {
+        info!("   Run again once the rate limit window resets to continue");
+    }
+    Ok(())
+}
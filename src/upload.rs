@@ -0,0 +1,167 @@
+//! Push a local FIT, TCX or GPX file to Strava through the uploads API, then
+//! poll until Strava has made an activity of it. Each file's SHA-256 is kept
+//! in data/uploads.json so the same file isn't sent twice, and Strava's own
+//! duplicate check (same device and start time) is reported rather than failed.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::format;
+use crate::storage::{sha256, write_atomic};
+use crate::strava::{self, access_token};
+
+/// Status polls before giving up; Strava usually needs a few seconds
+const MAX_POLLS: u32 = 30;
+
+/// Pause between status polls
+const POLL_DELAY_MS: u64 = 2000;
+
+/// Extensions Strava accepts, and the data_type it expects for each
+const DATA_TYPES: [(&str, &str); 6] =
+    [(".fit", "fit"), (".fit.gz", "fit.gz"), (".tcx", "tcx"), (".tcx.gz", "tcx.gz"), (".gpx", "gpx"), (".gpx.gz", "gpx.gz")];
+
+/// File SHA-256 -> what became of its upload
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct UploadLog {
+    pub schema_version: u32,
+    pub uploads: BTreeMap<String, UploadEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadEntry {
+    /// Name of the file as uploaded
+    pub file: String,
+    pub uploaded_at: String,
+    pub upload_id: String,
+    /// The activity created, or the one Strava found it duplicates
+    pub activity_id: Option<i64>,
+    /// "created", "duplicate" or "pending" when polling gave up
+    pub status: String,
+}
+
+/// Name, description and flags for the new activity
+#[derive(Debug, Default)]
+pub struct UploadOptions {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub trainer: bool,
+    pub commute: bool,
+    /// Upload even when data/uploads.json lists the file
+    pub force: bool,
+}
+
+impl UploadLog {
+    fn load() -> Result<Self, Box<dyn Error>> {
+        match fs::read_to_string(format::uploads_path()) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(UploadLog::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        self.schema_version = format::SCHEMA_VERSION;
+        write_atomic(&format::uploads_path(), serde_json::to_string_pretty(self)?.as_bytes())
+    }
+}
+
+fn data_type(file_name: &str) -> Result<&'static str, Box<dyn Error>> {
+    let lower = file_name.to_lowercase();
+    DATA_TYPES
+        .iter()
+        .filter(|(extension, _)| lower.ends_with(extension))
+        .max_by_key(|(extension, _)| extension.len())
+        .map(|&(_, data_type)| data_type)
+        .ok_or_else(|| format!("{}: Strava takes .fit, .tcx or .gpx files, optionally gzipped", file_name).into())
+}
+
+/// The activity an error such as "x.fit duplicate of activity 123" points to
+fn duplicate_of(error: &str) -> Option<i64> {
+    let rest = &error[error.find("duplicate of")? + "duplicate of".len()..];
+    let digits: String = rest.chars().skip_while(|c| !c.is_ascii_digit()).take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+/// Upload `path` to Strava and wait for the activity; the next sync stores it
+pub async fn run(path: &Path, options: UploadOptions) -> Result<(), Box<dyn Error>> {
+    let file_name = path.file_name().and_then(|n| n.to_str()).ok_or_else(|| format!("{}: not a file", path.display()))?.to_string();
+    let data_type = data_type(&file_name)?;
+    let contents = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let hash = sha256(&contents);
+
+    let mut log = UploadLog::load()?;
+    if let Some(previous) = log.uploads.get(&hash).filter(|e| e.status != "pending" && !options.force) {
+        info!(
+            "👯 {} was already uploaded on {}{}; --force sends it again",
+            file_name,
+            previous.uploaded_at.get(..10).unwrap_or(&previous.uploaded_at),
+            previous.activity_id.map(|id| format!(" (https://www.strava.com/activities/{})", id)).unwrap_or_default()
+        );
+        return Ok(());
+    }
+
+    let mut fields = vec![("data_type", data_type.to_string()), ("external_id", format!("watts-{}", &hash[..16]))];
+    if let Some(name) = options.name {
+        fields.push(("name", name));
+    }
+    if let Some(description) = options.description {
+        fields.push(("description", description));
+    }
+    if options.trainer {
+        fields.push(("trainer", "1".to_string()));
+    }
+    if options.commute {
+        fields.push(("commute", "1".to_string()));
+    }
+
+    let token = access_token().await?;
+    info!("📤 Uploading {} ({} KB) to Strava", file_name, contents.len().div_ceil(1024));
+    let mut upload = match strava::create_upload(&token, &file_name, &contents, &fields).await {
+        Ok(upload) => upload,
+        Err(e) if matches!(strava::status_of(e.as_ref()), Some(401 | 403)) => {
+            return Err(format!("{} (uploading needs the activity:write scope, authorize the app again with it)", e).into());
+        }
+        Err(e) => return Err(e),
+    };
+
+    let mut polls = 0;
+    while upload.activity_id.is_none() && upload.error.is_none() && polls < MAX_POLLS {
+        info!("   ⏳ {}", upload.status.as_deref().unwrap_or("Processing"));
+        tokio::time::sleep(tokio::time::Duration::from_millis(POLL_DELAY_MS)).await;
+        upload = strava::fetch_upload(&token, &upload.id_str).await?;
+        polls += 1;
+    }
+
+    let mut entry = UploadEntry {
+        file: file_name.clone(),
+        uploaded_at: Utc::now().to_rfc3339(),
+        upload_id: upload.id_str.clone(),
+        activity_id: upload.activity_id,
+        status: "created".to_string(),
+    };
+    match (&upload.error, upload.activity_id) {
+        (Some(error), _) => match duplicate_of(error) {
+            Some(existing) => {
+                info!("👯 Strava already has this ride as https://www.strava.com/activities/{}", existing);
+                entry.activity_id = Some(existing);
+                entry.status = "duplicate".to_string();
+            }
+            None => return Err(format!("Strava rejected {}: {}", file_name, error).into()),
+        },
+        (None, Some(id)) => {
+            info!("✅ Uploaded as https://www.strava.com/activities/{}, the next sync adds it to the archive", id);
+        }
+        (None, None) => {
+            warn!("   ⚠️  Upload {} still processing after {} s; it should appear on Strava shortly", upload.id_str, MAX_POLLS as u64 * POLL_DELAY_MS / 1000);
+            entry.status = "pending".to_string();
+        }
+    }
+    log.uploads.insert(hash, entry);
+    log.save()
+}
@@ -0,0 +1,164 @@
+//! `verify`: catch silent corruption in the archive.
+//!
+//! Every activity file must parse, have exactly one index entry agreeing with
+//! it on name, start, sport, distance and moving time, and carry streams that
+//! all have the same number of samples. `--repair` fixes what the archive
+//! itself can: index entries are rebuilt from their files (keeping tags, notes
+//! and derived metrics), duplicates dropped and streams aligned to the time
+//! stream as sync does. Rides whose streams were aligned are listed too.
+//! Unreadable files and entries without a file need a download, which `refetch`
+//! or `show --fetch` do.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use tracing::{info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::align;
+use crate::compression::Compression;
+use crate::model::{ActivityIndex, ActivitySummary, ActivityWithStreams};
+use crate::storage::{list_activity_ids, parse_activity_file, read_activity_file, write_activity_file};
+
+/// Distances are stored as Strava reports them; anything beyond rounding is a mismatch
+const DISTANCE_TOLERANCE: f64 = 0.5;
+
+#[derive(Default)]
+struct Findings {
+    problems: usize,
+    /// Problems `--repair` fixes (and fixed, when it ran)
+    fixable: usize,
+}
+
+/// Check the whole archive, repairing what can be when `repair` is set.
+/// Returns the number of problems left.
+pub fn run(repair: bool) -> Result<usize, Box<dyn Error>> {
+    let mut index = ActivityIndex::load()?;
+    let ids = list_activity_ids()?;
+    info!("🩺 Verifying {} activity files against {} index entries{}", ids.len(), index.activities.len(),
+        if repair { " (repairing)" } else { "" });
+    let mut findings = Findings::default();
+    let mut index_changed = false;
+
+    let mut entries: BTreeMap<ActivityId, usize> = BTreeMap::new();
+    for summary in &index.activities {
+        *entries.entry(summary.id).or_default() += 1;
+    }
+    for (id, count) in entries.iter().filter(|(_, count)| **count > 1) {
+        report(&mut findings, *id, &format!("{} index entries", count), repair);
+        if repair {
+            let mut seen = false;
+            index.activities.retain(|a| a.id != *id || !std::mem::replace(&mut seen, true));
+            index_changed = true;
+        }
+    }
+
+    let stored: BTreeSet<ActivityId> = ids.iter().copied().collect();
+    let mut aligned = Vec::new();
+    for id in ids {
+        let (mut file, compression) = match parse(id) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                warn!("   ❌ {}: unreadable ({}), `refetch {}` replaces it", id, e, id);
+                findings.problems += 1;
+                continue;
+            }
+        };
+
+        match index.activities.iter().find(|a| a.id == id) {
+            None => {
+                report(&mut findings, id, "not in the index", repair);
+                if repair {
+                    index.add_activity(&file.activity);
+                    index_changed = true;
+                }
+            }
+            Some(summary) => {
+                let differences = differences(summary, &file);
+                if !differences.is_empty() {
+                    report(&mut findings, id, &format!("index entry differs ({})", differences.join(", ")), repair);
+                    if repair {
+                        index.add_activity(&file.activity);
+                        index_changed = true;
+                    }
+                }
+            }
+        }
+
+        if let Some(streams) = file.streams.as_mut() {
+            if !align::is_aligned(streams) {
+                let listed: Vec<String> = align::lengths(streams).iter().map(|(name, len)| format!("{} {}", name, len)).collect();
+                report(&mut findings, id, &format!("stream lengths differ ({})", listed.join(", ")), repair);
+                if repair {
+                    align::align(streams);
+                    write_activity_file(&file, compression)?;
+                }
+            }
+        }
+        if file.streams.as_ref().is_some_and(|s| !s.misaligned.is_empty()) {
+            aligned.push(id);
+        }
+    }
+    if !aligned.is_empty() {
+        let listed: Vec<String> = aligned.iter().map(ActivityId::to_string).collect();
+        info!("📏 {} activities have streams aligned to their time stream: {}", aligned.len(), listed.join(", "));
+    }
+
+    for summary in index.activities.iter().filter(|a| !stored.contains(&a.id)) {
+        warn!("   ❌ {}: in the index without a file, `show {} --fetch` downloads it", summary.id, summary.id);
+        findings.problems += 1;
+    }
+
+    if index_changed {
+        index.save()?;
+    }
+    if findings.problems == 0 {
+        info!("✅ No problems found");
+        return Ok(0);
+    }
+    if repair {
+        let left = findings.problems - findings.fixable;
+        info!("🔧 {} problems found, {} repaired, {} left", findings.problems, findings.fixable, left);
+        Ok(left)
+    } else {
+        info!("🔎 {} problems found, `verify --repair` fixes {} of them", findings.problems, findings.fixable);
+        Ok(findings.problems)
+    }
+}
+
+/// Log a problem `--repair` fixes
+fn report(findings: &mut Findings, id: ActivityId, problem: &str, repair: bool) {
+    warn!("   {} {}: {}", if repair { "🔧" } else { "⚠️ " }, id, problem);
+    findings.problems += 1;
+    findings.fixable += 1;
+}
+
+fn parse(id: ActivityId) -> Result<(ActivityWithStreams, Compression), Box<dyn Error>> {
+    let (json, compression) = read_activity_file(id)?;
+    let file = parse_activity_file(&json)?;
+    if file.activity.id != id {
+        return Err(format!("holds activity {}", file.activity.id).into());
+    }
+    Ok((file, compression))
+}
+
+/// Index fields that disagree with the activity file
+fn differences(summary: &ActivitySummary, file: &ActivityWithStreams) -> Vec<&'static str> {
+    let activity = &file.activity;
+    let mut differences = Vec::new();
+    if summary.name != activity.name {
+        differences.push("name");
+    }
+    if activity.start() != Some(summary.start_date) {
+        differences.push("start_date");
+    }
+    if summary.sport_type.as_deref().is_some_and(|sport| sport != activity.sport_type) {
+        differences.push("sport_type");
+    }
+    if (summary.distance - activity.distance).abs() > DISTANCE_TOLERANCE {
+        differences.push("distance");
+    }
+    if summary.moving_time != activity.moving_time {
+        differences.push("moving_time");
+    }
+    differences
+}
@@ -0,0 +1,102 @@
+use std::error::Error;
+
+use serde_json::{json, Value};
+use tracing::{info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::config::Config;
+use crate::critical_power;
+use crate::metrics;
+use crate::model::{ActivityIndex, ActivitySummary, ActivityWithStreams};
+use crate::pipeline::Analyzer;
+use crate::storage::{load_activity_file, stored_compression, write_activity_file};
+
+/// CP and W' to model W'bal with: watts.json, else the latest `cp` fit
+pub fn model(config: &Config) -> Option<(f64, f64)> {
+    match (config.cp, config.w_prime) {
+        (Some(cp), Some(w_prime)) => Some((cp, w_prime)),
+        _ => critical_power::latest().map(|e| (e.cp, e.w_prime)),
+    }
+}
+
+/// Store the W'bal stream of a ride; false when it has no power data
+pub fn annotate(file: &mut ActivityWithStreams, (cp, w_prime): (f64, f64)) -> bool {
+    let Some(streams) = file.streams.as_mut() else { return false };
+    let Some(watts) = streams.watts.as_deref() else { return false };
+    let balance = metrics::w_prime_balance(watts, streams.time.as_deref(), cp, w_prime);
+    streams.w_balance = Some(balance.iter().map(|w| w.round()).collect());
+    true
+}
+
+/// W'bal stream, with the CP and W' `model` picks
+pub struct WBalance;
+
+impl Analyzer for WBalance {
+    fn name(&self) -> &'static str {
+        "wbal"
+    }
+
+    fn inputs(&self, config: &Config) -> Value {
+        json!(model(config))
+    }
+
+    fn analyze(&self, file: &mut ActivityWithStreams, _summary: &mut ActivitySummary, config: &Config) {
+        if let Some(model) = model(config) {
+            annotate(file, model);
+        }
+    }
+}
+
+/// Lowest W'bal in joules and seconds spent below half of W'
+pub fn summary(file: &ActivityWithStreams, w_prime: f64) -> Option<(f64, i32)> {
+    let streams = file.streams.as_ref()?;
+    let balance = streams.w_balance.as_deref()?;
+    let minimum = balance.iter().cloned().reduce(f64::min)?;
+    let below_half = match streams.time.as_deref().filter(|t| t.len() == balance.len()) {
+        Some(time) => (1..balance.len())
+            .filter(|&i| balance[i] < w_prime / 2.0)
+            .map(|i| (time[i] - time[i - 1]).max(0))
+            .sum(),
+        None => balance.iter().filter(|&&w| w < w_prime / 2.0).count() as i32,
+    };
+    Some((minimum, below_half))
+}
+
+/// Compute and store W'bal for one ride, or every ride, and report how deep each went
+pub fn run(id: Option<ActivityId>) -> Result<(), Box<dyn Error>> {
+    let config = Config::current()?;
+    let (cp, w_prime) = model(&config).ok_or("set \"cp\" and \"w_prime\" in watts.json or run `cp` first")?;
+    let ids = match id {
+        Some(id) => vec![id],
+        None => ActivityIndex::load()?.activities.iter().map(|a| a.id).collect(),
+    };
+    info!("🔋 W'bal with CP {:.0} W, W' {:.1} kJ", cp, w_prime / 1000.0);
+
+    println!("  {:<10} {:>9} {:>6} {:>9}  name", "date", "min W'bal", "%", "<50% W'");
+    for id in ids {
+        let compression = stored_compression(id)?.ok_or_else(|| format!("activity {} is not in the archive", id))?;
+        let mut file = match load_activity_file(id) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("   ⚠️  Skipping {}: {}", id, e);
+                continue;
+            }
+        };
+        if !annotate(&mut file, (cp, w_prime)) {
+            continue;
+        }
+        write_activity_file(&file, compression)?;
+        if let Some((minimum, below_half)) = summary(&file, w_prime) {
+            println!(
+                "  {:<10} {:>7.1}kJ {:>5.0}% {:>6}:{:02}  {}",
+                &file.activity.start_date_local[..file.activity.start_date_local.len().min(10)],
+                minimum / 1000.0,
+                minimum / w_prime * 100.0,
+                below_half / 60,
+                below_half % 60,
+                file.activity.name
+            );
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::config::Config;
+use crate::edit::TagFilter;
+use crate::format;
+use crate::model::ActivityIndex;
+use crate::stats::{self, GroupBy, Totals};
+use crate::storage::write_atomic;
+
+/// Precomputed weekly series for the dashboard charts
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeeklySeries {
+    pub schema_version: u32,
+    pub weeks: Vec<WeekSummary>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeekSummary {
+    /// Monday of the week, by the local date each ride started on, as the dashboard keys it
+    pub week: String,
+    #[serde(flatten)]
+    pub totals: Totals,
+    pub polarization_index: Option<f64>,
+}
+
+/// Aggregate every ride in the index by week, oldest first
+pub fn summarize(index: &ActivityIndex, config: &Config) -> Vec<WeekSummary> {
+    let mut weeks: BTreeMap<NaiveDate, Totals> = BTreeMap::new();
+    for summary in index.activities.iter().filter(|a| a.counted()) {
+        let day = summary.local_date();
+        weeks.entry(GroupBy::Week.start(day, day)).or_default().add(summary, config);
+    }
+
+    weeks
+        .into_iter()
+        .map(|(monday, mut totals)| {
+            let polarization_index = totals.polarization_index().map(|pi| (pi * 100.0).round() / 100.0);
+            totals.distance = totals.distance.round();
+            totals.tss = totals.tss.map(f64::round);
+            WeekSummary { week: monday.to_string(), totals, polarization_index }
+        })
+        .collect()
+}
+
+/// Write data/weekly.json from the current index
+pub fn write_series(index: &ActivityIndex) -> Result<(), Box<dyn Error>> {
+    let series = WeeklySeries { schema_version: format::SCHEMA_VERSION, weeks: summarize(index, &Config::current()?) };
+    write_atomic(&format::weekly_path(), serde_json::to_string_pretty(&series)?.as_bytes())
+}
+
+/// Print the weekly summaries, refreshing data/weekly.json on the way; the
+/// tag filter only applies to the printout
+pub fn run(since: Option<NaiveDate>, tags: &TagFilter) -> Result<(), Box<dyn Error>> {
+    let mut index = ActivityIndex::load()?;
+    write_series(&index)?;
+    tags.apply(&mut index);
+    let weeks = summarize(&index, &Config::current()?);
+    info!("📅 {} weeks of training", weeks.len());
+
+    let since = since.map(|d| d.to_string()).unwrap_or_default();
+    stats::print_header("week");
+    for week in weeks.iter().filter(|w| w.week >= since) {
+        stats::print_row(&week.week, &week.totals);
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+//! Power-to-weight at key durations, each ride divided by the weight snapshot
+//! closest to its date (see athlete_history).
+
+use std::error::Error;
+
+use chrono::NaiveDate;
+use tracing::{info, warn};
+
+use crate::activity_id::ActivityId;
+use crate::athlete_history::{self, AthleteSnapshot};
+use crate::metrics;
+use crate::model::{ActivityIndex, ActivityWithStreams};
+use crate::storage::load_activity_file;
+
+pub const DURATIONS: [(usize, &str); 4] = [(5, "5s"), (60, "1m"), (300, "5m"), (1200, "20m")];
+
+/// Best mean power and W/kg of a ride at each of `DURATIONS`
+pub fn peaks(file: &ActivityWithStreams, history: &[AthleteSnapshot]) -> [Option<(f64, Option<f64>)>; DURATIONS.len()] {
+    let watts = file.streams.as_ref().and_then(|s| s.watts.as_deref()).unwrap_or_default();
+    let weight = file.activity.local_start().and_then(|start| athlete_history::weight_at(history, start.date()));
+    DURATIONS.map(|(duration, _)| metrics::mean_maximal_power(watts, duration).map(|p| (p, weight.map(|kg| p / kg))))
+}
+
+/// Print W/kg peaks per ride and the all-time best W/kg curve
+pub fn run(since: Option<NaiveDate>) -> Result<(), Box<dyn Error>> {
+    let index = ActivityIndex::load()?;
+    let history = athlete_history::load()?;
+    if !history.iter().any(|s| s.weight.is_some()) {
+        return Err("no weight in data/athlete_history.json yet, run `athlete --fetch` first".into());
+    }
+
+    info!("⚖️  Power-to-weight per ride");
+    print!("  {:<10} {:>6}", "date", "kg");
+    for (_, label) in DURATIONS {
+        print!(" {:>6}", label);
+    }
+    println!();
+
+    let mut best: [Option<(f64, f64, ActivityId, NaiveDate)>; DURATIONS.len()] = Default::default();
+    for summary in index.activities.iter().filter(|a| a.counted() && !a.is_run()) {
+        let file = match load_activity_file(summary.id) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("   ⚠️  Skipping {}: {}", summary.id, e);
+                continue;
+            }
+        };
+        let peaks = peaks(&file, &history);
+        for (best, peak) in best.iter_mut().zip(peaks) {
+            if let Some((watts, Some(wkg))) = peak {
+                if best.as_ref().is_none_or(|b| wkg > b.1) {
+                    *best = Some((watts, wkg, summary.id, summary.local_date()));
+                }
+            }
+        }
+
+        if since.is_some_and(|s| summary.local_date() < s) || peaks.iter().all(Option::is_none) {
+            continue;
+        }
+        print!(
+            "  {:<10} {:>6}",
+            summary.local_date(),
+            athlete_history::weight_at(&history, summary.local_date()).map(|kg| format!("{:.1}", kg)).unwrap_or_else(|| "-".into())
+        );
+        for peak in peaks {
+            print!(" {:>6}", peak.and_then(|(_, wkg)| wkg).map(|w| format!("{:.2}", w)).unwrap_or_else(|| "-".into()));
+        }
+        println!();
+    }
+
+    println!();
+    info!("🏆 All-time power curve in W/kg");
+    println!("  {:<4} {:>6} {:>6}  {:<10} activity", "", "W/kg", "W", "date");
+    for ((_, label), best) in DURATIONS.iter().zip(&best) {
+        if let Some((watts, wkg, id, date)) = best {
+            println!("  {:<4} {:>6.2} {:>6.0}  {:<10} {}", label, wkg, watts, date, id);
+        }
+    }
+    Ok(())
+}
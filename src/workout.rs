@@ -0,0 +1,375 @@
+//! Structured workouts rebuilt from a past ride, to repeat a good session on a
+//! trainer: its laps (or, without a lap structure, the efforts detected in
+//! its power) become blocks at their average power as a fraction of FTP, the
+//! first and last blocks ramps, and repeated on/off pairs one set of
+//! intervals. A planned session from `plan import` becomes one block at the
+//! intensity its TSS and duration imply. `workout` writes either as a Zwift
+//! .zwo, an ERG (watts) or MRC (% FTP) file for other trainer apps, or a FIT
+//! workout for Garmin head units.
+
+use std::error::Error;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::NaiveDate;
+use tracing::info;
+
+use crate::activity_id::ActivityId;
+use crate::config::Config;
+use crate::escape;
+use crate::fit_workout;
+use crate::format;
+use crate::intervals::{self, IntervalSource};
+use crate::metrics;
+use crate::model::ActivityWithStreams;
+use crate::plan::{Plan, PlannedWorkout};
+use crate::storage::{load_activity_file, write_atomic};
+
+const WORKOUTS_DIR: &str = "workouts";
+
+/// Blocks shorter than this are folded into the previous one
+const MIN_BLOCK_SECONDS: i32 = 30;
+
+/// Seconds averaged at each end of the warm-up and cool-down ramps
+const RAMP_EDGE_SECONDS: usize = 60;
+
+/// On/off pairs repeat when their durations are within this fraction...
+const REPEAT_DURATION_TOLERANCE: f64 = 0.1;
+/// ...and their powers within this fraction of FTP
+const REPEAT_POWER_TOLERANCE: f64 = 0.05;
+
+/// A block of a workout; power is a fraction of FTP and durations are seconds
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Warmup { duration: i32, from: f64, to: f64 },
+    Steady { duration: i32, power: f64 },
+    Intervals { repeat: usize, on_duration: i32, off_duration: i32, on_power: f64, off_power: f64 },
+    Cooldown { duration: i32, from: f64, to: f64 },
+}
+
+impl Segment {
+    pub fn duration(&self) -> i32 {
+        match *self {
+            Segment::Warmup { duration, .. } | Segment::Steady { duration, .. } | Segment::Cooldown { duration, .. } => duration,
+            Segment::Intervals { repeat, on_duration, off_duration, .. } => repeat as i32 * (on_duration + off_duration),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Workout {
+    pub name: String,
+    pub description: String,
+    /// Watts the powers are fractions of, needed for ERG files
+    pub ftp: Option<f64>,
+    pub segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkoutFormat {
+    Zwo,
+    Erg,
+    Mrc,
+    Fit,
+}
+
+impl WorkoutFormat {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "zwo" => Ok(WorkoutFormat::Zwo),
+            "erg" => Ok(WorkoutFormat::Erg),
+            "mrc" => Ok(WorkoutFormat::Mrc),
+            "fit" => Ok(WorkoutFormat::Fit),
+            other => Err(format!("unknown workout format '{}', expected zwo, erg, mrc or fit", other)),
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            WorkoutFormat::Zwo => "zwo",
+            WorkoutFormat::Erg => "erg",
+            WorkoutFormat::Mrc => "mrc",
+            WorkoutFormat::Fit => "fit",
+        }
+    }
+}
+
+/// What to build the workout from
+#[derive(Debug, Clone, Copy)]
+pub enum Source {
+    Ride(ActivityId),
+    /// The workouts planned on a day
+    Plan(NaiveDate),
+}
+
+impl Workout {
+    /// Rebuild a ride against `ftp`, from its laps unless `detect` or it has none
+    pub fn from_ride(file: &ActivityWithStreams, ftp: f64, detect: bool) -> Result<Workout, Box<dyn Error>> {
+        let streams = file.streams.as_ref().ok_or("the ride has no streams")?;
+        let watts = streams.watts.as_deref().filter(|w| !w.is_empty()).ok_or("the ride has no power stream")?;
+        let time = streams.time.as_deref().filter(|t| t.len() == watts.len());
+
+        let (source, blocks) = if detect { (IntervalSource::Detected, intervals::detect_efforts(file)) } else { intervals::intervals(file) };
+        let mut edges = vec![0];
+        for block in &blocks {
+            edges.push(block.start.min(watts.len()));
+            if source == IntervalSource::Detected {
+                edges.push((block.start + block.duration as usize).min(watts.len()));
+            }
+        }
+        edges.push(watts.len());
+        edges.dedup();
+
+        // Seconds and average power of each block, short ones folded into the previous
+        let seconds = |a: usize, b: usize| time.map_or((b - a) as i32, |t| t[b - 1] - t[a] + 1);
+        let mut spans: Vec<(usize, usize)> = Vec::new();
+        for pair in edges.windows(2).filter(|p| p[1] > p[0]) {
+            match spans.last_mut() {
+                Some(last) if seconds(pair[0], pair[1]) < MIN_BLOCK_SECONDS => last.1 = pair[1],
+                _ => spans.push((pair[0], pair[1])),
+            }
+        }
+        let power = |values: &[f64]| metrics::mean(values).map_or(0.0, |p| round2(p / ftp));
+
+        let mut segments: Vec<Segment> = Vec::new();
+        for (i, &(a, b)) in spans.iter().enumerate() {
+            let duration = seconds(a, b);
+            let edge = RAMP_EDGE_SECONDS.min((b - a) / 2).max(1);
+            let (from, to) = (power(&watts[a..a + edge]), power(&watts[b - edge..b]));
+            segments.push(match i {
+                0 if spans.len() > 2 => Segment::Warmup { duration, from, to },
+                i if i == spans.len() - 1 && spans.len() > 2 => Segment::Cooldown { duration, from, to },
+                _ => Segment::Steady { duration, power: power(&watts[a..b]) },
+            });
+        }
+
+        let how = match source {
+            IntervalSource::Laps => "laps",
+            IntervalSource::Detected => "detected efforts",
+        };
+        Ok(Workout {
+            name: file.activity.name.clone(),
+            description: format!(
+                "Rebuilt from the {} of activity {} on {}, at {:.0} W FTP",
+                how,
+                file.activity.id,
+                file.activity.start_date_local.get(..10).unwrap_or(&file.activity.start_date_local),
+                ftp
+            ),
+            ftp: Some(ftp),
+            segments: repeats(segments),
+        })
+    }
+
+    /// The workouts planned on one day as a single block at the intensity
+    /// factor their TSS and duration imply (TSS = hours × IF² × 100)
+    pub fn from_plan(planned: &[&PlannedWorkout], ftp: Option<f64>) -> Result<Workout, Box<dyn Error>> {
+        let first = planned.first().ok_or("no workout planned on that day")?;
+        let duration: i32 = planned.iter().filter_map(|w| w.duration).sum();
+        let tss: f64 = planned.iter().filter_map(|w| w.tss).sum();
+        if duration <= 0 || tss <= 0.0 {
+            return Err(format!("the workout planned on {} needs a duration and a TSS", first.date).into());
+        }
+        let intensity = round2((tss / (duration as f64 / 3600.0 * 100.0)).sqrt());
+        Ok(Workout {
+            name: planned.iter().map(|w| w.name.as_str()).collect::<Vec<_>>().join(" + "),
+            description: format!("Planned for {}: {} min, {:.0} TSS (IF {:.2})", first.date, duration / 60, tss, intensity),
+            ftp,
+            segments: vec![Segment::Steady { duration, power: intensity }],
+        })
+    }
+
+    pub fn duration(&self) -> i32 {
+        self.segments.iter().map(Segment::duration).sum()
+    }
+
+    /// The workout as a Zwift .zwo file
+    pub fn to_zwo(&self) -> String {
+        let mut xml = String::from("<workout_file>\n");
+        let _ = writeln!(xml, "    <author>Watts Happening</author>");
+        let _ = writeln!(xml, "    <name>{}</name>", escape::xml(&self.name));
+        let _ = writeln!(xml, "    <description>{}</description>", escape::xml(&self.description));
+        xml.push_str("    <sportType>bike</sportType>\n    <workout>\n");
+        for segment in &self.segments {
+            let _ = match *segment {
+                Segment::Warmup { duration, from, to } => {
+                    writeln!(xml, r#"        <Warmup Duration="{}" PowerLow="{:.2}" PowerHigh="{:.2}"/>"#, duration, from, to)
+                }
+                Segment::Steady { duration, power } => writeln!(xml, r#"        <SteadyState Duration="{}" Power="{:.2}"/>"#, duration, power),
+                Segment::Intervals { repeat, on_duration, off_duration, on_power, off_power } => writeln!(
+                    xml,
+                    r#"        <IntervalsT Repeat="{}" OnDuration="{}" OffDuration="{}" OnPower="{:.2}" OffPower="{:.2}"/>"#,
+                    repeat, on_duration, off_duration, on_power, off_power
+                ),
+                Segment::Cooldown { duration, from, to } => {
+                    writeln!(xml, r#"        <Cooldown Duration="{}" PowerLow="{:.2}" PowerHigh="{:.2}"/>"#, duration, from, to)
+                }
+            };
+        }
+        xml.push_str("    </workout>\n</workout_file>\n");
+        xml
+    }
+
+    /// (minute, fraction of FTP) points tracing the workout, two per block
+    fn course(&self) -> Vec<(f64, f64)> {
+        let mut points = Vec::new();
+        let mut at = 0;
+        let mut block = |points: &mut Vec<(f64, f64)>, duration: i32, from: f64, to: f64| {
+            points.push((at as f64 / 60.0, from));
+            at += duration;
+            points.push((at as f64 / 60.0, to));
+        };
+        for segment in &self.segments {
+            match *segment {
+                Segment::Warmup { duration, from, to } | Segment::Cooldown { duration, from, to } => block(&mut points, duration, from, to),
+                Segment::Steady { duration, power } => block(&mut points, duration, power, power),
+                Segment::Intervals { repeat, on_duration, off_duration, on_power, off_power } => {
+                    for _ in 0..repeat {
+                        block(&mut points, on_duration, on_power, on_power);
+                        block(&mut points, off_duration, off_power, off_power);
+                    }
+                }
+            }
+        }
+        points
+    }
+
+    /// ERG (watts) or MRC (% FTP) course file
+    fn to_course(&self, format: WorkoutFormat) -> Result<String, Box<dyn Error>> {
+        let ftp = self.ftp.filter(|_| format == WorkoutFormat::Erg);
+        if format == WorkoutFormat::Erg && ftp.is_none() {
+            return Err("ERG files are in watts: set ftp in watts.json or pass --ftp W".into());
+        }
+        let mut text = String::from("[COURSE HEADER]\nVERSION = 2\nUNITS = ENGLISH\n");
+        let _ = writeln!(text, "DESCRIPTION = {}", self.description);
+        let _ = writeln!(text, "FILE NAME = {}", self.name);
+        match ftp {
+            Some(ftp) => {
+                let _ = writeln!(text, "FTP = {:.0}\nMINUTES WATTS", ftp);
+            }
+            None => text.push_str("MINUTES PERCENT\n"),
+        }
+        text.push_str("[END COURSE HEADER]\n[COURSE DATA]\n");
+        for (minute, power) in self.course() {
+            match ftp {
+                Some(ftp) => writeln!(text, "{:.2}\t{:.0}", minute, power * ftp)?,
+                None => writeln!(text, "{:.2}\t{:.0}", minute, power * 100.0)?,
+            }
+        }
+        text.push_str("[END COURSE DATA]\n");
+        Ok(text)
+    }
+
+    pub fn encode(&self, format: WorkoutFormat) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(match format {
+            WorkoutFormat::Zwo => self.to_zwo().into_bytes(),
+            WorkoutFormat::Erg | WorkoutFormat::Mrc => self.to_course(format)?.into_bytes(),
+            WorkoutFormat::Fit => fit_workout::encode(self),
+        })
+    }
+}
+
+fn round2(v: f64) -> f64 {
+    (v * 100.0).round() / 100.0
+}
+
+/// Two steady blocks (on, off) alike to the next two
+fn alike(a: (i32, f64), b: (i32, f64)) -> bool {
+    (a.0 - b.0).abs() as f64 <= REPEAT_DURATION_TOLERANCE * a.0.max(b.0) as f64 && (a.1 - b.1).abs() <= REPEAT_POWER_TOLERANCE
+}
+
+/// Merge runs of at least two alike on/off pairs into interval sets, with
+/// their average durations and powers
+fn repeats(segments: Vec<Segment>) -> Vec<Segment> {
+    let steady = |s: &Segment| match *s {
+        Segment::Steady { duration, power } => Some((duration, power)),
+        _ => None,
+    };
+    let mut merged = Vec::new();
+    let mut i = 0;
+    while i < segments.len() {
+        let pair = |j: usize| Some((steady(segments.get(j)?)?, steady(segments.get(j + 1)?)?));
+        let Some((on, off)) = pair(i).filter(|(on, off)| on.1 > off.1) else {
+            merged.push(segments[i].clone());
+            i += 1;
+            continue;
+        };
+        let mut count = 1;
+        while let Some((next_on, next_off)) = pair(i + 2 * count) {
+            if !alike(on, next_on) || !alike(off, next_off) {
+                break;
+            }
+            count += 1;
+        }
+        if count < 2 {
+            merged.push(segments[i].clone());
+            i += 1;
+            continue;
+        }
+        let set: Vec<((i32, f64), (i32, f64))> = (0..count).filter_map(|k| pair(i + 2 * k)).collect();
+        let average = |values: Vec<f64>| values.iter().sum::<f64>() / values.len() as f64;
+        merged.push(Segment::Intervals {
+            repeat: count,
+            on_duration: average(set.iter().map(|(on, _)| on.0 as f64).collect()).round() as i32,
+            off_duration: average(set.iter().map(|(_, off)| off.0 as f64).collect()).round() as i32,
+            on_power: round2(average(set.iter().map(|(on, _)| on.1).collect())),
+            off_power: round2(average(set.iter().map(|(_, off)| off.1).collect())),
+        });
+        i += 2 * count;
+    }
+    merged
+}
+
+/// data/workouts/<id>.<ext>, or plan-<date>.<ext> for a planned session
+pub fn default_path(source: Source, format: WorkoutFormat) -> PathBuf {
+    let stem = match source {
+        Source::Ride(id) => id.to_string(),
+        Source::Plan(date) => format!("plan-{}", date),
+    };
+    format::data_dir().join(WORKOUTS_DIR).join(format!("{}.{}", stem, format.extension()))
+}
+
+/// Write a ride or a planned session as a workout file to `output`
+/// (`default_path` when None)
+pub fn run(source: Source, ftp: Option<f64>, detect: bool, format: WorkoutFormat, output: Option<&Path>) -> Result<(), Box<dyn Error>> {
+    let config = Config::current()?;
+    let ftp = ftp.or_else(|| config.ftp());
+    if let Some(ftp) = ftp.filter(|f| *f <= 0.0) {
+        return Err(format!("invalid FTP {} W", ftp).into());
+    }
+    let workout = match source {
+        Source::Ride(id) => {
+            let ftp = ftp.ok_or("no FTP in watts.json to scale the workout to, pass --ftp W")?;
+            Workout::from_ride(&load_activity_file(id)?, ftp, detect)?
+        }
+        Source::Plan(date) => {
+            let plan = Plan::load()?;
+            Workout::from_plan(&plan.workouts.iter().filter(|w| w.date == date).collect::<Vec<_>>(), ftp)?
+        }
+    };
+
+    let path = output.map(Path::to_path_buf).unwrap_or_else(|| default_path(source, format));
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    write_atomic(&path, &workout.encode(format)?)?;
+    info!("🏋️ {} blocks, {} min, written to {}", workout.segments.len(), workout.duration() / 60, path.display());
+    for segment in &workout.segments {
+        let line = match *segment {
+            Segment::Warmup { duration, from, to } => format!("warm-up    {:>5}s  {:.0}% → {:.0}%", duration, from * 100.0, to * 100.0),
+            Segment::Steady { duration, power } => format!("steady     {:>5}s  {:.0}%", duration, power * 100.0),
+            Segment::Intervals { repeat, on_duration, off_duration, on_power, off_power } => format!(
+                "{:>2} x      {:>5}s  {:.0}% / {}s {:.0}%",
+                repeat,
+                on_duration,
+                on_power * 100.0,
+                off_duration,
+                off_power * 100.0
+            ),
+            Segment::Cooldown { duration, from, to } => format!("cool-down  {:>5}s  {:.0}% → {:.0}%", duration, from * 100.0, to * 100.0),
+        };
+        info!("   {}", line);
+    }
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use tracing::info;
+
+use crate::athlete;
+use crate::config::{Config, ZoneRange, Zones};
+use crate::format;
+use crate::storage::write_atomic;
+use crate::strava::{access_token, fetch_athlete_zones};
+
+const HISTORY_FILE: &str = "zones_history.json";
+
+/// Zones as Strava reported them at a point in time
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ZonesSnapshot {
+    pub fetched_at: String,
+    pub zones: Zones,
+}
+
+/// Fetch zones from Strava, record them when they changed, and seed watts.json.
+/// Existing local zones are only overwritten with `apply`.
+pub async fn fetch(apply: bool) -> Result<(), Box<dyn Error>> {
+    let access_token = access_token().await?;
+    let zones = parse_strava_zones(&fetch_athlete_zones(&access_token).await?);
+    print_zones(&zones);
+
+    let path = format::data_dir().join(HISTORY_FILE);
+    let mut history: Vec<ZonesSnapshot> = match fs::read_to_string(&path) {
+        Ok(json) => serde_json::from_str(&json)?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e.into()),
+    };
+    if history.last().map(|s| &s.zones) != Some(&zones) {
+        info!("📝 Strava zones changed, recorded in {}", path.display());
+        history.push(ZonesSnapshot {
+            fetched_at: chrono::Utc::now().to_rfc3339(),
+            zones: zones.clone(),
+        });
+        write_atomic(&path, serde_json::to_string_pretty(&history)?.as_bytes())?;
+    } else {
+        info!("✓ Strava zones unchanged since {}", history.last().map(|s| s.fetched_at.as_str()).unwrap_or("?"));
+    }
+
+    let local = Config::current()?.zones;
+    let replace = match &local {
+        None => true,
+        Some(local) if *local != zones && !apply => {
+            info!("⚠️  Local zones in watts.json differ from Strava, rerun with --apply to replace them");
+            false
+        }
+        Some(local) => *local != zones,
+    };
+    if replace {
+        // Zones belong to the athlete they were fetched for
+        let mut config = Config::load()?;
+        match athlete::active() {
+            Some(active) => {
+                if let Some(profile) = config.athletes.iter_mut().find(|a| a.id == active.id) {
+                    profile.zones = Some(zones);
+                }
+            }
+            None => config.zones = Some(zones),
+        }
+        config.save()?;
+        info!("💾 {} zones in watts.json", if local.is_none() { "Seeded" } else { "Replaced" });
+    }
+    Ok(())
+}
+
+/// Convert the `/athlete/zones` payload; power zones need a Strava subscription
+pub fn parse_strava_zones(value: &serde_json::Value) -> Zones {
+    let ranges = |kind: &str| -> Vec<ZoneRange> {
+        value
+            .get(kind)
+            .and_then(|v| v.get("zones"))
+            .and_then(|z| serde_json::from_value(z.clone()).ok())
+            .unwrap_or_default()
+    };
+    Zones {
+        heart_rate: ranges("heart_rate"),
+        power: ranges("power"),
+    }
+}
+
+fn print_zones(zones: &Zones) {
+    for (label, unit, ranges) in [("Heart rate", "bpm", &zones.heart_rate), ("Power", "W", &zones.power)] {
+        if ranges.is_empty() {
+            continue;
+        }
+        println!("{} zones:", label);
+        for (i, range) in ranges.iter().enumerate() {
+            if range.max < 0 {
+                println!("  Z{}  {}+ {}", i + 1, range.min, unit);
+            } else {
+                println!("  Z{}  {}-{} {}", i + 1, range.min, range.max, unit);
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+//! Zwift world and route detection against the bundled `zwift_routes.json`
+//! (lap length and climbing as Zwift lists them; extend it as routes are added).
+//!
+//! The world comes from the GPS position Zwift maps each world to, or from the
+//! activity name. The route comes from the name Zwift gives the activity, or
+//! failing that from distance and climbing matching a whole number of laps.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::sync::OnceLock;
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::model::{ActivityIndex, ActivitySummary, ActivityWithStreams};
+use crate::pipeline::Analyzer;
+use crate::storage::load_activity_file;
+
+/// Laps must match the ride distance within this fraction
+const DISTANCE_TOLERANCE: f64 = 0.05;
+
+/// ...and the climbing within this fraction (of at least 50 m)
+const ELEVATION_TOLERANCE: f64 = 0.3;
+
+#[derive(Debug, Deserialize)]
+struct Database {
+    worlds: Vec<World>,
+    routes: Vec<Route>,
+}
+
+#[derive(Debug, Deserialize)]
+struct World {
+    name: String,
+    lat: f64,
+    lng: f64,
+    radius_km: f64,
+}
+
+#[derive(Debug, Deserialize)]
+struct Route {
+    name: String,
+    world: String,
+    distance_km: f64,
+    elevation_m: f64,
+}
+
+fn database() -> &'static Database {
+    static DATABASE: OnceLock<Database> = OnceLock::new();
+    DATABASE.get_or_init(|| serde_json::from_str(include_str!("zwift_routes.json")).expect("bundled zwift_routes.json is valid"))
+}
+
+/// World and route of a Zwift activity, as far as they can be told
+pub fn detect(file: &ActivityWithStreams) -> (Option<&'static str>, Option<&'static str>) {
+    let db = database();
+    let activity = &file.activity;
+    let name = activity.name.to_lowercase();
+
+    let start = file.streams.as_ref().and_then(|s| s.latlng.as_ref()).and_then(|l| l.first());
+    let world = start
+        .and_then(|&[lat, lng]| {
+            db.worlds
+                .iter()
+                .map(|w| (w, distance_km(lat, lng, w.lat, w.lng)))
+                .filter(|(w, km)| *km <= w.radius_km)
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(w, _)| w.name.as_str())
+        })
+        .or_else(|| {
+            db.worlds
+                .iter()
+                .find(|w| name.ends_with(&format!(" in {}", w.name.to_lowercase())))
+                .map(|w| w.name.as_str())
+        });
+    let candidates = || db.routes.iter().filter(move |r| world.is_none_or(|w| r.world == w));
+
+    // Zwift names activities after their route; the longest match wins ("Figure 8" vs "London 8")
+    let named = candidates()
+        .filter(|r| name.contains(&r.name.to_lowercase()))
+        .max_by_key(|r| r.name.len());
+    let route = named.or_else(|| {
+        world?;
+        candidates()
+            .filter_map(|r| {
+                let route_m = r.distance_km * 1000.0;
+                let laps = (activity.distance / route_m).round().max(1.0);
+                let distance_error = (activity.distance - laps * route_m).abs() / (laps * route_m);
+                let elevation_error = (activity.total_elevation_gain - laps * r.elevation_m).abs() / (laps * r.elevation_m).max(50.0);
+                (distance_error <= DISTANCE_TOLERANCE && elevation_error <= ELEVATION_TOLERANCE)
+                    .then_some((r, distance_error + elevation_error / 2.0))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(r, _)| r)
+    });
+
+    (world.or(route.map(|r| r.world.as_str())), route.map(|r| r.name.as_str()))
+}
+
+/// Store the detected world and route on an index entry
+pub fn annotate(summary: &mut ActivitySummary, file: &ActivityWithStreams) {
+    let (world, route) = detect(file);
+    summary.world = world.map(String::from);
+    summary.route = route.map(String::from);
+}
+
+/// Zwift world and route on the index entry
+pub struct RouteDetection;
+
+impl Analyzer for RouteDetection {
+    fn name(&self) -> &'static str {
+        "route"
+    }
+
+    fn analyze(&self, file: &mut ActivityWithStreams, summary: &mut ActivitySummary, _config: &Config) {
+        annotate(summary, file);
+    }
+}
+
+/// Great-circle distance
+pub fn distance_km(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let dlat = lat2 - lat1;
+    let dlng = (lng2 - lng1).to_radians();
+    let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlng / 2.0).sin().powi(2);
+    6371.0 * 2.0 * a.sqrt().asin()
+}
+
+/// Re-detect worlds and routes for the archive and print rides per route
+pub fn run(since: Option<NaiveDate>) -> Result<(), Box<dyn Error>> {
+    let mut index = ActivityIndex::load()?;
+    for summary in index.activities.iter_mut() {
+        match load_activity_file(summary.id) {
+            Ok(file) => annotate(summary, &file),
+            Err(e) => warn!("   ⚠️  Skipping {}: {}", summary.id, e),
+        }
+    }
+    index.save()?;
+
+    let mut routes: BTreeMap<(&str, &str), (usize, f64)> = BTreeMap::new();
+    for summary in index.activities.iter().filter(|a| a.counted()) {
+        if since.is_some_and(|s| summary.local_date() < s) {
+            continue;
+        }
+        let key = (summary.world.as_deref().unwrap_or("?"), summary.route.as_deref().unwrap_or("?"));
+        let entry = routes.entry(key).or_default();
+        entry.0 += 1;
+        entry.1 += summary.distance / 1000.0;
+    }
+
+    info!("🗺️  Rides by Zwift world and route");
+    println!("  {:<16} {:<32} {:>5} {:>8}", "world", "route", "rides", "km");
+    for ((world, route), (rides, km)) in &routes {
+        println!("  {:<16} {:<32} {:>5} {:>8.1}", world, route, rides, km);
+    }
+    Ok(())
+}
@@ -0,0 +1,125 @@
+//! A scratch archive and a mock Strava API for the sync tests. The archive, the
+//! HTTP cache and watts.json all resolve against the working directory, so each
+//! harness moves it into a fresh temporary directory; tests sharing a binary
+//! hold `LOCK` for their whole run.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_json::{json, Value};
+use strava_fetcher::activity_id::ActivityId;
+use strava_fetcher::mock_strava;
+use strava_fetcher::model::{Activity, ActivityIndex};
+use strava_fetcher::strava;
+use tokio::net::TcpListener;
+
+pub static LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// The recorded ride every fixture activity is cut from
+const TEMPLATE_ID: &str = "16098409001";
+
+pub struct Harness {
+    root: PathBuf,
+    fixtures: PathBuf,
+}
+
+fn recorded() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/strava")
+}
+
+fn copy(from: &Path, to: &Path) {
+    fs::create_dir_all(to.parent().unwrap()).unwrap();
+    fs::copy(from, to).unwrap();
+}
+
+impl Harness {
+    /// An empty archive with the recorded token, athlete and stats fixtures
+    pub fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("watts-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let fixtures = root.join("fixtures");
+        for file in ["oauth/token.json", "api/v3/athlete.json", "api/v3/athletes/1/stats.json"] {
+            copy(&recorded().join(file), &fixtures.join(file));
+        }
+        fs::create_dir_all(root.join("data")).unwrap();
+        std::env::set_current_dir(&root).unwrap();
+
+        std::env::set_var("STRAVA_CLIENT_ID", "test");
+        std::env::set_var("STRAVA_CLIENT_SECRET", "test");
+        // As token.json answers, so no rotation is written back
+        std::env::set_var("STRAVA_REFRESH_TOKEN", "x");
+        std::env::set_var("WATTS_HTTP_CACHE", "off");
+        for var in ["WATTS_CASSETTE", "WATTS_TOKEN_STORE", "WATTS_FETCH_DETAIL"] {
+            std::env::remove_var(var);
+        }
+        Harness { root, fixtures }
+    }
+
+    /// Serve `activities` as page `page` of the activity list, with the
+    /// recorded streams and laps for each
+    pub fn page(&self, page: u32, activities: &[Value]) {
+        let list = self.fixtures.join(format!("api/v3/athlete/activities/page-{}.json", page));
+        fs::create_dir_all(list.parent().unwrap()).unwrap();
+        fs::write(list, serde_json::to_string(activities).unwrap()).unwrap();
+        for activity in activities {
+            for part in ["streams", "laps"] {
+                copy(
+                    &recorded().join(format!("api/v3/activities/{}/{}.json", TEMPLATE_ID, part)),
+                    &self.fixtures.join(format!("api/v3/activities/{}/{}.json", activity["id"], part)),
+                );
+            }
+        }
+    }
+
+    /// Put `activities` in the archive's index, as an earlier sync would have
+    pub fn seed(&self, activities: &[Value]) {
+        let mut index = ActivityIndex::load().unwrap();
+        for activity in activities {
+            index.add_activity(&serde_json::from_value::<Activity>(activity.clone()).unwrap());
+        }
+        index.save().unwrap();
+    }
+
+    /// Start the mock API on an ephemeral port and point requests at it
+    pub async fn start(&self, rate_limit: Option<u32>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        strava::set_base_url(Some(format!("http://{}", listener.local_addr().unwrap())));
+        let fixtures = self.fixtures.clone();
+        tokio::spawn(async move {
+            let _ = mock_strava::serve_on(listener, &fixtures, rate_limit).await;
+        });
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = std::env::set_current_dir(env!("CARGO_MANIFEST_DIR"));
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+/// A virtual ride `id`, `days` days after the recorded one
+pub fn activity(id: i64, days: i64) -> Value {
+    let list: Vec<Value> =
+        serde_json::from_str(&fs::read_to_string(recorded().join("api/v3/athlete/activities/page-1.json")).unwrap()).unwrap();
+    let mut activity = list[0].clone();
+    let start = chrono::NaiveDate::from_ymd_opt(2025, 10, 10).unwrap().and_hms_opt(18, 8, 22).unwrap() + chrono::Duration::days(days);
+    let stamp = |t: chrono::NaiveDateTime| t.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    activity["id"] = json!(id);
+    activity["name"] = json!(format!("Ride {}", id));
+    activity["start_date"] = json!(stamp(start));
+    activity["start_date_local"] = json!(stamp(start + chrono::Duration::hours(2)));
+    activity["gear_id"] = Value::Null;
+    activity
+}
+
+pub fn id(id: i64) -> ActivityId {
+    ActivityId::strava(id)
+}
+
+/// IDs in the archive's index
+pub fn indexed() -> Vec<ActivityId> {
+    ActivityIndex::load().unwrap().activities.iter().map(|a| a.id).collect()
+}
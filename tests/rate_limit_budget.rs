@@ -0,0 +1,30 @@
+//! A rate-limit budget running low partway through a sync. The budget is
+//! process-wide, so this runs in its own test binary.
+
+mod common;
+
+use common::{activity, id, indexed, Harness, LOCK};
+use strava_fetcher::failure::Failure;
+use strava_fetcher::sync;
+use strava_fetcher::sync_journal::SyncJournal;
+
+#[tokio::test]
+async fn low_budget_defers_the_rest() {
+    let _lock = LOCK.lock().await;
+    let harness = Harness::new("budget");
+    harness.page(1, &[activity(2, 1), activity(1, 0)]);
+    // Token and two pages leave 7 of 10: enough for one ride (2) and the tail (5)
+    harness.start(Some(10)).await;
+
+    let report = sync::run(None).await.unwrap();
+    assert_eq!(report.added, vec![id(2)]);
+    assert_eq!(report.deferred, vec![id(1)]);
+    let incomplete = report.failure().unwrap();
+    assert_eq!(incomplete.failure, Failure::RateLimited);
+    assert_eq!(incomplete.activities, report.deferred);
+
+    // The deferred ride waits in the journal for the next run
+    let journal = SyncJournal::load().unwrap().unwrap();
+    assert_eq!(journal.pending.iter().map(|a| a.id).collect::<Vec<_>>(), report.deferred);
+    assert_eq!(indexed(), vec![id(2)]);
+}
@@ -0,0 +1,22 @@
+//! A 429 from the mock Strava API. The rate-limit budget is process-wide, so
+//! this runs in its own test binary.
+
+mod common;
+
+use common::{activity, indexed, Harness, LOCK};
+use strava_fetcher::failure::Failure;
+use strava_fetcher::sync;
+
+#[tokio::test]
+async fn too_many_requests_fails_as_rate_limited() {
+    let _lock = LOCK.lock().await;
+    let harness = Harness::new("rate-limited");
+    harness.page(1, &[activity(2, 1)]);
+    harness.page(2, &[activity(1, 0)]);
+    // The token and page 1 go through, page 2 gets a 429
+    harness.start(Some(2)).await;
+
+    let error = sync::run(None).await.unwrap_err();
+    assert_eq!(Failure::of(error.as_ref()), Failure::RateLimited);
+    assert!(indexed().is_empty());
+}
@@ -0,0 +1,42 @@
+//! Sync against the mock Strava API: how far the activity list is read
+
+mod common;
+
+use common::{activity, id, indexed, Harness, LOCK};
+use strava_fetcher::sync;
+
+#[tokio::test]
+async fn pagination_stops_at_first_known_activity() {
+    let _lock = LOCK.lock().await;
+    let harness = Harness::new("pagination");
+    // Newest first: ride 3 is new, ride 2 was synced before, ride 1 is older still
+    harness.page(1, &[activity(3, 2), activity(2, 1)]);
+    harness.page(2, &[activity(1, 0)]);
+    harness.seed(&[activity(2, 1)]);
+    harness.start(None).await;
+
+    let report = sync::run(None).await.unwrap();
+    assert_eq!(report.added, vec![id(3)]);
+    assert!(report.failure().is_none());
+    // Page 2 lies past the known ride, so ride 1 is never listed
+    let indexed = indexed();
+    assert!(indexed.contains(&id(3)) && indexed.contains(&id(2)));
+    assert!(!indexed.contains(&id(1)));
+}
+
+#[tokio::test]
+async fn backfill_merges_pages_in_order() {
+    let _lock = LOCK.lock().await;
+    let harness = Harness::new("backfill");
+    harness.page(1, &[activity(6, 5), activity(5, 4)]);
+    harness.page(2, &[activity(4, 3), activity(3, 2)]);
+    harness.page(3, &[activity(2, 1), activity(1, 0)]);
+    harness.seed(&[activity(5, 4)]);
+    harness.start(None).await;
+
+    // Two pages at a time: pages 1-2, then 3 and the empty page 4
+    let report = sync::run(Some(2)).await.unwrap();
+    assert_eq!(report.added, vec![id(6), id(4), id(3), id(2), id(1)]);
+    assert!(report.failure().is_none());
+    assert_eq!(indexed().len(), 6);
+}